@@ -0,0 +1,461 @@
+use std::ops::RangeInclusive;
+
+use crate::css::{parse_value_list, AngleUnit, Declaration, Value};
+
+/// An `@font-face` rule, describing a font that can be loaded for a family.
+#[derive(Clone, PartialEq, Debug)]
+pub struct FontFace {
+    /// The family name the font is registered under.
+    pub family: String,
+    /// Where the font can be loaded from, in order of preference.
+    pub sources: Vec<FontSource>,
+    /// The code points the font covers. Defaults to all of Unicode.
+    pub unicode_range: Vec<RangeInclusive<u32>>,
+    /// The range of weights the font covers. Defaults to `400 400`.
+    pub weight: (f32, f32),
+    /// The style the font covers. Defaults to `normal`.
+    pub style: FontFaceStyle,
+    /// The range of widths the font covers, as percentages. Defaults to `100% 100%`.
+    pub stretch: (f32, f32),
+}
+
+/// An entry in the `src` descriptor of an `@font-face` rule.
+#[derive(Clone, PartialEq, Debug)]
+pub enum FontSource {
+    /// A font file to download, with its `format()` and `tech()` hints.
+    Url {
+        url: String,
+        format: Option<String>,
+        tech: Vec<String>,
+    },
+    /// A font installed locally, by full name or PostScript name.
+    Local(String),
+}
+
+/// The `font-style` descriptor of an `@font-face` rule.
+#[derive(Clone, PartialEq, Debug)]
+pub enum FontFaceStyle {
+    Normal,
+    Italic,
+    /// An oblique font covering the given range of angles, in degrees.
+    Oblique(f32, f32),
+}
+
+/// An `@keyframes` rule.
+#[derive(Clone, PartialEq, Debug)]
+pub struct Keyframes {
+    /// The name of the animation.
+    pub name: String,
+    /// The keyframes, in source order.
+    pub keyframes: Vec<Keyframe>,
+}
+
+/// A single keyframe of an `@keyframes` rule.
+#[derive(Clone, PartialEq, Debug)]
+pub struct Keyframe {
+    /// The offsets the keyframe applies at, as percentages. `from` is 0 and `to` is 100.
+    pub offsets: Vec<f32>,
+    /// The declarations applied at those offsets.
+    pub declarations: Vec<Declaration>,
+}
+
+/// An `@page` rule.
+#[derive(Clone, PartialEq, Debug)]
+pub struct PageRule {
+    /// The pages the rule applies to. Empty if the rule applies to every page.
+    pub selectors: Vec<PageSelector>,
+    /// The declarations for the page box itself.
+    pub declarations: Vec<Declaration>,
+    /// The margin boxes, e.g. `@top-center`, in source order.
+    pub margin_boxes: Vec<MarginBox>,
+}
+
+/// A page selector, e.g. `chapter:first`.
+#[derive(Clone, PartialEq, Debug)]
+pub struct PageSelector {
+    /// The named page the selector matches, if any.
+    pub name: Option<String>,
+    /// The page pseudo-classes, e.g. `first`, `left`, `right` or `blank`.
+    pub pseudo_classes: Vec<String>,
+}
+
+/// A margin box inside an `@page` rule.
+#[derive(Clone, PartialEq, Debug)]
+pub struct MarginBox {
+    /// Which of the sixteen margin boxes this is.
+    pub position: MarginBoxPosition,
+    /// The declarations for the margin box.
+    pub declarations: Vec<Declaration>,
+}
+
+/// The sixteen margin boxes around a page.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum MarginBoxPosition {
+    TopLeftCorner,
+    TopLeft,
+    TopCenter,
+    TopRight,
+    TopRightCorner,
+    RightTop,
+    RightMiddle,
+    RightBottom,
+    BottomRightCorner,
+    BottomRight,
+    BottomCenter,
+    BottomLeft,
+    BottomLeftCorner,
+    LeftBottom,
+    LeftMiddle,
+    LeftTop,
+}
+
+impl MarginBoxPosition {
+    /// Returns the margin box with the given at-rule name, e.g. `top-center`.
+    pub fn from_name(name: &str) -> Option<MarginBoxPosition> {
+        Some(match &*name.to_ascii_lowercase() {
+            "top-left-corner" => MarginBoxPosition::TopLeftCorner,
+            "top-left" => MarginBoxPosition::TopLeft,
+            "top-center" => MarginBoxPosition::TopCenter,
+            "top-right" => MarginBoxPosition::TopRight,
+            "top-right-corner" => MarginBoxPosition::TopRightCorner,
+            "right-top" => MarginBoxPosition::RightTop,
+            "right-middle" => MarginBoxPosition::RightMiddle,
+            "right-bottom" => MarginBoxPosition::RightBottom,
+            "bottom-right-corner" => MarginBoxPosition::BottomRightCorner,
+            "bottom-right" => MarginBoxPosition::BottomRight,
+            "bottom-center" => MarginBoxPosition::BottomCenter,
+            "bottom-left" => MarginBoxPosition::BottomLeft,
+            "bottom-left-corner" => MarginBoxPosition::BottomLeftCorner,
+            "left-bottom" => MarginBoxPosition::LeftBottom,
+            "left-middle" => MarginBoxPosition::LeftMiddle,
+            "left-top" => MarginBoxPosition::LeftTop,
+            _ => return None,
+        })
+    }
+}
+
+impl FontFace {
+    /// Builds a font face from the descriptors of an `@font-face` rule, given as names and
+    /// unparsed values.
+    ///
+    /// Invalid descriptors are ignored. Returns an error if `font-family` or `src` is missing or
+    /// invalid, since such a rule can't be used.
+    pub fn from_descriptors(descriptors: &[(String, String)]) -> Result<FontFace, &'static str> {
+        let mut family = None;
+        let mut sources = Vec::new();
+        let mut font_face = FontFace {
+            family: String::new(),
+            sources: Vec::new(),
+            unicode_range: vec![0..=0x10FFFF],
+            weight: (400.0, 400.0),
+            style: FontFaceStyle::Normal,
+            stretch: (100.0, 100.0),
+        };
+
+        for (name, value) in descriptors {
+            match &*name.to_ascii_lowercase() {
+                "font-family" => family = parse_family(value).or(family),
+                "src" => sources = parse_sources(value).unwrap_or(sources),
+                "unicode-range" => {
+                    if let Some(ranges) = parse_unicode_range(value) {
+                        font_face.unicode_range = ranges;
+                    }
+                }
+                "font-weight" => {
+                    if let Some(weight) = parse_range(value, weight_value) {
+                        font_face.weight = weight;
+                    }
+                }
+                "font-stretch" => {
+                    if let Some(stretch) = parse_range(value, stretch_value) {
+                        font_face.stretch = stretch;
+                    }
+                }
+                "font-style" => {
+                    if let Some(style) = parse_style(value) {
+                        font_face.style = style;
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        font_face.family = family.ok_or("@font-face without a valid font-family")?;
+        if sources.is_empty() {
+            return Err("@font-face without a valid src");
+        }
+        font_face.sources = sources;
+        Ok(font_face)
+    }
+
+    /// Returns true if the font covers the code point `c`.
+    pub fn covers(&self, c: char) -> bool {
+        self.unicode_range
+            .iter()
+            .any(|range| range.contains(&(c as u32)))
+    }
+}
+
+/// Parses a family name: a string, or a sequence of identifiers joined by spaces.
+fn parse_family(value: &str) -> Option<String> {
+    match &parse_value_list(value).ok()?[..] {
+        [values] => match &values[..] {
+            [Value::String(name)] => Some(name.clone()),
+            _ => keywords(values),
+        },
+        _ => None,
+    }
+}
+
+/// Joins a sequence of keywords with spaces, e.g. for unquoted family names.
+fn keywords(values: &[Value]) -> Option<String> {
+    let words = values
+        .iter()
+        .map(|value| match value {
+            Value::Keyword(word) => Some(word.as_str()),
+            _ => None,
+        })
+        .collect::<Option<Vec<_>>>()?;
+    (!words.is_empty()).then(|| words.join(" "))
+}
+
+/// Parses the `src` descriptor. Entries that can't be parsed are skipped.
+fn parse_sources(value: &str) -> Option<Vec<FontSource>> {
+    let sources = parse_value_list(value)
+        .ok()?
+        .iter()
+        .filter_map(|entry| match &entry[..] {
+            [Value::Function(name, args)] if name.eq_ignore_ascii_case("local") => {
+                match &args[..] {
+                    [Value::String(name)] => Some(FontSource::Local(name.clone())),
+                    _ => keywords(args).map(FontSource::Local),
+                }
+            }
+            [Value::Url(url), hints @ ..] => {
+                let mut format = None;
+                let mut tech = Vec::new();
+                for hint in hints {
+                    match hint {
+                        Value::Function(name, args) if name.eq_ignore_ascii_case("format") => {
+                            format = match args.first()? {
+                                Value::String(s) | Value::Keyword(s) => Some(s.clone()),
+                                _ => return None,
+                            };
+                        }
+                        Value::Function(name, args) if name.eq_ignore_ascii_case("tech") => {
+                            for arg in args {
+                                match arg {
+                                    Value::Keyword(s) => tech.push(s.clone()),
+                                    _ => return None,
+                                }
+                            }
+                        }
+                        _ => return None,
+                    }
+                }
+                Some(FontSource::Url {
+                    url: url.clone(),
+                    format,
+                    tech,
+                })
+            }
+            _ => None,
+        })
+        .collect::<Vec<_>>();
+    Some(sources)
+}
+
+/// Parses the `unicode-range` descriptor, e.g. `U+0000-00FF, U+4??`.
+fn parse_unicode_range(value: &str) -> Option<Vec<RangeInclusive<u32>>> {
+    value
+        .split(',')
+        .map(|range| {
+            let range = range.trim();
+            let digits = range
+                .strip_prefix("U+")
+                .or_else(|| range.strip_prefix("u+"))?;
+            let (start, end) = match digits.split_once('-') {
+                Some((start, end)) => (start.to_string(), end.to_string()),
+                // Wildcards cover every value of the digits they replace.
+                None => (digits.replace('?', "0"), digits.replace('?', "F")),
+            };
+            let start = u32::from_str_radix(&start, 16).ok()?;
+            let end = u32::from_str_radix(&end, 16).ok()?;
+            (start <= end && end <= 0x10FFFF).then_some(start..=end)
+        })
+        .collect()
+}
+
+/// Parses a descriptor that takes one value or a range of two, converting each with `convert`.
+fn parse_range(value: &str, convert: fn(&Value) -> Option<(f32, f32)>) -> Option<(f32, f32)> {
+    match &parse_value_list(value).ok()?[..] {
+        [values] => match &values[..] {
+            [value] => convert(value),
+            [min, max] => Some((convert(min)?.0, convert(max)?.1)),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+/// Converts a `font-weight` descriptor value to the weights it covers.
+fn weight_value(value: &Value) -> Option<(f32, f32)> {
+    let weight = match value {
+        Value::Keyword(keyword) => match &*keyword.to_ascii_lowercase() {
+            "normal" => 400.0,
+            "bold" => 700.0,
+            "auto" => return Some((1.0, 1000.0)),
+            _ => return None,
+        },
+        Value::Integer(weight) => *weight as f32,
+        Value::Number(weight) => *weight,
+        _ => return None,
+    };
+    (1.0..=1000.0).contains(&weight).then_some((weight, weight))
+}
+
+/// Converts a `font-stretch` descriptor value to the widths it covers, as percentages.
+fn stretch_value(value: &Value) -> Option<(f32, f32)> {
+    let stretch = match value {
+        Value::Keyword(keyword) => match &*keyword.to_ascii_lowercase() {
+            "ultra-condensed" => 50.0,
+            "extra-condensed" => 62.5,
+            "condensed" => 75.0,
+            "semi-condensed" => 87.5,
+            "normal" => 100.0,
+            "semi-expanded" => 112.5,
+            "expanded" => 125.0,
+            "extra-expanded" => 150.0,
+            "ultra-expanded" => 200.0,
+            "auto" => return Some((50.0, 200.0)),
+            _ => return None,
+        },
+        Value::Percentage(stretch) if *stretch >= 0.0 => *stretch,
+        _ => return None,
+    };
+    Some((stretch, stretch))
+}
+
+/// Parses the `font-style` descriptor.
+fn parse_style(value: &str) -> Option<FontFaceStyle> {
+    let values = match &parse_value_list(value).ok()?[..] {
+        [values] => values.clone(),
+        _ => return None,
+    };
+    let degrees = |value: &Value| match value {
+        Value::Angle(angle, unit) => Some(match unit {
+            AngleUnit::Deg => *angle,
+            AngleUnit::Rad => angle.to_degrees(),
+            AngleUnit::Grad => angle * 0.9,
+            AngleUnit::Turn => angle * 360.0,
+        }),
+        _ => None,
+    };
+    match &values[..] {
+        [Value::Keyword(keyword)] => match &*keyword.to_ascii_lowercase() {
+            "normal" => Some(FontFaceStyle::Normal),
+            "italic" => Some(FontFaceStyle::Italic),
+            "oblique" => Some(FontFaceStyle::Oblique(14.0, 14.0)),
+            _ => None,
+        },
+        [Value::Keyword(keyword), angles @ ..] if keyword.eq_ignore_ascii_case("oblique") => {
+            match angles {
+                [angle] => degrees(angle).map(|a| FontFaceStyle::Oblique(a, a)),
+                [min, max] => Some(FontFaceStyle::Oblique(degrees(min)?, degrees(max)?)),
+                _ => None,
+            }
+        }
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn descriptors(pairs: &[(&str, &str)]) -> Vec<(String, String)> {
+        pairs
+            .iter()
+            .map(|(name, value)| (name.to_string(), value.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn test_font_face_from_descriptors() {
+        let font_face = FontFace::from_descriptors(&descriptors(&[
+            ("font-family", "Open Sans"),
+            (
+                "src",
+                "local(\"Open Sans\"), url(open.woff2) format(\"woff2\") tech(variations), \
+                 url(open.ttf) format(truetype), bogus(1)",
+            ),
+            ("unicode-range", "U+0000-00FF, U+4??, U+0131"),
+            ("font-weight", "100 900"),
+            ("font-style", "oblique 0deg 20deg"),
+            ("font-stretch", "condensed 125%"),
+        ]))
+        .unwrap();
+
+        assert_eq!(font_face.family, "Open Sans");
+        assert_eq!(
+            font_face.sources,
+            vec![
+                FontSource::Local(String::from("Open Sans")),
+                FontSource::Url {
+                    url: String::from("open.woff2"),
+                    format: Some(String::from("woff2")),
+                    tech: vec![String::from("variations")],
+                },
+                FontSource::Url {
+                    url: String::from("open.ttf"),
+                    format: Some(String::from("truetype")),
+                    tech: vec![],
+                },
+            ]
+        );
+        assert_eq!(
+            font_face.unicode_range,
+            vec![0..=0xFF, 0x400..=0x4FF, 0x131..=0x131]
+        );
+        assert_eq!(font_face.weight, (100.0, 900.0));
+        assert_eq!(font_face.style, FontFaceStyle::Oblique(0.0, 20.0));
+        assert_eq!(font_face.stretch, (75.0, 125.0));
+        assert!(font_face.covers('A'));
+        assert!(!font_face.covers('€'));
+    }
+
+    #[test]
+    fn test_font_face_defaults_and_errors() {
+        let font_face = FontFace::from_descriptors(&descriptors(&[
+            ("font-family", "\"Mono\""),
+            ("src", "url(mono.ttf)"),
+            ("font-weight", "heavy"),
+        ]))
+        .unwrap();
+        assert_eq!(font_face.family, "Mono");
+        assert_eq!(font_face.unicode_range, vec![0..=0x10FFFF]);
+        assert_eq!(font_face.weight, (400.0, 400.0));
+        assert_eq!(font_face.style, FontFaceStyle::Normal);
+        assert_eq!(font_face.stretch, (100.0, 100.0));
+
+        assert!(FontFace::from_descriptors(&descriptors(&[("src", "url(a.ttf)")])).is_err());
+        assert!(FontFace::from_descriptors(&descriptors(&[
+            ("font-family", "A"),
+            ("src", "nothing(1)")
+        ]))
+        .is_err());
+    }
+
+    #[test]
+    fn test_margin_box_position_from_name() {
+        assert_eq!(
+            MarginBoxPosition::from_name("top-center"),
+            Some(MarginBoxPosition::TopCenter)
+        );
+        assert_eq!(
+            MarginBoxPosition::from_name("Bottom-Left-Corner"),
+            Some(MarginBoxPosition::BottomLeftCorner)
+        );
+        assert_eq!(MarginBoxPosition::from_name("middle"), None);
+    }
+}
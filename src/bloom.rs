@@ -0,0 +1,149 @@
+use crate::dom::ElementData;
+
+/// The number of bits of a hash used to pick a counter.
+const KEY_BITS: u32 = 12;
+
+/// The number of counters in the filter.
+const KEY_SIZE: usize = 1 << KEY_BITS;
+
+/// The mask extracting a counter index from a hash.
+const KEY_MASK: u32 = (1 << KEY_BITS) - 1;
+
+/// A counting Bloom filter of the tag names, ids and classes of the ancestors of the element
+/// being styled, like the one Servo and Blink use.
+///
+/// Each key sets two counters, picked from the low and high bits of its hash. Counters saturate
+/// at `u8::MAX` and are then never decremented, so the filter can only err towards containing a
+/// key.
+#[derive(Clone)]
+pub struct CountingBloomFilter {
+    counters: Box<[u8; KEY_SIZE]>,
+}
+
+impl Default for CountingBloomFilter {
+    fn default() -> Self {
+        CountingBloomFilter {
+            counters: Box::new([0; KEY_SIZE]),
+        }
+    }
+}
+
+impl CountingBloomFilter {
+    /// Adds the key with the given hash.
+    pub fn insert(&mut self, hash: u32) {
+        for i in indices(hash) {
+            let counter = &mut self.counters[i];
+            *counter = counter.saturating_add(1);
+        }
+    }
+
+    /// Removes the key with the given hash, which must have been inserted.
+    pub fn remove(&mut self, hash: u32) {
+        for i in indices(hash) {
+            let counter = &mut self.counters[i];
+            if *counter != u8::MAX {
+                *counter -= 1;
+            }
+        }
+    }
+
+    /// Returns false if the key with the given hash is definitely not in the filter.
+    pub fn might_contain(&self, hash: u32) -> bool {
+        indices(hash).iter().all(|&i| self.counters[i] != 0)
+    }
+
+    /// Adds the tag name, id and classes of `elem`.
+    pub fn insert_element(&mut self, elem: &ElementData) {
+        for hash in element_hashes(elem) {
+            self.insert(hash);
+        }
+    }
+
+    /// Removes the tag name, id and classes of `elem`, which must have been inserted.
+    pub fn remove_element(&mut self, elem: &ElementData) {
+        for hash in element_hashes(elem) {
+            self.remove(hash);
+        }
+    }
+}
+
+/// Returns the indices of the two counters for a hash.
+fn indices(hash: u32) -> [usize; 2] {
+    [
+        (hash & KEY_MASK) as usize,
+        ((hash >> KEY_BITS) & KEY_MASK) as usize,
+    ]
+}
+
+/// Returns the hashes of the tag name, id and classes of `elem`.
+fn element_hashes(elem: &ElementData) -> impl Iterator<Item = u32> + '_ {
+    std::iter::once(tag_hash(&elem.tag_name))
+        .chain(elem.id().map(|id| id_hash(id)))
+        .chain(elem.classes().into_iter().map(class_hash))
+}
+
+/// Returns the hash of a tag name.
+pub fn tag_hash(tag_name: &str) -> u32 {
+    hash(b't', tag_name)
+}
+
+/// Returns the hash of an id.
+pub fn id_hash(id: &str) -> u32 {
+    hash(b'#', id)
+}
+
+/// Returns the hash of a class.
+pub fn class_hash(class: &str) -> u32 {
+    hash(b'.', class)
+}
+
+/// Hashes `name` with FNV-1a, salted by the kind of key so that e.g. the class `p` and the tag
+/// `p` don't collide. Never returns 0, so that 0 can mean "no hash".
+fn hash(kind: u8, name: &str) -> u32 {
+    let hash = std::iter::once(kind)
+        .chain(name.bytes())
+        .fold(0x811c9dc5u32, |hash, byte| {
+            (hash ^ byte as u32).wrapping_mul(0x01000193)
+        });
+    hash.max(1)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_insert_and_remove() {
+        let mut filter = CountingBloomFilter::default();
+        let div = tag_hash("div");
+        let main = id_hash("main");
+        assert!(!filter.might_contain(div));
+
+        filter.insert(div);
+        filter.insert(div);
+        filter.insert(main);
+        assert!(filter.might_contain(div));
+        assert!(filter.might_contain(main));
+        assert!(!filter.might_contain(class_hash("div")));
+
+        filter.remove(div);
+        assert!(filter.might_contain(div));
+        filter.remove(div);
+        filter.remove(main);
+        assert!(!filter.might_contain(div));
+        assert!(!filter.might_contain(main));
+    }
+
+    #[test]
+    fn test_saturated_counters_stay_set() {
+        let mut filter = CountingBloomFilter::default();
+        let hash = class_hash("item");
+        for _ in 0..300 {
+            filter.insert(hash);
+        }
+        for _ in 0..300 {
+            filter.remove(hash);
+        }
+        assert!(filter.might_contain(hash));
+    }
+}
@@ -0,0 +1,3060 @@
+use std::{cmp::Reverse, collections::HashMap, iter, slice, sync::Arc};
+
+use itertools::Itertools;
+
+use crate::{
+    css::{Color, Unit, Value},
+    style::PropertyMap,
+};
+
+/// A length in px, or a percentage of a reference length that is only known during layout.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum LengthPercentage {
+    Px(f32),
+    Percent(f32),
+}
+
+/// A `LengthPercentage`, or `auto`.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum LengthPercentageAuto {
+    Auto,
+    Px(f32),
+    Percent(f32),
+}
+
+/// A `LengthPercentage`, or `none`, as used by the maximum sizes.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum LengthPercentageNone {
+    None,
+    Px(f32),
+    Percent(f32),
+}
+
+impl LengthPercentage {
+    /// Returns the length in px, resolving percentages against `reference`.
+    pub fn resolve(self, reference: f32) -> f32 {
+        match self {
+            LengthPercentage::Px(px) => px,
+            LengthPercentage::Percent(percent) => reference * percent / 100.0,
+        }
+    }
+}
+
+impl LengthPercentageAuto {
+    /// Returns the length in px, resolving percentages against `reference`, or `None` for
+    /// `auto`.
+    pub fn resolve(self, reference: f32) -> Option<f32> {
+        match self {
+            LengthPercentageAuto::Auto => None,
+            LengthPercentageAuto::Px(px) => Some(px),
+            LengthPercentageAuto::Percent(percent) => Some(reference * percent / 100.0),
+        }
+    }
+}
+
+impl LengthPercentageNone {
+    /// Returns the length in px, resolving percentages against `reference`, or `None` for
+    /// `none`.
+    pub fn resolve(self, reference: f32) -> Option<f32> {
+        match self {
+            LengthPercentageNone::None => None,
+            LengthPercentageNone::Px(px) => Some(px),
+            LengthPercentageNone::Percent(percent) => Some(reference * percent / 100.0),
+        }
+    }
+}
+
+/// An enum whose values are CSS keywords.
+trait Keyword: Sized {
+    /// Returns the value for `keyword`, which is lowercase.
+    fn from_keyword(keyword: &str) -> Option<Self>;
+}
+
+/// A computed value that can be serialized like `getComputedStyle` does.
+trait ToCss {
+    fn to_css(&self) -> String;
+}
+
+/// Declares an enum whose variants are the given CSS keywords.
+macro_rules! keyword_enum {
+    (
+        $(#[$doc:meta])*
+        pub enum $Name:ident {
+            $($(#[$variant_doc:meta])* $Variant:ident = $keyword:literal,)*
+        }
+    ) => {
+        $(#[$doc])*
+        #[derive(Clone, Copy, PartialEq, Eq, Debug)]
+        pub enum $Name {
+            $($(#[$variant_doc])* $Variant,)*
+        }
+
+        impl Keyword for $Name {
+            fn from_keyword(keyword: &str) -> Option<Self> {
+                match keyword {
+                    $($keyword => Some($Name::$Variant),)*
+                    _ => None,
+                }
+            }
+        }
+
+        impl ToCss for $Name {
+            fn to_css(&self) -> String {
+                let keyword = match *self {
+                    $($Name::$Variant => $keyword,)*
+                };
+                String::from(keyword)
+            }
+        }
+    };
+}
+
+/// The `display` property, in the model of CSS Display Level 3.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Display {
+    /// The element and its descendants generate no boxes.
+    None,
+    /// The element generates no box, but its children do, as if they replaced it.
+    Contents,
+    /// The element generates a principal box, with the given outer and inner display types and
+    /// possibly a marker box.
+    Box {
+        outside: DisplayOutside,
+        inside: DisplayInside,
+        list_item: bool,
+    },
+    /// The element has a layout-specific role inside a table or ruby.
+    Internal(DisplayInternal),
+}
+
+/// The outer display type: how a box takes part in its parent's formatting context.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum DisplayOutside {
+    Block,
+    Inline,
+    RunIn,
+}
+
+/// The inner display type: the formatting context a box establishes for its contents.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum DisplayInside {
+    /// Block and inline layout, establishing a new block formatting context only if needed.
+    Flow,
+    /// Block and inline layout in a new block formatting context.
+    FlowRoot,
+    Table,
+    Flex,
+    Grid,
+    Ruby,
+}
+
+/// The display types of boxes that only make sense inside a table or a ruby.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum DisplayInternal {
+    TableRowGroup,
+    TableHeaderGroup,
+    TableFooterGroup,
+    TableRow,
+    TableCell,
+    TableColumnGroup,
+    TableColumn,
+    TableCaption,
+    RubyBase,
+    RubyText,
+    RubyBaseContainer,
+    RubyTextContainer,
+}
+
+impl Display {
+    pub const BLOCK: Display = Display::new(DisplayOutside::Block, DisplayInside::Flow);
+    pub const INLINE: Display = Display::new(DisplayOutside::Inline, DisplayInside::Flow);
+    pub const INLINE_BLOCK: Display = Display::new(DisplayOutside::Inline, DisplayInside::FlowRoot);
+    pub const FLOW_ROOT: Display = Display::new(DisplayOutside::Block, DisplayInside::FlowRoot);
+    pub const FLEX: Display = Display::new(DisplayOutside::Block, DisplayInside::Flex);
+    pub const GRID: Display = Display::new(DisplayOutside::Block, DisplayInside::Grid);
+    pub const TABLE: Display = Display::new(DisplayOutside::Block, DisplayInside::Table);
+
+    /// Returns the display of a box with the given display types that isn't a list item.
+    pub const fn new(outside: DisplayOutside, inside: DisplayInside) -> Display {
+        Display::Box {
+            outside,
+            inside,
+            list_item: false,
+        }
+    }
+
+    /// Returns the outer display type, if the element generates a box with one.
+    pub fn outside(self) -> Option<DisplayOutside> {
+        match self {
+            Display::Box { outside, .. } => Some(outside),
+            _ => None,
+        }
+    }
+
+    /// Returns the inner display type, if the element generates a box with one.
+    pub fn inside(self) -> Option<DisplayInside> {
+        match self {
+            Display::Box { inside, .. } => Some(inside),
+            _ => None,
+        }
+    }
+
+    /// Returns true if the element generates a block-level box.
+    pub fn is_block_level(self) -> bool {
+        self.outside() == Some(DisplayOutside::Block)
+    }
+
+    /// Returns true if the element generates an inline-level box.
+    pub fn is_inline_level(self) -> bool {
+        self.outside() == Some(DisplayOutside::Inline)
+    }
+
+    /// Returns the display with a block-level outer display type, as computed for floats,
+    /// absolutely positioned boxes, flex and grid items, and the root element.
+    ///
+    /// Inline-level boxes become their block-level equivalents, e.g. `inline-block` becomes
+    /// `flow-root` and `inline-flex` becomes `flex`, and internal table and ruby boxes become
+    /// `block`.
+    pub fn blockify(self) -> Display {
+        match self {
+            Display::None | Display::Contents => self,
+            Display::Box {
+                inside, list_item, ..
+            } => Display::Box {
+                outside: DisplayOutside::Block,
+                inside,
+                list_item,
+            },
+            Display::Internal(_) => Display::BLOCK,
+        }
+    }
+}
+
+/// Parses the keywords of `display`, either a single legacy keyword like `inline-block` or up to
+/// three keywords giving the outer and inner display types and `list-item`, in any order.
+fn display(value: &Value, _: &Context) -> Option<Display> {
+    let keywords = match value {
+        Value::Keyword(keyword) => vec![keyword.to_ascii_lowercase()],
+        Value::List(values) => values
+            .iter()
+            .map(|value| match value {
+                Value::Keyword(keyword) => Some(keyword.to_ascii_lowercase()),
+                _ => None,
+            })
+            .collect::<Option<Vec<_>>>()?,
+        _ => return None,
+    };
+    if let [keyword] = keywords.as_slice() {
+        let display = match keyword.as_str() {
+            "none" => Display::None,
+            "contents" => Display::Contents,
+            "inline-block" => Display::INLINE_BLOCK,
+            "inline-table" => Display::new(DisplayOutside::Inline, DisplayInside::Table),
+            "inline-flex" => Display::new(DisplayOutside::Inline, DisplayInside::Flex),
+            "inline-grid" => Display::new(DisplayOutside::Inline, DisplayInside::Grid),
+            "table-row-group" => Display::Internal(DisplayInternal::TableRowGroup),
+            "table-header-group" => Display::Internal(DisplayInternal::TableHeaderGroup),
+            "table-footer-group" => Display::Internal(DisplayInternal::TableFooterGroup),
+            "table-row" => Display::Internal(DisplayInternal::TableRow),
+            "table-cell" => Display::Internal(DisplayInternal::TableCell),
+            "table-column-group" => Display::Internal(DisplayInternal::TableColumnGroup),
+            "table-column" => Display::Internal(DisplayInternal::TableColumn),
+            "table-caption" => Display::Internal(DisplayInternal::TableCaption),
+            "ruby-base" => Display::Internal(DisplayInternal::RubyBase),
+            "ruby-text" => Display::Internal(DisplayInternal::RubyText),
+            "ruby-base-container" => Display::Internal(DisplayInternal::RubyBaseContainer),
+            "ruby-text-container" => Display::Internal(DisplayInternal::RubyTextContainer),
+            _ => return display_types(&keywords),
+        };
+        return Some(display);
+    }
+    display_types(&keywords)
+}
+
+/// Parses the multi-keyword syntax of `display`, in which each keyword may appear once.
+fn display_types(keywords: &[String]) -> Option<Display> {
+    let (mut outside, mut inside, mut list_item) = (None, None, false);
+    for keyword in keywords {
+        let outer = match keyword.as_str() {
+            "block" => Some(DisplayOutside::Block),
+            "inline" => Some(DisplayOutside::Inline),
+            "run-in" => Some(DisplayOutside::RunIn),
+            _ => None,
+        };
+        let inner = match keyword.as_str() {
+            "flow" => Some(DisplayInside::Flow),
+            "flow-root" => Some(DisplayInside::FlowRoot),
+            "table" => Some(DisplayInside::Table),
+            "flex" => Some(DisplayInside::Flex),
+            "grid" => Some(DisplayInside::Grid),
+            "ruby" => Some(DisplayInside::Ruby),
+            _ => None,
+        };
+        match (outer, inner) {
+            (Some(outer), _) if outside.is_none() => outside = Some(outer),
+            (_, Some(inner)) if inside.is_none() => inside = Some(inner),
+            (None, None) if keyword == "list-item" && !list_item => list_item = true,
+            _ => return None,
+        }
+    }
+    // Only flow layout can have a marker box.
+    if list_item
+        && !matches!(
+            inside,
+            None | Some(DisplayInside::Flow | DisplayInside::FlowRoot)
+        )
+    {
+        return None;
+    }
+    let inside = inside.unwrap_or(DisplayInside::Flow);
+    let outside = outside.unwrap_or(match inside {
+        DisplayInside::Ruby => DisplayOutside::Inline,
+        _ => DisplayOutside::Block,
+    });
+    Some(Display::Box {
+        outside,
+        inside,
+        list_item,
+    })
+}
+
+keyword_enum! {
+    /// The `box-sizing` property: which box `width` and `height` size.
+    pub enum BoxSizing {
+        ContentBox = "content-box",
+        BorderBox = "border-box",
+    }
+}
+
+keyword_enum! {
+    /// The `overflow` property.
+    pub enum Overflow {
+        Visible = "visible",
+        Hidden = "hidden",
+        Clip = "clip",
+        Scroll = "scroll",
+        Auto = "auto",
+    }
+}
+
+keyword_enum! {
+    /// The `container-type` property.
+    pub enum ContainerType {
+        Normal = "normal",
+        Size = "size",
+        InlineSize = "inline-size",
+    }
+}
+
+keyword_enum! {
+    /// The `white-space` property.
+    pub enum WhiteSpace {
+        Normal = "normal",
+        Pre = "pre",
+        Nowrap = "nowrap",
+        PreWrap = "pre-wrap",
+        BreakSpaces = "break-spaces",
+        PreLine = "pre-line",
+    }
+}
+
+impl WhiteSpace {
+    /// Returns true if sequences of spaces and tabs collapse into a single space.
+    pub fn collapses_spaces(self) -> bool {
+        matches!(
+            self,
+            WhiteSpace::Normal | WhiteSpace::Nowrap | WhiteSpace::PreLine
+        )
+    }
+
+    /// Returns true if newlines are preserved as forced line breaks, rather than collapsing like
+    /// spaces.
+    pub fn preserves_newlines(self) -> bool {
+        !matches!(self, WhiteSpace::Normal | WhiteSpace::Nowrap)
+    }
+
+    /// Returns true if lines may wrap at soft wrap opportunities.
+    pub fn wraps(self) -> bool {
+        !matches!(self, WhiteSpace::Pre | WhiteSpace::Nowrap)
+    }
+}
+
+keyword_enum! {
+    /// The `word-break` property.
+    pub enum WordBreak {
+        Normal = "normal",
+        BreakAll = "break-all",
+        KeepAll = "keep-all",
+    }
+}
+
+keyword_enum! {
+    /// The `overflow-wrap` property.
+    pub enum OverflowWrap {
+        Normal = "normal",
+        /// Breaks words that don't fit on a line of their own, without counting these breaks
+        /// towards the min-content size.
+        BreakWord = "break-word",
+        Anywhere = "anywhere",
+    }
+}
+
+keyword_enum! {
+    /// The `text-align` property.
+    pub enum TextAlign {
+        Start = "start",
+        End = "end",
+        Left = "left",
+        Right = "right",
+        Center = "center",
+        Justify = "justify",
+    }
+}
+
+keyword_enum! {
+    /// The `font-style` property.
+    pub enum FontStyle {
+        Normal = "normal",
+        Italic = "italic",
+        Oblique = "oblique",
+    }
+}
+
+keyword_enum! {
+    /// The `border-*-style` properties.
+    pub enum BorderStyle {
+        None = "none",
+        Hidden = "hidden",
+        Dotted = "dotted",
+        Dashed = "dashed",
+        Solid = "solid",
+        Double = "double",
+        Groove = "groove",
+        Ridge = "ridge",
+        Inset = "inset",
+        Outset = "outset",
+    }
+}
+
+keyword_enum! {
+    /// The `table-layout` property: whether column widths depend on the contents of all the
+    /// cells or only on the table's width and its first row.
+    pub enum TableLayout {
+        Auto = "auto",
+        Fixed = "fixed",
+    }
+}
+
+keyword_enum! {
+    /// The `border-collapse` property: whether cells have borders of their own with space between
+    /// them, or share borders along the lines of the table grid.
+    pub enum BorderCollapse {
+        Separate = "separate",
+        Collapse = "collapse",
+    }
+}
+
+keyword_enum! {
+    /// The `caption-side` property.
+    pub enum CaptionSide {
+        Top = "top",
+        Bottom = "bottom",
+    }
+}
+
+keyword_enum! {
+    /// The `position` property.
+    pub enum Position {
+        Static = "static",
+        Relative = "relative",
+        Absolute = "absolute",
+        Fixed = "fixed",
+        Sticky = "sticky",
+    }
+}
+
+keyword_enum! {
+    /// The `float` property.
+    pub enum Float {
+        None = "none",
+        Left = "left",
+        Right = "right",
+        InlineStart = "inline-start",
+        InlineEnd = "inline-end",
+    }
+}
+
+keyword_enum! {
+    /// The `clear` property.
+    pub enum Clear {
+        None = "none",
+        Left = "left",
+        Right = "right",
+        Both = "both",
+        InlineStart = "inline-start",
+        InlineEnd = "inline-end",
+    }
+}
+
+keyword_enum! {
+    /// The `flex-direction` property.
+    pub enum FlexDirection {
+        Row = "row",
+        RowReverse = "row-reverse",
+        Column = "column",
+        ColumnReverse = "column-reverse",
+    }
+}
+
+keyword_enum! {
+    /// The `flex-wrap` property.
+    pub enum FlexWrap {
+        Nowrap = "nowrap",
+        Wrap = "wrap",
+        WrapReverse = "wrap-reverse",
+    }
+}
+
+keyword_enum! {
+    /// The `justify-content` and `align-content` properties.
+    pub enum ContentAlignment {
+        Normal = "normal",
+        Start = "start",
+        End = "end",
+        FlexStart = "flex-start",
+        FlexEnd = "flex-end",
+        Center = "center",
+        SpaceBetween = "space-between",
+        SpaceAround = "space-around",
+        SpaceEvenly = "space-evenly",
+        Stretch = "stretch",
+    }
+}
+
+keyword_enum! {
+    /// The `align-items` and `align-self` properties. `Auto` is only valid for `align-self`.
+    pub enum ItemAlignment {
+        Auto = "auto",
+        Normal = "normal",
+        Stretch = "stretch",
+        Start = "start",
+        End = "end",
+        FlexStart = "flex-start",
+        FlexEnd = "flex-end",
+        Center = "center",
+        Baseline = "baseline",
+    }
+}
+
+keyword_enum! {
+    /// The `grid-auto-flow` property.
+    pub enum GridAutoFlow {
+        Row = "row",
+        Column = "column",
+        RowDense = "row dense",
+        ColumnDense = "column dense",
+    }
+}
+
+/// The `line-height` property.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum LineHeight {
+    Normal,
+    /// A multiple of the element's font size, inherited as the multiple.
+    Number(f32),
+    Px(f32),
+}
+
+/// The `vertical-align` property.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum VerticalAlign {
+    Baseline,
+    Sub,
+    Super,
+    TextTop,
+    TextBottom,
+    Middle,
+    Top,
+    Bottom,
+    /// A shift of the baseline in px, upwards.
+    Px(f32),
+    /// A shift of the baseline by a percentage of the line height, upwards.
+    Percent(f32),
+}
+
+/// A family in the `font-family` property, which is a list of them in order of preference.
+#[derive(Clone, PartialEq, Debug)]
+pub enum FontFamily {
+    Serif,
+    SansSerif,
+    Monospace,
+    Cursive,
+    Fantasy,
+    SystemUi,
+    Named(Arc<str>),
+}
+
+/// A grid line a grid item is placed against, in `grid-row-start` and the like.
+#[derive(Clone, PartialEq, Debug)]
+pub enum GridLine {
+    Auto,
+    /// The nth line, or the nth line with the given name, counting from the end if negative.
+    /// Never 0.
+    Line(i32, Option<Arc<str>>),
+    /// A span of n tracks, or up to the nth line with the given name. Always positive.
+    Span(i32, Option<Arc<str>>),
+    /// The line named after a named area, or else the first line with the name.
+    Name(Arc<str>),
+}
+
+/// The size of a grid track, or a bound of a `minmax()` track size.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum TrackBreadth {
+    Px(f32),
+    Percent(f32),
+    /// A share of the free space, in `fr`. Never a minimum.
+    Fr(f32),
+    MinContent,
+    MaxContent,
+    Auto,
+}
+
+/// The sizing function of a grid track.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum TrackSize {
+    Breadth(TrackBreadth),
+    MinMax(TrackBreadth, TrackBreadth),
+    /// `fit-content()`, with the limit of the max-content size.
+    FitContent(LengthPercentage),
+}
+
+impl TrackSize {
+    /// Returns the minimum sizing function of the track. That of a flexible track is `auto`.
+    pub fn min(self) -> TrackBreadth {
+        match self {
+            TrackSize::Breadth(TrackBreadth::Fr(_)) | TrackSize::FitContent(_) => {
+                TrackBreadth::Auto
+            }
+            TrackSize::Breadth(breadth) | TrackSize::MinMax(breadth, _) => breadth,
+        }
+    }
+
+    /// Returns the maximum sizing function of the track, which is `max-content` for
+    /// `fit-content()`.
+    pub fn max(self) -> TrackBreadth {
+        match self {
+            TrackSize::Breadth(breadth) | TrackSize::MinMax(_, breadth) => breadth,
+            TrackSize::FitContent(_) => TrackBreadth::MaxContent,
+        }
+    }
+}
+
+/// The number of repetitions of `repeat()` in a track list.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum RepeatCount {
+    Count(u32),
+    /// As many as fit in the grid container, of which empty ones are kept.
+    AutoFill,
+    /// As many as fit in the grid container, of which empty ones are dropped.
+    AutoFit,
+}
+
+/// An entry of the track list of `grid-template-rows` or `grid-template-columns`.
+#[derive(Clone, PartialEq, Debug)]
+pub enum TrackListEntry {
+    /// The names of the line between the tracks around the entry.
+    LineNames(Arc<[Arc<str>]>),
+    Track(TrackSize),
+    /// `repeat()`, with the line names and tracks it repeats.
+    Repeat(RepeatCount, Arc<[TrackListEntry]>),
+}
+
+/// The named areas of `grid-template-areas`.
+#[derive(Clone, PartialEq, Debug)]
+pub struct TemplateAreas {
+    /// The strings of the rows of the template, as specified.
+    pub rows: Vec<String>,
+    /// The number of columns of the template.
+    pub columns: usize,
+    pub areas: Vec<NamedArea>,
+}
+
+/// A named area of a grid, with the numbers of the lines it lies between, from 1.
+#[derive(Clone, PartialEq, Debug)]
+pub struct NamedArea {
+    pub name: Arc<str>,
+    pub rows: (i32, i32),
+    pub columns: (i32, i32),
+}
+
+/// The values the parsers of the property values can depend on.
+struct Context<'a> {
+    /// The parent's computed style.
+    parent: &'a ComputedStyle,
+    /// The computed `color` of the element, which `currentcolor` stands for.
+    color: Color,
+    /// The computed `font-size` of the element, or of the parent while computing `font-size`.
+    font_size: f32,
+    /// The computed `font-size` of the root element, or the initial one while computing it.
+    root_font_size: f32,
+}
+
+/// Returns true for `inherited`, and false for `reset`.
+macro_rules! is_inherited {
+    (inherited) => {
+        true
+    };
+    (reset) => {
+        false
+    };
+}
+
+/// Serializes the computed value of a property parsed by `$parse`. Lengths held as plain numbers
+/// get their unit back.
+macro_rules! to_css {
+    (font_size, $value:expr) => {
+        format!("{}px", $value)
+    };
+    (font_stretch, $value:expr) => {
+        format!("{}%", $value)
+    };
+    (border_width, $value:expr) => {
+        format!("{}px", $value)
+    };
+    (image, $value:expr) => {
+        match $value {
+            Some(url) => format!("{}", Value::Url(url.to_string())),
+            None => String::from("none"),
+        }
+    };
+    ($parse:ident, $value:expr) => {
+        ToCss::to_css($value)
+    };
+}
+
+/// Declares `ComputedStyle` and its groups of properties, with an accessor for every property.
+///
+/// Each property is declared as `"css-name" => field: Type = initial, parse, inherited` where
+/// `parse` is a function that parses a specified value into a computed value, and `inherited`
+/// is `inherited` or `reset`.
+macro_rules! computed_style {
+    ($(
+        $(#[$group_doc:meta])*
+        $group:ident: $Group:ident {
+            $(
+                $(#[$doc:meta])*
+                $name:literal => $property:ident: $Type:ty = $initial:expr, $parse:ident, $inherited:ident;
+            )*
+        }
+    )*) => {
+        $(
+            $(#[$group_doc])*
+            #[derive(Clone, PartialEq, Debug)]
+            pub struct $Group {
+                $($(#[$doc])* pub $property: $Type,)*
+            }
+
+            impl Default for $Group {
+                /// Returns the initial values of the group's properties.
+                fn default() -> $Group {
+                    $Group {
+                        $($property: $initial,)*
+                    }
+                }
+            }
+        )*
+
+        /// The computed values of the properties of a node, in groups of related properties.
+        #[derive(Clone, PartialEq, Debug)]
+        pub struct ComputedStyle {
+            $($(#[$group_doc])* pub $group: $Group,)*
+            /// The computed font size of the root element, which `rem` lengths are relative to.
+            root_font_size: f32,
+            /// The custom properties by name, which are all inherited.
+            custom_properties: Arc<HashMap<String, Value>>,
+        }
+
+        impl Default for ComputedStyle {
+            /// Returns the initial values of all properties.
+            fn default() -> ComputedStyle {
+                ComputedStyle {
+                    $($group: $Group::default(),)*
+                    root_font_size: MEDIUM_FONT_SIZE,
+                    custom_properties: Arc::default(),
+                }
+            }
+        }
+
+        impl ComputedStyle {
+            $($(
+                $(#[$doc])*
+                pub fn $property(&self) -> $Type {
+                    Clone::clone(&self.$group.$property)
+                }
+            )*)*
+
+            /// Returns the computed value of the longhand property `name`, serialized.
+            fn longhand_value(&self, name: &str) -> Option<String> {
+                match name {
+                    $($(
+                        $name => Some(to_css!($parse, &self.$group.$property)),
+                    )*)*
+                    _ => None,
+                }
+            }
+
+            /// Returns the style of a child of `parent` that has no declarations: inherited
+            /// properties take the parent's values, and the others their initial values.
+            pub fn inherit(parent: &ComputedStyle) -> ComputedStyle {
+                ComputedStyle {
+                    $($group: $Group {
+                        $($property: match is_inherited!($inherited) {
+                            true => Clone::clone(&parent.$group.$property),
+                            false => $initial,
+                        },)*
+                    },)*
+                    root_font_size: parent.root_font_size,
+                    custom_properties: parent.custom_properties.clone(),
+                }
+            }
+
+            /// Sets the longhand property `name` to the computed value of `value`. Returns false,
+            /// leaving the style unchanged, if the property is unknown or the value is invalid.
+            fn set_longhand(&mut self, name: &str, value: &Value, parent: &ComputedStyle) -> bool {
+                let context = Context {
+                    parent,
+                    color: self.text.color,
+                    font_size: self.font.font_size,
+                    root_font_size: self.root_font_size,
+                };
+                let wide_keyword = css_wide_keyword(value);
+                match name {
+                    $($(
+                        $name => {
+                            let inherit = match wide_keyword {
+                                Some(CssWideKeyword::Inherit) => true,
+                                Some(CssWideKeyword::Initial) => false,
+                                Some(CssWideKeyword::Unset) => is_inherited!($inherited),
+                                None => match $parse(value, &context) {
+                                    Some(computed) => {
+                                        self.$group.$property = computed;
+                                        return true;
+                                    }
+                                    None => return false,
+                                },
+                            };
+                            self.$group.$property = match inherit {
+                                true => Clone::clone(&parent.$group.$property),
+                                false => $initial,
+                            };
+                            true
+                        }
+                    )*)*
+                    _ => false,
+                }
+            }
+        }
+    };
+}
+
+computed_style! {
+    /// The properties of the box an element generates.
+    box_: BoxProperties {
+        /// How the element generates boxes.
+        "display" => display: Display = Display::INLINE, display, reset;
+        /// Which box `width` and `height` size.
+        "box-sizing" => box_sizing: BoxSizing = BoxSizing::ContentBox, keyword, reset;
+        "width" => width: LengthPercentageAuto = LengthPercentageAuto::Auto, length_percentage_auto, reset;
+        "height" => height: LengthPercentageAuto = LengthPercentageAuto::Auto, length_percentage_auto, reset;
+        "min-width" => min_width: LengthPercentageAuto = LengthPercentageAuto::Auto, length_percentage_auto, reset;
+        "min-height" => min_height: LengthPercentageAuto = LengthPercentageAuto::Auto, length_percentage_auto, reset;
+        "max-width" => max_width: LengthPercentageNone = LengthPercentageNone::None, length_percentage_none, reset;
+        "max-height" => max_height: LengthPercentageNone = LengthPercentageNone::None, length_percentage_none, reset;
+        "margin-top" => margin_top: LengthPercentageAuto = LengthPercentageAuto::Px(0.0), length_percentage_auto, reset;
+        "margin-right" => margin_right: LengthPercentageAuto = LengthPercentageAuto::Px(0.0), length_percentage_auto, reset;
+        "margin-bottom" => margin_bottom: LengthPercentageAuto = LengthPercentageAuto::Px(0.0), length_percentage_auto, reset;
+        "margin-left" => margin_left: LengthPercentageAuto = LengthPercentageAuto::Px(0.0), length_percentage_auto, reset;
+        "padding-top" => padding_top: LengthPercentage = LengthPercentage::Px(0.0), non_negative_length_percentage, reset;
+        "padding-right" => padding_right: LengthPercentage = LengthPercentage::Px(0.0), non_negative_length_percentage, reset;
+        "padding-bottom" => padding_bottom: LengthPercentage = LengthPercentage::Px(0.0), non_negative_length_percentage, reset;
+        "padding-left" => padding_left: LengthPercentage = LengthPercentage::Px(0.0), non_negative_length_percentage, reset;
+        "overflow" => overflow: Overflow = Overflow::Visible, keyword, reset;
+        /// The opacity, between 0 and 1.
+        "opacity" => opacity: f32 = 1.0, alpha, reset;
+        "container-type" => container_type: ContainerType = ContainerType::Normal, keyword, reset;
+        "container-name" => container_name: Option<Arc<str>> = None, name, reset;
+    }
+
+    /// The properties of the text inside an element.
+    text: TextProperties {
+        /// The foreground color, which `currentcolor` stands for.
+        "color" => color: Color = BLACK, color, inherited;
+        "white-space" => white_space: WhiteSpace = WhiteSpace::Normal, keyword, inherited;
+        "text-align" => text_align: TextAlign = TextAlign::Start, keyword, inherited;
+        "word-break" => word_break: WordBreak = WordBreak::Normal, keyword, inherited;
+        "overflow-wrap" => overflow_wrap: OverflowWrap = OverflowWrap::Normal, keyword, inherited;
+        "line-height" => line_height: LineHeight = LineHeight::Normal, line_height, inherited;
+        "vertical-align" => vertical_align: VerticalAlign = VerticalAlign::Baseline, vertical_align, reset;
+    }
+
+    /// The properties selecting the font text is set in.
+    font: FontProperties {
+        /// The families to take glyphs from, in order of preference.
+        "font-family" => font_family: Arc<[FontFamily]> = Arc::from([FontFamily::Serif]), font_family, inherited;
+        /// The font size in px.
+        "font-size" => font_size: f32 = MEDIUM_FONT_SIZE, font_size, inherited;
+        /// The font weight, from 1 to 1000.
+        "font-weight" => font_weight: u16 = 400, font_weight, inherited;
+        "font-style" => font_style: FontStyle = FontStyle::Normal, keyword, inherited;
+        /// The font width, as a percentage of the normal width.
+        "font-stretch" => font_stretch: f32 = 100.0, font_stretch, inherited;
+    }
+
+    /// The properties of the background of an element.
+    background: BackgroundProperties {
+        "background-color" => background_color: Color = TRANSPARENT, color, reset;
+        /// The URL of the background image, if any.
+        "background-image" => background_image: Option<Arc<str>> = None, image, reset;
+    }
+
+    /// The properties of the borders of an element. The widths are in px, and are 0 for borders
+    /// whose style is `none` or `hidden`.
+    border: BorderProperties {
+        "border-top-width" => border_top_width: f32 = MEDIUM_BORDER_WIDTH, border_width, reset;
+        "border-right-width" => border_right_width: f32 = MEDIUM_BORDER_WIDTH, border_width, reset;
+        "border-bottom-width" => border_bottom_width: f32 = MEDIUM_BORDER_WIDTH, border_width, reset;
+        "border-left-width" => border_left_width: f32 = MEDIUM_BORDER_WIDTH, border_width, reset;
+        "border-top-style" => border_top_style: BorderStyle = BorderStyle::None, keyword, reset;
+        "border-right-style" => border_right_style: BorderStyle = BorderStyle::None, keyword, reset;
+        "border-bottom-style" => border_bottom_style: BorderStyle = BorderStyle::None, keyword, reset;
+        "border-left-style" => border_left_style: BorderStyle = BorderStyle::None, keyword, reset;
+        "border-top-color" => border_top_color: Color = BLACK, color, reset;
+        "border-right-color" => border_right_color: Color = BLACK, color, reset;
+        "border-bottom-color" => border_bottom_color: Color = BLACK, color, reset;
+        "border-left-color" => border_left_color: Color = BLACK, color, reset;
+    }
+
+    /// The properties that take an element out of normal flow or offset it.
+    position: PositionProperties {
+        "position" => position: Position = Position::Static, keyword, reset;
+        "top" => top: LengthPercentageAuto = LengthPercentageAuto::Auto, length_percentage_auto, reset;
+        "right" => right: LengthPercentageAuto = LengthPercentageAuto::Auto, length_percentage_auto, reset;
+        "bottom" => bottom: LengthPercentageAuto = LengthPercentageAuto::Auto, length_percentage_auto, reset;
+        "left" => left: LengthPercentageAuto = LengthPercentageAuto::Auto, length_percentage_auto, reset;
+        /// The stack level, or `None` for `auto`.
+        "z-index" => z_index: Option<i32> = None, z_index, reset;
+        "float" => float: Float = Float::None, keyword, reset;
+        "clear" => clear: Clear = Clear::None, keyword, reset;
+    }
+
+    /// The properties of flex containers and flex items, and the box alignment properties they
+    /// share with grids.
+    flex: FlexProperties {
+        "flex-direction" => flex_direction: FlexDirection = FlexDirection::Row, keyword, reset;
+        "flex-wrap" => flex_wrap: FlexWrap = FlexWrap::Nowrap, keyword, reset;
+        "flex-grow" => flex_grow: f32 = 0.0, non_negative_number, reset;
+        "flex-shrink" => flex_shrink: f32 = 1.0, non_negative_number, reset;
+        "flex-basis" => flex_basis: LengthPercentageAuto = LengthPercentageAuto::Auto, length_percentage_auto, reset;
+        "order" => order: i32 = 0, integer, reset;
+        "justify-content" => justify_content: ContentAlignment = ContentAlignment::Normal, keyword, reset;
+        "align-content" => align_content: ContentAlignment = ContentAlignment::Normal, keyword, reset;
+        "align-items" => align_items: ItemAlignment = ItemAlignment::Normal, item_alignment, reset;
+        "align-self" => align_self: ItemAlignment = ItemAlignment::Auto, keyword, reset;
+        "row-gap" => row_gap: LengthPercentage = LengthPercentage::Px(0.0), gap, reset;
+        "column-gap" => column_gap: LengthPercentage = LengthPercentage::Px(0.0), gap, reset;
+    }
+
+    /// The properties of grid containers and grid items.
+    grid: GridProperties {
+        /// The explicit tracks, with the names of the lines between them. Empty for `none`.
+        "grid-template-rows" => grid_template_rows: Arc<[TrackListEntry]> = Arc::from([]), track_list, reset;
+        "grid-template-columns" => grid_template_columns: Arc<[TrackListEntry]> = Arc::from([]), track_list, reset;
+        "grid-template-areas" => grid_template_areas: Option<Arc<TemplateAreas>> = None, template_areas, reset;
+        /// The sizes of the implicit tracks, repeated in order.
+        "grid-auto-rows" => grid_auto_rows: Arc<[TrackSize]> = Arc::from([AUTO_TRACK]), track_sizes, reset;
+        "grid-auto-columns" => grid_auto_columns: Arc<[TrackSize]> = Arc::from([AUTO_TRACK]), track_sizes, reset;
+        "grid-auto-flow" => grid_auto_flow: GridAutoFlow = GridAutoFlow::Row, grid_auto_flow, reset;
+        "grid-row-start" => grid_row_start: GridLine = GridLine::Auto, grid_line, reset;
+        "grid-row-end" => grid_row_end: GridLine = GridLine::Auto, grid_line, reset;
+        "grid-column-start" => grid_column_start: GridLine = GridLine::Auto, grid_line, reset;
+        "grid-column-end" => grid_column_end: GridLine = GridLine::Auto, grid_line, reset;
+        "justify-items" => justify_items: ItemAlignment = ItemAlignment::Normal, item_alignment, reset;
+        "justify-self" => justify_self: ItemAlignment = ItemAlignment::Auto, keyword, reset;
+    }
+
+    /// The properties of tables and their captions.
+    table: TableProperties {
+        "table-layout" => table_layout: TableLayout = TableLayout::Auto, keyword, reset;
+        "border-collapse" => border_collapse: BorderCollapse = BorderCollapse::Separate, keyword, inherited;
+        /// The horizontal and vertical spacing between the borders of adjacent cells, in px.
+        "border-spacing" => border_spacing: (f32, f32) = (0.0, 0.0), border_spacing, inherited;
+        "caption-side" => caption_side: CaptionSide = CaptionSide::Top, keyword, inherited;
+    }
+}
+
+const BLACK: Color = Color::new(0, 0, 0, 255);
+const TRANSPARENT: Color = Color::new(0, 0, 0, 0);
+
+/// The initial size of implicit grid tracks.
+const AUTO_TRACK: TrackSize = TrackSize::Breadth(TrackBreadth::Auto);
+
+/// The font size of the `medium` keyword, in px.
+const MEDIUM_FONT_SIZE: f32 = 16.0;
+
+/// The border width of the `medium` keyword, in px.
+const MEDIUM_BORDER_WIDTH: f32 = 3.0;
+
+/// Returns the longhands a shorthand property sets, in the order `ComputedStyle::expand` gives
+/// them values, or `None` if `name` isn't a shorthand.
+fn longhands(name: &str) -> Option<&'static [&'static str]> {
+    Some(match name {
+        "margin" => &["margin-top", "margin-right", "margin-bottom", "margin-left"],
+        "padding" => &[
+            "padding-top",
+            "padding-right",
+            "padding-bottom",
+            "padding-left",
+        ],
+        "border-width" => &[
+            "border-top-width",
+            "border-right-width",
+            "border-bottom-width",
+            "border-left-width",
+        ],
+        "border-style" => &[
+            "border-top-style",
+            "border-right-style",
+            "border-bottom-style",
+            "border-left-style",
+        ],
+        "border-color" => &[
+            "border-top-color",
+            "border-right-color",
+            "border-bottom-color",
+            "border-left-color",
+        ],
+        "border" => &[
+            "border-top-width",
+            "border-right-width",
+            "border-bottom-width",
+            "border-left-width",
+            "border-top-style",
+            "border-right-style",
+            "border-bottom-style",
+            "border-left-style",
+            "border-top-color",
+            "border-right-color",
+            "border-bottom-color",
+            "border-left-color",
+        ],
+        "border-top" => &["border-top-width", "border-top-style", "border-top-color"],
+        "border-right" => &[
+            "border-right-width",
+            "border-right-style",
+            "border-right-color",
+        ],
+        "border-bottom" => &[
+            "border-bottom-width",
+            "border-bottom-style",
+            "border-bottom-color",
+        ],
+        "border-left" => &[
+            "border-left-width",
+            "border-left-style",
+            "border-left-color",
+        ],
+        "inset" => &["top", "right", "bottom", "left"],
+        "font" => &[
+            "font-style",
+            "font-weight",
+            "font-stretch",
+            "font-size",
+            "line-height",
+            "font-family",
+        ],
+        "background" => &["background-color", "background-image"],
+        "flex" => &["flex-grow", "flex-shrink", "flex-basis"],
+        "flex-flow" => &["flex-direction", "flex-wrap"],
+        "gap" => &["row-gap", "column-gap"],
+        "place-content" => &["align-content", "justify-content"],
+        "place-items" => &["align-items", "justify-items"],
+        "place-self" => &["align-self", "justify-self"],
+        "grid-row" => &["grid-row-start", "grid-row-end"],
+        "grid-column" => &["grid-column-start", "grid-column-end"],
+        "grid-area" => &[
+            "grid-row-start",
+            "grid-column-start",
+            "grid-row-end",
+            "grid-column-end",
+        ],
+        _ => return None,
+    })
+}
+
+/// Expands a declaration of the property `name` into declarations of the longhands it sets, so
+/// that the cascade can pick the winning value of each longhand by the precedence and order of
+/// the declarations that set it. A declaration of a longhand or an unknown property is kept as it
+/// is, and a shorthand with an invalid value expands to nothing.
+pub(crate) fn expand_declaration(name: &str, value: &Value) -> Vec<(String, Value)> {
+    let Some(longhands) = longhands(name) else {
+        return vec![(String::from(name), value.clone())];
+    };
+    // Whether a value fits a shorthand doesn't depend on the style it's checked against.
+    let initial = ComputedStyle::default();
+    initial
+        .longhand_values(name, value, &initial)
+        .map(|values| {
+            longhands
+                .iter()
+                .map(|&longhand| String::from(longhand))
+                .zip(values)
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+impl ComputedStyle {
+    /// Computes the style of an element from its specified values and the computed style of its
+    /// parent, or of the initial values for the root.
+    ///
+    /// Invalid declarations are ignored. The cascade expands shorthands with
+    /// `expand_declaration`, so the specified values of a styled element only hold longhands; a
+    /// shorthand that is given here anyway is applied before the longhands.
+    ///
+    /// The display of the root, floats, absolutely positioned elements and the children of flex
+    /// and grid containers is blockified. A child of a `display: contents` element isn't
+    /// recognized as a flex or grid item.
+    pub fn compute(specified: &PropertyMap, parent: Option<&ComputedStyle>) -> ComputedStyle {
+        let root = parent.is_none();
+        let initial = ComputedStyle::default();
+        let parent = parent.unwrap_or(&initial);
+        let mut style = ComputedStyle::inherit(parent);
+
+        let mut custom = specified
+            .iter()
+            .filter(|(name, _)| name.starts_with("--"))
+            .peekable();
+        if custom.peek().is_some() {
+            let mut custom_properties = (*style.custom_properties).clone();
+            custom_properties.extend(custom.map(|(name, value)| (name.clone(), value.clone())));
+            style.custom_properties = Arc::new(custom_properties);
+        }
+
+        // `currentcolor` depends on `color`, and percentage line heights on the font size.
+        for name in ["color", "font", "font-size"] {
+            if let Some(value) = specified.get(name) {
+                style.set(name, value, parent);
+            }
+        }
+        if root {
+            style.root_font_size = style.font.font_size;
+        }
+        // The border colors are initially `currentcolor`.
+        let border = &mut style.border;
+        border.border_top_color = style.text.color;
+        border.border_right_color = style.text.color;
+        border.border_bottom_color = style.text.color;
+        border.border_left_color = style.text.color;
+
+        let (mut shorthands, longhands): (Vec<_>, Vec<_>) = specified
+            .iter()
+            .filter(|(name, _)| !matches!(name.as_str(), "color" | "font" | "font-size"))
+            .partition(|(name, _)| self::longhands(name).is_some());
+        // Shorthands that set more longhands go first, so that `border-top` wins over `border`.
+        shorthands.sort_by_key(|(name, _)| Reverse(self::longhands(name).map_or(0, <[_]>::len)));
+        for (name, value) in shorthands {
+            style.set(name, value, parent);
+        }
+        for (name, value) in longhands {
+            style.set(name, value, parent);
+        }
+
+        let positioned = matches!(
+            style.position.position,
+            Position::Absolute | Position::Fixed
+        );
+        if positioned {
+            style.position.float = Float::None;
+        }
+        let item = matches!(
+            parent.box_.display.inside(),
+            Some(DisplayInside::Flex | DisplayInside::Grid)
+        );
+        if root || positioned || item || style.position.float != Float::None {
+            style.box_.display = style.box_.display.blockify();
+        }
+
+        let border = &mut style.border;
+        for (width, border_style) in [
+            (&mut border.border_top_width, border.border_top_style),
+            (&mut border.border_right_width, border.border_right_style),
+            (&mut border.border_bottom_width, border.border_bottom_style),
+            (&mut border.border_left_width, border.border_left_style),
+        ] {
+            if matches!(border_style, BorderStyle::None | BorderStyle::Hidden) {
+                *width = 0.0;
+            }
+        }
+        style
+    }
+
+    /// Sets the property `name`, which may be a shorthand, to the computed value of `value`.
+    /// Returns false, leaving the style unchanged, if the property is unknown or the value is
+    /// invalid.
+    fn set(&mut self, name: &str, value: &Value, parent: &ComputedStyle) -> bool {
+        let Some(longhands) = longhands(name) else {
+            return self.set_longhand(name, value, parent);
+        };
+        let Some(values) = self.longhand_values(name, value, parent) else {
+            return false;
+        };
+        for (longhand, value) in longhands.iter().zip(&values) {
+            self.set_longhand(longhand, value, parent);
+        }
+        true
+    }
+
+    /// Returns the values the shorthand `name` set to `value` gives its longhands, in the order
+    /// `longhands` lists them, or `None` if `name` isn't a shorthand or the value is invalid.
+    /// Either all of the longhands are valid or none.
+    fn longhand_values(
+        &self,
+        name: &str,
+        value: &Value,
+        parent: &ComputedStyle,
+    ) -> Option<Vec<Value>> {
+        let longhands = longhands(name)?;
+        let values = match css_wide_keyword(value) {
+            Some(_) => vec![value.clone(); longhands.len()],
+            None => self.expand(name, value, parent)?,
+        };
+        let mut scratch = self.clone();
+        longhands
+            .iter()
+            .zip(&values)
+            .all(|(longhand, value)| scratch.set_longhand(longhand, value, parent))
+            .then_some(values)
+    }
+
+    /// Splits the value of the shorthand `name` into the values of its longhands, in the order
+    /// `longhands` lists them, or returns `None` if it doesn't fit the shorthand's syntax.
+    /// Longhands the value leaves out are reset to their initial values.
+    fn expand(&self, name: &str, value: &Value, parent: &ComputedStyle) -> Option<Vec<Value>> {
+        let values = match value {
+            Value::List(values) => values.as_slice(),
+            value => slice::from_ref(value),
+        };
+        match name {
+            "margin" | "padding" | "border-width" | "border-style" | "border-color" | "inset" => {
+                // Top, right, bottom and left, with the missing sides copied from the opposite.
+                let [top, right, bottom, left] = match values {
+                    [all] => [all; 4],
+                    [vertical, horizontal] => [vertical, horizontal, vertical, horizontal],
+                    [top, horizontal, bottom] => [top, horizontal, bottom, horizontal],
+                    [top, right, bottom, left] => [top, right, bottom, left],
+                    _ => return None,
+                };
+                Some(vec![
+                    top.clone(),
+                    right.clone(),
+                    bottom.clone(),
+                    left.clone(),
+                ])
+            }
+            "gap" | "place-content" | "place-items" | "place-self" => match values {
+                [both] => Some(vec![both.clone(), both.clone()]),
+                [first, second] => Some(vec![first.clone(), second.clone()]),
+                _ => None,
+            },
+            "border" => {
+                let side = ["border-top-width", "border-top-style", "border-top-color"];
+                let [width, style, color] = self.any_order(values, side, parent)?;
+                Some(
+                    [width, style, color]
+                        .into_iter()
+                        .flat_map(|value| iter::repeat_n(value, 4))
+                        .collect(),
+                )
+            }
+            "border-top" | "border-right" | "border-bottom" | "border-left" => {
+                let side = longhands(name)?.try_into().ok()?;
+                Some(self.any_order::<3>(values, side, parent)?.to_vec())
+            }
+            "background" => {
+                let longhands = ["background-color", "background-image"];
+                Some(self.any_order(values, longhands, parent)?.to_vec())
+            }
+            "font" => self.expand_font(value, parent),
+            "flex" => expand_flex(values),
+            "grid-row" | "grid-column" => expand_grid_placement(values, 2),
+            "grid-area" => expand_grid_placement(values, 4),
+            "flex-flow" => {
+                let longhands = ["flex-direction", "flex-wrap"];
+                Some(self.any_order(values, longhands, parent)?.to_vec())
+            }
+            _ => None,
+        }
+    }
+
+    /// Matches each of `values` to the first of `longhands` that it is valid for and no earlier
+    /// value took, and returns the value of each longhand, or `None` if a value is left over.
+    /// Border colors that are left out are `currentcolor`, and other longhands `initial`.
+    fn any_order<const N: usize>(
+        &self,
+        values: &[Value],
+        longhands: [&str; N],
+        parent: &ComputedStyle,
+    ) -> Option<[Value; N]> {
+        let mut scratch = self.clone();
+        let mut matched: [Option<Value>; N] = std::array::from_fn(|_| None);
+        for value in values {
+            let index = (0..N).find(|&i| {
+                matched[i].is_none() && scratch.set_longhand(longhands[i], value, parent)
+            })?;
+            matched[index] = Some(value.clone());
+        }
+        Some(std::array::from_fn(|i| {
+            matched[i].take().unwrap_or_else(|| {
+                let initial = match longhands[i].ends_with("-color") {
+                    true => "currentcolor",
+                    false => "initial",
+                };
+                Value::Keyword(String::from(initial))
+            })
+        }))
+    }
+
+    /// Splits the value of the `font` shorthand: up to one each of a style, a weight and a
+    /// stretch keyword, in any order, then a size, optionally followed by `/` and a line height,
+    /// then the families.
+    fn expand_font(&self, value: &Value, parent: &ComputedStyle) -> Option<Vec<Value>> {
+        // The commas separate the families, so all but the last family are in the first group.
+        let (first, families) = match value {
+            Value::CommaList(groups) => (&groups[0], &groups[1..]),
+            value => (value, &[][..]),
+        };
+        let values = match first {
+            Value::List(values) => values.as_slice(),
+            value => slice::from_ref(value),
+        };
+        let mut scratch = self.clone();
+        let prefix = ["font-style", "font-weight", "font-stretch"];
+        let mut matched: [Option<&Value>; 3] = [None; 3];
+        let mut rest = values;
+        while let [value, tail @ ..] = rest {
+            // `normal` is valid for all three, and resets whichever it stands for.
+            if !is_keyword(value, "normal") {
+                let index = (0..3).find(|&i| {
+                    matched[i].is_none()
+                        // Only keywords stretch a font in the shorthand.
+                        && !(i == 2 && matches!(value, Value::Percentage(_)))
+                        && scratch.set_longhand(prefix[i], value, parent)
+                });
+                match index {
+                    Some(index) => matched[index] = Some(value),
+                    None => break,
+                }
+            }
+            rest = tail;
+        }
+        let (size, line_height, family) = match rest {
+            [size, Value::Delimiter('/'), line_height, family @ ..] => {
+                (size, Some(line_height), family)
+            }
+            [size, family @ ..] => (size, None, family),
+            [] => return None,
+        };
+        let family = match family {
+            [] => return None,
+            [family] => family.clone(),
+            words => Value::List(words.to_vec()),
+        };
+        let family = match families {
+            [] => family,
+            families => {
+                Value::CommaList(iter::once(family).chain(families.iter().cloned()).collect())
+            }
+        };
+        let initial = Value::Keyword(String::from("initial"));
+        let mut expanded = matched
+            .map(|value| value.cloned().unwrap_or_else(|| initial.clone()))
+            .to_vec();
+        expanded.push(size.clone());
+        expanded.push(line_height.cloned().unwrap_or(initial));
+        expanded.push(family);
+        Some(expanded)
+    }
+}
+
+/// Splits the value of the `flex` shorthand: `none`, `auto`, or a grow factor optionally followed
+/// by a shrink factor, and a basis before or after them. The factors default to 1, and the basis
+/// to 0 if there is a grow factor, as in CSS Flexbox § 7.1.
+fn expand_flex(values: &[Value]) -> Option<Vec<Value>> {
+    let factor = |value: &Value| matches!(value, Value::Number(_) | Value::Integer(_));
+    let one = Value::Integer(1);
+    let zero = Value::Length(0.0, Unit::Px);
+    let auto = Value::Keyword(String::from("auto"));
+    // A unitless zero is a factor rather than a basis wherever a factor may go.
+    let (grow, shrink, basis) = match values {
+        [value] if is_keyword(value, "none") => (Value::Integer(0), Value::Integer(0), auto),
+        [value] if is_keyword(value, "auto") => (one.clone(), one, auto),
+        [grow] if factor(grow) => (grow.clone(), one, zero),
+        [basis] => (one.clone(), one, basis.clone()),
+        [grow, shrink] if factor(grow) && factor(shrink) => (grow.clone(), shrink.clone(), zero),
+        [grow, basis] if factor(grow) => (grow.clone(), one, basis.clone()),
+        [basis, grow] if factor(grow) => (grow.clone(), one, basis.clone()),
+        [grow, shrink, basis] if factor(grow) && factor(shrink) => {
+            (grow.clone(), shrink.clone(), basis.clone())
+        }
+        [basis, grow, shrink] if factor(grow) && factor(shrink) => {
+            (grow.clone(), shrink.clone(), basis.clone())
+        }
+        _ => return None,
+    };
+    Some(vec![grow, shrink, basis])
+}
+
+/// Splits the value of `grid-row`, `grid-column` or `grid-area` into `count` lines separated by
+/// `/`. A line that is left out is the same as the line it pairs with if that is a name, as in
+/// CSS Grid § 8.4, and `auto` otherwise.
+fn expand_grid_placement(values: &[Value], count: usize) -> Option<Vec<Value>> {
+    let mut lines = values
+        .split(|value| *value == Value::Delimiter('/'))
+        .map(|line| match line {
+            [] => None,
+            [value] => Some(value.clone()),
+            values => Some(Value::List(values.to_vec())),
+        })
+        .collect::<Option<Vec<_>>>()?;
+    if lines.len() > count {
+        return None;
+    }
+    while lines.len() < count {
+        // Each end line pairs with its start line, and the column start with the row start.
+        let pair = &lines[lines.len().saturating_sub(2)];
+        let line = match custom_ident(pair, &["auto", "span"]) {
+            Some(_) => pair.clone(),
+            None => Value::Keyword(String::from("auto")),
+        };
+        lines.push(line);
+    }
+    Some(lines)
+}
+
+impl ComputedStyle {
+    /// Returns the resolved value of the property `name`, which may be a shorthand, serialized
+    /// like `getComputedStyle` does, or `None` if the property is unknown. Custom properties
+    /// resolve to their specified value, or their parent's if they have none.
+    ///
+    /// Values that depend on layout, like percentage widths, are returned as computed here;
+    /// `LayoutBox::property_value` resolves them to the used values once the element has a box.
+    pub fn property_value(&self, name: &str) -> Option<String> {
+        serialize_property(name, |longhand| self.resolved_value(longhand))
+    }
+
+    /// Returns the resolved value of the longhand property `name` that doesn't depend on layout,
+    /// serialized.
+    fn resolved_value(&self, name: &str) -> Option<String> {
+        if name.starts_with("--") {
+            return self.custom_properties.get(name).map(Value::to_string);
+        }
+        if name == "line-height" {
+            return Some(match self.line_height() {
+                LineHeight::Normal => String::from("normal"),
+                LineHeight::Number(number) => format!("{}px", number * self.font_size()),
+                LineHeight::Px(px) => format!("{}px", px),
+            });
+        }
+        self.longhand_value(name)
+    }
+}
+
+/// Serializes the property `name` with `longhand` giving the serialized value of each longhand.
+/// A shorthand is serialized in its own syntax from its longhands, or as the empty string if its
+/// longhands have values it can't express, as CSSOM does. Returns `None` if the property or one
+/// of its longhands is unknown.
+pub(crate) fn serialize_property(
+    name: &str,
+    longhand: impl Fn(&str) -> Option<String>,
+) -> Option<String> {
+    let Some(longhands) = longhands(name) else {
+        return longhand(name);
+    };
+    let values = longhands
+        .iter()
+        .map(|name| longhand(name))
+        .collect::<Option<Vec<_>>>()?;
+    Some(match name {
+        "margin" | "padding" | "inset" | "border-width" | "border-style" | "border-color" => {
+            serialize_sides(&values)
+        }
+        // `border` sets all four sides alike, so it can only express equal sides.
+        "border" => match values.chunks(4).all(|sides| sides.iter().all_equal()) {
+            true => values.iter().step_by(4).join(" "),
+            false => String::new(),
+        },
+        "font" => serialize_font(&values),
+        _ if values.iter().all_equal() => values.into_iter().next()?,
+        "grid-row" | "grid-column" | "grid-area" => values.join(" / "),
+        _ => values.join(" "),
+    })
+}
+
+/// Serializes the top, right, bottom and left values of a four-sided shorthand in its shortest
+/// form: values that equal the ones they would be copied from are left out.
+fn serialize_sides(sides: &[String]) -> String {
+    let count = match sides {
+        [_, right, _, left] if left != right => 4,
+        [top, _, bottom, _] if bottom != top => 3,
+        [top, right, ..] if right != top => 2,
+        _ => 1,
+    };
+    sides[..count].join(" ")
+}
+
+/// Serializes the longhands of `font` in its syntax, leaving out those with their initial values.
+/// A font width that isn't one of the `font-stretch` keywords can't be expressed.
+fn serialize_font(values: &[String]) -> String {
+    let [style, weight, stretch, size, line_height, family] = values else {
+        return String::new();
+    };
+    let Some(&(stretch, _)) = FONT_STRETCH_KEYWORDS
+        .iter()
+        .find(|&&(_, percent)| format!("{}%", percent) == *stretch)
+    else {
+        return String::new();
+    };
+    let mut font = [
+        (style.as_str(), "normal"),
+        (weight, "400"),
+        (stretch, "normal"),
+    ]
+    .into_iter()
+    .filter(|&(value, initial)| value != initial)
+    .map(|(value, _)| value)
+    .chain(iter::once(size.as_str()))
+    .join(" ");
+    if line_height != "normal" {
+        font = format!("{} / {}", font, line_height);
+    }
+    format!("{} {}", font, family)
+}
+
+impl ToCss for f32 {
+    fn to_css(&self) -> String {
+        self.to_string()
+    }
+}
+
+impl ToCss for u16 {
+    fn to_css(&self) -> String {
+        self.to_string()
+    }
+}
+
+impl ToCss for i32 {
+    fn to_css(&self) -> String {
+        self.to_string()
+    }
+}
+
+impl ToCss for Color {
+    fn to_css(&self) -> String {
+        self.to_string()
+    }
+}
+
+impl ToCss for LengthPercentage {
+    fn to_css(&self) -> String {
+        match *self {
+            LengthPercentage::Px(px) => format!("{}px", px),
+            LengthPercentage::Percent(percent) => format!("{}%", percent),
+        }
+    }
+}
+
+impl ToCss for LengthPercentageAuto {
+    fn to_css(&self) -> String {
+        match *self {
+            LengthPercentageAuto::Auto => String::from("auto"),
+            LengthPercentageAuto::Px(px) => format!("{}px", px),
+            LengthPercentageAuto::Percent(percent) => format!("{}%", percent),
+        }
+    }
+}
+
+impl ToCss for LengthPercentageNone {
+    fn to_css(&self) -> String {
+        match *self {
+            LengthPercentageNone::None => String::from("none"),
+            LengthPercentageNone::Px(px) => format!("{}px", px),
+            LengthPercentageNone::Percent(percent) => format!("{}%", percent),
+        }
+    }
+}
+
+/// `z-index`, where `None` is `auto`.
+impl ToCss for Option<i32> {
+    fn to_css(&self) -> String {
+        match self {
+            Some(z_index) => z_index.to_string(),
+            None => String::from("auto"),
+        }
+    }
+}
+
+/// `container-name`, where `None` is `none`.
+impl ToCss for Option<Arc<str>> {
+    fn to_css(&self) -> String {
+        match self {
+            Some(name) => name.to_string(),
+            None => String::from("none"),
+        }
+    }
+}
+
+impl ToCss for Display {
+    /// Uses the legacy single keywords where there is one, like browsers do.
+    fn to_css(&self) -> String {
+        let (outside, inside, list_item) = match *self {
+            Display::None => return String::from("none"),
+            Display::Contents => return String::from("contents"),
+            Display::Internal(internal) => {
+                let keyword = match internal {
+                    DisplayInternal::TableRowGroup => "table-row-group",
+                    DisplayInternal::TableHeaderGroup => "table-header-group",
+                    DisplayInternal::TableFooterGroup => "table-footer-group",
+                    DisplayInternal::TableRow => "table-row",
+                    DisplayInternal::TableCell => "table-cell",
+                    DisplayInternal::TableColumnGroup => "table-column-group",
+                    DisplayInternal::TableColumn => "table-column",
+                    DisplayInternal::TableCaption => "table-caption",
+                    DisplayInternal::RubyBase => "ruby-base",
+                    DisplayInternal::RubyText => "ruby-text",
+                    DisplayInternal::RubyBaseContainer => "ruby-base-container",
+                    DisplayInternal::RubyTextContainer => "ruby-text-container",
+                };
+                return String::from(keyword);
+            }
+            Display::Box {
+                outside,
+                inside,
+                list_item,
+            } => (outside, inside, list_item),
+        };
+        use {DisplayInside as In, DisplayOutside as Out};
+        let legacy = match (outside, inside, list_item) {
+            (Out::Block, In::Flow, false) => "block",
+            (Out::Inline, In::Flow, false) => "inline",
+            (Out::RunIn, In::Flow, false) => "run-in",
+            (Out::Block, In::Flow, true) => "list-item",
+            (Out::Block, In::FlowRoot, false) => "flow-root",
+            (Out::Inline, In::FlowRoot, false) => "inline-block",
+            (Out::Block, In::Table, _) => "table",
+            (Out::Inline, In::Table, _) => "inline-table",
+            (Out::Block, In::Flex, _) => "flex",
+            (Out::Inline, In::Flex, _) => "inline-flex",
+            (Out::Block, In::Grid, _) => "grid",
+            (Out::Inline, In::Grid, _) => "inline-grid",
+            (Out::Inline, In::Ruby, _) => "ruby",
+            _ => "",
+        };
+        if !legacy.is_empty() {
+            return String::from(legacy);
+        }
+        let outside = match outside {
+            Out::Block => "block",
+            Out::Inline => "inline",
+            Out::RunIn => "run-in",
+        };
+        let inside = match inside {
+            In::Flow => "flow",
+            In::FlowRoot => "flow-root",
+            In::Table => "table",
+            In::Flex => "flex",
+            In::Grid => "grid",
+            In::Ruby => "ruby",
+        };
+        match list_item {
+            true => format!("{} {} list-item", outside, inside),
+            false => format!("{} {}", outside, inside),
+        }
+    }
+}
+
+impl ToCss for LineHeight {
+    fn to_css(&self) -> String {
+        match *self {
+            LineHeight::Normal => String::from("normal"),
+            LineHeight::Number(number) => number.to_string(),
+            LineHeight::Px(px) => format!("{}px", px),
+        }
+    }
+}
+
+impl ToCss for VerticalAlign {
+    fn to_css(&self) -> String {
+        let keyword = match *self {
+            VerticalAlign::Baseline => "baseline",
+            VerticalAlign::Sub => "sub",
+            VerticalAlign::Super => "super",
+            VerticalAlign::TextTop => "text-top",
+            VerticalAlign::TextBottom => "text-bottom",
+            VerticalAlign::Middle => "middle",
+            VerticalAlign::Top => "top",
+            VerticalAlign::Bottom => "bottom",
+            VerticalAlign::Px(px) => return format!("{}px", px),
+            VerticalAlign::Percent(percent) => return format!("{}%", percent),
+        };
+        String::from(keyword)
+    }
+}
+
+impl ToCss for (f32, f32) {
+    fn to_css(&self) -> String {
+        format!("{}px {}px", self.0, self.1)
+    }
+}
+
+impl ToCss for Arc<[FontFamily]> {
+    fn to_css(&self) -> String {
+        self.iter().map(ToCss::to_css).join(", ")
+    }
+}
+
+impl ToCss for FontFamily {
+    fn to_css(&self) -> String {
+        let generic = match *self {
+            FontFamily::Serif => "serif",
+            FontFamily::SansSerif => "sans-serif",
+            FontFamily::Monospace => "monospace",
+            FontFamily::Cursive => "cursive",
+            FontFamily::Fantasy => "fantasy",
+            FontFamily::SystemUi => "system-ui",
+            FontFamily::Named(ref name) if name.contains(char::is_whitespace) => {
+                return Value::String(name.to_string()).to_string();
+            }
+            FontFamily::Named(ref name) => return name.to_string(),
+        };
+        String::from(generic)
+    }
+}
+
+impl ToCss for GridLine {
+    fn to_css(&self) -> String {
+        match self {
+            GridLine::Auto => String::from("auto"),
+            GridLine::Line(line, None) => line.to_string(),
+            GridLine::Line(line, Some(name)) => format!("{} {}", line, name),
+            GridLine::Span(span, None) => format!("span {}", span),
+            GridLine::Span(1, Some(name)) => format!("span {}", name),
+            GridLine::Span(span, Some(name)) => format!("span {} {}", span, name),
+            GridLine::Name(name) => name.to_string(),
+        }
+    }
+}
+
+impl ToCss for TrackBreadth {
+    fn to_css(&self) -> String {
+        match *self {
+            TrackBreadth::Px(px) => format!("{}px", px),
+            TrackBreadth::Percent(percent) => format!("{}%", percent),
+            TrackBreadth::Fr(fr) => format!("{}fr", fr),
+            TrackBreadth::MinContent => String::from("min-content"),
+            TrackBreadth::MaxContent => String::from("max-content"),
+            TrackBreadth::Auto => String::from("auto"),
+        }
+    }
+}
+
+impl ToCss for TrackSize {
+    fn to_css(&self) -> String {
+        match self {
+            TrackSize::Breadth(breadth) => breadth.to_css(),
+            TrackSize::MinMax(min, max) => format!("minmax({}, {})", min.to_css(), max.to_css()),
+            TrackSize::FitContent(limit) => format!("fit-content({})", limit.to_css()),
+        }
+    }
+}
+
+impl ToCss for TrackListEntry {
+    fn to_css(&self) -> String {
+        match self {
+            TrackListEntry::LineNames(names) => format!("[{}]", names.iter().join(" ")),
+            TrackListEntry::Track(size) => size.to_css(),
+            TrackListEntry::Repeat(count, entries) => {
+                let count = match count {
+                    RepeatCount::Count(count) => count.to_string(),
+                    RepeatCount::AutoFill => String::from("auto-fill"),
+                    RepeatCount::AutoFit => String::from("auto-fit"),
+                };
+                format!(
+                    "repeat({}, {})",
+                    count,
+                    entries.iter().map(ToCss::to_css).join(" ")
+                )
+            }
+        }
+    }
+}
+
+/// `grid-template-rows` and `grid-template-columns`, where an empty list is `none`.
+impl ToCss for Arc<[TrackListEntry]> {
+    fn to_css(&self) -> String {
+        match self.is_empty() {
+            true => String::from("none"),
+            false => self.iter().map(ToCss::to_css).join(" "),
+        }
+    }
+}
+
+impl ToCss for Arc<[TrackSize]> {
+    fn to_css(&self) -> String {
+        self.iter().map(ToCss::to_css).join(" ")
+    }
+}
+
+/// `grid-template-areas`, where `None` is `none`.
+impl ToCss for Option<Arc<TemplateAreas>> {
+    fn to_css(&self) -> String {
+        match self {
+            Some(areas) => areas
+                .rows
+                .iter()
+                .map(|row| Value::String(row.clone()).to_string())
+                .join(" "),
+            None => String::from("none"),
+        }
+    }
+}
+
+/// Returns true if `name` is a property with a computed value and `value` is valid for it.
+pub fn is_valid_declaration(name: &str, value: &Value) -> bool {
+    let initial = ComputedStyle::default();
+    initial.clone().set(name, value, &initial)
+}
+
+/// The keywords every property accepts.
+enum CssWideKeyword {
+    Inherit,
+    Initial,
+    Unset,
+}
+
+fn css_wide_keyword(value: &Value) -> Option<CssWideKeyword> {
+    match value {
+        Value::Keyword(keyword) => match &*keyword.to_ascii_lowercase() {
+            "inherit" => Some(CssWideKeyword::Inherit),
+            "initial" => Some(CssWideKeyword::Initial),
+            "unset" => Some(CssWideKeyword::Unset),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+fn keyword<T: Keyword>(value: &Value, _: &Context) -> Option<T> {
+    match value {
+        Value::Keyword(keyword) => T::from_keyword(&keyword.to_ascii_lowercase()),
+        _ => None,
+    }
+}
+
+/// Returns the length in px of a length value, including unitless zero. Font-relative lengths
+/// are relative to the font sizes in `context`.
+fn px(value: &Value, context: &Context) -> Option<f32> {
+    let (length, unit) = match value {
+        Value::Length(length, unit) => (*length, unit),
+        Value::Integer(0) => return Some(0.0),
+        _ => return None,
+    };
+    let px_per_unit = match unit {
+        Unit::Px => 1.0,
+        Unit::Em => context.font_size,
+        Unit::Rem => context.root_font_size,
+        Unit::In => 96.0,
+        Unit::Cm => 96.0 / 2.54,
+        Unit::Mm => 96.0 / 25.4,
+        Unit::Q => 96.0 / 101.6,
+        Unit::Pt => 96.0 / 72.0,
+        Unit::Pc => 16.0,
+        Unit::Fr => return None,
+    };
+    Some(length * px_per_unit)
+}
+
+fn is_keyword(value: &Value, expected: &str) -> bool {
+    matches!(value, Value::Keyword(keyword) if keyword.eq_ignore_ascii_case(expected))
+}
+
+fn length_percentage(value: &Value, context: &Context) -> Option<LengthPercentage> {
+    match value {
+        Value::Percentage(percent) => Some(LengthPercentage::Percent(*percent)),
+        _ => px(value, context).map(LengthPercentage::Px),
+    }
+}
+
+fn non_negative_length_percentage(value: &Value, context: &Context) -> Option<LengthPercentage> {
+    length_percentage(value, context).filter(|length| match *length {
+        LengthPercentage::Px(length) | LengthPercentage::Percent(length) => length >= 0.0,
+    })
+}
+
+fn length_percentage_auto(value: &Value, context: &Context) -> Option<LengthPercentageAuto> {
+    if is_keyword(value, "auto") {
+        return Some(LengthPercentageAuto::Auto);
+    }
+    Some(match length_percentage(value, context)? {
+        LengthPercentage::Px(px) => LengthPercentageAuto::Px(px),
+        LengthPercentage::Percent(percent) => LengthPercentageAuto::Percent(percent),
+    })
+}
+
+fn length_percentage_none(value: &Value, context: &Context) -> Option<LengthPercentageNone> {
+    if is_keyword(value, "none") {
+        return Some(LengthPercentageNone::None);
+    }
+    Some(match non_negative_length_percentage(value, context)? {
+        LengthPercentage::Px(px) => LengthPercentageNone::Px(px),
+        LengthPercentage::Percent(percent) => LengthPercentageNone::Percent(percent),
+    })
+}
+
+fn gap(value: &Value, context: &Context) -> Option<LengthPercentage> {
+    match is_keyword(value, "normal") {
+        true => Some(LengthPercentage::Px(0.0)),
+        false => non_negative_length_percentage(value, context),
+    }
+}
+
+fn number(value: &Value) -> Option<f32> {
+    match *value {
+        Value::Number(number) => Some(number),
+        Value::Integer(integer) => Some(integer as f32),
+        _ => None,
+    }
+}
+
+fn non_negative_number(value: &Value, _: &Context) -> Option<f32> {
+    number(value).filter(|number| *number >= 0.0)
+}
+
+fn alpha(value: &Value, _: &Context) -> Option<f32> {
+    let alpha = match *value {
+        Value::Percentage(percent) => percent / 100.0,
+        _ => number(value)?,
+    };
+    Some(alpha.clamp(0.0, 1.0))
+}
+
+fn integer(value: &Value, _: &Context) -> Option<i32> {
+    match *value {
+        Value::Integer(integer) => Some(integer),
+        _ => None,
+    }
+}
+
+fn z_index(value: &Value, context: &Context) -> Option<Option<i32>> {
+    match is_keyword(value, "auto") {
+        true => Some(None),
+        false => integer(value, context).map(Some),
+    }
+}
+
+fn name(value: &Value, _: &Context) -> Option<Option<Arc<str>>> {
+    match value {
+        Value::Keyword(keyword) if keyword.eq_ignore_ascii_case("none") => Some(None),
+        Value::Keyword(keyword) => Some(Some(Arc::from(keyword.as_str()))),
+        _ => None,
+    }
+}
+
+fn image(value: &Value, _: &Context) -> Option<Option<Arc<str>>> {
+    match value {
+        Value::Url(url) => Some(Some(Arc::from(url.as_str()))),
+        _ if is_keyword(value, "none") => Some(None),
+        _ => None,
+    }
+}
+
+fn color(value: &Value, context: &Context) -> Option<Color> {
+    let keyword = match value {
+        Value::ColorValue(color) => return Some(*color),
+        Value::Keyword(keyword) => keyword.to_ascii_lowercase(),
+        _ => return None,
+    };
+    let (r, g, b) = match &*keyword {
+        "currentcolor" => return Some(context.color),
+        "transparent" => return Some(TRANSPARENT),
+        "black" => (0, 0, 0),
+        "silver" => (192, 192, 192),
+        "gray" | "grey" => (128, 128, 128),
+        "white" => (255, 255, 255),
+        "maroon" => (128, 0, 0),
+        "red" => (255, 0, 0),
+        "purple" => (128, 0, 128),
+        "fuchsia" | "magenta" => (255, 0, 255),
+        "green" => (0, 128, 0),
+        "lime" => (0, 255, 0),
+        "olive" => (128, 128, 0),
+        "yellow" => (255, 255, 0),
+        "navy" => (0, 0, 128),
+        "blue" => (0, 0, 255),
+        "teal" => (0, 128, 128),
+        "aqua" | "cyan" => (0, 255, 255),
+        "orange" => (255, 165, 0),
+        _ => return None,
+    };
+    Some(Color::new(r, g, b, 255))
+}
+
+fn border_width(value: &Value, context: &Context) -> Option<f32> {
+    let width = match value {
+        Value::Keyword(keyword) => match &*keyword.to_ascii_lowercase() {
+            "thin" => 1.0,
+            "medium" => MEDIUM_BORDER_WIDTH,
+            "thick" => 5.0,
+            _ => return None,
+        },
+        _ => px(value, context)?,
+    };
+    (width >= 0.0).then_some(width)
+}
+
+/// Parses `border-spacing`: a horizontal and a vertical length, or one length for both.
+fn border_spacing(value: &Value, context: &Context) -> Option<(f32, f32)> {
+    let (horizontal, vertical) = match value {
+        Value::List(values) if values.len() == 2 => {
+            (px(&values[0], context)?, px(&values[1], context)?)
+        }
+        value => {
+            let length = px(value, context)?;
+            (length, length)
+        }
+    };
+    (horizontal >= 0.0 && vertical >= 0.0).then_some((horizontal, vertical))
+}
+
+fn line_height(value: &Value, context: &Context) -> Option<LineHeight> {
+    match *value {
+        _ if is_keyword(value, "normal") => Some(LineHeight::Normal),
+        Value::Percentage(percent) => Some(LineHeight::Px(context.font_size * percent / 100.0)),
+        _ => match number(value) {
+            Some(number) => Some(LineHeight::Number(number)),
+            None => px(value, context).map(LineHeight::Px),
+        },
+    }
+    .filter(|line_height| match *line_height {
+        LineHeight::Normal => true,
+        LineHeight::Number(length) | LineHeight::Px(length) => length >= 0.0,
+    })
+}
+
+fn vertical_align(value: &Value, context: &Context) -> Option<VerticalAlign> {
+    match value {
+        Value::Keyword(keyword) => Some(match &*keyword.to_ascii_lowercase() {
+            "baseline" => VerticalAlign::Baseline,
+            "sub" => VerticalAlign::Sub,
+            "super" => VerticalAlign::Super,
+            "text-top" => VerticalAlign::TextTop,
+            "text-bottom" => VerticalAlign::TextBottom,
+            "middle" => VerticalAlign::Middle,
+            "top" => VerticalAlign::Top,
+            "bottom" => VerticalAlign::Bottom,
+            _ => return None,
+        }),
+        Value::Percentage(percent) => Some(VerticalAlign::Percent(*percent)),
+        _ => px(value, context).map(VerticalAlign::Px),
+    }
+}
+
+fn font_family(value: &Value, _: &Context) -> Option<Arc<[FontFamily]>> {
+    let family = |value: &Value| match value {
+        Value::String(name) => Some(FontFamily::Named(Arc::from(name.as_str()))),
+        Value::Keyword(keyword) => Some(match &*keyword.to_ascii_lowercase() {
+            "serif" => FontFamily::Serif,
+            "sans-serif" => FontFamily::SansSerif,
+            "monospace" => FontFamily::Monospace,
+            "cursive" => FontFamily::Cursive,
+            "fantasy" => FontFamily::Fantasy,
+            "system-ui" => FontFamily::SystemUi,
+            _ => FontFamily::Named(Arc::from(keyword.as_str())),
+        }),
+        // An unquoted name of several words, which is never a generic family.
+        Value::List(words) => {
+            let words = words
+                .iter()
+                .map(|word| match word {
+                    Value::Keyword(word) => Some(word.as_str()),
+                    _ => None,
+                })
+                .collect::<Option<Vec<_>>>()?;
+            Some(FontFamily::Named(Arc::from(words.join(" "))))
+        }
+        _ => None,
+    };
+    match value {
+        Value::CommaList(values) => values.iter().map(family).collect(),
+        value => Some(Arc::from([family(value)?])),
+    }
+}
+
+/// The `font-stretch` keywords and the percentages they stand for.
+const FONT_STRETCH_KEYWORDS: [(&str, f32); 9] = [
+    ("ultra-condensed", 50.0),
+    ("extra-condensed", 62.5),
+    ("condensed", 75.0),
+    ("semi-condensed", 87.5),
+    ("normal", 100.0),
+    ("semi-expanded", 112.5),
+    ("expanded", 125.0),
+    ("extra-expanded", 150.0),
+    ("ultra-expanded", 200.0),
+];
+
+fn font_stretch(value: &Value, _: &Context) -> Option<f32> {
+    match *value {
+        Value::Percentage(percent) if percent >= 0.0 => Some(percent),
+        Value::Keyword(ref keyword) => FONT_STRETCH_KEYWORDS
+            .iter()
+            .find(|(name, _)| keyword.eq_ignore_ascii_case(name))
+            .map(|&(_, percent)| percent),
+        _ => None,
+    }
+}
+
+/// The ratio between consecutive font size keywords, used by `larger` and `smaller`.
+const FONT_SIZE_RATIO: f32 = 1.2;
+
+fn font_size(value: &Value, context: &Context) -> Option<f32> {
+    // While computing `font-size`, `context.font_size` is the parent's.
+    let size = match value {
+        Value::Keyword(keyword) => match &*keyword.to_ascii_lowercase() {
+            "xx-small" => MEDIUM_FONT_SIZE * 3.0 / 5.0,
+            "x-small" => MEDIUM_FONT_SIZE * 3.0 / 4.0,
+            "small" => MEDIUM_FONT_SIZE * 8.0 / 9.0,
+            "medium" => MEDIUM_FONT_SIZE,
+            "large" => MEDIUM_FONT_SIZE * 6.0 / 5.0,
+            "x-large" => MEDIUM_FONT_SIZE * 3.0 / 2.0,
+            "xx-large" => MEDIUM_FONT_SIZE * 2.0,
+            "xxx-large" => MEDIUM_FONT_SIZE * 3.0,
+            "larger" => context.font_size * FONT_SIZE_RATIO,
+            "smaller" => context.font_size / FONT_SIZE_RATIO,
+            _ => return None,
+        },
+        Value::Percentage(percent) => context.font_size * percent / 100.0,
+        _ => px(value, context)?,
+    };
+    (size >= 0.0).then_some(size)
+}
+
+fn font_weight(value: &Value, context: &Context) -> Option<u16> {
+    let parent = context.parent.font.font_weight;
+    match *value {
+        Value::Integer(weight @ 1..=1000) => Some(weight as u16),
+        Value::Keyword(ref keyword) => match &*keyword.to_ascii_lowercase() {
+            "normal" => Some(400),
+            "bold" => Some(700),
+            // The relative weights of CSS Fonts Level 4, section 2.2.1.
+            "bolder" => Some(match parent {
+                0..350 => 400,
+                350..550 => 700,
+                550..900 => 900,
+                _ => parent,
+            }),
+            "lighter" => Some(match parent {
+                0..100 => parent,
+                100..550 => 100,
+                550..750 => 400,
+                _ => 700,
+            }),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+fn item_alignment(value: &Value, context: &Context) -> Option<ItemAlignment> {
+    keyword(value, context).filter(|alignment| *alignment != ItemAlignment::Auto)
+}
+
+fn grid_auto_flow(value: &Value, _: &Context) -> Option<GridAutoFlow> {
+    let keywords = match value {
+        Value::List(values) if values.len() == 2 => values.iter().collect(),
+        value => vec![value],
+    };
+    let (mut column, mut row, mut dense) = (false, false, false);
+    for value in keywords {
+        let flag = match value {
+            _ if is_keyword(value, "row") => &mut row,
+            _ if is_keyword(value, "column") => &mut column,
+            _ if is_keyword(value, "dense") => &mut dense,
+            _ => return None,
+        };
+        if *flag {
+            return None;
+        }
+        *flag = true;
+    }
+    match (row, column, dense) {
+        (_, false, false) => Some(GridAutoFlow::Row),
+        (_, false, true) => Some(GridAutoFlow::RowDense),
+        (false, true, false) => Some(GridAutoFlow::Column),
+        (false, true, true) => Some(GridAutoFlow::ColumnDense),
+        (true, true, _) => None,
+    }
+}
+
+/// Returns the name of a custom identifier, which can't be a keyword that has another meaning
+/// where it is used.
+fn custom_ident<'v>(value: &'v Value, reserved: &[&str]) -> Option<&'v str> {
+    match value {
+        Value::Keyword(name) if css_wide_keyword(value).is_none() => {
+            let reserved = reserved.iter().any(|word| name.eq_ignore_ascii_case(word));
+            (!reserved).then_some(name.as_str())
+        }
+        _ => None,
+    }
+}
+
+fn grid_line(value: &Value, _: &Context) -> Option<GridLine> {
+    let values = match value {
+        _ if is_keyword(value, "auto") => return Some(GridLine::Auto),
+        Value::List(values) if values.len() <= 3 => values.iter().collect(),
+        value => vec![value],
+    };
+    let (mut span, mut number, mut name) = (false, None, None);
+    for value in values {
+        match *value {
+            _ if is_keyword(value, "span") && !span => span = true,
+            Value::Integer(integer) if integer != 0 && number.is_none() => number = Some(integer),
+            _ if name.is_none() => name = Some(Arc::from(custom_ident(value, &["auto", "span"])?)),
+            _ => return None,
+        }
+    }
+    match (span, number, name) {
+        (false, None, Some(name)) => Some(GridLine::Name(name)),
+        (false, Some(number), name) => Some(GridLine::Line(number, name)),
+        (true, Some(number), _) if number < 0 => None,
+        (true, None, None) => None,
+        (true, number, name) => Some(GridLine::Span(number.unwrap_or(1), name)),
+        (false, None, None) => None,
+    }
+}
+
+fn track_breadth(value: &Value, context: &Context) -> Option<TrackBreadth> {
+    let breadth = match *value {
+        Value::Length(fr, Unit::Fr) => TrackBreadth::Fr(fr),
+        Value::Percentage(percent) => TrackBreadth::Percent(percent),
+        _ if is_keyword(value, "min-content") => TrackBreadth::MinContent,
+        _ if is_keyword(value, "max-content") => TrackBreadth::MaxContent,
+        _ if is_keyword(value, "auto") => TrackBreadth::Auto,
+        _ => TrackBreadth::Px(px(value, context)?),
+    };
+    match breadth {
+        TrackBreadth::Px(size) | TrackBreadth::Percent(size) | TrackBreadth::Fr(size)
+            if size < 0.0 =>
+        {
+            None
+        }
+        breadth => Some(breadth),
+    }
+}
+
+fn track_size(value: &Value, context: &Context) -> Option<TrackSize> {
+    match value {
+        Value::Function(name, arguments) if name.eq_ignore_ascii_case("minmax") => {
+            let [min, max] = &arguments[..] else {
+                return None;
+            };
+            match (track_breadth(min, context)?, track_breadth(max, context)?) {
+                (TrackBreadth::Fr(_), _) => None,
+                (min, max) => Some(TrackSize::MinMax(min, max)),
+            }
+        }
+        Value::Function(name, arguments) if name.eq_ignore_ascii_case("fit-content") => {
+            let [limit] = &arguments[..] else {
+                return None;
+            };
+            non_negative_length_percentage(limit, context).map(TrackSize::FitContent)
+        }
+        _ => track_breadth(value, context).map(TrackSize::Breadth),
+    }
+}
+
+/// Returns true if a track of size `size` has a fixed minimum or maximum size, as tracks
+/// repeated to fill a grid must.
+fn is_fixed(size: TrackSize) -> bool {
+    let fixed = |breadth| matches!(breadth, TrackBreadth::Px(_) | TrackBreadth::Percent(_));
+    match size {
+        TrackSize::Breadth(breadth) => fixed(breadth),
+        TrackSize::MinMax(min, max) => fixed(min) || fixed(max),
+        TrackSize::FitContent(_) => false,
+    }
+}
+
+fn track_sizes(value: &Value, context: &Context) -> Option<Arc<[TrackSize]>> {
+    match value {
+        Value::List(values) => values
+            .iter()
+            .map(|value| track_size(value, context))
+            .collect(),
+        value => Some(Arc::from([track_size(value, context)?])),
+    }
+}
+
+fn track_list(value: &Value, context: &Context) -> Option<Arc<[TrackListEntry]>> {
+    let values = match value {
+        _ if is_keyword(value, "none") => return Some(Arc::from([])),
+        Value::List(values) => &values[..],
+        value => std::slice::from_ref(value),
+    };
+    let entries = track_list_entries(values, true, context)?;
+    // Tracks repeated to fill the grid must all have fixed sizes, as must the others then.
+    let mut auto_repeats = 0;
+    let mut sizes = Vec::new();
+    for entry in &entries {
+        match entry {
+            TrackListEntry::Track(size) => sizes.push(*size),
+            TrackListEntry::Repeat(count, entries) => {
+                if !matches!(count, RepeatCount::Count(_)) {
+                    auto_repeats += 1;
+                }
+                sizes.extend(entries.iter().filter_map(|entry| match entry {
+                    TrackListEntry::Track(size) => Some(*size),
+                    _ => None,
+                }));
+            }
+            TrackListEntry::LineNames(_) => (),
+        }
+    }
+    match auto_repeats {
+        0 => Some(Arc::from(entries)),
+        1 if sizes.into_iter().all(is_fixed) => Some(Arc::from(entries)),
+        _ => None,
+    }
+}
+
+/// Parses the line names, track sizes and, if `repeat`, `repeat()`s of a track list.
+fn track_list_entries(
+    values: &[Value],
+    repeat: bool,
+    context: &Context,
+) -> Option<Vec<TrackListEntry>> {
+    let mut entries = Vec::new();
+    for value in values {
+        let entry = match value {
+            Value::LineNames(names) => {
+                let names = names
+                    .iter()
+                    .map(|name| {
+                        let name = Value::Keyword(name.clone());
+                        custom_ident(&name, &["auto", "span"]).map(Arc::from)
+                    })
+                    .collect::<Option<_>>()?;
+                TrackListEntry::LineNames(names)
+            }
+            Value::Function(name, arguments) if repeat && name.eq_ignore_ascii_case("repeat") => {
+                let (count, values) = arguments.split_first()?;
+                let count = match *count {
+                    Value::Integer(count) if count > 0 => RepeatCount::Count(count as u32),
+                    _ if is_keyword(count, "auto-fill") => RepeatCount::AutoFill,
+                    _ if is_keyword(count, "auto-fit") => RepeatCount::AutoFit,
+                    _ => return None,
+                };
+                let entries = track_list_entries(values, false, context)?;
+                if !entries
+                    .iter()
+                    .any(|entry| matches!(entry, TrackListEntry::Track(_)))
+                {
+                    return None;
+                }
+                TrackListEntry::Repeat(count, Arc::from(entries))
+            }
+            value => TrackListEntry::Track(track_size(value, context)?),
+        };
+        entries.push(entry);
+    }
+    Some(entries)
+}
+
+fn template_areas(value: &Value, _: &Context) -> Option<Option<Arc<TemplateAreas>>> {
+    let values = match value {
+        _ if is_keyword(value, "none") => return Some(None),
+        Value::List(values) => &values[..],
+        value => std::slice::from_ref(value),
+    };
+    let mut rows = Vec::new();
+    let mut cells = Vec::new();
+    for value in values {
+        let Value::String(row) = value else {
+            return None;
+        };
+        // A cell is a name, or a run of periods for no name.
+        let row_cells = row
+            .split_whitespace()
+            .map(|cell| match cell.chars().all(|c| c == '.') {
+                true => Some(None),
+                false => cell
+                    .chars()
+                    .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+                    .then_some(Some(cell)),
+            })
+            .collect::<Option<Vec<_>>>()?;
+        rows.push(row.clone());
+        cells.push(row_cells);
+    }
+    let columns = cells[0].len();
+    if columns == 0 || cells.iter().any(|row| row.len() != columns) {
+        return None;
+    }
+
+    // Each name must fill a rectangle: the bounding box of its cells, which it has as many
+    // cells as.
+    let mut areas: Vec<NamedArea> = Vec::new();
+    let mut counts = Vec::new();
+    for (row, row_cells) in cells.iter().enumerate() {
+        for (column, cell) in row_cells.iter().enumerate() {
+            let Some(name) = cell else {
+                continue;
+            };
+            let (row, column) = (row as i32 + 1, column as i32 + 1);
+            match areas.iter().position(|area| &*area.name == *name) {
+                Some(i) => {
+                    let area = &mut areas[i];
+                    area.rows = (area.rows.0.min(row), area.rows.1.max(row + 1));
+                    area.columns = (area.columns.0.min(column), area.columns.1.max(column + 1));
+                    counts[i] += 1;
+                }
+                None => {
+                    areas.push(NamedArea {
+                        name: Arc::from(*name),
+                        rows: (row, row + 1),
+                        columns: (column, column + 1),
+                    });
+                    counts.push(1);
+                }
+            }
+        }
+    }
+    let rectangular = areas.iter().zip(counts).all(|(area, count)| {
+        (area.rows.1 - area.rows.0) * (area.columns.1 - area.columns.0) == count
+    });
+    rectangular.then(|| {
+        Some(Arc::new(TemplateAreas {
+            rows,
+            columns,
+            areas,
+        }))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::css;
+    use std::collections::HashMap;
+
+    fn specified(declarations: &[(&str, Value)]) -> PropertyMap {
+        declarations
+            .iter()
+            .map(|(name, value)| (String::from(*name), value.clone()))
+            .collect::<HashMap<_, _>>()
+    }
+
+    fn keyword(keyword: &str) -> Value {
+        Value::Keyword(String::from(keyword))
+    }
+
+    #[test]
+    fn test_compute_parses_typed_values() {
+        let style = ComputedStyle::compute(
+            &specified(&[
+                ("display", keyword("FLEX")),
+                ("width", Value::Percentage(50.0)),
+                ("margin", keyword("auto")),
+                ("margin-top", Value::Length(4.0, Unit::Px)),
+                ("padding-left", Value::Integer(0)),
+                ("position", keyword("absolute")),
+                ("z-index", Value::Integer(3)),
+                ("flex-grow", Value::Number(1.5)),
+                ("background-color", keyword("red")),
+                ("display-mode", keyword("block")),
+            ]),
+            None,
+        );
+        assert_eq!(
+            style.display(),
+            Display::new(DisplayOutside::Block, DisplayInside::Flex)
+        );
+        assert_eq!(style.width(), LengthPercentageAuto::Percent(50.0));
+        assert_eq!(style.width().resolve(300.0), Some(150.0));
+        assert_eq!(style.margin_top(), LengthPercentageAuto::Px(4.0));
+        assert_eq!(style.margin_left(), LengthPercentageAuto::Auto);
+        assert_eq!(style.padding_left(), LengthPercentage::Px(0.0));
+        assert_eq!(style.position(), Position::Absolute);
+        assert_eq!(style.z_index(), Some(3));
+        assert_eq!(style.flex_grow(), 1.5);
+        assert_eq!(style.background_color(), Color::new(255, 0, 0, 255));
+        assert_eq!(style.height(), LengthPercentageAuto::Auto);
+    }
+
+    #[test]
+    fn test_compute_ignores_invalid_values() {
+        let style = ComputedStyle::compute(
+            &specified(&[
+                ("display", keyword("blokc")),
+                ("padding", keyword("auto")),
+                ("opacity", Value::Number(1.5)),
+                ("order", Value::Number(1.5)),
+            ]),
+            None,
+        );
+        assert_eq!(style, ComputedStyle::compute(&PropertyMap::new(), None));
+    }
+
+    #[test]
+    fn test_compute_expands_shorthands() {
+        let declarations = |source: &str| {
+            let stylesheet = css::parse(format!("p {{ {} }}", source)).unwrap();
+            stylesheet.rules[0]
+                .declarations
+                .iter()
+                .map(|declaration| (declaration.name.clone(), declaration.value.clone()))
+                .collect::<PropertyMap>()
+        };
+        let style = ComputedStyle::compute(
+            &declarations(
+                "color: lime; margin: 0 auto; padding: 1px 2px 3px; inset: 1px 2px 3px 4px; \
+                 border: 5px solid; border-left: red dotted; gap: 1px 2%; \
+                 font: italic bold 20px/1.5 \"DejaVu Sans\", serif; background: url(a.png) blue;",
+            ),
+            None,
+        );
+        let px = LengthPercentageAuto::Px;
+        assert_eq!(style.margin_top(), px(0.0));
+        assert_eq!(style.margin_right(), LengthPercentageAuto::Auto);
+        assert_eq!(style.margin_left(), LengthPercentageAuto::Auto);
+        assert_eq!(style.padding_left(), LengthPercentage::Px(2.0));
+        assert_eq!(style.padding_bottom(), LengthPercentage::Px(3.0));
+        assert_eq!(
+            [style.top(), style.right(), style.bottom(), style.left()],
+            [px(1.0), px(2.0), px(3.0), px(4.0)]
+        );
+        assert_eq!(style.border_top_width(), 5.0);
+        assert_eq!(style.border_top_style(), BorderStyle::Solid);
+        assert_eq!(style.border_top_color(), Color::new(0, 255, 0, 255));
+        assert_eq!(style.border_left_width(), MEDIUM_BORDER_WIDTH);
+        assert_eq!(style.border_left_style(), BorderStyle::Dotted);
+        assert_eq!(style.border_left_color(), Color::new(255, 0, 0, 255));
+        assert_eq!(style.row_gap(), LengthPercentage::Px(1.0));
+        assert_eq!(style.column_gap(), LengthPercentage::Percent(2.0));
+        assert_eq!(style.font_style(), FontStyle::Italic);
+        assert_eq!(style.font_weight(), 700);
+        assert_eq!(style.font_stretch(), 100.0);
+        assert_eq!(style.font_size(), 20.0);
+        assert_eq!(style.line_height(), LineHeight::Number(1.5));
+        assert_eq!(
+            style.font_family()[..],
+            [
+                FontFamily::Named(Arc::from("DejaVu Sans")),
+                FontFamily::Serif
+            ]
+        );
+        assert_eq!(style.background_color(), Color::new(0, 0, 255, 255));
+        assert_eq!(style.background_image().as_deref(), Some("a.png"));
+
+        // A longhand wins over the shorthand, and the shorthand resets what it leaves out.
+        let style = ComputedStyle::compute(
+            &declarations("font-size: 10px; font: 20px monospace; font-weight: bold;"),
+            Some(&style),
+        );
+        assert_eq!(style.font_size(), 10.0);
+        assert_eq!(style.font_weight(), 700);
+        assert_eq!(style.font_style(), FontStyle::Normal);
+        assert_eq!(style.line_height(), LineHeight::Normal);
+
+        for source in [
+            "margin: 1px 2px 3px 4px 5px;",
+            "padding: 1px -2px;",
+            "border: 1px 2px;",
+            "background: red blue;",
+            "font: bold serif;",
+            "font: 12px;",
+            "font: 12px/ serif;",
+        ] {
+            let declarations = declarations(source);
+            let (name, value) = declarations.iter().next().unwrap();
+            assert!(!is_valid_declaration(name, value), "{}", source);
+        }
+    }
+
+    #[test]
+    fn test_compute_inherits() {
+        let parent = ComputedStyle::compute(
+            &specified(&[
+                ("color", keyword("blue")),
+                ("font-size", Value::Length(20.0, Unit::Px)),
+                ("line-height", Value::Percentage(150.0)),
+                ("width", Value::Length(100.0, Unit::Px)),
+                ("font-weight", keyword("bold")),
+            ]),
+            None,
+        );
+        let style = ComputedStyle::compute(
+            &specified(&[
+                ("font-size", Value::Percentage(50.0)),
+                ("height", keyword("inherit")),
+                ("font-weight", keyword("bolder")),
+                ("border-style", keyword("solid")),
+                ("border-left-style", keyword("none")),
+            ]),
+            Some(&parent),
+        );
+        assert_eq!(style.color(), Color::new(0, 0, 255, 255));
+        assert_eq!(style.font_size(), 10.0);
+        // Percentages compute to a length with the parent's font size, which is inherited as is.
+        assert_eq!(style.line_height(), LineHeight::Px(30.0));
+        assert_eq!(style.width(), LengthPercentageAuto::Auto);
+        assert_eq!(style.height(), parent.height());
+        assert_eq!(style.font_weight(), 900);
+        assert_eq!(style.border_top_color(), style.color());
+        assert_eq!(style.border_top_width(), MEDIUM_BORDER_WIDTH);
+        assert_eq!(style.border_left_width(), 0.0);
+    }
+
+    #[test]
+    fn test_compute_resolves_relative_and_absolute_lengths() {
+        let root = ComputedStyle::compute(
+            &specified(&[
+                ("font-size", Value::Length(2.0, Unit::Rem)),
+                ("width", Value::Length(10.0, Unit::Em)),
+            ]),
+            None,
+        );
+        assert_eq!(root.font_size(), 2.0 * MEDIUM_FONT_SIZE);
+        assert_eq!(
+            root.width(),
+            LengthPercentageAuto::Px(20.0 * MEDIUM_FONT_SIZE)
+        );
+        let style = ComputedStyle::compute(
+            &specified(&[
+                ("font-size", Value::Length(0.5, Unit::Em)),
+                ("margin-top", Value::Length(1.0, Unit::Rem)),
+                ("margin-left", Value::Length(2.0, Unit::Em)),
+                ("padding-top", Value::Length(1.0, Unit::In)),
+                ("padding-left", Value::Length(3.0, Unit::Pt)),
+            ]),
+            Some(&root),
+        );
+        assert_eq!(style.font_size(), MEDIUM_FONT_SIZE);
+        assert_eq!(
+            style.margin_top(),
+            LengthPercentageAuto::Px(2.0 * MEDIUM_FONT_SIZE)
+        );
+        assert_eq!(
+            style.margin_left(),
+            LengthPercentageAuto::Px(2.0 * MEDIUM_FONT_SIZE)
+        );
+        assert_eq!(style.padding_top(), LengthPercentage::Px(96.0));
+        assert_eq!(style.padding_left(), LengthPercentage::Px(4.0));
+    }
+
+    #[test]
+    fn test_display() {
+        let display = |value: Value| {
+            display(
+                &value,
+                &Context {
+                    parent: &ComputedStyle::default(),
+                    color: BLACK,
+                    font_size: MEDIUM_FONT_SIZE,
+                    root_font_size: MEDIUM_FONT_SIZE,
+                },
+            )
+        };
+        let list = |keywords: &[&str]| Value::List(keywords.iter().map(|k| keyword(k)).collect());
+        assert_eq!(
+            display(keyword("inline-flex")),
+            Some(Display::new(DisplayOutside::Inline, DisplayInside::Flex))
+        );
+        assert_eq!(
+            display(list(&["inline", "flex"])),
+            display(keyword("inline-flex"))
+        );
+        assert_eq!(
+            display(list(&["flow-root", "inline"])),
+            Some(Display::INLINE_BLOCK)
+        );
+        assert_eq!(display(keyword("grid")), Some(Display::GRID));
+        assert_eq!(
+            display(keyword("ruby")),
+            Some(Display::new(DisplayOutside::Inline, DisplayInside::Ruby))
+        );
+        assert_eq!(
+            display(keyword("list-item")),
+            Some(Display::Box {
+                outside: DisplayOutside::Block,
+                inside: DisplayInside::Flow,
+                list_item: true,
+            })
+        );
+        assert_eq!(
+            display(keyword("table-cell")),
+            Some(Display::Internal(DisplayInternal::TableCell))
+        );
+        assert_eq!(display(keyword("contents")), Some(Display::Contents));
+        assert_eq!(display(list(&["block", "inline"])), None);
+        assert_eq!(display(list(&["list-item", "flex"])), None);
+        assert_eq!(display(list(&["inline", "table-cell"])), None);
+        assert_eq!(display(keyword("blokc")), None);
+    }
+
+    #[test]
+    fn test_compute_blockifies() {
+        let parent = ComputedStyle::compute(&PropertyMap::new(), None);
+        let flex = ComputedStyle::compute(&specified(&[("display", keyword("flex"))]), None);
+        let compute = |declarations: &[(&str, Value)], parent: &ComputedStyle| {
+            ComputedStyle::compute(&specified(declarations), Some(parent)).display()
+        };
+        // The root is blockified.
+        assert_eq!(parent.display(), Display::BLOCK);
+        assert_eq!(compute(&[], &parent), Display::INLINE);
+        assert_eq!(
+            compute(
+                &[
+                    ("display", keyword("inline-block")),
+                    ("float", keyword("left"))
+                ],
+                &parent
+            ),
+            Display::FLOW_ROOT
+        );
+        assert_eq!(
+            compute(
+                &[
+                    ("display", keyword("inline-flex")),
+                    ("position", keyword("absolute"))
+                ],
+                &parent
+            ),
+            Display::FLEX
+        );
+        assert_eq!(
+            compute(&[("display", keyword("table-row"))], &flex),
+            Display::BLOCK
+        );
+        assert_eq!(
+            compute(&[("display", keyword("inline-table"))], &flex),
+            Display::TABLE
+        );
+        assert_eq!(
+            compute(
+                &[("display", keyword("none")), ("float", keyword("left"))],
+                &parent
+            ),
+            Display::None
+        );
+
+        let positioned = ComputedStyle::compute(
+            &specified(&[("position", keyword("fixed")), ("float", keyword("right"))]),
+            Some(&parent),
+        );
+        assert_eq!(positioned.float(), Float::None);
+    }
+
+    #[test]
+    fn test_property_value() {
+        let style = ComputedStyle::compute(
+            &specified(&[
+                (
+                    "display",
+                    Value::List(vec![keyword("inline"), keyword("flow-root")]),
+                ),
+                ("color", Value::ColorValue(Color::new(255, 0, 0, 255))),
+                ("background-color", keyword("transparent")),
+                ("font-size", Value::Length(10.0, Unit::Px)),
+                ("line-height", Value::Number(1.5)),
+                ("margin", Value::Length(2.0, Unit::Px)),
+                ("margin-left", Value::Percentage(10.0)),
+                ("padding", Value::Length(3.0, Unit::Px)),
+                (
+                    "font-family",
+                    Value::CommaList(vec![
+                        Value::String(String::from("DejaVu Sans")),
+                        Value::List(vec![keyword("Liberation"), keyword("Mono")]),
+                        keyword("monospace"),
+                    ]),
+                ),
+                ("font-stretch", keyword("condensed")),
+                ("background-image", Value::Url(String::from("a.png"))),
+            ]),
+            Some(&ComputedStyle::default()),
+        );
+        let value = |name| style.property_value(name);
+        assert_eq!(value("display").as_deref(), Some("inline-block"));
+        assert_eq!(value("color").as_deref(), Some("rgb(255, 0, 0)"));
+        assert_eq!(
+            value("background-color").as_deref(),
+            Some("rgba(0, 0, 0, 0)")
+        );
+        assert_eq!(value("border-top-color").as_deref(), Some("rgb(255, 0, 0)"));
+        assert_eq!(value("line-height").as_deref(), Some("15px"));
+        assert_eq!(value("margin").as_deref(), Some("2px 2px 2px 10%"));
+        assert_eq!(value("padding").as_deref(), Some("3px"));
+        assert_eq!(value("border-width").as_deref(), Some("0px"));
+        assert_eq!(value("z-index").as_deref(), Some("auto"));
+        assert_eq!(
+            value("font-family").as_deref(),
+            Some("\"DejaVu Sans\", \"Liberation Mono\", monospace")
+        );
+        assert_eq!(value("font-stretch").as_deref(), Some("75%"));
+        assert_eq!(value("background-image").as_deref(), Some("url(\"a.png\")"));
+        assert_eq!(value("width").as_deref(), Some("auto"));
+        assert_eq!(value("text-wrap"), None);
+    }
+
+    #[test]
+    fn test_grid_properties() {
+        let parse = |source: &str| {
+            let mut values = css::parse_value_list(source).unwrap().remove(0);
+            match values.len() {
+                1 => values.remove(0),
+                _ => Value::List(values),
+            }
+        };
+        let style = ComputedStyle::compute(
+            &specified(&[
+                (
+                    "grid-template-columns",
+                    parse("[a] 100px repeat(auto-fill, [b] minmax(10%, 1fr)) [c d]"),
+                ),
+                ("grid-template-rows", parse("1fr fit-content(50%)")),
+                ("grid-template-areas", parse("\"a a .\" \"b b c\"")),
+                ("grid-auto-flow", parse("dense column")),
+                ("grid-row", parse("span 2 a")),
+                ("grid-column-start", parse("-1 b")),
+                ("grid-column-end", parse("a")),
+                ("place-items", keyword("center")),
+            ]),
+            None,
+        );
+        let value = |name| style.property_value(name);
+        assert_eq!(
+            value("grid-template-columns").as_deref(),
+            Some("[a] 100px repeat(auto-fill, [b] minmax(10%, 1fr)) [c d]")
+        );
+        assert_eq!(
+            style.grid_template_rows()[0],
+            TrackListEntry::Track(TrackSize::Breadth(TrackBreadth::Fr(1.0)))
+        );
+        assert_eq!(
+            value("grid-template-areas").as_deref(),
+            Some("\"a a .\" \"b b c\"")
+        );
+        let areas = style.grid_template_areas().unwrap();
+        assert_eq!(areas.columns, 3);
+        assert_eq!(
+            areas.areas[1],
+            NamedArea {
+                name: Arc::from("b"),
+                rows: (2, 3),
+                columns: (1, 3),
+            }
+        );
+        assert_eq!(style.grid_auto_flow(), GridAutoFlow::ColumnDense);
+        assert_eq!(
+            style.grid_row_start(),
+            GridLine::Span(2, Some(Arc::from("a")))
+        );
+        assert_eq!(style.grid_row_end(), GridLine::Auto);
+        assert_eq!(value("grid-row").as_deref(), Some("span 2 a / auto"));
+        assert_eq!(value("grid-column").as_deref(), Some("-1 b / a"));
+        assert_eq!(value("grid-auto-rows").as_deref(), Some("auto"));
+        assert_eq!(style.justify_items(), ItemAlignment::Center);
+
+        for (name, source) in [
+            ("grid-template-columns", "minmax(1fr, 100px)"),
+            (
+                "grid-template-columns",
+                "repeat(auto-fit, 10px) repeat(auto-fill, 10px)",
+            ),
+            ("grid-template-columns", "repeat(auto-fill, auto)"),
+            ("grid-template-columns", "repeat(2, repeat(2, 10px))"),
+            ("grid-template-columns", "-10px"),
+            ("grid-template-areas", "\"a b a\""),
+            ("grid-template-areas", "\"a a\" \"a\""),
+            ("grid-auto-flow", "row column"),
+            ("grid-row-start", "span"),
+            ("grid-row-start", "span -1"),
+            ("grid-row-start", "0"),
+            ("grid-row", "1 / 2 / 3"),
+            ("grid-row", "1 /"),
+            ("grid-area", "1 / 2 / 3 / 4 / 5"),
+        ] {
+            assert!(!is_valid_declaration(name, &parse(source)), "{}", source);
+        }
+    }
+
+    #[test]
+    fn test_property_value_serializes_shorthands() {
+        let value = |declarations: &str, name: &str| {
+            let stylesheet = css::parse(format!("p {{ {} }}", declarations)).unwrap();
+            let specified = stylesheet.rules[0]
+                .declarations
+                .iter()
+                .map(|declaration| (declaration.name.clone(), declaration.value.clone()))
+                .collect();
+            ComputedStyle::compute(&specified, None).property_value(name)
+        };
+        let border = "border: 3px solid red";
+        assert_eq!(
+            value(border, "border").as_deref(),
+            Some("3px solid rgb(255, 0, 0)")
+        );
+        assert_eq!(
+            value(border, "border-top").as_deref(),
+            Some("3px solid rgb(255, 0, 0)")
+        );
+        assert_eq!(value(border, "border-width").as_deref(), Some("3px"));
+        let border = "border: 3px solid red; border-left-width: 1px";
+        assert_eq!(value(border, "border").as_deref(), Some(""));
+        assert_eq!(
+            value(border, "border-width").as_deref(),
+            Some("3px 3px 3px 1px")
+        );
+
+        assert_eq!(
+            value("font: 12px serif", "font").as_deref(),
+            Some("12px serif")
+        );
+        assert_eq!(
+            value("font: italic bold condensed 12px/1.5 monospace", "font").as_deref(),
+            Some("italic 700 condensed 12px / 18px monospace")
+        );
+        assert_eq!(
+            value("font: 12px serif; font-stretch: 80%", "font").as_deref(),
+            Some("")
+        );
+
+        assert_eq!(
+            value("margin: 1px 2px", "margin").as_deref(),
+            Some("1px 2px")
+        );
+        assert_eq!(
+            value("margin: 1px 2px 3px", "margin").as_deref(),
+            Some("1px 2px 3px")
+        );
+        assert_eq!(value("margin: 1px", "margin").as_deref(), Some("1px"));
+        assert_eq!(
+            value("padding: 1px 2px 1px 3px", "padding").as_deref(),
+            Some("1px 2px 1px 3px")
+        );
+    }
+
+    #[test]
+    fn test_grid_placement_shorthands() {
+        let parse = |source: &str| {
+            let mut values = css::parse_value_list(source).unwrap().remove(0);
+            match values.len() {
+                1 => values.remove(0),
+                _ => Value::List(values),
+            }
+        };
+        let compute =
+            |name, source| ComputedStyle::compute(&specified(&[(name, parse(source))]), None);
+        let lines = |style: &ComputedStyle| {
+            [
+                style.grid_row_start(),
+                style.grid_column_start(),
+                style.grid_row_end(),
+                style.grid_column_end(),
+            ]
+        };
+        let name = |name: &str| GridLine::Name(Arc::from(name));
+        let style = compute("grid-column", "2 / span 2");
+        assert_eq!(style.grid_column_start(), GridLine::Line(2, None));
+        assert_eq!(style.grid_column_end(), GridLine::Span(2, None));
+        assert_eq!(style.grid_row_start(), GridLine::Auto);
+
+        let style = compute("grid-area", "1 / 2 / 3 / 4");
+        assert_eq!(
+            lines(&style),
+            [1, 2, 3, 4].map(|line| GridLine::Line(line, None))
+        );
+        assert_eq!(
+            style.property_value("grid-area").as_deref(),
+            Some("1 / 2 / 3 / 4")
+        );
+
+        // Left-out lines copy the lines they pair with if those are names.
+        assert_eq!(
+            lines(&compute("grid-area", "a")),
+            [name("a"), name("a"), name("a"), name("a")]
+        );
+        assert_eq!(
+            lines(&compute("grid-area", "a / 2")),
+            [
+                name("a"),
+                GridLine::Line(2, None),
+                name("a"),
+                GridLine::Auto
+            ]
+        );
+        assert_eq!(
+            lines(&compute("grid-area", "1 / b / 3")),
+            [
+                GridLine::Line(1, None),
+                name("b"),
+                GridLine::Line(3, None),
+                name("b")
+            ]
+        );
+    }
+
+    #[test]
+    fn test_table_properties() {
+        let parent = ComputedStyle::compute(
+            &specified(&[
+                ("table-layout", keyword("fixed")),
+                ("border-collapse", keyword("collapse")),
+                ("border-spacing", Value::Length(2.0, Unit::Px)),
+                ("caption-side", keyword("bottom")),
+            ]),
+            None,
+        );
+        assert_eq!(parent.table_layout(), TableLayout::Fixed);
+        assert_eq!(parent.border_spacing(), (2.0, 2.0));
+        let style = ComputedStyle::compute(&PropertyMap::new(), Some(&parent));
+        assert_eq!(style.table_layout(), TableLayout::Auto);
+        assert_eq!(style.border_collapse(), BorderCollapse::Collapse);
+        assert_eq!(style.caption_side(), CaptionSide::Bottom);
+        assert_eq!(
+            style.property_value("border-spacing").as_deref(),
+            Some("2px 2px")
+        );
+
+        let spacing = Value::List(vec![
+            Value::Length(1.0, Unit::Px),
+            Value::Length(3.0, Unit::Px),
+        ]);
+        let style = ComputedStyle::compute(&specified(&[("border-spacing", spacing)]), None);
+        assert_eq!(style.border_spacing(), (1.0, 3.0));
+        assert!(!is_valid_declaration(
+            "border-spacing",
+            &Value::Length(-1.0, Unit::Px)
+        ));
+        assert!(!is_valid_declaration("border-spacing", &keyword("auto")));
+    }
+
+    #[test]
+    fn test_is_valid_declaration() {
+        assert!(is_valid_declaration("margin", &keyword("auto")));
+        assert!(is_valid_declaration("float", &keyword("inherit")));
+        assert!(!is_valid_declaration(
+            "padding",
+            &Value::Length(-1.0, Unit::Px)
+        ));
+        assert!(!is_valid_declaration("text-wrap", &keyword("balance")));
+    }
+}
@@ -1,26 +1,148 @@
-use crate::parser::Parser;
+use std::fmt;
+
+use itertools::Itertools;
+
+use crate::{
+    at_rules::{
+        FontFace, Keyframe, Keyframes, MarginBox, MarginBoxPosition, PageRule, PageSelector,
+    },
+    media::{
+        Comparison, ContainerQuery, FeatureTest, MediaCondition, MediaFeature, MediaQuery,
+        MediaQueryList, MediaType, Qualifier,
+    },
+    parser::Parser,
+    supports::SupportsCondition,
+};
 
 /// Represents a CSS stylesheet, which contains a list of rules.
 pub struct Stylesheet {
     pub rules: Vec<Rule>,
+    /// The cascade layers declared in the stylesheet, in order of first declaration. Sub-layers
+    /// are named by their full dotted path, e.g. `framework.base`.
+    pub layers: Vec<String>,
+    /// The `@import` rules that haven't been resolved yet, in source order.
+    pub imports: Vec<Import>,
+    /// The valid `@font-face` rules, in source order.
+    pub font_faces: Vec<FontFace>,
+    /// The `@keyframes` rules, in source order.
+    pub keyframes: Vec<Keyframes>,
+    /// The `@page` rules, in source order.
+    pub pages: Vec<PageRule>,
+}
+
+/// An `@import` rule, e.g. `@import url(a.css) layer(base) supports(display: block) screen;`.
+#[derive(Clone, PartialEq, Debug)]
+pub struct Import {
+    /// The URL of the imported stylesheet, as written.
+    pub url: String,
+    /// The full name of the layer the imported rules are placed in, if any.
+    pub layer: Option<String>,
+    /// The condition under which the stylesheet is imported, if any.
+    pub supports: Option<SupportsCondition>,
+    /// The media the imported rules apply to.
+    pub media: MediaQueryList,
+    /// The number of layers declared before the import, which is where the imported stylesheet's
+    /// own layers go in the cascade order.
+    pub layer_position: usize,
 }
 
 /// A CSS rule containing a list of selectors and declarations.
-#[derive(Clone)]
+#[derive(Clone, PartialEq, Debug)]
 pub struct Rule {
     pub selectors: Vec<Selector>,
     pub declarations: Vec<Declaration>,
+    /// The conditions of the at-rules enclosing this rule, outermost first.
+    pub conditions: Vec<Condition>,
+    /// The full name of the cascade layer the rule belongs to, if any.
+    pub layer: Option<String>,
+    /// Where the rule's selectors start in the source.
+    pub source: SourceLocation,
+}
+
+/// A position in the source of a stylesheet.
+#[derive(Clone, PartialEq, Debug, Default)]
+pub struct SourceLocation {
+    /// The URL of the stylesheet, if it was loaded from one.
+    pub url: Option<String>,
+    /// The byte offset in the source.
+    pub offset: usize,
+    /// The line number, starting at 1.
+    pub line: usize,
+    /// The column number in characters, starting at 1.
+    pub column: usize,
+}
+
+/// A condition imposed on a rule by an enclosing conditional at-rule.
+#[derive(Clone, PartialEq, Debug)]
+pub enum Condition {
+    /// The rule is inside an `@media` block.
+    Media(MediaQueryList),
+    /// The rule is inside an `@supports` block.
+    Supports(SupportsCondition),
+    /// The rule is inside an `@container` block.
+    Container(ContainerQuery),
+    /// The rule is inside an `@scope` block.
+    Scope(Scope),
+}
+
+/// The prelude of an `@scope` rule: the rules inside apply to descendants of an element
+/// matching `root`, but not to an element matching `limit` or its descendants.
+#[derive(Clone, PartialEq, Debug)]
+pub struct Scope {
+    /// Selectors for the scoping root. Empty if the rule has no prelude.
+    pub root: Vec<Selector>,
+    /// Selectors for the scoping limit. Empty if the rule has no `to` clause.
+    pub limit: Vec<Selector>,
+}
+
+/// The state rules inherit from the at-rules and style rules enclosing them.
+#[derive(Clone, Default)]
+struct RuleContext {
+    conditions: Vec<Condition>,
+    layer: Option<String>,
+    /// The selectors of the enclosing style rule, which `&` stands for, if the rules are nested.
+    parent: Option<Vec<Selector>>,
 }
 
 /// Represents a CSS selector.
-#[derive(Clone)]
+#[derive(Clone, PartialEq, Debug)]
 pub enum Selector {
     /// A simple CSS selector.
     Simple(SimpleSelector),
+    /// Compound selectors joined by combinators, e.g. `main > p.note` or `& .item`.
+    Complex(ComplexSelector),
+}
+
+/// A struct representing a complex CSS selector.
+#[derive(Clone, PartialEq, Debug)]
+pub struct ComplexSelector {
+    /// The compound selectors to the left of the subject, each with the combinator following it,
+    /// leftmost first.
+    pub ancestors: Vec<(CompoundSelector, Combinator)>,
+    /// The compound selector the element itself must match.
+    pub subject: CompoundSelector,
+}
+
+/// A simple selector that may also contain the nesting selector `&`, e.g. `&.active`.
+#[derive(Clone, PartialEq, Debug)]
+pub struct CompoundSelector {
+    pub simple: SimpleSelector,
+    /// The selectors `&` stands for, if the compound contains it. The element must match one of
+    /// them, like with `:is()`.
+    pub nesting: Option<Vec<Selector>>,
+}
+
+/// An enum representing the combinators between compound selectors.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Combinator {
+    /// Whitespace: the left compound matches an ancestor.
+    Descendant,
+    /// `>`: the left compound matches the parent.
+    Child,
 }
 
 /// A struct representing a simple CSS selector.
-#[derive(Clone)]
+#[derive(Clone, PartialEq, Debug)]
 pub struct SimpleSelector {
     pub tag_name: Option<String>,
     pub id: Option<String>,
@@ -28,10 +150,12 @@ pub struct SimpleSelector {
 }
 
 /// A struct representing a CSS declaration, consisting of a name and a value.
-#[derive(Clone, Debug)]
+#[derive(Clone, PartialEq, Debug)]
 pub struct Declaration {
     pub name: String,
     pub value: Value,
+    /// Whether the declaration is marked `!important`.
+    pub important: bool,
 }
 
 /// An enum representing different types of CSS values.
@@ -43,16 +167,99 @@ pub enum Value {
     Length(f32, Unit),
     /// A color value, represented as a `Color` struct.
     ColorValue(Color),
+    /// A quoted string value, e.g. `"Helvetica Neue"`, with the quotes removed.
+    String(String),
+    /// A `url()` value, represented as the referenced URL.
+    Url(String),
+    /// A unitless number with a fractional part, e.g. `0.5`.
+    Number(f32),
+    /// A unitless integer, e.g. `3`.
+    Integer(i32),
+    /// A percentage, e.g. `50%`, represented as the number before the `%`.
+    Percentage(f32),
+    /// An angle value, represented as a float and an angle unit.
+    Angle(f32, AngleUnit),
+    /// A time value, represented as a float and a time unit.
+    Time(f32, TimeUnit),
+    /// A resolution value, represented as a float and a resolution unit.
+    Resolution(f32, ResolutionUnit),
+    /// A function call, represented as the function name and its arguments.
+    Function(String, Vec<Value>),
+    /// Several space-separated values, e.g. `inline flex`.
+    List(Vec<Value>),
+    /// Several comma-separated values, e.g. `"DejaVu Sans", sans-serif`, each of which is a single
+    /// value or a `List`.
+    CommaList(Vec<Value>),
+    /// A bracketed list of grid line names, e.g. `[header-start main]`.
+    LineNames(Vec<String>),
+    /// A delimiter between values, e.g. the `/` in `rgb(0 0 0 / 50%)` or the `+` in
+    /// `calc(1px + 2px)`.
+    Delimiter(char),
 }
 
 /// An enum representing different units of measurement used in CSS.
 #[derive(Clone, PartialEq, Debug)]
 pub enum Unit {
     Px,
+    /// The computed font size of the element, or of its parent in `font-size`.
+    Em,
+    /// The computed font size of the root element.
+    Rem,
+    In,
+    Cm,
+    Mm,
+    /// A quarter of a millimeter.
+    Q,
+    Pt,
+    Pc,
+    /// A fraction of the free space in a grid container.
+    Fr,
 }
 
-/// A struct representing a color with red, green, blue, and alpha channels.
+impl Unit {
+    /// Returns the unit as written in CSS.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Unit::Px => "px",
+            Unit::Em => "em",
+            Unit::Rem => "rem",
+            Unit::In => "in",
+            Unit::Cm => "cm",
+            Unit::Mm => "mm",
+            Unit::Q => "q",
+            Unit::Pt => "pt",
+            Unit::Pc => "pc",
+            Unit::Fr => "fr",
+        }
+    }
+}
+
+/// An enum representing the units of a CSS angle.
 #[derive(Clone, PartialEq, Debug)]
+pub enum AngleUnit {
+    Deg,
+    Rad,
+    Grad,
+    Turn,
+}
+
+/// An enum representing the units of a CSS time.
+#[derive(Clone, PartialEq, Debug)]
+pub enum TimeUnit {
+    S,
+    Ms,
+}
+
+/// An enum representing the units of a CSS resolution.
+#[derive(Clone, PartialEq, Debug)]
+pub enum ResolutionUnit {
+    Dpi,
+    Dpcm,
+    Dppx,
+}
+
+/// A struct representing a color with red, green, blue, and alpha channels.
+#[derive(Clone, Copy, PartialEq, Debug)]
 pub struct Color {
     r: u8,
     g: u8,
@@ -60,6 +267,13 @@ pub struct Color {
     a: u8,
 }
 
+impl Color {
+    /// Returns the color with the given channels.
+    pub const fn new(r: u8, g: u8, b: u8, a: u8) -> Color {
+        Color { r, g, b, a }
+    }
+}
+
 /// A parser for CSS files.
 struct CssParser {
     position: usize,
@@ -87,10 +301,20 @@ impl Parser for CssParser {
     fn set_current_position(&mut self, position: usize) {
         self.position = position;
     }
+
+    /// Consumes whitespace and comments, which CSS treats alike between tokens.
+    fn consume_whitespace(&mut self) -> Result<(), &'static str> {
+        loop {
+            self.consume_while(char::is_whitespace)?;
+            if !self.skip_comment() {
+                return Ok(());
+            }
+        }
+    }
 }
 
 impl CssParser {
-    /// Parses the CSS rules and returns a vector of `Rule`s.
+    /// Parses the whole input as a stylesheet and returns its rules and layers.
     ///
     /// # Arguments
     ///
@@ -98,270 +322,1988 @@ impl CssParser {
     ///
     /// # Returns
     ///
-    /// * `Result<Vec<Rule>, &str>` - A `Result` containing a vector of `Rule`s if parsing is successful,
-    /// or an error message if parsing fails.
+    /// * `Result<Stylesheet, &str>` - A `Result` containing the `Stylesheet` if parsing is successful,
+    ///   or an error message if parsing fails.
     ///
     /// # Examples
     ///
     /// TODO: Fix example.
     /// ```
     /// let mut css = Css::new("body { background-color: red; }");
-    /// let rules = css.parse_rules().unwrap();
+    /// let rules = css.parse_stylesheet().unwrap().rules;
     /// assert_eq!(rules.len(), 1);
     /// ```
-    fn parse_rules(&mut self) -> Result<Vec<Rule>, &str> {
-        let mut rules = Vec::new();
+    fn parse_stylesheet(&mut self) -> Result<Stylesheet, &'static str> {
+        let mut stylesheet = Stylesheet {
+            rules: Vec::new(),
+            layers: Vec::new(),
+            imports: Vec::new(),
+            font_faces: Vec::new(),
+            keyframes: Vec::new(),
+            pages: Vec::new(),
+        };
         loop {
-            self.consume_whitespace()?;
+            self.parse_rule_list(&RuleContext::default(), &mut stylesheet)?;
             if self.eof() {
-                break;
+                return Ok(stylesheet);
             }
-            rules.push(self.parse_rule().unwrap());
-        }
-        Ok(rules)
-    }
-
-    /// Parses a simple CSS selector and returns a `SimpleSelector` struct.
-    ///
-    /// This function reads the input string character by character and constructs a `SimpleSelector`
-    /// struct based on the characters it reads. It looks for a tag name, an ID, and any number of
-    /// classes in the input string, and constructs a `SimpleSelector` struct with those values.
-    ///
-    /// # Returns
-    ///
-    /// Returns a `Result` containing the `SimpleSelector` struct if parsing was successful, or an
-    /// error message if parsing failed.
-    fn parse_simple_selector(&mut self) -> Result<SimpleSelector, &str> {
-        let mut selector = SimpleSelector {
-            tag_name: None,
-            id: None,
-            class: Vec::new(),
-        };
-
-        while !self.eof() {
-            match self.next_char() {
-                Ok('#') => {
-                    self.consume_char()?;
-                    selector.id = Some(self.parse_identifier().unwrap());
-                }
-                Ok('.') => {
-                    self.consume_char()?;
-                    selector.class.push(self.parse_identifier().unwrap());
-                }
-                Ok('*') => {
-                    self.consume_char()?;
-                }
-                Ok(c) if valid_identifier_char(c) => {
-                    selector.tag_name = Some(self.parse_identifier().unwrap());
-                }
-                _ => break,
+            // A `}` at the top level closes nothing. It starts an invalid rule that runs up to
+            // the end of the next `{}` block, and is dropped.
+            self.consume_char()?;
+            self.skip_component_values(|c| c == '{')?;
+            if !self.eof() {
+                self.skip_block()?;
             }
         }
-
-        Ok(selector)
     }
 
-    /// Parses a list of CSS declarations.
-    ///
-    /// # Arguments
-    ///
-    /// * `self` - A mutable reference to the `CssParser` instance.
-    ///
-    /// # Returns
-    ///
-    /// A `Result` containing a vector of `Declaration` instances if parsing is successful, otherwise an error message.
-    ///
-    /// # Examples
+    /// Parses rules until the end of input or an unmatched `}`, adding them to `stylesheet`.
     ///
-    /// ```
-    /// let mut parser = CssParser::new("body { background-color: red; }");
-    /// let declarations = parser.parse_declarations().unwrap();
-    /// assert_eq!(declarations.len(), 1);
-    /// ```
-    fn parse_declarations(&mut self) -> Result<Vec<Declaration>, &str> {
-        assert_eq!(self.consume_char()?, '{');
+    /// Each parsed rule inherits the conditions and layer of `context`. Inside a style rule,
+    /// declarations may be mixed with the rules; each run of them becomes a rule with the parent's
+    /// selectors, so that they keep their place in the cascade order.
+    fn parse_rule_list(
+        &mut self,
+        context: &RuleContext,
+        stylesheet: &mut Stylesheet,
+    ) -> Result<(), &'static str> {
         let mut declarations = Vec::new();
+        let mut offset = self.position;
         loop {
             self.consume_whitespace()?;
-            if self.next_char()? == '}' {
-                self.consume_char()?;
-                break;
+            let declaration = match self.next_char() {
+                Err(_) | Ok('}') => break,
+                Ok('@') => false,
+                Ok(_) => context.parent.is_some() && self.at_declaration(),
+            };
+            if declaration {
+                if declarations.is_empty() {
+                    offset = self.position;
+                }
+                declarations.extend(self.consume_declaration()?);
+                continue;
+            }
+            if let (Some(parent), false) = (&context.parent, declarations.is_empty()) {
+                let declarations = std::mem::take(&mut declarations);
+                stylesheet
+                    .rules
+                    .push(context.rule(parent.clone(), declarations, offset));
+            }
+            match self.next_char()? {
+                '@' => self.parse_at_rule(context, stylesheet)?,
+                _ => self.parse_style_rule(context, stylesheet)?,
             }
-            declarations.push(self.parse_declaration().unwrap());
         }
-        Ok(declarations)
+        if let (Some(parent), false) = (&context.parent, declarations.is_empty()) {
+            stylesheet
+                .rules
+                .push(context.rule(parent.clone(), declarations, offset));
+        }
+        Ok(())
     }
 
-    /// Parses an identifier from the input stream.
+    /// Parses a style rule and the rules nested inside it, adding them to `stylesheet`.
     ///
-    /// # Returns
-    ///
-    /// Returns a `Result` containing the parsed identifier as a `String` if successful, or an error message as a `&str` if unsuccessful.
-    fn parse_identifier(&mut self) -> Result<String, &str> {
-        self.consume_while(valid_identifier_char)
+    /// A rule whose selectors fail to parse is skipped, along with its block. Invalid declarations
+    /// in the block are dropped on their own, leaving the rest of the rule.
+    fn parse_style_rule(
+        &mut self,
+        context: &RuleContext,
+        stylesheet: &mut Stylesheet,
+    ) -> Result<(), &'static str> {
+        let start = self.position;
+        let rule = match self.parse_rule(context.parent.as_deref()) {
+            Ok(rule) => rule,
+            Err(_) => {
+                self.position = start;
+                return self.skip_at_rule();
+            }
+        };
+
+        let mut context = context.clone();
+        context.parent = Some(rule.selectors.clone());
+        stylesheet
+            .rules
+            .push(context.rule(rule.selectors, rule.declarations, rule.source.offset));
+        self.parse_rule_list(&context, stylesheet)?;
+        match self.consume_char()? {
+            '}' => Ok(()),
+            _ => Err("expected '}'"),
+        }
     }
 
-    /// Parses a CSS rule and returns a `Result` containing a `Rule` struct or an error message.
-    fn parse_rule(&mut self) -> Result<Rule, &str> {
-        Ok(Rule {
-            selectors: self.parse_selectors().unwrap(),
-            declarations: self.parse_declarations()?,
-        })
+    /// Returns true if the input at the current position is a declaration rather than a nested
+    /// rule, i.e. it reaches a `;` or `}` before a `{`.
+    fn at_declaration(&mut self) -> bool {
+        let start = self.position;
+        let is_declaration = self
+            .skip_component_values(|c| matches!(c, ';' | '{' | '}'))
+            .is_ok()
+            && self.next_char() != Ok('{');
+        self.position = start;
+        is_declaration
     }
 
-    /// Parses a CSS value from the input stream.
+    /// Parses an at-rule, adding any style rules and layers it contains to `stylesheet`.
     ///
-    /// Returns a `Result` containing the parsed `Value` or an error message.
-    fn parse_value(&mut self) -> Result<Value, &str> {
-        match self.next_char() {
-            Ok('0'..='9') => Ok(self.parse_length()?),
-            Ok('#') => Ok(self.parse_color()?),
-            _ => Ok(Value::Keyword(self.parse_identifier()?)),
+    /// At-rules that aren't supported, or whose prelude fails to parse, are skipped.
+    fn parse_at_rule(
+        &mut self,
+        context: &RuleContext,
+        stylesheet: &mut Stylesheet,
+    ) -> Result<(), &'static str> {
+        assert_eq!(self.consume_char()?, '@');
+        let name = self.parse_identifier()?.to_ascii_lowercase();
+        self.consume_whitespace()?;
+        // Only conditional group rules and `@layer` can be nested inside style rules.
+        if context.parent.is_some()
+            && !matches!(
+                &*name,
+                "media" | "supports" | "container" | "scope" | "layer"
+            )
+        {
+            return self.skip_at_rule();
+        }
+        let condition = match &*name {
+            "media" => self.parse_media_query_list().map(Condition::Media),
+            "supports" => self.parse_supports_condition().map(Condition::Supports),
+            "container" => self.parse_container_query().map(Condition::Container),
+            "scope" => self.parse_scope().map(Condition::Scope),
+            "layer" => return self.parse_layer_rule(context, stylesheet),
+            "font-face" => {
+                let descriptors = self.parse_descriptors()?;
+                // A font face missing required descriptors is dropped.
+                if let Ok(font_face) = FontFace::from_descriptors(&descriptors) {
+                    stylesheet.font_faces.push(font_face);
+                }
+                return Ok(());
+            }
+            "keyframes" | "-webkit-keyframes" => {
+                return match self.parse_keyframes() {
+                    Ok(keyframes) => {
+                        stylesheet.keyframes.push(keyframes);
+                        Ok(())
+                    }
+                    Err(_) => self.skip_at_rule(),
+                };
+            }
+            "page" => {
+                let start = self.position;
+                return match self.parse_page() {
+                    Ok(page) => {
+                        stylesheet.pages.push(page);
+                        Ok(())
+                    }
+                    Err(_) => {
+                        self.position = start;
+                        self.skip_at_rule()
+                    }
+                };
+            }
+            // `@import` is only valid at the top level, before any style rules.
+            "import"
+                if context.conditions.is_empty()
+                    && context.layer.is_none()
+                    && stylesheet.rules.is_empty() =>
+            {
+                let start = self.position;
+                return match self.parse_import(stylesheet) {
+                    Ok(import) => {
+                        stylesheet.imports.push(import);
+                        Ok(())
+                    }
+                    Err(_) => {
+                        self.position = start;
+                        self.skip_at_rule()
+                    }
+                };
+            }
+            _ => return self.skip_at_rule(),
+        };
+        self.consume_whitespace()?;
+        match condition {
+            Ok(condition) if self.next_char() == Ok('{') => {
+                let mut context = context.clone();
+                context.conditions.push(condition);
+                self.parse_rule_block(&context, stylesheet)
+            }
+            _ => self.skip_at_rule(),
         }
     }
 
-    /// Parses a length value and returns a `Value` enum variant containing the parsed length value.
-    ///
-    /// # Arguments
-    ///
-    /// * `self` - a mutable reference to the `CssParser` instance.
-    ///
-    /// # Returns
-    ///
-    /// Returns a `Result` containing a `Value` enum variant with the parsed length value if successful,
-    /// otherwise returns an error message as a `&str`.
-    fn parse_length(&mut self) -> Result<Value, &str> {
-        Ok(Value::Length(
-            self.parse_float().unwrap(),
-            self.parse_unit()?,
-        ))
+    /// Parses a `{}`-delimited block of rules, adding them to `stylesheet`.
+    fn parse_rule_block(
+        &mut self,
+        context: &RuleContext,
+        stylesheet: &mut Stylesheet,
+    ) -> Result<(), &'static str> {
+        if self.consume_char()? != '{' {
+            return Err("expected '{'");
+        }
+        self.parse_rule_list(context, stylesheet)?;
+        match self.consume_char()? {
+            '}' => Ok(()),
+            _ => Err("expected '}'"),
+        }
     }
 
-    /// Parses a float value from the input stream.
-    ///
-    /// # Returns
-    ///
-    /// Returns a `Result` containing the parsed float value if successful, otherwise an error message.
-    fn parse_float(&mut self) -> Result<f32, &str> {
-        let s = self.consume_while(|c| matches!(c, '0'..='9' | '.'))?;
-        Ok(s.parse().unwrap())
-    }
+    /// Parses the rest of an `@layer` rule: either a statement declaring a list of layers, or a
+    /// block of rules belonging to a named or anonymous layer.
+    fn parse_layer_rule(
+        &mut self,
+        context: &RuleContext,
+        stylesheet: &mut Stylesheet,
+    ) -> Result<(), &'static str> {
+        let mut names = Vec::new();
+        while self.next_char().is_ok_and(valid_identifier_char) {
+            names.push(self.parse_layer_name()?);
+            self.consume_whitespace()?;
+            if self.next_char()? != ',' {
+                break;
+            }
+            self.consume_char()?;
+            self.consume_whitespace()?;
+        }
 
-    /// Parses a unit from the input string.
-    ///
-    /// # Returns
-    ///
-    /// Returns a `Result` containing the parsed `Unit` if successful, or an error message if the unit is unrecognized.
-    fn parse_unit(&mut self) -> Result<Unit, &str> {
-        match &*self.parse_identifier()?.to_ascii_lowercase() {
-            "px" => Ok(Unit::Px),
-            _ => Err("unrecognized unit"),
+        match self.next_char()? {
+            ';' if !names.is_empty() => {
+                self.consume_char()?;
+                for name in names {
+                    stylesheet.declare_layer(context.layer.as_deref(), &name);
+                }
+                Ok(())
+            }
+            '{' if names.len() <= 1 => {
+                // Anonymous layers get a name that can't be written in a stylesheet.
+                let name = names
+                    .pop()
+                    .unwrap_or_else(|| format!("#{}", stylesheet.layers.len()));
+                let mut context = context.clone();
+                context.layer = Some(stylesheet.declare_layer(context.layer.as_deref(), &name));
+                self.parse_rule_block(&context, stylesheet)
+            }
+            _ => self.skip_at_rule(),
         }
     }
 
-    /// Parses a color value from a CSS hex code.
-    ///
-    /// # Arguments
-    ///
-    /// * `self` - A mutable reference to the `CssParser` instance.
+    /// Parses a `{}` block of descriptors, returning each descriptor's name and unparsed value.
     ///
-    /// # Returns
-    ///
-    /// Returns a `Result` containing a `Value` enum variant `ColorValue` with the parsed `Color` struct.
-    ///
-    /// # Errors
-    ///
-    /// Returns an error if the first character consumed is not `#`.
-    fn parse_color(&mut self) -> Result<Value, &str> {
-        assert_eq!(self.consume_char()?, '#');
-        Ok(Value::ColorValue(Color {
-            r: self.parse_hex_pair(),
-            g: self.parse_hex_pair(),
-            b: self.parse_hex_pair(),
-            a: 255,
-        }))
+    /// Descriptors that can't be parsed are skipped.
+    fn parse_descriptors(&mut self) -> Result<Vec<(String, String)>, &'static str> {
+        if self.consume_char()? != '{' {
+            return Err("expected '{'");
+        }
+        let mut descriptors = Vec::new();
+        loop {
+            self.consume_whitespace()?;
+            if self.next_char()? == '}' {
+                self.consume_char()?;
+                break;
+            }
+            if let Some(descriptor) = self.parse_descriptor()? {
+                descriptors.push(descriptor);
+            }
+        }
+        Ok(descriptors)
     }
 
-    /// Parses a hexadecimal pair from the input string and returns the corresponding u8 value.
-    /// Advances the parser's position by 2.
-    fn parse_hex_pair(&mut self) -> u8 {
-        let s = &self.input[self.position..self.position + 2];
-        self.position += 2;
-        u8::from_str_radix(s, 16).unwrap()
+    /// Parses a single `name: value` descriptor, up to and including the `;` that ends it.
+    ///
+    /// Returns the name and unparsed value, or `None` if the descriptor is malformed.
+    fn parse_descriptor(&mut self) -> Result<Option<(String, String)>, &'static str> {
+        let name = self.parse_identifier()?.to_ascii_lowercase();
+        self.consume_whitespace()?;
+        let valid = !name.is_empty() && self.next_char()? == ':';
+        if valid {
+            self.consume_char()?;
+        }
+        let start = self.position;
+        self.skip_component_values(|c| c == ';' || c == '}')?;
+        let value = self.input[start..self.position].trim().to_string();
+        if self.next_char()? == ';' {
+            self.consume_char()?;
+        }
+        Ok(valid.then_some((name, value)))
     }
 
-    /// Parses a CSS declaration and returns a `Declaration` struct.
-    ///
-    /// # Arguments
-    ///
-    /// * `self` - The mutable reference to the `CssParser` instance.
-    ///
-    /// # Returns
+    /// Parses the rest of an `@keyframes` rule.
     ///
-    /// Returns a `Result` containing the `Declaration` struct if parsing is successful,
-    /// otherwise returns an error message as a `&str`.
-    fn parse_declaration(&mut self) -> Result<Declaration, &str> {
-        let property_name = self.parse_identifier().unwrap();
-        self.consume_whitespace()?;
-        assert_eq!(self.consume_char()?, ':');
-        self.consume_whitespace()?;
-        let value = self.parse_value().unwrap();
+    /// Keyframes with invalid selectors are skipped.
+    fn parse_keyframes(&mut self) -> Result<Keyframes, &'static str> {
+        let name = match self.next_char()? {
+            '"' | '\'' => self.parse_string()?,
+            _ => self.parse_identifier()?,
+        };
         self.consume_whitespace()?;
-        assert_eq!(self.consume_char()?, ';');
+        if name.is_empty() || self.consume_char()? != '{' {
+            return Err("expected a name and '{' after @keyframes");
+        }
 
-        Ok(Declaration {
-            name: property_name,
-            value,
-        })
+        let mut keyframes = Vec::new();
+        loop {
+            self.consume_whitespace()?;
+            if self.next_char()? == '}' {
+                self.consume_char()?;
+                break;
+            }
+            let start = self.position;
+            match self.parse_keyframe_offsets() {
+                Ok(offsets) => keyframes.push(Keyframe {
+                    offsets,
+                    declarations: self.parse_declarations()?,
+                }),
+                Err(_) => {
+                    self.position = start;
+                    self.skip_component_values(|c| c == '{' || c == '}')?;
+                    if self.next_char()? == '{' {
+                        self.skip_block()?;
+                    }
+                }
+            }
+        }
+        Ok(Keyframes { name, keyframes })
     }
 
-    /// Parses a list of selectors and returns a vector of `Selector`s.
-    ///
-    /// # Returns
-    ///
-    /// Returns a `Result` containing a vector of `Selector`s if parsing is successful,
-    /// otherwise returns an error message as a string slice.
-    fn parse_selectors(&mut self) -> Result<Vec<Selector>, &str> {
-        let mut selectors = Vec::new();
+    /// Parses a comma-separated list of keyframe selectors, returning them as percentages.
+    fn parse_keyframe_offsets(&mut self) -> Result<Vec<f32>, &'static str> {
+        let mut offsets = Vec::new();
         loop {
-            selectors.push(Selector::Simple(self.parse_simple_selector().unwrap()));
+            let offset = match self.parse_value()? {
+                Value::Keyword(keyword) if keyword.eq_ignore_ascii_case("from") => 0.0,
+                Value::Keyword(keyword) if keyword.eq_ignore_ascii_case("to") => 100.0,
+                Value::Percentage(offset) if (0.0..=100.0).contains(&offset) => offset,
+                _ => return Err("invalid keyframe selector"),
+            };
+            offsets.push(offset);
             self.consume_whitespace()?;
-            match self.next_char() {
-                Ok(',') => {
+            match self.next_char()? {
+                ',' => {
                     self.consume_char()?;
                     self.consume_whitespace()?;
                 }
-                Ok('{') | Err(_) => break,
-                Ok(c) => panic!("Unexpected character {} in selector list", c),
+                '{' => return Ok(offsets),
+                _ => return Err("invalid keyframe selector"),
             }
         }
-        selectors.sort_by_key(|b| std::cmp::Reverse(b.specificity()));
-        Ok(selectors)
     }
-}
-
-/// Parse a whole CSS stylesheet.
-pub fn parse(source: String) -> Result<Stylesheet, &'static str> {
-    let mut parser = CssParser {
-        position: 0,
-        input: source,
-    };
 
-    Ok(Stylesheet {
-        rules: parser.parse_rules().unwrap(),
-    })
-}
+    /// Parses the rest of an `@page` rule: its selectors, declarations and margin boxes.
+    ///
+    /// Declarations whose value isn't a single value webwraith can parse are skipped, as are
+    /// unknown margin boxes.
+    fn parse_page(&mut self) -> Result<PageRule, &'static str> {
+        let mut selectors = Vec::new();
+        while self.next_char()? != '{' {
+            let name = self.parse_identifier()?;
+            let mut pseudo_classes = Vec::new();
+            while self.next_char()? == ':' {
+                self.consume_char()?;
+                pseudo_classes.push(self.parse_identifier()?.to_ascii_lowercase());
+            }
+            if name.is_empty() && pseudo_classes.is_empty() {
+                return Err("invalid page selector");
+            }
+            selectors.push(PageSelector {
+                name: (!name.is_empty()).then_some(name),
+                pseudo_classes,
+            });
+            self.consume_whitespace()?;
+            if self.next_char()? == ',' {
+                self.consume_char()?;
+                self.consume_whitespace()?;
+            }
+        }
 
-impl Selector {
+        self.consume_char()?;
+        let mut declarations = Vec::new();
+        let mut margin_boxes = Vec::new();
+        loop {
+            self.consume_whitespace()?;
+            match self.next_char()? {
+                '}' => {
+                    self.consume_char()?;
+                    break;
+                }
+                '@' => {
+                    self.consume_char()?;
+                    let name = self.parse_identifier()?;
+                    self.consume_whitespace()?;
+                    match MarginBoxPosition::from_name(&name) {
+                        Some(position) => margin_boxes.push(MarginBox {
+                            position,
+                            declarations: self
+                                .parse_descriptors()?
+                                .into_iter()
+                                .filter_map(|(name, value)| single_value_declaration(name, &value))
+                                .collect(),
+                        }),
+                        None => self.skip_at_rule()?,
+                    }
+                }
+                _ => {
+                    if let Some((name, value)) = self.parse_descriptor()? {
+                        declarations.extend(single_value_declaration(name, &value));
+                    }
+                }
+            }
+        }
+
+        Ok(PageRule {
+            selectors,
+            declarations,
+            margin_boxes,
+        })
+    }
+
+    /// Parses the rest of an `@import` rule, declaring the layer it imports into, if any.
+    fn parse_import(&mut self, stylesheet: &mut Stylesheet) -> Result<Import, &'static str> {
+        let url = match self.parse_value()? {
+            Value::Url(url) | Value::String(url) => url,
+            _ => return Err("expected a url in @import"),
+        };
+        self.consume_whitespace()?;
+
+        let layer = if self.consume_keyword("layer") {
+            let name = match self.next_char()? {
+                '(' => {
+                    self.consume_char()?;
+                    self.consume_whitespace()?;
+                    let name = self.parse_layer_name()?;
+                    self.consume_whitespace()?;
+                    if name.is_empty() || self.consume_char()? != ')' {
+                        return Err("expected a layer name in @import");
+                    }
+                    name
+                }
+                _ => format!("#{}", stylesheet.layers.len()),
+            };
+            self.consume_whitespace()?;
+            Some(name)
+        } else {
+            None
+        };
+
+        let supports = if self.consume_keyword("supports") {
+            if self.consume_char()? != '(' {
+                return Err("expected '(' after supports");
+            }
+            self.consume_whitespace()?;
+            // The argument is either a supports condition or a bare declaration.
+            let start = self.position;
+            let condition = match self.parse_supports_condition() {
+                Ok(condition) if self.next_char() == Ok(')') => condition,
+                _ => {
+                    self.position = start;
+                    let name = self.parse_identifier()?;
+                    self.consume_whitespace()?;
+                    if name.is_empty() || self.consume_char()? != ':' {
+                        return Err("expected a supports condition in @import");
+                    }
+                    self.consume_whitespace()?;
+                    let value = self.parse_value()?;
+                    self.consume_whitespace()?;
+                    SupportsCondition::Declaration(name, value)
+                }
+            };
+            if self.consume_char()? != ')' {
+                return Err("expected ')' after supports condition");
+            }
+            self.consume_whitespace()?;
+            Some(condition)
+        } else {
+            None
+        };
+
+        let media = self.parse_media_query_list()?;
+        if self.consume_char()? != ';' {
+            return Err("expected ';' after @import");
+        }
+
+        // Only declare the layer once the whole rule has parsed.
+        let layer = layer.map(|name| stylesheet.declare_layer(None, &name));
+        Ok(Import {
+            url,
+            layer,
+            supports,
+            media,
+            layer_position: stylesheet.layers.len(),
+        })
+    }
+
+    /// Parses a possibly dotted layer name, e.g. `framework.base`.
+    fn parse_layer_name(&mut self) -> Result<String, &'static str> {
+        let mut name = self.parse_identifier()?;
+        while self.next_char() == Ok('.') {
+            self.consume_char()?;
+            let part = self.parse_identifier()?;
+            if part.is_empty() {
+                return Err("expected a layer name after '.'");
+            }
+            name.push('.');
+            name.push_str(&part);
+        }
+        Ok(name)
+    }
+
+    /// Parses an `@supports` condition: a `not` condition, or parenthesized conditions joined by
+    /// `and` or by `or`.
+    fn parse_supports_condition(&mut self) -> Result<SupportsCondition, &'static str> {
+        self.consume_whitespace()?;
+        if self.consume_keyword("not") {
+            self.consume_whitespace()?;
+            return Ok(SupportsCondition::Not(Box::new(
+                self.parse_supports_in_parens()?,
+            )));
+        }
+
+        let first = self.parse_supports_in_parens()?;
+        self.consume_whitespace()?;
+        let combinator = if self.consume_keyword("and") {
+            "and"
+        } else if self.consume_keyword("or") {
+            "or"
+        } else {
+            return Ok(first);
+        };
+
+        let mut conditions = vec![first];
+        loop {
+            self.consume_whitespace()?;
+            conditions.push(self.parse_supports_in_parens()?);
+            self.consume_whitespace()?;
+            if !self.consume_keyword(combinator) {
+                break;
+            }
+        }
+        if self.consume_keyword("and") || self.consume_keyword("or") {
+            return Err("cannot mix 'and' and 'or' in a supports condition");
+        }
+
+        Ok(match combinator {
+            "and" => SupportsCondition::And(conditions),
+            _ => SupportsCondition::Or(conditions),
+        })
+    }
+
+    /// Parses a parenthesized supports condition or declaration, or a `selector()` function.
+    ///
+    /// Any other parenthesized content or function becomes `SupportsCondition::Unknown`.
+    fn parse_supports_in_parens(&mut self) -> Result<SupportsCondition, &'static str> {
+        let start = self.position;
+        let condition = if self.consume_keyword("selector") {
+            self.parse_supports_selector()
+        } else {
+            self.parse_supports_in_parens_contents()
+        };
+        match condition {
+            Ok(condition) => Ok(condition),
+            Err(_) => {
+                self.position = start;
+                self.parse_identifier()?;
+                if self.next_char()? != '(' {
+                    return Err("expected '(' in supports condition");
+                }
+                self.skip_block()?;
+                Ok(SupportsCondition::Unknown)
+            }
+        }
+    }
+
+    /// Parses the contents of a parenthesized supports condition or declaration, including the
+    /// parentheses.
+    fn parse_supports_in_parens_contents(&mut self) -> Result<SupportsCondition, &'static str> {
+        if self.consume_char()? != '(' {
+            return Err("expected '(' in supports condition");
+        }
+        self.consume_whitespace()?;
+        let start = self.position;
+        let condition = if self.next_char()? == '(' || self.consume_keyword("not") {
+            self.position = start;
+            self.parse_supports_condition()?
+        } else {
+            let name = self.parse_identifier()?;
+            self.consume_whitespace()?;
+            if name.is_empty() || self.consume_char()? != ':' {
+                return Err("expected a declaration in supports condition");
+            }
+            self.consume_whitespace()?;
+            SupportsCondition::Declaration(name, self.parse_value()?)
+        };
+        self.consume_whitespace()?;
+        match self.consume_char()? {
+            ')' => Ok(condition),
+            _ => Err("expected ')' in supports condition"),
+        }
+    }
+
+    /// Parses the argument of a `selector()` supports function, including the parentheses.
+    fn parse_supports_selector(&mut self) -> Result<SupportsCondition, &'static str> {
+        if self.consume_char()? != '(' {
+            return Err("expected '(' after selector");
+        }
+        self.consume_whitespace()?;
+        let selector = self.parse_simple_selector()?;
+        self.consume_whitespace()?;
+        match self.consume_char()? {
+            ')' => Ok(SupportsCondition::Selector(Selector::Simple(selector))),
+            _ => Err("unsupported selector"),
+        }
+    }
+
+    /// Parses an `@container` prelude: an optional container name followed by a condition.
+    fn parse_container_query(&mut self) -> Result<ContainerQuery, &'static str> {
+        let start = self.position;
+        let name = self.parse_identifier()?;
+        let name = if name.is_empty() || name.eq_ignore_ascii_case("not") {
+            self.position = start;
+            None
+        } else {
+            self.consume_whitespace()?;
+            Some(name)
+        };
+        Ok(ContainerQuery {
+            name,
+            condition: self.parse_media_condition()?,
+        })
+    }
+
+    /// Parses an `@scope` prelude: `[(<selectors>)]? [to (<selectors>)]?`.
+    fn parse_scope(&mut self) -> Result<Scope, &'static str> {
+        let root = match self.next_char()? {
+            '(' => self.parse_scope_selectors()?,
+            _ => Vec::new(),
+        };
+        self.consume_whitespace()?;
+        let limit = match self.consume_keyword("to") {
+            true => {
+                self.consume_whitespace()?;
+                self.parse_scope_selectors()?
+            }
+            false => Vec::new(),
+        };
+        Ok(Scope { root, limit })
+    }
+
+    /// Parses a parenthesized selector list in an `@scope` prelude.
+    fn parse_scope_selectors(&mut self) -> Result<Vec<Selector>, &'static str> {
+        assert_eq!(self.consume_char()?, '(');
+        self.consume_whitespace()?;
+        let selectors = self.parse_selectors()?;
+        match self.consume_char()? {
+            ')' => Ok(selectors),
+            _ => Err("expected ')' after scope selectors"),
+        }
+    }
+
+    /// Skips the rest of an at-rule: everything up to and including a `;` or a `{}` block.
+    fn skip_at_rule(&mut self) -> Result<(), &'static str> {
+        self.skip_component_values(|c| c == ';' || c == '{')?;
+        match self.next_char() {
+            Ok('{') => self.skip_block(),
+            Ok(_) => self.consume_char().map(|_| ()),
+            Err(_) => Ok(()),
+        }
+    }
+
+    /// Skips input until the end of input or a character for which `stop` returns true,
+    /// outside of any brackets or strings. The stop character is not consumed.
+    fn skip_component_values<F>(&mut self, stop: F) -> Result<(), &'static str>
+    where
+        F: Fn(char) -> bool,
+    {
+        loop {
+            match self.next_char() {
+                Err(_) => return Ok(()),
+                Ok(c) if stop(c) => return Ok(()),
+                Ok('/') if self.skip_comment() => {}
+                Ok('(' | '[' | '{') => self.skip_block()?,
+                Ok('"' | '\'') => {
+                    self.parse_string()?;
+                }
+                Ok(_) => {
+                    self.consume_char()?;
+                }
+            }
+        }
+    }
+
+    /// Skips a `/* */` comment if the input continues with one. A comment that isn't closed runs
+    /// to the end of the input.
+    ///
+    /// # Returns
+    ///
+    /// Returns true if a comment was skipped.
+    fn skip_comment(&mut self) -> bool {
+        let rest = &self.input[self.position..];
+        if !rest.starts_with("/*") {
+            return false;
+        }
+        self.position += rest[2..].find("*/").map_or(rest.len(), |end| end + 4);
+        true
+    }
+
+    /// Skips a `()`, `[]` or `{}` block, including any nested blocks.
+    fn skip_block(&mut self) -> Result<(), &'static str> {
+        let close = match self.consume_char()? {
+            '(' => ')',
+            '[' => ']',
+            '{' => '}',
+            _ => return Err("expected an opening bracket"),
+        };
+        self.skip_component_values(|c| c == close)?;
+        match self.consume_char() {
+            Ok(_) => Ok(()),
+            Err(_) => Err("unterminated block"),
+        }
+    }
+
+    /// Consumes `keyword` if the input continues with it, ignoring ASCII case, followed by a
+    /// character that can't continue an identifier.
+    ///
+    /// # Returns
+    ///
+    /// Returns true if the keyword was consumed.
+    fn consume_keyword(&mut self, keyword: &str) -> bool {
+        let rest = &self.input[self.position..];
+        let matches = rest
+            .get(..keyword.len())
+            .is_some_and(|prefix| prefix.eq_ignore_ascii_case(keyword))
+            && !rest[keyword.len()..]
+                .chars()
+                .next()
+                .is_some_and(valid_identifier_char);
+        if matches {
+            self.position += keyword.len();
+        }
+        matches
+    }
+
+    /// Parses a comma-separated media query list, up to but not including the `{` or `;` that
+    /// follows.
+    ///
+    /// Queries that fail to parse are replaced by `not all`, as the Media Queries spec requires.
+    fn parse_media_query_list(&mut self) -> Result<MediaQueryList, &'static str> {
+        let mut queries = Vec::new();
+        loop {
+            self.consume_whitespace()?;
+            if matches!(self.next_char()?, '{' | ';') {
+                break;
+            }
+            let start = self.position;
+            let query = match self.parse_media_query() {
+                Ok(query) if matches!(self.next_char(), Ok(',' | '{' | ';')) => query,
+                _ => {
+                    self.position = start;
+                    self.skip_component_values(|c| matches!(c, ',' | '{' | ';'))?;
+                    MediaQuery::not_all()
+                }
+            };
+            queries.push(query);
+            if self.next_char()? == ',' {
+                self.consume_char()?;
+            }
+        }
+        Ok(MediaQueryList(queries))
+    }
+
+    /// Parses a single media query, e.g. `not print and (hover)` or `(width > 400px)`.
+    fn parse_media_query(&mut self) -> Result<MediaQuery, &'static str> {
+        let start = self.position;
+        let qualifier = if self.consume_keyword("not") {
+            Some(Qualifier::Not)
+        } else if self.consume_keyword("only") {
+            Some(Qualifier::Only)
+        } else {
+            None
+        };
+        self.consume_whitespace()?;
+
+        if self.next_char()? == '(' {
+            // A media condition on its own. A leading `not` belongs to the condition.
+            self.position = start;
+            let condition = self.parse_media_condition()?;
+            self.consume_whitespace()?;
+            return Ok(MediaQuery {
+                qualifier: None,
+                media_type: MediaType::All,
+                condition: Some(condition),
+            });
+        }
+
+        let media_type = match &*self.parse_identifier()?.to_ascii_lowercase() {
+            "" => return Err("expected a media type"),
+            "all" => MediaType::All,
+            "screen" => MediaType::Screen,
+            "print" => MediaType::Print,
+            other => MediaType::Other(other.to_string()),
+        };
+        self.consume_whitespace()?;
+        let condition = if self.consume_keyword("and") {
+            self.consume_whitespace()?;
+            Some(self.parse_media_condition()?)
+        } else {
+            None
+        };
+        self.consume_whitespace()?;
+
+        Ok(MediaQuery {
+            qualifier,
+            media_type,
+            condition,
+        })
+    }
+
+    /// Parses a media condition: a `not` condition, or parenthesized conditions joined by
+    /// `and` or by `or`. Mixing `and` and `or` without parentheses is an error.
+    fn parse_media_condition(&mut self) -> Result<MediaCondition, &'static str> {
+        self.consume_whitespace()?;
+        if self.consume_keyword("not") {
+            self.consume_whitespace()?;
+            return Ok(MediaCondition::Not(Box::new(self.parse_media_in_parens()?)));
+        }
+
+        let first = self.parse_media_in_parens()?;
+        self.consume_whitespace()?;
+        let combinator = if self.consume_keyword("and") {
+            "and"
+        } else if self.consume_keyword("or") {
+            "or"
+        } else {
+            return Ok(first);
+        };
+
+        let mut conditions = vec![first];
+        loop {
+            self.consume_whitespace()?;
+            conditions.push(self.parse_media_in_parens()?);
+            self.consume_whitespace()?;
+            if !self.consume_keyword(combinator) {
+                break;
+            }
+        }
+        if self.consume_keyword("and") || self.consume_keyword("or") {
+            return Err("cannot mix 'and' and 'or' in a media condition");
+        }
+
+        Ok(match combinator {
+            "and" => MediaCondition::And(conditions),
+            _ => MediaCondition::Or(conditions),
+        })
+    }
+
+    /// Parses a parenthesized media condition or media feature.
+    ///
+    /// Parenthesized content that can't be parsed becomes `MediaCondition::Unknown`.
+    fn parse_media_in_parens(&mut self) -> Result<MediaCondition, &'static str> {
+        if self.next_char()? != '(' {
+            return Err("expected '(' in media condition");
+        }
+        let start = self.position;
+        match self.parse_media_in_parens_contents() {
+            Ok(condition) => Ok(condition),
+            Err(_) => {
+                self.position = start;
+                self.skip_block()?;
+                Ok(MediaCondition::Unknown)
+            }
+        }
+    }
+
+    /// Parses the contents of a parenthesized media condition or feature, including the
+    /// parentheses.
+    fn parse_media_in_parens_contents(&mut self) -> Result<MediaCondition, &'static str> {
+        assert_eq!(self.consume_char()?, '(');
+        self.consume_whitespace()?;
+        let start = self.position;
+        let condition = if self.next_char()? == '(' || self.consume_keyword("not") {
+            self.position = start;
+            self.parse_media_condition()?
+        } else {
+            MediaCondition::Feature(self.parse_media_feature()?)
+        };
+        self.consume_whitespace()?;
+        match self.consume_char()? {
+            ')' => Ok(condition),
+            _ => Err("expected ')' after media feature"),
+        }
+    }
+
+    /// Parses a media feature in plain (`width: 400px`), boolean (`hover`) or range
+    /// (`400px <= width < 800px`) syntax, without the surrounding parentheses.
+    fn parse_media_feature(&mut self) -> Result<MediaFeature, &'static str> {
+        if self.starts_number() {
+            // `value op name [op value]`
+            let value = self.parse_value()?;
+            self.consume_whitespace()?;
+            let first = self.parse_comparison()?;
+            self.consume_whitespace()?;
+            let name = self.parse_identifier()?.to_ascii_lowercase();
+            self.consume_whitespace()?;
+            let mut comparisons = vec![(flip_comparison(first), value)];
+            if matches!(self.next_char()?, '<' | '>' | '=') {
+                let second = self.parse_comparison()?;
+                let ascending = |c| matches!(c, Comparison::Lt | Comparison::Le);
+                let descending = |c| matches!(c, Comparison::Gt | Comparison::Ge);
+                if !(ascending(first) && ascending(second)
+                    || descending(first) && descending(second))
+                {
+                    return Err("invalid media feature range");
+                }
+                self.consume_whitespace()?;
+                comparisons.push((second, self.parse_value()?));
+            }
+            return Ok(MediaFeature {
+                name,
+                test: FeatureTest::Range(comparisons),
+            });
+        }
+
+        let name = self.parse_identifier()?.to_ascii_lowercase();
+        if name.is_empty() {
+            return Err("expected a media feature name");
+        }
+        self.consume_whitespace()?;
+        match self.next_char()? {
+            ')' => Ok(MediaFeature {
+                name,
+                test: FeatureTest::Boolean,
+            }),
+            ':' => {
+                self.consume_char()?;
+                self.consume_whitespace()?;
+                let value = self.parse_value()?;
+                Ok(
+                    match (name.strip_prefix("min-"), name.strip_prefix("max-")) {
+                        (Some(name), _) => MediaFeature {
+                            name: name.to_string(),
+                            test: FeatureTest::Range(vec![(Comparison::Ge, value)]),
+                        },
+                        (_, Some(name)) => MediaFeature {
+                            name: name.to_string(),
+                            test: FeatureTest::Range(vec![(Comparison::Le, value)]),
+                        },
+                        _ => MediaFeature {
+                            name,
+                            test: FeatureTest::Plain(value),
+                        },
+                    },
+                )
+            }
+            _ => {
+                let comparison = self.parse_comparison()?;
+                self.consume_whitespace()?;
+                Ok(MediaFeature {
+                    name,
+                    test: FeatureTest::Range(vec![(comparison, self.parse_value()?)]),
+                })
+            }
+        }
+    }
+
+    /// Parses a range comparison operator: `<`, `<=`, `>`, `>=` or `=`.
+    fn parse_comparison(&mut self) -> Result<Comparison, &'static str> {
+        let comparison = match self.consume_char()? {
+            '=' => return Ok(Comparison::Eq),
+            '<' => Comparison::Lt,
+            '>' => Comparison::Gt,
+            _ => return Err("expected a comparison operator"),
+        };
+        if self.next_char() == Ok('=') {
+            self.consume_char()?;
+            return Ok(match comparison {
+                Comparison::Lt => Comparison::Le,
+                _ => Comparison::Ge,
+            });
+        }
+        Ok(comparison)
+    }
+
+    /// Parses a simple CSS selector and returns a `SimpleSelector` struct.
+    ///
+    /// This function reads the input string character by character and constructs a `SimpleSelector`
+    /// struct based on the characters it reads. It looks for a tag name, an ID, and any number of
+    /// classes in the input string, and constructs a `SimpleSelector` struct with those values.
+    ///
+    /// # Returns
+    ///
+    /// Returns a `Result` containing the `SimpleSelector` struct if parsing was successful, or an
+    /// error message if parsing failed.
+    fn parse_simple_selector(&mut self) -> Result<SimpleSelector, &'static str> {
+        let mut selector = SimpleSelector {
+            tag_name: None,
+            id: None,
+            class: Vec::new(),
+        };
+
+        while !self.eof() {
+            match self.next_char() {
+                Ok('#') => {
+                    self.consume_char()?;
+                    selector.id = Some(self.parse_identifier().unwrap());
+                }
+                Ok('.') => {
+                    self.consume_char()?;
+                    selector.class.push(self.parse_identifier().unwrap());
+                }
+                Ok('*') => {
+                    self.consume_char()?;
+                }
+                Ok(c) if valid_identifier_char(c) => {
+                    selector.tag_name = Some(self.parse_identifier().unwrap());
+                }
+                _ => break,
+            }
+        }
+
+        Ok(selector)
+    }
+
+    /// Parses a list of CSS declarations.
+    ///
+    /// # Arguments
+    ///
+    /// * `self` - A mutable reference to the `CssParser` instance.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing a vector of `Declaration` instances if parsing is successful, otherwise an error message.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// let mut parser = CssParser::new("body { background-color: red; }");
+    /// let declarations = parser.parse_declarations().unwrap();
+    /// assert_eq!(declarations.len(), 1);
+    /// ```
+    fn parse_declarations(&mut self) -> Result<Vec<Declaration>, &'static str> {
+        assert_eq!(self.consume_char()?, '{');
+        let mut declarations = Vec::new();
+        loop {
+            self.consume_whitespace()?;
+            if self.next_char()? == '}' {
+                self.consume_char()?;
+                break;
+            }
+            declarations.extend(self.consume_declaration()?);
+        }
+        Ok(declarations)
+    }
+
+    /// Parses an identifier from the input stream.
+    ///
+    /// # Returns
+    ///
+    /// Returns a `Result` containing the parsed identifier as a `String` if successful, or an error message as a `&str` if unsuccessful.
+    fn parse_identifier(&mut self) -> Result<String, &'static str> {
+        self.consume_while(valid_identifier_char)
+    }
+
+    /// Parses the selectors of a CSS rule and the declarations at the start of its block, and
+    /// returns a `Result` containing a `Rule` struct or an error message.
+    ///
+    /// Inside a style rule, `parent` holds its selectors. Parsing stops at the end of the block or
+    /// at the first nested rule, which is left for the caller.
+    fn parse_rule(&mut self, parent: Option<&[Selector]>) -> Result<Rule, &'static str> {
+        let offset = self.position;
+        let selectors = self.parse_selector_list(parent)?;
+        if self.consume_char()? != '{' {
+            return Err("expected '{' after selectors");
+        }
+        let mut declarations = Vec::new();
+        loop {
+            self.consume_whitespace()?;
+            if self.next_char()? == '}' || self.next_char()? == '@' || !self.at_declaration() {
+                break;
+            }
+            declarations.extend(self.consume_declaration()?);
+        }
+        Ok(Rule {
+            selectors,
+            declarations,
+            conditions: Vec::new(),
+            layer: None,
+            source: SourceLocation {
+                offset,
+                ..SourceLocation::default()
+            },
+        })
+    }
+
+    /// Parses a CSS value from the input stream.
+    ///
+    /// Returns a `Result` containing the parsed `Value` or an error message.
+    fn parse_value(&mut self) -> Result<Value, &'static str> {
+        match self.next_char() {
+            Ok('"' | '\'') => Ok(Value::String(self.parse_string()?)),
+            Ok('#') => Ok(self.parse_color()?),
+            Ok('[') => Ok(Value::LineNames(self.parse_line_names()?)),
+            _ if self.starts_number() => Ok(self.parse_numeric()?),
+            Ok(c @ ('/' | '+' | '*')) => {
+                self.consume_char()?;
+                Ok(Value::Delimiter(c))
+            }
+            _ => {
+                let name = self.parse_identifier()?;
+                if self.next_char() != Ok('(') {
+                    return Ok(Value::Keyword(name));
+                }
+                if name.eq_ignore_ascii_case("url") {
+                    Ok(Value::Url(self.parse_url()?))
+                } else {
+                    Ok(Value::Function(name, self.parse_arguments()?))
+                }
+            }
+        }
+    }
+
+    /// Returns true if the input at the current position starts a number, i.e. a digit, or a
+    /// sign or decimal point followed by a digit.
+    fn starts_number(&self) -> bool {
+        let mut chars = self.input[self.position..].chars();
+        match chars.next() {
+            Some('0'..='9') => true,
+            Some('+' | '-') => match chars.next() {
+                Some('0'..='9') => true,
+                Some('.') => matches!(chars.next(), Some('0'..='9')),
+                _ => false,
+            },
+            Some('.') => matches!(chars.next(), Some('0'..='9')),
+            _ => false,
+        }
+    }
+
+    /// Parses a numeric value and returns the `Value` variant matching its unit.
+    ///
+    /// A number without a unit is returned as an `Integer` if it has no fractional part, and as
+    /// a `Number` otherwise.
+    ///
+    /// # Returns
+    ///
+    /// Returns a `Result` containing the parsed `Value` if successful, or an error message if the
+    /// unit is unrecognized.
+    fn parse_numeric(&mut self) -> Result<Value, &'static str> {
+        let start = self.position;
+        let number = self.parse_float()?;
+        if self.next_char() == Ok('%') {
+            self.consume_char()?;
+            return Ok(Value::Percentage(number));
+        }
+        if self.eof() || !valid_identifier_char(self.next_char()?) {
+            let literal = &self.input[start..self.position];
+            return Ok(match literal.parse() {
+                Ok(integer) => Value::Integer(integer),
+                Err(_) => Value::Number(number),
+            });
+        }
+
+        let unit = self.parse_identifier()?.to_ascii_lowercase();
+        match &*unit {
+            "deg" => Ok(Value::Angle(number, AngleUnit::Deg)),
+            "rad" => Ok(Value::Angle(number, AngleUnit::Rad)),
+            "grad" => Ok(Value::Angle(number, AngleUnit::Grad)),
+            "turn" => Ok(Value::Angle(number, AngleUnit::Turn)),
+            "s" => Ok(Value::Time(number, TimeUnit::S)),
+            "ms" => Ok(Value::Time(number, TimeUnit::Ms)),
+            "dpi" => Ok(Value::Resolution(number, ResolutionUnit::Dpi)),
+            "dpcm" => Ok(Value::Resolution(number, ResolutionUnit::Dpcm)),
+            "dppx" | "x" => Ok(Value::Resolution(number, ResolutionUnit::Dppx)),
+            _ => {
+                // Anything else must be a length; re-parse it as one.
+                self.position = start;
+                self.parse_length()
+            }
+        }
+    }
+
+    /// Parses a length value and returns a `Value` enum variant containing the parsed length value.
+    ///
+    /// # Arguments
+    ///
+    /// * `self` - a mutable reference to the `CssParser` instance.
+    ///
+    /// # Returns
+    ///
+    /// Returns a `Result` containing a `Value` enum variant with the parsed length value if successful,
+    /// otherwise returns an error message as a `&str`.
+    fn parse_length(&mut self) -> Result<Value, &'static str> {
+        Ok(Value::Length(self.parse_float()?, self.parse_unit()?))
+    }
+
+    /// Parses a float value from the input stream, with an optional sign and exponent.
+    ///
+    /// # Returns
+    ///
+    /// Returns a `Result` containing the parsed float value if successful, otherwise an error message.
+    fn parse_float(&mut self) -> Result<f32, &'static str> {
+        let mut s = String::new();
+        if let Ok(sign @ ('+' | '-')) = self.next_char() {
+            self.consume_char()?;
+            s.push(sign);
+        }
+        s.push_str(&self.consume_while(|c| matches!(c, '0'..='9' | '.'))?);
+        // An `e` is only an exponent if digits follow it, so `1em` is a length.
+        let mut exponent = self.input[self.position..].chars();
+        if matches!(exponent.next(), Some('e' | 'E')) {
+            let digit = match exponent.next() {
+                Some('+' | '-') => exponent.next(),
+                c => c,
+            };
+            if digit.is_some_and(|c| c.is_ascii_digit()) {
+                s.push(self.consume_char()?);
+                if let Ok(sign @ ('+' | '-')) = self.next_char() {
+                    self.consume_char()?;
+                    s.push(sign);
+                }
+                s.push_str(&self.consume_while(|c| c.is_ascii_digit())?);
+            }
+        }
+        s.parse().map_err(|_| "invalid number")
+    }
+
+    /// Parses a unit from the input string.
+    ///
+    /// # Returns
+    ///
+    /// Returns a `Result` containing the parsed `Unit` if successful, or an error message if the unit is unrecognized.
+    fn parse_unit(&mut self) -> Result<Unit, &'static str> {
+        let unit = self.parse_identifier()?.to_ascii_lowercase();
+        [
+            Unit::Px,
+            Unit::Em,
+            Unit::Rem,
+            Unit::In,
+            Unit::Cm,
+            Unit::Mm,
+            Unit::Q,
+            Unit::Pt,
+            Unit::Pc,
+            Unit::Fr,
+        ]
+        .into_iter()
+        .find(|candidate| candidate.as_str() == unit)
+        .ok_or("unrecognized unit")
+    }
+
+    /// Parses a quoted string and returns its contents without the quotes.
+    ///
+    /// Backslash escapes are resolved as in CSS Syntax § 4.3.7: 1 to 6 hex digits, plus one
+    /// optional whitespace character after them, stand for the code point they spell out, an
+    /// escaped newline is dropped, and any other escaped character is kept as-is.
+    ///
+    /// # Returns
+    ///
+    /// Returns a `Result` containing the string contents, or an error message if the string is
+    /// not terminated.
+    fn parse_string(&mut self) -> Result<String, &'static str> {
+        let quote = self.consume_char()?;
+        let mut result = String::new();
+        loop {
+            match self.consume_char()? {
+                c if c == quote => break,
+                '\\' => match self.next_char()? {
+                    '\n' => {
+                        self.consume_char()?;
+                    }
+                    c if c.is_ascii_hexdigit() => result.push(self.parse_hex_escape()?),
+                    _ => result.push(self.consume_char()?),
+                },
+                c => result.push(c),
+            }
+        }
+        Ok(result)
+    }
+
+    /// Parses the hex digits of an escape and the whitespace character after them, if any, and
+    /// returns the code point they stand for, or U+FFFD if it isn't a valid one.
+    fn parse_hex_escape(&mut self) -> Result<char, &'static str> {
+        let start = self.position;
+        while self.position - start < 6 && self.next_char().is_ok_and(|c| c.is_ascii_hexdigit()) {
+            self.consume_char()?;
+        }
+        let code = u32::from_str_radix(&self.input[start..self.position], 16)
+            .map_err(|_| "expected hex digits in escape")?;
+        if self.next_char().is_ok_and(char::is_whitespace) {
+            self.consume_char()?;
+        }
+        Ok(match code {
+            0 => char::REPLACEMENT_CHARACTER,
+            code => char::from_u32(code).unwrap_or(char::REPLACEMENT_CHARACTER),
+        })
+    }
+
+    /// Parses the parenthesized part of a `url()` value, which may be quoted or unquoted.
+    ///
+    /// # Returns
+    ///
+    /// Returns a `Result` containing the URL, or an error message if the closing parenthesis is
+    /// missing.
+    fn parse_url(&mut self) -> Result<String, &'static str> {
+        assert_eq!(self.consume_char()?, '(');
+        self.consume_whitespace()?;
+        let url = match self.next_char()? {
+            '"' | '\'' => self.parse_string()?,
+            _ => self.consume_while(|c| c != ')' && !c.is_whitespace())?,
+        };
+        self.consume_whitespace()?;
+        match self.consume_char()? {
+            ')' => Ok(url),
+            _ => Err("expected ')' after url"),
+        }
+    }
+
+    /// Parses a parenthesized list of function arguments separated by commas or whitespace.
+    ///
+    /// # Returns
+    ///
+    /// Returns a `Result` containing the parsed argument values, or an error message if the
+    /// closing parenthesis is missing.
+    fn parse_arguments(&mut self) -> Result<Vec<Value>, &'static str> {
+        assert_eq!(self.consume_char()?, '(');
+        let mut arguments = Vec::new();
+        loop {
+            self.consume_while(|c| c == ',' || c.is_whitespace())?;
+            match self.next_char()? {
+                ')' => {
+                    self.consume_char()?;
+                    break;
+                }
+                _ => {
+                    let position = self.position;
+                    arguments.push(self.parse_value()?);
+                    if self.position == position {
+                        return Err("unexpected character in function arguments");
+                    }
+                }
+            }
+        }
+        Ok(arguments)
+    }
+
+    /// Parses a bracketed list of whitespace-separated grid line names.
+    ///
+    /// # Returns
+    ///
+    /// Returns a `Result` containing the names, or an error message if the closing bracket is
+    /// missing or something other than a name is in the brackets.
+    fn parse_line_names(&mut self) -> Result<Vec<String>, &'static str> {
+        assert_eq!(self.consume_char()?, '[');
+        let mut names = Vec::new();
+        loop {
+            self.consume_whitespace()?;
+            if self.next_char()? == ']' {
+                self.consume_char()?;
+                return Ok(names);
+            }
+            let name = self.parse_identifier()?;
+            if name.is_empty() {
+                return Err("expected a line name");
+            }
+            names.push(name);
+        }
+    }
+
+    /// Parses a color value from a CSS hex code of 3, 4, 6 or 8 digits. The 4- and 8-digit
+    /// forms end with the alpha channel, and each digit of the short forms is doubled.
+    ///
+    /// # Returns
+    ///
+    /// Returns a `Result` containing a `Value` enum variant `ColorValue` with the parsed `Color` struct.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the input doesn't start with `#` followed by 3, 4, 6 or 8 hex digits.
+    fn parse_color(&mut self) -> Result<Value, &'static str> {
+        if self.consume_char()? != '#' {
+            return Err("expected '#'");
+        }
+        let digits = self.input[self.position..]
+            .chars()
+            .take_while(char::is_ascii_hexdigit)
+            .count();
+        let channels = match digits {
+            3 | 4 => (0..digits)
+                .map(|_| Ok(self.parse_hex_digit()? * 17))
+                .collect::<Result<Vec<_>, _>>()?,
+            6 | 8 => (0..digits / 2)
+                .map(|_| self.parse_hex_pair())
+                .collect::<Result<Vec<_>, _>>()?,
+            _ => return Err("expected 3, 4, 6 or 8 hex digits"),
+        };
+        if self.next_char().is_ok_and(valid_identifier_char) {
+            return Err("expected 3, 4, 6 or 8 hex digits");
+        }
+        Ok(Value::ColorValue(Color {
+            r: channels[0],
+            g: channels[1],
+            b: channels[2],
+            a: channels.get(3).copied().unwrap_or(255),
+        }))
+    }
+
+    /// Parses a hexadecimal pair from the input string and returns the corresponding u8 value.
+    /// Advances the parser's position by 2.
+    fn parse_hex_pair(&mut self) -> Result<u8, &'static str> {
+        let pair = self
+            .input
+            .get(self.position..self.position + 2)
+            .filter(|pair| pair.chars().all(|c| c.is_ascii_hexdigit()))
+            .ok_or("expected two hex digits")?;
+        let value = u8::from_str_radix(pair, 16).map_err(|_| "expected two hex digits")?;
+        self.position += 2;
+        Ok(value)
+    }
+
+    /// Parses a hexadecimal digit from the input string and returns its value.
+    fn parse_hex_digit(&mut self) -> Result<u8, &'static str> {
+        let digit = self
+            .next_char()?
+            .to_digit(16)
+            .ok_or("expected a hex digit")?;
+        self.consume_char()?;
+        Ok(digit as u8)
+    }
+
+    /// Parses a declaration, or skips past it if it is invalid, as CSS Syntax says: up to
+    /// and including the next `;`, or up to the `}` that ends the block, outside of any brackets.
+    ///
+    /// # Returns
+    ///
+    /// Returns a `Result` containing the declaration, or `None` if it was invalid, or an error
+    /// message if the input ends inside brackets or a string.
+    fn consume_declaration(&mut self) -> Result<Option<Declaration>, &'static str> {
+        let start = self.position;
+        if let Ok(declaration) = self.parse_declaration() {
+            return Ok(Some(declaration));
+        }
+        self.position = start;
+        self.skip_component_values(|c| c == ';' || c == '}')?;
+        if self.next_char() == Ok(';') {
+            self.consume_char()?;
+        }
+        Ok(None)
+    }
+
+    /// Parses a CSS declaration and returns a `Declaration` struct.
+    ///
+    /// # Arguments
+    ///
+    /// * `self` - The mutable reference to the `CssParser` instance.
+    ///
+    /// # Returns
+    ///
+    /// Returns a `Result` containing the `Declaration` struct if parsing is successful,
+    /// otherwise returns an error message as a `&str`.
+    fn parse_declaration(&mut self) -> Result<Declaration, &'static str> {
+        let property_name = self.parse_identifier()?;
+        if property_name.is_empty() {
+            return Err("expected a property name");
+        }
+        self.consume_whitespace()?;
+        if self.consume_char()? != ':' {
+            return Err("expected ':' after property name");
+        }
+        self.consume_whitespace()?;
+        let mut groups = vec![Vec::new()];
+        let mut important = false;
+        while !matches!(self.next_char()?, ';' | '}') {
+            if important {
+                return Err("expected the end of the declaration after '!important'");
+            }
+            if self.next_char()? == '!' {
+                self.consume_char()?;
+                self.consume_whitespace()?;
+                if !self.consume_keyword("important") {
+                    return Err("expected 'important' after '!'");
+                }
+                important = true;
+                self.consume_whitespace()?;
+                continue;
+            }
+            if self.next_char()? == ',' {
+                self.consume_char()?;
+                self.consume_whitespace()?;
+                groups.push(Vec::new());
+                continue;
+            }
+            let position = self.position;
+            let value = self.parse_value()?;
+            if self.position == position {
+                return Err("unexpected character in declaration value");
+            }
+            // `groups` always has a last group.
+            groups.last_mut().unwrap().push(value);
+            self.consume_whitespace()?;
+        }
+        // The `}` that ends a block ends its last declaration too, and is left for the block.
+        if self.next_char()? == ';' {
+            self.consume_char()?;
+        }
+
+        let mut values = groups
+            .into_iter()
+            .map(|mut values| match values.len() {
+                0 => Err("missing declaration value"),
+                1 => Ok(values.pop().unwrap()),
+                _ => Ok(Value::List(values)),
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        let value = match values.len() {
+            1 => values.pop().unwrap(),
+            _ => Value::CommaList(values),
+        };
+        Ok(Declaration {
+            name: property_name,
+            value,
+            important,
+        })
+    }
+
+    /// Parses a list of selectors and returns a vector of `Selector`s.
+    ///
+    /// # Returns
+    ///
+    /// Returns a `Result` containing a vector of `Selector`s if parsing is successful,
+    /// otherwise returns an error message as a string slice.
+    fn parse_selectors(&mut self) -> Result<Vec<Selector>, &'static str> {
+        self.parse_selector_list(None)
+    }
+
+    /// Parses a comma-separated list of selectors, sorted by decreasing specificity.
+    ///
+    /// Inside a style rule, `parent` holds its selectors: `&` stands for them, and selectors that
+    /// don't contain `&` are relative to them, e.g. `> p` means `& > p`.
+    fn parse_selector_list(
+        &mut self,
+        parent: Option<&[Selector]>,
+    ) -> Result<Vec<Selector>, &'static str> {
+        let mut selectors = Vec::new();
+        loop {
+            selectors.push(self.parse_complex_selector(parent)?);
+            match self.next_char() {
+                Ok(',') => {
+                    self.consume_char()?;
+                    self.consume_whitespace()?;
+                }
+                Ok('{' | ')') | Err(_) => break,
+                Ok(_) => return Err("unexpected character in selector list"),
+            }
+        }
+        selectors.sort_by_key(|b| std::cmp::Reverse(b.specificity()));
+        Ok(selectors)
+    }
+
+    /// Parses a selector made of compound selectors joined by combinators, along with any
+    /// whitespace after it.
+    ///
+    /// Selectors without combinators or `&` are returned as `Selector::Simple`.
+    fn parse_complex_selector(
+        &mut self,
+        parent: Option<&[Selector]>,
+    ) -> Result<Selector, &'static str> {
+        let leading = self.parse_combinator()?;
+        if leading.is_some() && parent.is_none() {
+            return Err("relative selector outside of a style rule");
+        }
+
+        let mut ancestors = Vec::new();
+        let mut subject = self.parse_compound_selector(parent)?;
+        while let Some(combinator) = self.parse_combinator()? {
+            let next = self.parse_compound_selector(parent)?;
+            ancestors.push((std::mem::replace(&mut subject, next), combinator));
+        }
+
+        let nested =
+            subject.nesting.is_some() || ancestors.iter().any(|(c, _)| c.nesting.is_some());
+        if let (Some(parent), false) = (parent, nested) {
+            let compound = CompoundSelector {
+                simple: SimpleSelector {
+                    tag_name: None,
+                    id: None,
+                    class: Vec::new(),
+                },
+                nesting: Some(parent.to_vec()),
+            };
+            ancestors.insert(0, (compound, leading.unwrap_or(Combinator::Descendant)));
+        }
+
+        Ok(match (ancestors.is_empty(), subject.nesting) {
+            (true, None) => Selector::Simple(subject.simple),
+            (_, nesting) => Selector::Complex(ComplexSelector {
+                ancestors,
+                subject: CompoundSelector {
+                    simple: subject.simple,
+                    nesting,
+                },
+            }),
+        })
+    }
+
+    /// Parses a simple selector that may contain `&`, which stands for the selectors in `parent`.
+    fn parse_compound_selector(
+        &mut self,
+        parent: Option<&[Selector]>,
+    ) -> Result<CompoundSelector, &'static str> {
+        let start = self.position;
+        let mut simple = self.parse_simple_selector()?;
+        let mut nesting = None;
+        while self.next_char() == Ok('&') {
+            self.consume_char()?;
+            nesting = Some(parent.ok_or("'&' outside of a style rule")?.to_vec());
+            let rest = self.parse_simple_selector()?;
+            if rest.tag_name.is_some() {
+                return Err("type selector after '&'");
+            }
+            simple.id = rest.id.or(simple.id);
+            simple.class.extend(rest.class);
+        }
+        if self.position == start {
+            return Err("expected a selector");
+        }
+        Ok(CompoundSelector { simple, nesting })
+    }
+
+    /// Parses the combinator after a compound selector, along with the whitespace around it.
+    ///
+    /// Returns `None` at the end of the selector. Sibling combinators aren't supported, since
+    /// matching doesn't track siblings.
+    fn parse_combinator(&mut self) -> Result<Option<Combinator>, &'static str> {
+        let start = self.position;
+        self.consume_whitespace()?;
+        match self.next_char() {
+            Ok('>') => {
+                self.consume_char()?;
+                self.consume_whitespace()?;
+                Ok(Some(Combinator::Child))
+            }
+            Ok('+' | '~') => Err("sibling combinators aren't supported"),
+            Ok(',' | '{' | ')') | Err(_) => Ok(None),
+            Ok(_) if self.position > start => Ok(Some(Combinator::Descendant)),
+            Ok(_) => Ok(None),
+        }
+    }
+}
+
+/// Parse a whole CSS stylesheet.
+pub fn parse(source: String) -> Result<Stylesheet, &'static str> {
+    let mut parser = CssParser {
+        position: 0,
+        input: source,
+    };
+
+    let mut stylesheet = parser.parse_stylesheet()?;
+    locate_rules(&parser.input, &mut stylesheet.rules);
+    Ok(stylesheet)
+}
+
+/// Fills in the line and column of the rules from their byte offsets in `source`.
+fn locate_rules(source: &str, rules: &mut [Rule]) {
+    let mut rules = rules.iter_mut().collect::<Vec<_>>();
+    rules.sort_by_key(|rule| rule.source.offset);
+    let (mut offset, mut line, mut column) = (0, 1, 1);
+    let mut chars = source.char_indices().peekable();
+    for rule in rules {
+        while offset < rule.source.offset {
+            let Some((_, c)) = chars.next() else {
+                break;
+            };
+            offset = chars.peek().map_or(source.len(), |&(i, _)| i);
+            match c {
+                '\n' => (line, column) = (line + 1, 1),
+                _ => column += 1,
+            }
+        }
+        rule.source.line = line;
+        rule.source.column = column;
+    }
+}
+
+/// Parses a list of comma-separated groups of whitespace-separated values, e.g. the value of a
+/// `src` descriptor.
+pub fn parse_value_list(source: &str) -> Result<Vec<Vec<Value>>, &'static str> {
+    let mut parser = CssParser {
+        position: 0,
+        input: source.to_string(),
+    };
+    let mut groups = vec![Vec::new()];
+    loop {
+        parser.consume_whitespace()?;
+        match parser.next_char() {
+            Err(_) => break,
+            Ok(',') => {
+                parser.consume_char()?;
+                groups.push(Vec::new());
+            }
+            Ok(_) => {
+                let position = parser.position;
+                let value = parser.parse_value()?;
+                if parser.position == position {
+                    return Err("unexpected character in value");
+                }
+                groups.last_mut().unwrap().push(value);
+            }
+        }
+    }
+    Ok(groups)
+}
+
+/// Returns a declaration with the given name if `value` is exactly one value.
+fn single_value_declaration(name: String, value: &str) -> Option<Declaration> {
+    match parse_value_list(value).ok()?.as_mut_slice() {
+        [values] if values.len() == 1 => Some(Declaration {
+            name,
+            value: values.pop()?,
+            important: false,
+        }),
+        _ => None,
+    }
+}
+
+impl Stylesheet {
+    /// Declares the layer `name` inside the layer `parent`, along with any parent layers it
+    /// implies, and returns its full name.
+    ///
+    /// Layers that have already been declared keep their original position.
+    fn declare_layer(&mut self, parent: Option<&str>, name: &str) -> String {
+        let full_name = match parent {
+            Some(parent) => format!("{}.{}", parent, name),
+            None => name.to_string(),
+        };
+        let prefixes = full_name.match_indices('.').map(|(i, _)| &full_name[..i]);
+        for layer in prefixes.chain(std::iter::once(full_name.as_str())) {
+            if !self.layers.iter().any(|declared| declared == layer) {
+                self.layers.push(layer.to_string());
+            }
+        }
+        full_name
+    }
+}
+
+impl RuleContext {
+    /// Returns a rule with the given selectors and declarations in this context, starting at
+    /// the byte `offset` in the source.
+    fn rule(
+        &self,
+        selectors: Vec<Selector>,
+        declarations: Vec<Declaration>,
+        offset: usize,
+    ) -> Rule {
+        Rule {
+            selectors,
+            declarations,
+            conditions: self.conditions.clone(),
+            layer: self.layer.clone(),
+            source: SourceLocation {
+                offset,
+                ..SourceLocation::default()
+            },
+        }
+    }
+}
+
+impl Selector {
+    /// Returns the simple selector the matched element itself must match, ignoring any `&`.
+    pub fn subject(&self) -> &SimpleSelector {
+        match *self {
+            Selector::Simple(ref simple) => simple,
+            Selector::Complex(ref complex) => &complex.subject.simple,
+        }
+    }
+
+    pub fn specificity(&self) -> Specificity {
+        match *self {
+            Selector::Simple(ref simple) => simple.specificity(),
+            Selector::Complex(ref complex) => complex
+                .ancestors
+                .iter()
+                .map(|(compound, _)| compound)
+                .chain(std::iter::once(&complex.subject))
+                .map(CompoundSelector::specificity)
+                .fold((0, 0, 0), |(a, b, c), (x, y, z)| (a + x, b + y, c + z)),
+        }
+    }
+}
+
+impl SimpleSelector {
     pub fn specificity(&self) -> Specificity {
-        let Selector::Simple(ref simple) = *self;
-        let a = simple.id.iter().count();
-        let b = simple.class.len();
-        let c = simple.tag_name.iter().count();
+        let a = self.id.iter().count();
+        let b = self.class.len();
+        let c = self.tag_name.iter().count();
         (a, b, c)
     }
 }
 
+impl CompoundSelector {
+    /// Returns the specificity of the compound selector. Like `:is()`, `&` counts as the most
+    /// specific of the selectors it stands for.
+    pub fn specificity(&self) -> Specificity {
+        let (a, b, c) = self.simple.specificity();
+        let (x, y, z) = self
+            .nesting
+            .iter()
+            .flatten()
+            .map(Selector::specificity)
+            .max()
+            .unwrap_or((0, 0, 0));
+        (a + x, b + y, c + z)
+    }
+}
+
+impl Rule {
+    /// Returns true if the rules are the same apart from where they are in the source.
+    pub fn eq_ignoring_source(&self, other: &Rule) -> bool {
+        self.selectors == other.selectors
+            && self.declarations == other.declarations
+            && self.conditions == other.conditions
+            && self.layer == other.layer
+    }
+}
+
+impl fmt::Display for SourceLocation {
+    /// Formats the location as `url:line:column`, or `line:column` without a URL.
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if let Some(ref url) = self.url {
+            write!(f, "{}:", url)?;
+        }
+        write!(f, "{}:{}", self.line, self.column)
+    }
+}
+
+impl fmt::Display for Selector {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Selector::Simple(ref simple) => write!(f, "{}", simple),
+            Selector::Complex(ref complex) => {
+                for (compound, combinator) in &complex.ancestors {
+                    match combinator {
+                        Combinator::Descendant => write!(f, "{} ", compound)?,
+                        Combinator::Child => write!(f, "{} > ", compound)?,
+                    }
+                }
+                write!(f, "{}", complex.subject)
+            }
+        }
+    }
+}
+
+impl fmt::Display for CompoundSelector {
+    /// Formats `&` as the `:is()` of the selectors it stands for, since the nesting is resolved.
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let simple = &self.simple;
+        if let Some(ref tag_name) = simple.tag_name {
+            write!(f, "{}", tag_name)?;
+        }
+        if let Some(ref nesting) = self.nesting {
+            write!(f, ":is({})", nesting.iter().join(", "))?;
+        } else if simple.tag_name.is_none() && simple.id.is_none() && simple.class.is_empty() {
+            write!(f, "*")?;
+        }
+        if let Some(ref id) = simple.id {
+            write!(f, "#{}", id)?;
+        }
+        for class in &simple.class {
+            write!(f, ".{}", class)?;
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for SimpleSelector {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let compound = CompoundSelector {
+            simple: self.clone(),
+            nesting: None,
+        };
+        write!(f, "{}", compound)
+    }
+}
+
+impl fmt::Display for Value {
+    /// Formats the value as CSS.
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Value::Keyword(ref keyword) => write!(f, "{}", keyword),
+            Value::Length(length, ref unit) => write!(f, "{}{}", length, unit.as_str()),
+            Value::ColorValue(color) => write!(f, "{}", color),
+            Value::String(ref string) => write_string(f, string),
+            Value::Url(ref url) => {
+                write!(f, "url(")?;
+                write_string(f, url)?;
+                write!(f, ")")
+            }
+            Value::Number(number) => write!(f, "{}", number),
+            Value::Integer(integer) => write!(f, "{}", integer),
+            Value::Percentage(percentage) => write!(f, "{}%", percentage),
+            Value::Angle(angle, ref unit) => {
+                let unit = match unit {
+                    AngleUnit::Deg => "deg",
+                    AngleUnit::Rad => "rad",
+                    AngleUnit::Grad => "grad",
+                    AngleUnit::Turn => "turn",
+                };
+                write!(f, "{}{}", angle, unit)
+            }
+            Value::Time(time, ref unit) => {
+                let unit = match unit {
+                    TimeUnit::S => "s",
+                    TimeUnit::Ms => "ms",
+                };
+                write!(f, "{}{}", time, unit)
+            }
+            Value::Resolution(resolution, ref unit) => {
+                let unit = match unit {
+                    ResolutionUnit::Dpi => "dpi",
+                    ResolutionUnit::Dpcm => "dpcm",
+                    ResolutionUnit::Dppx => "dppx",
+                };
+                write!(f, "{}{}", resolution, unit)
+            }
+            Value::Function(ref name, ref arguments) => {
+                write!(f, "{}({})", name, arguments.iter().join(", "))
+            }
+            Value::List(ref values) => write!(f, "{}", values.iter().join(" ")),
+            Value::CommaList(ref values) => write!(f, "{}", values.iter().join(", ")),
+            Value::LineNames(ref names) => write!(f, "[{}]", names.iter().join(" ")),
+            Value::Delimiter(delimiter) => write!(f, "{}", delimiter),
+        }
+    }
+}
+
+impl fmt::Display for Color {
+    /// Formats the color as `rgb()`, or as `rgba()` if it isn't opaque, like `getComputedStyle`.
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.a {
+            255 => write!(f, "rgb({}, {}, {})", self.r, self.g, self.b),
+            a => {
+                let alpha = (a as f32 / 255.0 * 1000.0).round() / 1000.0;
+                write!(f, "rgba({}, {}, {}, {})", self.r, self.g, self.b, alpha)
+            }
+        }
+    }
+}
+
+/// Writes `string` as a double-quoted CSS string.
+fn write_string(f: &mut fmt::Formatter, string: &str) -> fmt::Result {
+    write!(f, "\"")?;
+    for c in string.chars() {
+        if matches!(c, '"' | '\\') {
+            write!(f, "\\")?;
+        }
+        write!(f, "{}", c)?;
+    }
+    write!(f, "\"")
+}
+
+/// Swaps the operands of a comparison, so that `a < b` becomes `b > a`.
+fn flip_comparison(comparison: Comparison) -> Comparison {
+    match comparison {
+        Comparison::Lt => Comparison::Gt,
+        Comparison::Le => Comparison::Ge,
+        Comparison::Gt => Comparison::Lt,
+        Comparison::Ge => Comparison::Le,
+        Comparison::Eq => Comparison::Eq,
+    }
+}
+
 /// Returns true if the given character is a valid identifier character in CSS.
 ///
 /// # Arguments
@@ -385,6 +2327,7 @@ fn valid_identifier_char(c: char) -> bool {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::at_rules::FontSource;
 
     #[test]
     fn test_parse_simple_selector() {
@@ -433,14 +2376,14 @@ mod tests {
             position: 0,
             input: String::from("body { background-color: red; }"),
         };
-        let rules = parser.parse_rules().unwrap();
+        let rules = parser.parse_stylesheet().unwrap().rules;
         assert_eq!(rules.len(), 1);
 
         let mut parser = CssParser {
             position: 0,
             input: String::from("body { background-color: red; } p { color: #000000; }"),
         };
-        let rules = parser.parse_rules().unwrap();
+        let rules = parser.parse_stylesheet().unwrap().rules;
         assert_eq!(rules.len(), 2);
     }
 
@@ -491,7 +2434,7 @@ mod tests {
             position: 0,
             input: String::from("body { background-color: red; }"),
         };
-        let rule = parser.parse_rule().unwrap();
+        let rule = parser.parse_rule(None).unwrap();
         assert_eq!(rule.selectors.len(), 1);
         assert_eq!(rule.declarations.len(), 1);
 
@@ -499,7 +2442,7 @@ mod tests {
             position: 0,
             input: String::from("body { background-color: red; color: #000000; }"),
         };
-        let rule = parser.parse_rule().unwrap();
+        let rule = parser.parse_rule(None).unwrap();
         assert_eq!(rule.selectors.len(), 1);
         assert_eq!(rule.declarations.len(), 2);
     }
@@ -526,13 +2469,198 @@ mod tests {
         };
         let value = parser.parse_value().unwrap();
         assert_eq!(
-            value,
-            Value::ColorValue(Color {
-                r: 0,
-                g: 0,
-                b: 0,
-                a: 255
-            })
+            value,
+            Value::ColorValue(Color {
+                r: 0,
+                g: 0,
+                b: 0,
+                a: 255
+            })
+        );
+    }
+
+    #[test]
+    fn test_parse_value_strings_and_urls() {
+        let mut parser = CssParser {
+            position: 0,
+            input: String::from("\"Helvetica Neue\""),
+        };
+        let value = parser.parse_value().unwrap();
+        assert_eq!(value, Value::String(String::from("Helvetica Neue")));
+
+        let mut parser = CssParser {
+            position: 0,
+            input: String::from("'\\2192 \\'x'"),
+        };
+        let value = parser.parse_value().unwrap();
+        assert_eq!(value, Value::String(String::from("→'x")));
+
+        let mut parser = CssParser {
+            position: 0,
+            input: String::from("\"\\26 B\\0\\\nc\""),
+        };
+        let value = parser.parse_value().unwrap();
+        assert_eq!(value, Value::String(String::from("&B\u{fffd}c")));
+
+        let mut parser = CssParser {
+            position: 0,
+            input: String::from("url(a.png)"),
+        };
+        let value = parser.parse_value().unwrap();
+        assert_eq!(value, Value::Url(String::from("a.png")));
+
+        let mut parser = CssParser {
+            position: 0,
+            input: String::from("url( \"img/b c.png\" )"),
+        };
+        let value = parser.parse_value().unwrap();
+        assert_eq!(value, Value::Url(String::from("img/b c.png")));
+    }
+
+    #[test]
+    fn test_parse_value_numbers() {
+        let cases = [
+            ("0.5", Value::Number(0.5)),
+            (".5", Value::Number(0.5)),
+            ("3", Value::Integer(3)),
+            ("-2", Value::Integer(-2)),
+            ("-1.5px", Value::Length(-1.5, Unit::Px)),
+            ("2fr", Value::Length(2.0, Unit::Fr)),
+            ("1.5em", Value::Length(1.5, Unit::Em)),
+            ("2REM", Value::Length(2.0, Unit::Rem)),
+            ("12pt", Value::Length(12.0, Unit::Pt)),
+            ("1e3", Value::Number(1000.0)),
+            ("2.5E-1", Value::Number(0.25)),
+            ("1e+2px", Value::Length(100.0, Unit::Px)),
+            ("45deg", Value::Angle(45.0, AngleUnit::Deg)),
+            ("0.25turn", Value::Angle(0.25, AngleUnit::Turn)),
+            ("200ms", Value::Time(200.0, TimeUnit::Ms)),
+            ("1s", Value::Time(1.0, TimeUnit::S)),
+            ("96dpi", Value::Resolution(96.0, ResolutionUnit::Dpi)),
+            ("2x", Value::Resolution(2.0, ResolutionUnit::Dppx)),
+            ("50%", Value::Percentage(50.0)),
+        ];
+        for (input, expected) in cases {
+            let mut parser = CssParser {
+                position: 0,
+                input: String::from(input),
+            };
+            assert_eq!(parser.parse_value().unwrap(), expected, "{}", input);
+        }
+
+        let mut parser = CssParser {
+            position: 0,
+            input: String::from("3furlongs"),
+        };
+        assert!(parser.parse_value().is_err());
+
+        let mut parser = CssParser {
+            position: 0,
+            input: String::from("-webkit-box"),
+        };
+        let value = parser.parse_value().unwrap();
+        assert_eq!(value, Value::Keyword(String::from("-webkit-box")));
+    }
+
+    #[test]
+    fn test_parse_value_line_names() {
+        let mut parser = CssParser {
+            position: 0,
+            input: String::from("[ header-start  main ]"),
+        };
+        let value = parser.parse_value().unwrap();
+        assert_eq!(
+            value,
+            Value::LineNames(vec![String::from("header-start"), String::from("main")])
+        );
+        assert_eq!(value.to_string(), "[header-start main]");
+
+        let mut parser = CssParser {
+            position: 0,
+            input: String::from("[a, b]"),
+        };
+        assert!(parser.parse_value().is_err());
+    }
+
+    #[test]
+    fn test_parse_value_functions() {
+        let mut parser = CssParser {
+            position: 0,
+            input: String::from("rotate(45deg)"),
+        };
+        let value = parser.parse_value().unwrap();
+        assert_eq!(
+            value,
+            Value::Function(
+                String::from("rotate"),
+                vec![Value::Angle(45.0, AngleUnit::Deg)]
+            )
+        );
+
+        let mut parser = CssParser {
+            position: 0,
+            input: String::from("translate(10px, -5px)"),
+        };
+        let value = parser.parse_value().unwrap();
+        assert_eq!(
+            value,
+            Value::Function(
+                String::from("translate"),
+                vec![Value::Length(10.0, Unit::Px), Value::Length(-5.0, Unit::Px)]
+            )
+        );
+
+        let mut parser = CssParser {
+            position: 0,
+            input: String::from("var(--accent, rgb(0 0 0))"),
+        };
+        let value = parser.parse_value().unwrap();
+        assert_eq!(
+            value,
+            Value::Function(
+                String::from("var"),
+                vec![
+                    Value::Keyword(String::from("--accent")),
+                    Value::Function(
+                        String::from("rgb"),
+                        vec![Value::Integer(0), Value::Integer(0), Value::Integer(0)]
+                    ),
+                ]
+            )
+        );
+
+        let stylesheet = parse(String::from(
+            "p { width: calc(1px + 2px); color: rgb(0 0 0 / 50%); }",
+        ))
+        .unwrap();
+        assert_eq!(
+            stylesheet.rules[0].declarations[0].value,
+            Value::Function(
+                String::from("calc"),
+                vec![
+                    Value::Length(1.0, Unit::Px),
+                    Value::Delimiter('+'),
+                    Value::Length(2.0, Unit::Px),
+                ]
+            )
+        );
+        assert_eq!(
+            stylesheet.rules[0].declarations[1].value,
+            Value::Function(
+                String::from("rgb"),
+                vec![
+                    Value::Integer(0),
+                    Value::Integer(0),
+                    Value::Integer(0),
+                    Value::Delimiter('/'),
+                    Value::Percentage(50.0),
+                ]
+            )
+        );
+        assert!(parse(String::from("p { width: f(1px ! 2px); }")).is_ok());
+        assert_eq!(
+            parse_value_list("1px ! 2px"),
+            Err("unexpected character in value")
         );
     }
 
@@ -582,6 +2710,24 @@ mod tests {
                 a: 255
             })
         );
+
+        let color = |input: &str| {
+            let mut parser = CssParser {
+                position: 0,
+                input: String::from(input),
+            };
+            parser.parse_color()
+        };
+        let white = Value::ColorValue(Color::new(255, 255, 255, 255));
+        assert_eq!(color("#fff"), Ok(white.clone()));
+        assert_eq!(color("#ffff"), Ok(white));
+        assert_eq!(
+            color("#ff000080"),
+            Ok(Value::ColorValue(Color::new(255, 0, 0, 128)))
+        );
+        assert!(color("#f").is_err());
+        assert!(color("#fffff").is_err());
+        assert!(color("#ffg").is_err());
     }
 
     #[test]
@@ -591,7 +2737,13 @@ mod tests {
             input: String::from("00"),
         };
         let value = parser.parse_hex_pair();
-        assert_eq!(value, 0);
+        assert_eq!(value, Ok(0));
+
+        let mut parser = CssParser {
+            position: 0,
+            input: String::from("f"),
+        };
+        assert!(parser.parse_hex_pair().is_err());
     }
 
     #[test]
@@ -611,6 +2763,64 @@ mod tests {
         let declaration = parser.parse_declaration().unwrap();
         assert_eq!(declaration.name, String::from("background-color"));
         assert_eq!(declaration.value, Value::Keyword(String::from("red")));
+
+        let mut parser = CssParser {
+            position: 0,
+            input: String::from("display: inline  flex ;"),
+        };
+        let declaration = parser.parse_declaration().unwrap();
+        assert_eq!(
+            declaration.value,
+            Value::List(vec![
+                Value::Keyword(String::from("inline")),
+                Value::Keyword(String::from("flex")),
+            ])
+        );
+
+        let mut parser = CssParser {
+            position: 0,
+            input: String::from("font-family: DejaVu Sans , \"Mono\",serif;"),
+        };
+        let declaration = parser.parse_declaration().unwrap();
+        assert_eq!(
+            declaration.value,
+            Value::CommaList(vec![
+                Value::List(vec![
+                    Value::Keyword(String::from("DejaVu")),
+                    Value::Keyword(String::from("Sans")),
+                ]),
+                Value::String(String::from("Mono")),
+                Value::Keyword(String::from("serif")),
+            ])
+        );
+        assert_eq!(
+            declaration.value.to_string(),
+            "DejaVu Sans, \"Mono\", serif"
+        );
+
+        let mut parser = CssParser {
+            position: 0,
+            input: String::from("font-family: a, ;"),
+        };
+        assert!(parser.parse_declaration().is_err());
+
+        let mut parser = CssParser {
+            position: 0,
+            input: String::from("width: 1px }"),
+        };
+        assert!(parser.parse_declaration().is_ok());
+        assert_eq!(parser.next_char(), Ok('}'));
+
+        let mut parser = CssParser {
+            position: 0,
+            input: String::from("width 1px;"),
+        };
+        assert!(parser.parse_declaration().is_err());
+
+        let stylesheet = parse(String::from("div{display:block} p{color:red;width:1px}")).unwrap();
+        assert_eq!(stylesheet.rules.len(), 2);
+        assert_eq!(stylesheet.rules[0].declarations.len(), 1);
+        assert_eq!(stylesheet.rules[1].declarations.len(), 2);
     }
 
     #[test]
@@ -637,6 +2847,171 @@ mod tests {
         assert_eq!(selectors.len(), 2);
     }
 
+    #[test]
+    fn test_parse_complex_selectors() {
+        let mut parser = CssParser {
+            position: 0,
+            input: String::from("main > p.note  a, div {"),
+        };
+        let selectors = parser.parse_selectors().unwrap();
+        assert_eq!(selectors.len(), 2);
+        assert_eq!(selectors[0].specificity(), (0, 1, 3));
+        let Selector::Complex(ref complex) = selectors[0] else {
+            panic!("expected a complex selector");
+        };
+        let combinators = complex
+            .ancestors
+            .iter()
+            .map(|&(_, combinator)| combinator)
+            .collect::<Vec<_>>();
+        assert_eq!(combinators, vec![Combinator::Child, Combinator::Descendant]);
+        assert_eq!(complex.subject.simple.tag_name.as_deref(), Some("a"));
+        assert!(matches!(selectors[1], Selector::Simple(_)));
+
+        for input in ["a + b {", "a:hover {", "& a {", "> a {"] {
+            let mut parser = CssParser {
+                position: 0,
+                input: String::from(input),
+            };
+            assert!(parser.parse_selectors().is_err(), "{}", input);
+        }
+    }
+
+    #[test]
+    fn test_parse_nested_rules() {
+        let stylesheet = parse(String::from(
+            ".card, #main { \
+               color: red; \
+               .title { color: blue; } \
+               &.active > p { color: green; } \
+               @media (min-width: 600px) { width: 100px; } \
+               background-color: white; \
+               @font-face { font-family: x; src: url(x.woff); } \
+               + .sibling { color: black; } \
+             } \
+             p { color: black; }",
+        ))
+        .unwrap();
+        let declarations = stylesheet
+            .rules
+            .iter()
+            .map(|rule| rule.declarations[0].name.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            declarations,
+            vec![
+                "color",
+                "color",
+                "color",
+                "width",
+                "background-color",
+                "color"
+            ]
+        );
+        assert!(stylesheet.font_faces.is_empty());
+
+        let rules = &stylesheet.rules;
+        assert_eq!(rules[0].selectors.len(), 2);
+        // Nested selectors get the specificity of the most specific parent, like `:is()`.
+        assert_eq!(rules[1].selectors[0].specificity(), (1, 1, 0));
+        assert_eq!(rules[2].selectors[0].specificity(), (1, 1, 1));
+        assert!(matches!(rules[3].conditions[..], [Condition::Media(_)]));
+        assert_eq!(rules[3].selectors, rules[0].selectors);
+        assert_eq!(rules[4].selectors, rules[0].selectors);
+        assert!(rules[4].conditions.is_empty());
+    }
+
+    #[test]
+    fn test_parse_drops_invalid_declarations() {
+        let names = |stylesheet: &Stylesheet| {
+            stylesheet
+                .rules
+                .iter()
+                .map(|rule| rule.declarations.iter().map(|d| d.name.as_str()).join(" "))
+                .collect::<Vec<_>>()
+        };
+        let stylesheet = parse(String::from(
+            "p { display: block; width: 10furlongs; foo bar; height: 1px; }",
+        ))
+        .unwrap();
+        assert_eq!(names(&stylesheet), vec!["display height"]);
+
+        let stylesheet = parse(String::from(
+            ".a { .b { color: red; } width: 10em; height: f(\"}\"); } div { color: red; }",
+        ))
+        .unwrap();
+        assert_eq!(
+            names(&stylesheet),
+            vec!["", "color", "width height", "color"]
+        );
+
+        let stylesheet = parse(String::from(
+            "p { color: #fff; background: #f; } a { color: #f}",
+        ))
+        .unwrap();
+        assert_eq!(names(&stylesheet), vec!["color", ""]);
+    }
+
+    #[test]
+    fn test_parse_skips_comments() {
+        let stylesheet = parse(String::from(
+            "/* c */ .a { width: 50px; /* ; } */ height: /* c */ 1px /* c */; } \
+             .b { /* c */ .c { /* c */ color: red /* c */ } /* c */ } /* c",
+        ))
+        .unwrap();
+        let declarations = stylesheet
+            .rules
+            .iter()
+            .map(|rule| {
+                let declarations = rule.declarations.iter();
+                declarations
+                    .map(|d| format!("{}: {}", d.name, d.value))
+                    .join("; ")
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            declarations,
+            vec!["width: 50px; height: 1px", "", "color: red"]
+        );
+    }
+
+    #[test]
+    fn test_parse_drops_rules_after_stray_close_braces() {
+        let stylesheet = parse(String::from(
+            ".a { color: red; } } .b { color: blue; } .c { color: green; }",
+        ))
+        .unwrap();
+        // The `}` starts an invalid rule that takes `.b` with it.
+        let selectors = stylesheet
+            .rules
+            .iter()
+            .map(|rule| rule.selectors[0].to_string())
+            .collect::<Vec<_>>();
+        assert_eq!(selectors, vec![".a", ".c"]);
+        assert!(parse(String::from("}")).unwrap().rules.is_empty());
+    }
+
+    #[test]
+    fn test_parse_important() {
+        let stylesheet = parse(String::from(
+            "p { width: 50px !important; height: 1px ! IMPORTANT; color: red; margin: 0 !foo; }",
+        ))
+        .unwrap();
+        let declarations = stylesheet.rules[0]
+            .declarations
+            .iter()
+            .map(|d| (d.name.as_str(), d.value.to_string(), d.important))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            declarations,
+            vec![
+                ("width", String::from("50px"), true),
+                ("height", String::from("1px"), true),
+                ("color", String::from("red"), false),
+            ]
+        );
+    }
+
     #[test]
     fn test_parse() {
         let stylesheet = parse(String::from("body { background-color: red; }")).unwrap();
@@ -647,6 +3022,475 @@ mod tests {
         ))
         .unwrap();
         assert_eq!(stylesheet.rules.len(), 2);
+
+        let stylesheet = parse(String::from(
+            "p { font-family: \"Helvetica Neue\"; opacity: 0.5; z-index: 3; } \
+             div { background-image: url(a.png); transform: rotate(45deg); }",
+        ))
+        .unwrap();
+        assert_eq!(stylesheet.rules.len(), 2);
+        assert_eq!(stylesheet.rules[0].declarations.len(), 3);
+        assert_eq!(stylesheet.rules[1].declarations.len(), 2);
+    }
+
+    #[test]
+    fn test_parse_media_rules() {
+        let stylesheet = parse(String::from(
+            "p { color: red; } \
+             @media screen and (min-width: 400px) { p { color: blue; } div { color: green; } } \
+             @media print { @media (hover) { a { color: black; } } }",
+        ))
+        .unwrap();
+        assert_eq!(stylesheet.rules.len(), 4);
+        assert!(stylesheet.rules[0].conditions.is_empty());
+        assert_eq!(
+            stylesheet.rules[1].conditions,
+            vec![Condition::Media(MediaQueryList(vec![MediaQuery {
+                qualifier: None,
+                media_type: MediaType::Screen,
+                condition: Some(MediaCondition::Feature(MediaFeature {
+                    name: String::from("width"),
+                    test: FeatureTest::Range(vec![(
+                        Comparison::Ge,
+                        Value::Length(400.0, Unit::Px)
+                    )]),
+                })),
+            }]))]
+        );
+        assert_eq!(
+            stylesheet.rules[2].conditions,
+            stylesheet.rules[1].conditions
+        );
+        assert_eq!(stylesheet.rules[3].conditions.len(), 2);
+
+        // Unsupported at-rules are skipped.
+        let stylesheet = parse(String::from(
+            "@charset \"utf-8\"; @unknown foo { a { b: c; } } p { color: red; }",
+        ))
+        .unwrap();
+        assert_eq!(stylesheet.rules.len(), 1);
+    }
+
+    #[test]
+    fn test_parse_media_query_list() {
+        let parse_list = |input: &str| {
+            let mut parser = CssParser {
+                position: 0,
+                input: format!("{} {{", input),
+            };
+            parser.parse_media_query_list().unwrap()
+        };
+        let feature = |name: &str, test: FeatureTest| {
+            MediaCondition::Feature(MediaFeature {
+                name: String::from(name),
+                test,
+            })
+        };
+        let px = |n: f32| Value::Length(n, Unit::Px);
+
+        assert_eq!(parse_list(""), MediaQueryList(vec![]));
+        assert_eq!(
+            parse_list("not print, only screen"),
+            MediaQueryList(vec![
+                MediaQuery {
+                    qualifier: Some(Qualifier::Not),
+                    media_type: MediaType::Print,
+                    condition: None,
+                },
+                MediaQuery {
+                    qualifier: Some(Qualifier::Only),
+                    media_type: MediaType::Screen,
+                    condition: None,
+                },
+            ])
+        );
+        assert_eq!(
+            parse_list("(400px <= width < 800px)").0[0].condition,
+            Some(feature(
+                "width",
+                FeatureTest::Range(vec![
+                    (Comparison::Ge, px(400.0)),
+                    (Comparison::Lt, px(800.0))
+                ])
+            ))
+        );
+        assert_eq!(
+            parse_list("(height > 100px)").0[0].condition,
+            Some(feature(
+                "height",
+                FeatureTest::Range(vec![(Comparison::Gt, px(100.0))])
+            ))
+        );
+        assert_eq!(
+            parse_list("not (hover) ").0[0].condition,
+            Some(MediaCondition::Not(Box::new(feature(
+                "hover",
+                FeatureTest::Boolean
+            ))))
+        );
+        assert_eq!(
+            parse_list("(orientation: portrait) or (prefers-color-scheme: dark)").0[0].condition,
+            Some(MediaCondition::Or(vec![
+                feature(
+                    "orientation",
+                    FeatureTest::Plain(Value::Keyword(String::from("portrait")))
+                ),
+                feature(
+                    "prefers-color-scheme",
+                    FeatureTest::Plain(Value::Keyword(String::from("dark")))
+                ),
+            ]))
+        );
+        assert_eq!(
+            parse_list("screen and ((pointer: fine) or (max-resolution: 2dppx))").0[0].condition,
+            Some(MediaCondition::Or(vec![
+                feature(
+                    "pointer",
+                    FeatureTest::Plain(Value::Keyword(String::from("fine")))
+                ),
+                feature(
+                    "resolution",
+                    FeatureTest::Range(vec![(
+                        Comparison::Le,
+                        Value::Resolution(2.0, ResolutionUnit::Dppx)
+                    )])
+                ),
+            ]))
+        );
+        assert_eq!(
+            parse_list("(foo: bar baz)").0[0].condition,
+            Some(MediaCondition::Unknown)
+        );
+
+        // Invalid queries become `not all` without affecting the rest of the list.
+        assert_eq!(
+            parse_list("(hover) and (pointer) or (width), print"),
+            MediaQueryList(vec![
+                MediaQuery::not_all(),
+                MediaQuery {
+                    qualifier: None,
+                    media_type: MediaType::Print,
+                    condition: None,
+                },
+            ])
+        );
+    }
+
+    #[test]
+    fn test_parse_supports_rules() {
+        let stylesheet = parse(String::from(
+            "@supports (display: block) and (not (display: grid)) { p { color: red; } } \
+             @supports selector(a.b) or font-tech(color-COLRv1) { a { color: blue; } } \
+             @supports nonsense { b { color: green; } }",
+        ))
+        .unwrap();
+        assert_eq!(stylesheet.rules.len(), 2);
+
+        let block = SupportsCondition::Declaration(
+            String::from("display"),
+            Value::Keyword(String::from("block")),
+        );
+        let grid = SupportsCondition::Declaration(
+            String::from("display"),
+            Value::Keyword(String::from("grid")),
+        );
+        assert_eq!(
+            stylesheet.rules[0].conditions,
+            vec![Condition::Supports(SupportsCondition::And(vec![
+                block,
+                SupportsCondition::Not(Box::new(grid)),
+            ]))]
+        );
+        assert_eq!(
+            stylesheet.rules[1].conditions,
+            vec![Condition::Supports(SupportsCondition::Or(vec![
+                SupportsCondition::Selector(Selector::Simple(SimpleSelector {
+                    tag_name: Some(String::from("a")),
+                    id: None,
+                    class: vec![String::from("b")],
+                })),
+                SupportsCondition::Unknown,
+            ]))]
+        );
+    }
+
+    #[test]
+    fn test_parse_layer_rules() {
+        let stylesheet = parse(String::from(
+            "@layer reset, framework.base; \
+             @layer framework { @layer theme { p { color: red; } } a { color: blue; } } \
+             @layer { b { color: green; } } \
+             i { color: black; }",
+        ))
+        .unwrap();
+        assert_eq!(
+            stylesheet.layers,
+            vec![
+                "reset",
+                "framework",
+                "framework.base",
+                "framework.theme",
+                "#4"
+            ]
+        );
+        let layers = stylesheet
+            .rules
+            .iter()
+            .map(|rule| rule.layer.as_deref())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            layers,
+            vec![Some("framework.theme"), Some("framework"), Some("#4"), None]
+        );
+    }
+
+    #[test]
+    fn test_parse_container_and_scope_rules() {
+        let stylesheet = parse(String::from(
+            "@container card (min-width: 400px) { p { color: red; } } \
+             @container not (orientation: portrait) { a { color: blue; } } \
+             @scope (.card, #main) to (.content) { img { color: green; } } \
+             @scope { b { color: black; } }",
+        ))
+        .unwrap();
+        assert_eq!(stylesheet.rules.len(), 4);
+
+        match &stylesheet.rules[0].conditions[0] {
+            Condition::Container(query) => assert_eq!(query.name.as_deref(), Some("card")),
+            condition => panic!("unexpected condition {:?}", condition),
+        }
+        match &stylesheet.rules[1].conditions[0] {
+            Condition::Container(query) => {
+                assert_eq!(query.name, None);
+                assert!(matches!(query.condition, MediaCondition::Not(_)));
+            }
+            condition => panic!("unexpected condition {:?}", condition),
+        }
+        match &stylesheet.rules[2].conditions[0] {
+            Condition::Scope(scope) => {
+                assert_eq!(scope.root.len(), 2);
+                assert_eq!(scope.limit.len(), 1);
+            }
+            condition => panic!("unexpected condition {:?}", condition),
+        }
+        assert_eq!(
+            stylesheet.rules[3].conditions,
+            vec![Condition::Scope(Scope {
+                root: vec![],
+                limit: vec![]
+            })]
+        );
+    }
+
+    #[test]
+    fn test_parse_imports() {
+        let stylesheet = parse(String::from(
+            "@charset \"utf-8\"; \
+             @import url(a.css) layer(base.reset) supports(display: block) screen, print; \
+             @import \"b.css\" layer; \
+             @import 'c.css' supports((display: block) and (not (display: grid))); \
+             p { color: red; } \
+             @import url(ignored.css);",
+        ))
+        .unwrap();
+        assert_eq!(stylesheet.rules.len(), 1);
+        assert_eq!(stylesheet.layers, vec!["base", "base.reset", "#2"]);
+        assert_eq!(stylesheet.imports.len(), 3);
+
+        let a = &stylesheet.imports[0];
+        assert_eq!(a.url, "a.css");
+        assert_eq!(a.layer.as_deref(), Some("base.reset"));
+        assert_eq!(
+            a.supports,
+            Some(SupportsCondition::Declaration(
+                String::from("display"),
+                Value::Keyword(String::from("block"))
+            ))
+        );
+        assert_eq!(a.media.0.len(), 2);
+        assert_eq!(a.layer_position, 2);
+
+        let b = &stylesheet.imports[1];
+        assert_eq!(b.url, "b.css");
+        assert_eq!(b.layer.as_deref(), Some("#2"));
+        assert_eq!(b.supports, None);
+        assert_eq!(b.media, MediaQueryList(vec![]));
+
+        let c = &stylesheet.imports[2];
+        assert_eq!(c.layer, None);
+        assert!(matches!(c.supports, Some(SupportsCondition::And(_))));
+    }
+
+    #[test]
+    fn test_parse_font_face_rules() {
+        let stylesheet = parse(String::from(
+            "@font-face { \
+               font-family: \"Open Sans\"; \
+               src: local(\"Open Sans\"), url(open.woff2) format(\"woff2\"); \
+               font-weight: 300 700; \
+               unicode-range: U+0-7F, U+4??; \
+             } \
+             @font-face { font-family: Missing Source; } \
+             p { color: red; }",
+        ))
+        .unwrap();
+        assert_eq!(stylesheet.rules.len(), 1);
+        assert_eq!(stylesheet.font_faces.len(), 1);
+
+        let font_face = &stylesheet.font_faces[0];
+        assert_eq!(font_face.family, "Open Sans");
+        assert_eq!(
+            font_face.sources,
+            vec![
+                FontSource::Local(String::from("Open Sans")),
+                FontSource::Url {
+                    url: String::from("open.woff2"),
+                    format: Some(String::from("woff2")),
+                    tech: vec![],
+                },
+            ]
+        );
+        assert_eq!(font_face.weight, (300.0, 700.0));
+        assert!(font_face.covers('a'));
+        assert!(font_face.covers('\u{4a0}'));
+        assert!(!font_face.covers('é'));
+    }
+
+    #[test]
+    fn test_parse_keyframes_rules() {
+        let stylesheet = parse(String::from(
+            "@keyframes fade { \
+               from { opacity: 0; } \
+               50%, 75% { opacity: 0.5; } \
+               bogus { opacity: 1; } \
+               to { opacity: 1; } \
+             } \
+             @-webkit-keyframes \"spin\" {} \
+             p { color: red; }",
+        ))
+        .unwrap();
+        assert_eq!(stylesheet.rules.len(), 1);
+        assert_eq!(stylesheet.keyframes.len(), 2);
+
+        let fade = &stylesheet.keyframes[0];
+        assert_eq!(fade.name, "fade");
+        let offsets = fade
+            .keyframes
+            .iter()
+            .map(|keyframe| keyframe.offsets.clone())
+            .collect::<Vec<_>>();
+        assert_eq!(offsets, vec![vec![0.0], vec![50.0, 75.0], vec![100.0]]);
+        assert_eq!(
+            fade.keyframes[1].declarations,
+            vec![Declaration {
+                name: String::from("opacity"),
+                value: Value::Number(0.5),
+                important: false,
+            }]
+        );
+        assert_eq!(stylesheet.keyframes[1].name, "spin");
+        assert!(stylesheet.keyframes[1].keyframes.is_empty());
+    }
+
+    #[test]
+    fn test_parse_page_rules() {
+        let stylesheet = parse(String::from(
+            "@page { margin: 10px; } \
+             @page chapter:first, :left { \
+               margin-top: 20px; \
+               size: 210px 297px; \
+               @top-center { content: \"Title\"; } \
+               @middle-nowhere { content: \"Lost\"; } \
+             } \
+             p { color: red; }",
+        ))
+        .unwrap();
+        assert_eq!(stylesheet.rules.len(), 1);
+        assert_eq!(stylesheet.pages.len(), 2);
+
+        let all = &stylesheet.pages[0];
+        assert!(all.selectors.is_empty());
+        assert_eq!(all.declarations.len(), 1);
+
+        let chapter = &stylesheet.pages[1];
+        assert_eq!(
+            chapter.selectors,
+            vec![
+                PageSelector {
+                    name: Some(String::from("chapter")),
+                    pseudo_classes: vec![String::from("first")],
+                },
+                PageSelector {
+                    name: None,
+                    pseudo_classes: vec![String::from("left")],
+                },
+            ]
+        );
+        // `size` has two values, which webwraith can't represent yet.
+        assert_eq!(chapter.declarations.len(), 1);
+        assert_eq!(chapter.margin_boxes.len(), 1);
+        assert_eq!(
+            chapter.margin_boxes[0].position,
+            MarginBoxPosition::TopCenter
+        );
+        assert_eq!(
+            chapter.margin_boxes[0].declarations[0].value,
+            Value::String(String::from("Title"))
+        );
+    }
+
+    #[test]
+    fn test_source_locations() {
+        let stylesheet = parse(String::from(
+            "p { color: red; }\n\n  @media print {\n    a, b { color: blue; }\n  }\n\
+             .card {\n  color: green;\n  & span { color: red; }\n}",
+        ))
+        .unwrap();
+        let locations = stylesheet
+            .rules
+            .iter()
+            .map(|rule| (rule.source.line, rule.source.column))
+            .collect::<Vec<_>>();
+        assert_eq!(locations, vec![(1, 1), (4, 5), (6, 1), (8, 3)]);
+        assert_eq!(stylesheet.rules[1].source.to_string(), "4:5");
+    }
+
+    #[test]
+    fn test_display() {
+        let stylesheet = parse(String::from(
+            "div > p.a#b, ul li, * { \
+                 color: #ff0000; \
+                 font-family: \"Dejavu \\\"Sans\\\"\"; \
+                 margin: 1.5px; \
+                 background: url(a.png); \
+             }",
+        ))
+        .unwrap();
+        let rule = &stylesheet.rules[0];
+        let selectors = rule
+            .selectors
+            .iter()
+            .map(|s| s.to_string())
+            .collect::<Vec<_>>();
+        assert_eq!(selectors, vec!["div > p#b.a", "ul li", "*"]);
+        let values = rule
+            .declarations
+            .iter()
+            .map(|declaration| declaration.value.to_string())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            values,
+            vec![
+                "rgb(255, 0, 0)",
+                "\"Dejavu \\\"Sans\\\"\"",
+                "1.5px",
+                "url(\"a.png\")"
+            ]
+        );
+        assert_eq!(
+            Color::new(255, 0, 0, 128).to_string(),
+            "rgba(255, 0, 0, 0.502)"
+        );
     }
 
     #[test]
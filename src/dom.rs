@@ -18,6 +18,10 @@ pub enum NodeType {
     Element(ElementData),
     // A comment node containing a string.
     Comment(String),
+    /// A processing instruction, e.g. `<?xml version="1.0"?>`, containing its contents.
+    ProcessingInstruction(String),
+    /// A doctype, e.g. `<!DOCTYPE html>`, containing its name.
+    Doctype(String),
 }
 
 /// Represents the data associated with an HTML element.
@@ -58,6 +62,20 @@ pub fn comment(data: String) -> Node {
     }
 }
 
+pub fn processing_instruction(data: String) -> Node {
+    Node {
+        children: Vec::new(),
+        node_type: NodeType::ProcessingInstruction(data),
+    }
+}
+
+pub fn doctype(name: String) -> Node {
+    Node {
+        children: Vec::new(),
+        node_type: NodeType::Doctype(name),
+    }
+}
+
 impl Node {
     /// Pretty-prints the DOM tree rooted at this node.
     pub fn pretty_print(&self) {
@@ -88,6 +106,12 @@ impl Node {
             NodeType::Comment(comment) => {
                 println!("{}<!-- {} -->", " ".repeat(indent), comment);
             }
+            NodeType::ProcessingInstruction(data) => {
+                println!("{}<?{}>", " ".repeat(indent), data);
+            }
+            NodeType::Doctype(name) => {
+                println!("{}<!DOCTYPE {}>", " ".repeat(indent), name);
+            }
         }
     }
 }
@@ -0,0 +1,224 @@
+use std::{collections::BTreeMap, fmt::Write};
+
+use itertools::Itertools;
+use serde_json::{json, Value as Json};
+
+use crate::{
+    css::{Declaration, Rule},
+    dom::{Node, NodeType},
+    style::CascadeTrace,
+};
+
+/// The declarations for one property that apply to a node, in cascade order, lowest precedence
+/// first, each with the index of its rule in `CascadeTrace::rules`. The last one wins.
+type Cascade<'a> = Vec<(&'a Declaration, usize)>;
+
+/// Dumps the styled tree as indented text: each node, the rules that matched it with their
+/// specificity and source location, and the declarations for each property, the winning one
+/// first.
+///
+/// ```text
+/// <p class="note">
+///   matched .note (0, 1, 0) at style.css:3:1
+///   matched p (0, 0, 1) at style.css:1:1
+///   color: red; /* .note, style.css:3:1 */
+///   color: blue; /* overridden, p, style.css:1:1 */
+///   "Hello"
+/// ```
+pub fn to_text(trace: &CascadeTrace) -> String {
+    let mut text = String::new();
+    write_text(trace, 0, &mut text);
+    text
+}
+
+fn write_text(trace: &CascadeTrace, indent: usize, text: &mut String) {
+    let pad = " ".repeat(indent);
+    // Writing to a `String` can't fail.
+    let _ = writeln!(text, "{}{}", pad, label(trace.styled.node));
+    for &(specificity, rule) in trace.rules.iter().rev() {
+        let _ = write!(
+            text,
+            "{}  matched {} {:?} at {}",
+            pad,
+            selectors(rule),
+            specificity,
+            rule.source
+        );
+        if let Some(ref layer) = rule.layer {
+            let _ = write!(text, " in layer {}", layer);
+        }
+        text.push('\n');
+    }
+    for (name, cascade) in cascades(trace) {
+        for (i, &(declaration, rule)) in cascade.iter().rev().enumerate() {
+            let rule = trace.rules[rule].1;
+            let overridden = if i == 0 { "" } else { "overridden, " };
+            let _ = writeln!(
+                text,
+                "{}  {}: {}; /* {}{}, {} */",
+                pad,
+                name,
+                declaration.value,
+                overridden,
+                selectors(rule),
+                rule.source
+            );
+        }
+    }
+    for child in &trace.children {
+        write_text(child, indent + 2, text);
+    }
+}
+
+/// Dumps the styled tree as JSON, with the same information as `to_text`.
+///
+/// Each element has its `rules` in cascade order, lowest precedence first, and maps each
+/// property in `declarations` to the winning `value` and the `overridden` ones. Declarations
+/// refer to their rule by its index in `rules`.
+pub fn to_json(trace: &CascadeTrace) -> Json {
+    let children = trace.children.iter().map(to_json).collect::<Vec<_>>();
+    let node = trace.styled.node;
+    let elem = match node.node_type {
+        NodeType::Element(ref elem) => elem,
+        NodeType::Text(ref text) => return json!({ "text": text }),
+        _ => return json!({ "node": label(node), "children": children }),
+    };
+    let rules = trace
+        .rules
+        .iter()
+        .map(|&(specificity, rule)| {
+            json!({
+                "selector": selectors(rule),
+                "specificity": [specificity.0, specificity.1, specificity.2],
+                "source": rule.source.to_string(),
+                "layer": rule.layer,
+            })
+        })
+        .collect::<Vec<_>>();
+    let declarations = cascades(trace)
+        .into_iter()
+        .map(|(name, mut cascade)| {
+            let declaration = |&(declaration, rule): &(&Declaration, usize)| {
+                json!({ "value": declaration.value.to_string(), "rule": rule })
+            };
+            // Every property in a cascade has at least one declaration.
+            let winner = cascade.pop().unwrap();
+            let overridden = cascade.iter().rev().map(declaration).collect::<Vec<_>>();
+            let mut value = declaration(&winner);
+            value["overridden"] = Json::Array(overridden);
+            (name.to_string(), value)
+        })
+        .collect::<serde_json::Map<_, _>>();
+    json!({
+        "element": elem.tag_name,
+        "attributes": elem.attributes,
+        "rules": rules,
+        "declarations": declarations,
+        "children": children,
+    })
+}
+
+/// Groups the declarations of the rules that matched the node by property, sorted by name.
+fn cascades<'a>(trace: &CascadeTrace<'a>) -> BTreeMap<&'a str, Cascade<'a>> {
+    let mut cascades = BTreeMap::<_, Cascade>::new();
+    for (i, &(_, rule)) in trace.rules.iter().enumerate() {
+        for declaration in &rule.declarations {
+            cascades
+                .entry(declaration.name.as_str())
+                .or_default()
+                .push((declaration, i));
+        }
+    }
+    cascades
+}
+
+/// Returns the selector list of `rule`.
+fn selectors(rule: &Rule) -> String {
+    rule.selectors.iter().join(", ")
+}
+
+/// Returns a one-line description of `node`.
+fn label(node: &Node) -> String {
+    match node.node_type {
+        NodeType::Element(ref elem) => {
+            let attributes = elem
+                .attributes
+                .iter()
+                .sorted()
+                .map(|(name, value)| format!(" {}={:?}", name, value))
+                .join("");
+            format!("<{}{}>", elem.tag_name, attributes)
+        }
+        NodeType::Text(ref text) => format!("{:?}", text),
+        NodeType::Comment(ref comment) => format!("<!--{}-->", comment),
+        NodeType::ProcessingInstruction(ref data) => format!("<?{}>", data),
+        NodeType::Doctype(ref name) => format!("<!DOCTYPE {}>", name),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        css, html,
+        media::Environment,
+        rule_index::RuleIndex,
+        style::{style_tree_with_index, trace_cascade},
+    };
+
+    fn dump(html: &str, css: &str, f: impl Fn(&CascadeTrace) -> String) -> String {
+        let root = html::parse(String::from(html)).unwrap();
+        let stylesheet = css::parse(String::from(css)).unwrap();
+        let index = RuleIndex::new(&stylesheet);
+        let environment = Environment::default();
+        let styled = style_tree_with_index(&root, &index, &environment);
+        f(&trace_cascade(&styled, &index, &environment))
+    }
+
+    const CSS: &str = "p { color: blue; margin: 0; }\n\
+                       @layer base { * { color: green; } }\n\
+                       .note { color: red; }";
+
+    #[test]
+    fn test_to_text() {
+        let text = dump(r#"<div><p class="note">Hi</p></div>"#, CSS, to_text);
+        assert_eq!(
+            text,
+            "<div>\n\
+             \x20 matched * (0, 0, 0) at 2:15 in layer base\n\
+             \x20 color: green; /* *, 2:15 */\n\
+             \x20 <p class=\"note\">\n\
+             \x20   matched .note (0, 1, 0) at 3:1\n\
+             \x20   matched p (0, 0, 1) at 1:1\n\
+             \x20   matched * (0, 0, 0) at 2:15 in layer base\n\
+             \x20   color: red; /* .note, 3:1 */\n\
+             \x20   color: blue; /* overridden, p, 1:1 */\n\
+             \x20   color: green; /* overridden, *, 2:15 */\n\
+             \x20   margin: 0; /* p, 1:1 */\n\
+             \x20   \"Hi\"\n"
+        );
+    }
+
+    #[test]
+    fn test_to_json() {
+        let json = dump(r#"<div><p class="note">Hi</p></div>"#, CSS, |trace| {
+            to_json(trace).to_string()
+        });
+        let json = serde_json::from_str::<Json>(&json).unwrap();
+        let p = &json["children"][0];
+        assert_eq!(p["element"], "p");
+        assert_eq!(p["attributes"]["class"], "note");
+        assert_eq!(p["rules"][2]["selector"], ".note");
+        assert_eq!(p["rules"][2]["specificity"], json!([0, 1, 0]));
+        assert_eq!(p["rules"][0]["layer"], "base");
+        assert_eq!(
+            p["declarations"]["color"],
+            json!({
+                "value": "red",
+                "rule": 2,
+                "overridden": [{ "value": "blue", "rule": 1 }, { "value": "green", "rule": 0 }],
+            })
+        );
+        assert_eq!(p["children"][0]["text"], "Hi");
+    }
+}
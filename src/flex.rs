@@ -0,0 +1,975 @@
+use std::ops::Range;
+
+use crate::{
+    computed::{
+        ContentAlignment, DisplayInside, FlexDirection, FlexWrap, ItemAlignment,
+        LengthPercentageAuto, LengthPercentageNone, Overflow,
+    },
+    font::FontDatabase,
+    layout::{content_size, ContainingBlock, EdgeSizes, LayoutBox},
+};
+
+/// A flex item being laid out, with its sizes along the main and cross axes of its container.
+/// Sizes are of the content box.
+struct FlexItem<'b, 'a> {
+    layout_box: &'b mut LayoutBox<'a>,
+    /// The padding and borders along the main and cross axes.
+    main_edges: f32,
+    cross_edges: f32,
+    /// The margins at the physical start and end of each axis, left and right or top and bottom,
+    /// or `None` while they are `auto`.
+    main_margins: (Option<f32>, Option<f32>),
+    cross_margins: (Option<f32>, Option<f32>),
+    base_size: f32,
+    hypothetical_size: f32,
+    /// The used minimum and maximum main sizes, including the automatic minimum size.
+    min_size: f32,
+    max_size: f32,
+    /// The target main size while flexible lengths are resolved, and then the used main size.
+    main_size: f32,
+    frozen: bool,
+    cross_size: f32,
+    min_cross_size: f32,
+    max_cross_size: f32,
+    /// Whether the cross size is `auto`, so that the item can stretch to fill its line.
+    auto_cross_size: bool,
+    stretched: bool,
+    /// The used `align-self`, with `auto`, `normal`, `start` and `end` resolved.
+    alignment: ItemAlignment,
+    /// The distance from the cross-start margin edge to the baseline of an item aligned by its
+    /// baseline.
+    baseline: Option<f32>,
+}
+
+impl LayoutBox<'_> {
+    /// Returns true if the box is a flex container, with `display: flex` or `inline-flex`.
+    pub(crate) fn is_flex_container(&self) -> bool {
+        self.style().display().inside() == Some(DisplayInside::Flex)
+    }
+
+    /// Lays out the children of a flex container as flex items, as in CSS Flexbox 1 § 9, in
+    /// `containing_block`, its content area. Its height there is the container's definite
+    /// height, if it has one, and `min_height` and `max_height` limit it otherwise. Returns the
+    /// height of the contents.
+    ///
+    /// Absolutely positioned children are given the start of the content area as their static
+    /// position.
+    pub(crate) fn layout_flex(
+        &mut self,
+        containing_block: ContainingBlock,
+        min_height: f32,
+        max_height: Option<f32>,
+        fonts: &FontDatabase,
+    ) -> f32 {
+        let style = self.style();
+        let direction = style.flex_direction();
+        let row = matches!(direction, FlexDirection::Row | FlexDirection::RowReverse);
+        let reverse = matches!(
+            direction,
+            FlexDirection::RowReverse | FlexDirection::ColumnReverse
+        );
+        let wrap = style.flex_wrap();
+        let wrap_reverse = wrap == FlexWrap::WrapReverse;
+        let rect = containing_block.rect;
+        let height = containing_block.height;
+        let column_gap = style.column_gap().resolve(rect.width);
+        let row_gap = style.row_gap().resolve(height.unwrap_or(0.0));
+        let (main_gap, cross_gap) = match row {
+            true => (column_gap, row_gap),
+            false => (row_gap, column_gap),
+        };
+        // `start` and `end` are the physical start and end of an axis, which flex-start and
+        // flex-end are the other way around when the axis is reversed.
+        let justify = match (style.justify_content(), reverse) {
+            (ContentAlignment::Normal | ContentAlignment::Stretch, _) => {
+                ContentAlignment::FlexStart
+            }
+            (ContentAlignment::Start, true) => ContentAlignment::FlexEnd,
+            (ContentAlignment::End, true) => ContentAlignment::FlexStart,
+            (justify, _) => justify,
+        };
+        let align_content = match (style.align_content(), wrap_reverse) {
+            (ContentAlignment::Normal, _) => ContentAlignment::Stretch,
+            (ContentAlignment::Start, true) => ContentAlignment::FlexEnd,
+            (ContentAlignment::End, true) => ContentAlignment::FlexStart,
+            (align, _) => align,
+        };
+        let align_items = style.align_items();
+        let clamp_height = |height: f32| {
+            max_height
+                .map_or(height, |max| height.min(max))
+                .max(min_height)
+        };
+
+        let mut items = Vec::new();
+        for child in &mut self.children {
+            if child.is_absolutely_positioned() {
+                child.set_static_position(rect.x, rect.y);
+                continue;
+            }
+            let item = FlexItem::new(child, row, containing_block, align_items, fonts);
+            items.push(item);
+        }
+        if items.is_empty() {
+            return 0.0;
+        }
+        // A stable sort keeps items with the same `order` in document order.
+        items.sort_by_key(|item| item.layout_box.style().order());
+        for item in &mut items {
+            item.alignment = match (item.alignment, wrap_reverse) {
+                (ItemAlignment::Start, true) => ItemAlignment::FlexEnd,
+                (ItemAlignment::End, true) => ItemAlignment::FlexStart,
+                (alignment, _) => alignment,
+            };
+        }
+
+        // Collect the items into lines, and find the main size of a column container whose
+        // height depends on its contents.
+        let available = match row {
+            true => Some(rect.width),
+            false => height.or(max_height),
+        };
+        let lines = collect_lines(&items, wrap, available.unwrap_or(f32::INFINITY), main_gap);
+        let gaps = |count: usize, gap: f32| gap * count.saturating_sub(1) as f32;
+        let container_main = match (row, height) {
+            (true, _) => rect.width,
+            (false, Some(height)) => height,
+            (false, None) => clamp_height(
+                lines
+                    .iter()
+                    .map(|line| {
+                        let items = &items[line.clone()];
+                        let sizes = items
+                            .iter()
+                            .map(|item| item.outer_main(item.hypothetical_size));
+                        sizes.sum::<f32>() + gaps(items.len(), main_gap)
+                    })
+                    .fold(0.0, f32::max),
+            ),
+        };
+        for line in &lines {
+            let gaps = gaps(line.len(), main_gap);
+            resolve_flexible_lengths(&mut items[line.clone()], container_main - gaps);
+        }
+
+        // The hypothetical cross size of an item in a row is its height at its used width.
+        if row {
+            for item in &mut items {
+                let margin = item.margins(true);
+                let layout_box = &mut *item.layout_box;
+                let size = (item.main_size, None);
+                layout_box.layout_item(containing_block, (rect.x, rect.y), margin, size, fonts);
+                let dimensions = layout_box.dimensions;
+                item.cross_size = dimensions.content.height;
+                let auto_margin = item.cross_margins.0.is_none() || item.cross_margins.1.is_none();
+                if item.alignment == ItemAlignment::Baseline && !auto_margin {
+                    let border_box = dimensions.border_box();
+                    let baseline = layout_box
+                        .first_baseline()
+                        .unwrap_or(border_box.y + border_box.height);
+                    item.baseline = Some(baseline - dimensions.margin_box().y);
+                }
+            }
+        }
+
+        // Size the lines, which a single line in a container of definite cross size fills.
+        let single_line = wrap == FlexWrap::Nowrap;
+        let definite_cross = match row {
+            true => height,
+            false => Some(rect.width),
+        };
+        let mut line_sizes = lines
+            .iter()
+            .map(|line| match (single_line, definite_cross) {
+                (true, Some(cross)) => cross,
+                _ => {
+                    let items = &items[line.clone()];
+                    let (ascent, descent) = items
+                        .iter()
+                        .filter_map(|item| {
+                            let baseline = item.baseline?;
+                            Some((baseline, item.outer_cross() - baseline))
+                        })
+                        .fold((0.0, 0.0), |(ascent, descent), (above, below)| {
+                            (f32::max(ascent, above), f32::max(descent, below))
+                        });
+                    let size = items
+                        .iter()
+                        .filter(|item| item.baseline.is_none())
+                        .map(FlexItem::outer_cross)
+                        .fold(ascent + descent, f32::max);
+                    match single_line {
+                        true => clamp_height(size),
+                        false => size,
+                    }
+                }
+            })
+            .collect::<Vec<_>>();
+        let container_cross = definite_cross.unwrap_or_else(|| {
+            clamp_height(line_sizes.iter().sum::<f32>() + gaps(lines.len(), cross_gap))
+        });
+
+        // Distribute the free space in the cross axis among the lines.
+        let mut line_offset = 0.0;
+        let mut line_spacing = 0.0;
+        if !single_line {
+            let free =
+                container_cross - line_sizes.iter().sum::<f32>() - gaps(lines.len(), cross_gap);
+            match align_content {
+                ContentAlignment::Stretch if free > 0.0 => {
+                    let extra = free / lines.len() as f32;
+                    for size in &mut line_sizes {
+                        *size += extra;
+                    }
+                }
+                alignment => (line_offset, line_spacing) = distribute(alignment, free, lines.len()),
+            }
+        }
+
+        for (line, &line_size) in lines.iter().zip(&line_sizes) {
+            let items = &mut items[line.clone()];
+            let count = items.len();
+            for item in items.iter_mut() {
+                let auto_margin = item.cross_margins.0.is_none() || item.cross_margins.1.is_none();
+                if item.alignment == ItemAlignment::Stretch && item.auto_cross_size && !auto_margin
+                {
+                    let size = line_size - (item.outer_cross() - item.cross_size);
+                    item.cross_size = size.min(item.max_cross_size).max(item.min_cross_size);
+                    item.stretched = true;
+                }
+            }
+
+            // Auto margins take up the free space in the main axis, and otherwise
+            // `justify-content` distributes it.
+            let used = items
+                .iter()
+                .map(|item| item.outer_main(item.main_size))
+                .sum::<f32>()
+                + gaps(count, main_gap);
+            let free = container_main - used;
+            let auto_margins = items
+                .iter()
+                .map(|item| {
+                    let (start, end) = item.main_margins;
+                    start.is_none() as usize + end.is_none() as usize
+                })
+                .sum::<usize>();
+            let (mut main_offset, spacing) = match free > 0.0 && auto_margins > 0 {
+                true => {
+                    let share = free / auto_margins as f32;
+                    for item in items.iter_mut() {
+                        let (start, end) = &mut item.main_margins;
+                        start.get_or_insert(share);
+                        end.get_or_insert(share);
+                    }
+                    (0.0, 0.0)
+                }
+                false => distribute(justify, free, count),
+            };
+
+            let max_baseline = items
+                .iter()
+                .filter_map(|item| item.baseline)
+                .fold(0.0, f32::max);
+            for item in items.iter_mut() {
+                let (start, end) = item.main_margins;
+                item.main_margins = (Some(start.unwrap_or(0.0)), Some(end.unwrap_or(0.0)));
+                let outer_main = item.outer_main(item.main_size);
+
+                // Auto margins take up the free space in the cross axis, and otherwise the item
+                // is aligned in its line by `align-self`.
+                let free = line_size - item.outer_cross();
+                let cross_offset = match item.cross_margins {
+                    (Some(_), Some(_)) => match item.alignment {
+                        ItemAlignment::FlexEnd => free,
+                        ItemAlignment::Center => free / 2.0,
+                        ItemAlignment::Baseline => max_baseline - item.baseline.unwrap_or(0.0),
+                        _ => 0.0,
+                    },
+                    (start, end) => {
+                        let autos = start.is_none() as usize + end.is_none() as usize;
+                        let (start_share, end_share) = match free > 0.0 {
+                            true => (free / autos as f32, free / autos as f32),
+                            false => (0.0, free),
+                        };
+                        item.cross_margins = (
+                            Some(start.unwrap_or(start_share)),
+                            Some(end.unwrap_or(end_share)),
+                        );
+                        0.0
+                    }
+                };
+                let outer_cross = item.outer_cross();
+
+                let main_start = match reverse {
+                    true => container_main - main_offset - outer_main,
+                    false => main_offset,
+                };
+                let cross_start = match wrap_reverse {
+                    true => container_cross - line_offset - cross_offset - outer_cross,
+                    false => line_offset + cross_offset,
+                };
+                main_offset += outer_main + spacing + main_gap;
+                let margin = item.margins(row);
+                let (x, y, width, height) = match row {
+                    true => (
+                        rect.x + main_start,
+                        rect.y + cross_start,
+                        item.main_size,
+                        item.stretched.then_some(item.cross_size),
+                    ),
+                    false => (
+                        rect.x + cross_start,
+                        rect.y + main_start,
+                        item.cross_size,
+                        Some(item.main_size),
+                    ),
+                };
+                let layout_box = &mut *item.layout_box;
+                layout_box.layout_item(containing_block, (x, y), margin, (width, height), fonts);
+                layout_box.apply_relative_offset(containing_block);
+            }
+            line_offset += line_size + line_spacing + cross_gap;
+        }
+
+        match row {
+            true => container_cross,
+            false => container_main,
+        }
+    }
+
+    /// Lays out a flex or grid item in its container's content area or its grid area
+    /// `containing_block`, with the top left corner of its margin box at `position` and the given
+    /// margins. `size` is the width of its content box and its height, or `None` to size it as a
+    /// block would be.
+    pub(crate) fn layout_item(
+        &mut self,
+        containing_block: ContainingBlock,
+        position: (f32, f32),
+        margin: EdgeSizes,
+        size: (f32, Option<f32>),
+        fonts: &FontDatabase,
+    ) {
+        let ((x, y), (width, height)) = (position, size);
+        let style = self.style();
+        let reference = containing_block.rect.width;
+        let padding = EdgeSizes {
+            left: style.padding_left().resolve(reference),
+            right: style.padding_right().resolve(reference),
+            top: style.padding_top().resolve(reference),
+            bottom: style.padding_bottom().resolve(reference),
+        };
+        let border = EdgeSizes {
+            left: style.border_left_width(),
+            right: style.border_right_width(),
+            top: style.border_top_width(),
+            bottom: style.border_bottom_width(),
+        };
+        let dimensions = &mut self.dimensions;
+        dimensions.margin = margin;
+        dimensions.padding = padding;
+        dimensions.border = border;
+        dimensions.content.x = x + margin.left + border.left + padding.left;
+        dimensions.content.y = y + margin.top + border.top + padding.top;
+        dimensions.content.width = width;
+        let (auto_height, min_height, max_height) = self.vertical_sizes(containing_block);
+        let height = height.or(auto_height);
+        self.layout_contents((height, min_height, max_height), None, fonts);
+    }
+
+    /// Returns the first baseline of a flex container, which is that of its first item with one.
+    pub(crate) fn flex_first_baseline(&self) -> Option<f32> {
+        let mut items = self
+            .children
+            .iter()
+            .filter(|child| !child.is_absolutely_positioned())
+            .collect::<Vec<_>>();
+        items.sort_by_key(|item| item.style().order());
+        items.into_iter().find_map(LayoutBox::first_baseline)
+    }
+
+    /// Returns the min-content and max-content widths of the contents of a flex container: its
+    /// items side by side in a row, or the widest of them in a column or when a row can wrap.
+    pub(crate) fn flex_intrinsic_widths(&self, fonts: &FontDatabase) -> (f32, f32) {
+        let style = self.style();
+        let row = matches!(
+            style.flex_direction(),
+            FlexDirection::Row | FlexDirection::RowReverse
+        );
+        let widths = self
+            .children
+            .iter()
+            .filter(|child| !child.is_absolutely_positioned())
+            .map(|child| child.intrinsic_outer_widths(fonts))
+            .collect::<Vec<_>>();
+        let gaps = style.column_gap().resolve(0.0) * widths.len().saturating_sub(1) as f32;
+        let widest = widths
+            .iter()
+            .fold((0.0, 0.0), |(min, max), &(item_min, item_max)| {
+                (f32::max(min, item_min), f32::max(max, item_max))
+            });
+        match (row, style.flex_wrap()) {
+            (false, _) => widest,
+            (true, wrap) => {
+                let max_content = widths.iter().map(|(_, max)| max).sum::<f32>() + gaps;
+                let min_content = match wrap {
+                    FlexWrap::Nowrap => widths.iter().map(|(min, _)| min).sum::<f32>() + gaps,
+                    _ => widest.0,
+                };
+                (min_content, max_content)
+            }
+        }
+    }
+}
+
+impl<'b, 'a> FlexItem<'b, 'a> {
+    /// Computes the flex base size and hypothetical main size of `layout_box`, a child of a flex
+    /// container whose content area is `containing_block`, and, in a column, its cross size.
+    fn new(
+        layout_box: &'b mut LayoutBox<'a>,
+        row: bool,
+        containing_block: ContainingBlock,
+        align_items: ItemAlignment,
+        fonts: &FontDatabase,
+    ) -> FlexItem<'b, 'a> {
+        let style = layout_box.style();
+        // Margins and padding resolve against the width of the container in both axes.
+        let reference = containing_block.rect.width;
+        let horizontal_edges = style.padding_left().resolve(reference)
+            + style.padding_right().resolve(reference)
+            + style.border_left_width()
+            + style.border_right_width();
+        let vertical_edges = style.padding_top().resolve(reference)
+            + style.padding_bottom().resolve(reference)
+            + style.border_top_width()
+            + style.border_bottom_width();
+        let horizontal_margins = (
+            style.margin_left().resolve(reference),
+            style.margin_right().resolve(reference),
+        );
+        let vertical_margins = (
+            style.margin_top().resolve(reference),
+            style.margin_bottom().resolve(reference),
+        );
+        let width_size = content_size(style.box_sizing(), horizontal_edges);
+        let height_size = content_size(style.box_sizing(), vertical_edges);
+        // Percentages of an indefinite height behave as `auto`, or as `none` for the maximum.
+        let height_percent = |size: LengthPercentageAuto| match (size, containing_block.height) {
+            (LengthPercentageAuto::Percent(_), None) => None,
+            (size, height) => size.resolve(height.unwrap_or(0.0)),
+        };
+        let width = style.width().resolve(reference).map(width_size);
+        let height = height_percent(style.height()).map(height_size);
+        let min_width = style.min_width().resolve(reference).map(width_size);
+        let min_height = height_percent(style.min_height()).map(height_size);
+        let max_width = style.max_width().resolve(reference).map(width_size);
+        let max_height = match (style.max_height(), containing_block.height) {
+            (LengthPercentageNone::Percent(_), None) => None,
+            (size, height) => size.resolve(height.unwrap_or(0.0)),
+        }
+        .map(height_size);
+        let alignment = match style.align_self() {
+            ItemAlignment::Auto => align_items,
+            alignment => alignment,
+        };
+        let alignment = match alignment {
+            ItemAlignment::Normal => ItemAlignment::Stretch,
+            // Baseline alignment is only in rows, and falls back to flex-start in columns.
+            ItemAlignment::Baseline if !row => ItemAlignment::FlexStart,
+            alignment => alignment,
+        };
+        let (min_content, max_content) = layout_box.intrinsic_widths(fonts);
+        let overflow = style.overflow();
+        let flex_basis = style.flex_basis();
+        let automatic_minimum = match row {
+            true => style.min_width() == LengthPercentageAuto::Auto,
+            false => style.min_height() == LengthPercentageAuto::Auto,
+        } && overflow == Overflow::Visible;
+
+        // The cross size of an item in a column is its width, which fits its contents unless it
+        // is stretched to fill its line later.
+        let clamp_width = |width: f32| {
+            max_width
+                .map_or(width, |max| width.min(max))
+                .max(min_width.unwrap_or(0.0))
+        };
+        let cross_size = match row {
+            true => 0.0,
+            false => clamp_width(width.unwrap_or_else(|| {
+                let margins =
+                    horizontal_margins.0.unwrap_or(0.0) + horizontal_margins.1.unwrap_or(0.0);
+                (reference - horizontal_edges - margins)
+                    .max(min_content)
+                    .min(max_content)
+            })),
+        };
+        // The size of the contents in the main axis: the max-content and min-content widths in a
+        // row, or the height at the cross size in a column.
+        let (min_content_main, max_content_main) = match row {
+            true => (min_content, max_content),
+            false => {
+                let rect = containing_block.rect;
+                let margin = EdgeSizes::default();
+                let size = (cross_size, None);
+                layout_box.layout_item(containing_block, (rect.x, rect.y), margin, size, fonts);
+                let height = layout_box.dimensions.content.height;
+                (height, height)
+            }
+        };
+
+        let (main_size, main_edges, main_reference) = match row {
+            true => (width, horizontal_edges, Some(reference)),
+            false => (height, vertical_edges, containing_block.height),
+        };
+        let main_content_size = match row {
+            true => width_size,
+            false => height_size,
+        };
+        let base_size = match (flex_basis, main_reference) {
+            (LengthPercentageAuto::Auto, _) => main_size,
+            (LengthPercentageAuto::Percent(_), None) => None,
+            (basis, reference) => basis
+                .resolve(reference.unwrap_or(0.0))
+                .map(main_content_size),
+        }
+        .unwrap_or(max_content_main);
+        let (min_size, max_size) = match row {
+            true => (min_width, max_width),
+            false => (min_height, max_height),
+        };
+        let max_size = max_size.unwrap_or(f32::INFINITY);
+        // The automatic minimum size keeps an item from shrinking below its min-content size,
+        // or its specified size if that is smaller.
+        let min_size = match automatic_minimum {
+            true => main_size
+                .map_or(min_content_main, |size| size.min(min_content_main))
+                .min(max_size),
+            false => min_size.unwrap_or(0.0),
+        };
+        let hypothetical_size = base_size.min(max_size).max(min_size);
+
+        let (main_margins, cross_margins, cross_edges) = match row {
+            true => (horizontal_margins, vertical_margins, vertical_edges),
+            false => (vertical_margins, horizontal_margins, horizontal_edges),
+        };
+        let (min_cross_size, max_cross_size, auto_cross_size) = match row {
+            true => (
+                min_height.unwrap_or(0.0),
+                max_height.unwrap_or(f32::INFINITY),
+                height.is_none(),
+            ),
+            false => (
+                min_width.unwrap_or(0.0),
+                max_width.unwrap_or(f32::INFINITY),
+                width.is_none(),
+            ),
+        };
+        FlexItem {
+            layout_box,
+            main_edges,
+            cross_edges,
+            main_margins,
+            cross_margins,
+            base_size,
+            hypothetical_size,
+            min_size,
+            max_size,
+            main_size: hypothetical_size,
+            frozen: false,
+            cross_size,
+            min_cross_size,
+            max_cross_size,
+            auto_cross_size,
+            stretched: false,
+            alignment,
+            baseline: None,
+        }
+    }
+
+    /// Returns the outer main size of the item if its main size is `size`, with `auto` margins
+    /// as 0.
+    fn outer_main(&self, size: f32) -> f32 {
+        let (start, end) = self.main_margins;
+        size + self.main_edges + start.unwrap_or(0.0) + end.unwrap_or(0.0)
+    }
+
+    /// Returns the outer cross size of the item, with `auto` margins as 0.
+    fn outer_cross(&self) -> f32 {
+        let (start, end) = self.cross_margins;
+        self.cross_size + self.cross_edges + start.unwrap_or(0.0) + end.unwrap_or(0.0)
+    }
+
+    /// Returns the physical margins of the item, with `auto` margins as 0, where `row` is whether
+    /// the main axis is horizontal.
+    fn margins(&self, row: bool) -> EdgeSizes {
+        let (horizontal, vertical) = match row {
+            true => (self.main_margins, self.cross_margins),
+            false => (self.cross_margins, self.main_margins),
+        };
+        EdgeSizes {
+            left: horizontal.0.unwrap_or(0.0),
+            right: horizontal.1.unwrap_or(0.0),
+            top: vertical.0.unwrap_or(0.0),
+            bottom: vertical.1.unwrap_or(0.0),
+        }
+    }
+
+    /// Returns the flex grow factor of the item if `grow`, or its flex shrink factor.
+    fn flex_factor(&self, grow: bool) -> f32 {
+        let style = self.layout_box.style();
+        match grow {
+            true => style.flex_grow(),
+            false => style.flex_shrink(),
+        }
+    }
+}
+
+/// Collects `items`, in order, into flex lines of at most `available` outer main size with `gap`
+/// between the items, or into a single line if they don't wrap. A line always has at least one
+/// item.
+fn collect_lines(
+    items: &[FlexItem],
+    wrap: FlexWrap,
+    available: f32,
+    gap: f32,
+) -> Vec<Range<usize>> {
+    let mut lines = Vec::new();
+    let mut start = 0;
+    let mut used = 0.0;
+    for (i, item) in items.iter().enumerate() {
+        let size = item.outer_main(item.hypothetical_size);
+        if i == start {
+            used = size;
+        } else if wrap != FlexWrap::Nowrap && used + gap + size > available {
+            lines.push(start..i);
+            start = i;
+            used = size;
+        } else {
+            used += gap + size;
+        }
+    }
+    lines.push(start..items.len());
+    lines
+}
+
+/// Resolves the main sizes of the items of a flex line whose outer main sizes must add up to
+/// `available`, as in CSS Flexbox 1 § 9.7: the free space is distributed in proportion to the
+/// items' flex grow factors, or taken away in proportion to their flex shrink factors scaled by
+/// their flex base sizes, freezing the items that violate their minimum or maximum sizes until
+/// none do.
+fn resolve_flexible_lengths(items: &mut [FlexItem], available: f32) {
+    let hypothetical = items
+        .iter()
+        .map(|item| item.outer_main(item.hypothetical_size))
+        .sum::<f32>();
+    let grow = hypothetical < available;
+    // Items that can't flex, or would have to move away from their hypothetical size in the
+    // wrong direction, are frozen at it.
+    for item in items.iter_mut() {
+        item.main_size = item.hypothetical_size;
+        item.frozen = item.flex_factor(grow) == 0.0
+            || (grow && item.base_size > item.hypothetical_size)
+            || (!grow && item.base_size < item.hypothetical_size);
+    }
+    let free_space = |items: &[FlexItem]| {
+        available
+            - items
+                .iter()
+                .map(|item| match item.frozen {
+                    true => item.outer_main(item.main_size),
+                    false => item.outer_main(item.base_size),
+                })
+                .sum::<f32>()
+    };
+    let initial_free_space = free_space(items);
+
+    while items.iter().any(|item| !item.frozen) {
+        let mut remaining = free_space(items);
+        let factors = items
+            .iter()
+            .filter(|item| !item.frozen)
+            .map(|item| item.flex_factor(grow))
+            .sum::<f32>();
+        // Flex factors that add up to less than 1 take only that fraction of the free space.
+        if factors < 1.0 && (initial_free_space * factors).abs() < remaining.abs() {
+            remaining = initial_free_space * factors;
+        }
+        let scaled_shrink = items
+            .iter()
+            .filter(|item| !item.frozen)
+            .map(|item| item.flex_factor(false) * item.base_size)
+            .sum::<f32>();
+        for item in items.iter_mut().filter(|item| !item.frozen) {
+            item.main_size = match grow {
+                true => item.base_size + remaining * item.flex_factor(true) / factors,
+                false if scaled_shrink > 0.0 => {
+                    let ratio = item.flex_factor(false) * item.base_size / scaled_shrink;
+                    item.base_size - remaining.abs() * ratio
+                }
+                false => item.base_size,
+            };
+        }
+
+        // Clamp the items to their minimum and maximum sizes, and freeze those that violated
+        // them in the direction of the total violation, or all of them if there was none.
+        let mut total_violation = 0.0;
+        let mut violations = Vec::with_capacity(items.len());
+        for item in items.iter_mut() {
+            let mut violation = 0.0;
+            if !item.frozen {
+                let clamped = item
+                    .main_size
+                    .min(item.max_size)
+                    .max(item.min_size)
+                    .max(0.0);
+                violation = clamped - item.main_size;
+                item.main_size = clamped;
+            }
+            total_violation += violation;
+            violations.push(violation);
+        }
+        for (item, violation) in items.iter_mut().zip(violations) {
+            item.frozen |= match total_violation {
+                total if total > 0.0 => violation > 0.0,
+                total if total < 0.0 => violation < 0.0,
+                _ => true,
+            };
+        }
+    }
+}
+
+/// Returns the space before the first of `count` items or lines and between each of them when
+/// `alignment` distributes `free` space among them. Distributed alignments fall back to
+/// flex-start or center when there is no space to distribute.
+pub(crate) fn distribute(alignment: ContentAlignment, free: f32, count: usize) -> (f32, f32) {
+    let count = count as f32;
+    match alignment {
+        ContentAlignment::End | ContentAlignment::FlexEnd => (free, 0.0),
+        ContentAlignment::Center => (free / 2.0, 0.0),
+        ContentAlignment::SpaceBetween if free > 0.0 && count > 1.0 => (0.0, free / (count - 1.0)),
+        ContentAlignment::SpaceAround if free > 0.0 => (free / count / 2.0, free / count),
+        ContentAlignment::SpaceEvenly if free > 0.0 => (free / (count + 1.0), free / (count + 1.0)),
+        ContentAlignment::SpaceAround | ContentAlignment::SpaceEvenly => (free / 2.0, 0.0),
+        _ => (0.0, 0.0),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::layout::{
+        tests::{border_boxes, with_monospace_layout, CH},
+        BoxType,
+    };
+
+    /// Returns the x coordinate and width of the border box of each child of `layout_box`.
+    fn horizontal(layout_box: &LayoutBox) -> Vec<(f32, f32)> {
+        border_boxes(layout_box)
+            .into_iter()
+            .map(|(x, _, width, _)| (x, width))
+            .collect()
+    }
+
+    const ITEMS: &str = "<html><div><a></a><b></b><i></i></div></html>";
+
+    #[test]
+    fn test_flexible_lengths() {
+        let css = "div { display: flex; } a, b { width: 100px; } i { width: 200px; } \
+                   a { flex-grow: 1; } b { flex-grow: 3; }";
+        with_monospace_layout(ITEMS, css, |root| {
+            let div = &root.children[0];
+            assert_eq!(
+                horizontal(div),
+                [(0.0, 200.0), (200.0, 400.0), (600.0, 200.0)]
+            );
+        });
+        // Shrinking is in proportion to the flex shrink factors times the flex base sizes.
+        let css = "div { display: flex; } a, b { flex-basis: 500px; } b { flex-shrink: 3; } \
+                   i { flex-shrink: 0; }";
+        with_monospace_layout(ITEMS, css, |root| {
+            let div = &root.children[0];
+            assert_eq!(
+                horizontal(div),
+                [(0.0, 450.0), (450.0, 350.0), (800.0, 0.0)]
+            );
+        });
+        // An item that would shrink below its minimum is frozen there, and the others shrink
+        // more.
+        let css = "div { display: flex; } a, b { flex-basis: 500px; } \
+                   b { flex-shrink: 3; min-width: 400px; } i { flex-shrink: 0; }";
+        with_monospace_layout(ITEMS, css, |root| {
+            let div = &root.children[0];
+            assert_eq!(
+                horizontal(div),
+                [(0.0, 400.0), (400.0, 400.0), (800.0, 0.0)]
+            );
+        });
+        // Flex factors that add up to less than 1 take only part of the free space.
+        let css = "div { display: flex; } a { flex-grow: 0.25; } b { flex-grow: 0.25; }";
+        with_monospace_layout(ITEMS, css, |root| {
+            let div = &root.children[0];
+            assert_eq!(
+                horizontal(div),
+                [(0.0, 200.0), (200.0, 200.0), (400.0, 0.0)]
+            );
+        });
+    }
+
+    #[test]
+    fn test_flex_shorthand() {
+        with_monospace_layout(
+            "<html><div><ul>a</ul></div></html>",
+            "div { display: flex; } ul { flex: 1; }",
+            |root| assert_eq!(horizontal(&root.children[0]), [(0.0, 800.0)]),
+        );
+        let css = "div { display: flex; } a { flex: 1 1 0; } b { flex: 2 1 0px; } \
+                   i { flex: none; width: 200px; }";
+        with_monospace_layout(ITEMS, css, |root| {
+            let div = &root.children[0];
+            assert_eq!(
+                horizontal(div),
+                [(0.0, 200.0), (200.0, 400.0), (600.0, 200.0)]
+            );
+        });
+        // `auto` keeps the items' widths as their bases, and a lone length is a basis.
+        let css = "div { display: flex; flex-flow: wrap row-reverse; width: 300px; } \
+                   a { flex: auto; width: 100px; } b { flex: 150px; } i { width: 100px; }";
+        with_monospace_layout(ITEMS, css, |root| {
+            let div = &root.children[0];
+            assert_eq!(
+                horizontal(div),
+                [(175.0, 125.0), (0.0, 175.0), (200.0, 100.0)]
+            );
+        });
+    }
+
+    #[test]
+    fn test_direction_and_order() {
+        let css = "div { display: flex; flex-direction: row-reverse; } \
+                   a, b, i { width: 100px; } b { order: -1; }";
+        with_monospace_layout(ITEMS, css, |root| {
+            let div = &root.children[0];
+            assert_eq!(
+                horizontal(div),
+                [(600.0, 100.0), (700.0, 100.0), (500.0, 100.0)]
+            );
+        });
+    }
+
+    #[test]
+    fn test_wrapping_and_alignment() {
+        let css = "div { display: flex; flex-wrap: wrap; width: 300px; height: 200px; \
+                   column-gap: 10px; justify-content: center; align-content: space-between; } \
+                   a, b, i, s { width: 100px; height: 50px; }";
+        with_monospace_layout(
+            "<html><div><a></a><b></b><i></i><s></s></div></html>",
+            css,
+            |root| {
+                let div = &root.children[0];
+                assert_eq!(
+                    border_boxes(div),
+                    [
+                        (45.0, 0.0, 100.0, 50.0),
+                        (155.0, 0.0, 100.0, 50.0),
+                        (45.0, 150.0, 100.0, 50.0),
+                        (155.0, 150.0, 100.0, 50.0),
+                    ]
+                );
+            },
+        );
+        // Lines stretch to fill the container, and so do the items in them that have no height.
+        // The first line is at the bottom.
+        let css = "div { display: flex; flex-wrap: wrap-reverse; width: 300px; height: 200px; } \
+                   a, b { width: 200px; } a { height: 50px; }";
+        with_monospace_layout("<html><div><a></a><b></b></div></html>", css, |root| {
+            let div = &root.children[0];
+            assert_eq!(
+                border_boxes(div),
+                [(0.0, 150.0, 200.0, 50.0), (0.0, 0.0, 200.0, 75.0)]
+            );
+        });
+    }
+
+    #[test]
+    fn test_columns_and_auto_margins() {
+        let css = "div { display: flex; flex-direction: column; width: 200px; height: 300px; } \
+                   a { height: 50px; } b { flex-grow: 1; } \
+                   i { height: 50px; width: 20px; margin-left: auto; }";
+        with_monospace_layout(ITEMS, css, |root| {
+            let div = &root.children[0];
+            assert_eq!(
+                border_boxes(div),
+                [
+                    (0.0, 0.0, 200.0, 50.0),
+                    (0.0, 50.0, 200.0, 200.0),
+                    (180.0, 250.0, 20.0, 50.0),
+                ]
+            );
+        });
+        // Auto margins in the main axis take up the free space before `justify-content`.
+        let css = "div { display: flex; justify-content: flex-end; align-items: center; \
+                   height: 100px; } a, b, i { width: 100px; height: 20px; } \
+                   b { margin-left: auto; margin-right: auto; }";
+        with_monospace_layout(ITEMS, css, |root| {
+            let div = &root.children[0];
+            assert_eq!(
+                border_boxes(div),
+                [
+                    (0.0, 40.0, 100.0, 20.0),
+                    (350.0, 40.0, 100.0, 20.0),
+                    (700.0, 40.0, 100.0, 20.0),
+                ]
+            );
+        });
+    }
+
+    #[test]
+    fn test_automatic_minimum_size() {
+        let css = "div { display: flex; width: 20px; } p { flex-basis: 100px; }";
+        with_monospace_layout("<html><div><p>aaaa</p></div></html>", css, |root| {
+            let p = &root.children[0].children[0];
+            assert_eq!(p.dimensions.content.width, 4.0 * CH);
+        });
+        let css = "div { display: flex; width: 20px; } p { flex-basis: 100px; min-width: 0; }";
+        with_monospace_layout("<html><div><p>aaaa</p></div></html>", css, |root| {
+            let p = &root.children[0].children[0];
+            assert_eq!(p.dimensions.content.width, 20.0);
+        });
+    }
+
+    #[test]
+    fn test_baseline_alignment() {
+        let css = "div { display: flex; align-items: baseline; line-height: 10px; } \
+                   a { padding-top: 10px; }";
+        with_monospace_layout("<html><div><a>x</a><b>y</b></div></html>", css, |root| {
+            let div = &root.children[0];
+            let (a, b) = (&div.children[0], &div.children[1]);
+            assert_eq!(a.lines[0].baseline, b.lines[0].baseline);
+            assert_eq!(b.dimensions.border_box().y, 10.0);
+            assert_eq!(div.dimensions.content.height, 20.0);
+        });
+    }
+
+    #[test]
+    fn test_inline_flex_and_anonymous_items() {
+        let css = "p { display: block; } span { display: inline-flex; column-gap: 5px; } \
+                   b { width: 30px; } i { width: 40px; }";
+        with_monospace_layout(
+            "<html><p>a<span>b<b></b><i></i></span></p></html>",
+            css,
+            |root| {
+                let span = &root.children[0].children[1];
+                // The text is wrapped in an anonymous flex item.
+                assert!(matches!(
+                    span.children[0].box_type,
+                    BoxType::AnonymousBlock(_)
+                ));
+                assert_eq!(span.dimensions.content.width, CH + 80.0);
+                assert_eq!(
+                    horizontal(span),
+                    [(CH, CH), (2.0 * CH + 5.0, 30.0), (2.0 * CH + 40.0, 40.0)]
+                );
+            },
+        );
+    }
+}
@@ -0,0 +1,174 @@
+use crate::{
+    computed::{Clear, Float},
+    layout::Rect,
+};
+
+/// The side of its containing block a float is pushed to. Text is laid out left to right, so the
+/// inline start is the left.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Side {
+    Left,
+    Right,
+}
+
+impl Side {
+    /// Returns the side a box with the given `float` value floats to, or `None` if it isn't
+    /// floated.
+    pub fn of(float: Float) -> Option<Side> {
+        match float {
+            Float::None => None,
+            Float::Left | Float::InlineStart => Some(Side::Left),
+            Float::Right | Float::InlineEnd => Some(Side::Right),
+        }
+    }
+
+    /// Returns true if a box with the given `clear` value clears floats on this side.
+    fn is_cleared_by(self, clear: Clear) -> bool {
+        match clear {
+            Clear::None => false,
+            Clear::Both => true,
+            Clear::Left | Clear::InlineStart => self == Side::Left,
+            Clear::Right | Clear::InlineEnd => self == Side::Right,
+        }
+    }
+}
+
+/// The floats placed so far in a block formatting context, which its line boxes and the boxes in
+/// it that establish new block formatting contexts flow around, as in CSS 2.1 § 9.5.
+///
+/// Positions are in the same coordinates as the boxes, relative to the document origin.
+#[derive(Default, Debug)]
+pub(crate) struct FloatContext {
+    /// The margin box of each float and the side it floats to, in the order they were placed.
+    floats: Vec<(Side, Rect)>,
+}
+
+impl FloatContext {
+    /// Returns the left and right edges of the space between `left` and `right` that no float
+    /// covers anywhere from `y` down to `y + height`.
+    pub(crate) fn band(&self, y: f32, height: f32, left: f32, right: f32) -> (f32, f32) {
+        let bottom = y + height;
+        self.floats
+            .iter()
+            .filter(|(_, rect)| (rect.y <= y || rect.y < bottom) && rect.y + rect.height > y)
+            .fold((left, right), |(left, right), &(side, rect)| match side {
+                Side::Left => (left.max(rect.x + rect.width), right),
+                Side::Right => (left, right.min(rect.x)),
+            })
+    }
+
+    /// Returns the first bottom edge of a float below `y`, where the space beside the floats may
+    /// widen.
+    pub(crate) fn next_bottom(&self, y: f32) -> Option<f32> {
+        self.floats
+            .iter()
+            .map(|(_, rect)| rect.y + rect.height)
+            .filter(|&bottom| bottom > y)
+            .min_by(f32::total_cmp)
+    }
+
+    /// Returns the bottom of the lowest float, if there are any.
+    pub(crate) fn bottom(&self) -> Option<f32> {
+        self.floats
+            .iter()
+            .map(|(_, rect)| rect.y + rect.height)
+            .max_by(f32::total_cmp)
+    }
+
+    /// Returns the bottom of the lowest float a box with the given `clear` value must be placed
+    /// below, if there is one.
+    pub(crate) fn clearance(&self, clear: Clear) -> Option<f32> {
+        self.floats
+            .iter()
+            .filter(|(side, _)| side.is_cleared_by(clear))
+            .map(|(_, rect)| rect.y + rect.height)
+            .max_by(f32::total_cmp)
+    }
+
+    /// Places a float whose margin box is `width` by `height` on `side` of the space between
+    /// `left` and `right`, and returns the position of its margin box.
+    ///
+    /// The float goes as high as it fits beside the floats already placed, but no higher than `y`
+    /// or the top of an earlier float. If it is too wide to fit beside any of them, it goes below
+    /// them all.
+    pub(crate) fn place(
+        &mut self,
+        side: Side,
+        width: f32,
+        height: f32,
+        y: f32,
+        left: f32,
+        right: f32,
+    ) -> (f32, f32) {
+        let mut y = self.floats.iter().map(|(_, rect)| rect.y).fold(y, f32::max);
+        let (band_left, band_right) = loop {
+            let band = self.band(y, height, left, right);
+            match self.next_bottom(y) {
+                Some(bottom) if width > band.1 - band.0 => y = bottom,
+                _ => break band,
+            }
+        };
+        let x = match side {
+            Side::Left => band_left,
+            Side::Right => band_right - width,
+        };
+        self.floats.push((
+            side,
+            Rect {
+                x,
+                y,
+                width,
+                height,
+            },
+        ));
+        (x, y)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_place() {
+        let mut floats = FloatContext::default();
+        assert_eq!(
+            floats.place(Side::Left, 100.0, 50.0, 0.0, 0.0, 300.0),
+            (0.0, 0.0)
+        );
+        assert_eq!(
+            floats.place(Side::Right, 50.0, 20.0, 0.0, 0.0, 300.0),
+            (250.0, 0.0)
+        );
+        assert_eq!(
+            floats.place(Side::Left, 100.0, 10.0, 0.0, 0.0, 300.0),
+            (100.0, 0.0)
+        );
+        // Too wide for the space beside the floats, so it moves down until there's room.
+        assert_eq!(
+            floats.place(Side::Left, 150.0, 10.0, 0.0, 0.0, 300.0),
+            (100.0, 10.0)
+        );
+        // No higher than an earlier float, and below the ones it doesn't fit beside.
+        assert_eq!(
+            floats.place(Side::Right, 10.0, 10.0, 0.0, 0.0, 300.0),
+            (290.0, 20.0)
+        );
+        assert_eq!(floats.band(30.0, 0.0, 0.0, 300.0), (100.0, 300.0));
+        assert_eq!(floats.band(30.0, 30.0, 0.0, 300.0), (100.0, 300.0));
+        assert_eq!(floats.band(50.0, 0.0, 0.0, 300.0), (0.0, 300.0));
+    }
+
+    #[test]
+    fn test_clearance() {
+        let mut floats = FloatContext::default();
+        floats.place(Side::Left, 10.0, 50.0, 0.0, 0.0, 100.0);
+        floats.place(Side::Right, 10.0, 20.0, 0.0, 0.0, 100.0);
+        assert_eq!(floats.clearance(Clear::None), None);
+        assert_eq!(floats.clearance(Clear::Right), Some(20.0));
+        assert_eq!(floats.clearance(Clear::InlineStart), Some(50.0));
+        assert_eq!(floats.clearance(Clear::Both), Some(50.0));
+        assert_eq!(floats.bottom(), Some(50.0));
+        assert_eq!(floats.next_bottom(0.0), Some(20.0));
+    }
+}
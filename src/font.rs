@@ -0,0 +1,840 @@
+use std::{cmp::Ordering, fmt, fs, ops::Range, ops::RangeInclusive, path::Path, sync::Arc};
+
+use rustybuzz::{ttf_parser, UnicodeBuffer};
+
+use crate::{
+    at_rules::{FontFace, FontFaceStyle, FontSource},
+    computed::{ComputedStyle, FontFamily, FontStyle},
+    loader::{resolve_url, ResourceLoader},
+};
+
+/// The fonts bundled with the engine, which the generic families map to, so that text is laid
+/// out the same on machines without the fonts a document asks for.
+const SANS: &[u8] = include_bytes!("../fonts/DejaVuSans.ttf");
+const MONO: &[u8] = include_bytes!("../fonts/DejaVuSansMono.ttf");
+
+/// The families of the bundled fonts.
+const SANS_FAMILY: &str = "DejaVu Sans";
+const MONO_FAMILY: &str = "DejaVu Sans Mono";
+
+/// The `tech()` hints of `@font-face` sources that fonts can use.
+const SUPPORTED_TECH: [&str; 2] = ["features-opentype", "variations"];
+
+/// The contents of a font file, shared by the faces in it.
+type FontData = Arc<dyn AsRef<[u8]> + Send + Sync>;
+
+/// A font face, and the family, weights, style, widths and code points it is used for, which
+/// come from the font itself or from the `@font-face` rule that loaded it.
+#[derive(Clone)]
+pub struct Font {
+    data: FontData,
+    /// The index of the face in a font collection, or 0.
+    index: u32,
+    pub family: String,
+    /// The full name and PostScript name of the face, which `local()` sources refer to it by.
+    pub full_name: Option<String>,
+    pub postscript_name: Option<String>,
+    pub weight: (f32, f32),
+    pub style: FontFaceStyle,
+    /// The range of widths, as percentages.
+    pub stretch: (f32, f32),
+    pub unicode_range: Vec<RangeInclusive<u32>>,
+    /// The code points the face has glyphs for, as sorted ranges, read from its `cmap` table
+    /// once so that choosing fonts for text doesn't parse the face.
+    glyphs: Arc<[RangeInclusive<u32>]>,
+}
+
+/// Identifies a font in a `FontDatabase`.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct FontId(usize);
+
+/// The vertical metrics of a font at some size, in px.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct FontMetrics {
+    /// How far the font extends above the baseline.
+    pub ascent: f32,
+    /// How far the font extends below the baseline.
+    pub descent: f32,
+    /// The space the font recommends between lines.
+    pub line_gap: f32,
+    pub x_height: f32,
+}
+
+/// Text shaped into glyphs, in runs set in one font each.
+#[derive(Clone, Default, PartialEq, Debug)]
+pub struct ShapedText {
+    pub runs: Vec<GlyphRun>,
+    /// The total advance of the glyphs.
+    pub width: f32,
+}
+
+/// A sequence of glyphs from one font.
+#[derive(Clone, PartialEq, Debug)]
+pub struct GlyphRun {
+    pub font: FontId,
+    /// The font size in px.
+    pub size: f32,
+    /// The byte range of the text the glyphs are for.
+    pub range: Range<usize>,
+    pub glyphs: Vec<Glyph>,
+}
+
+/// A glyph positioned relative to the start of the text on the baseline, in px.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Glyph {
+    pub id: u16,
+    /// The byte offset in the text of the cluster of characters the glyph is for. A ligature
+    /// is one glyph for several characters, and a character may need several glyphs.
+    pub cluster: usize,
+    pub x: f32,
+    /// How far the glyph is below the baseline.
+    pub y: f32,
+    /// How far the pen moves after the glyph.
+    pub advance: f32,
+}
+
+impl fmt::Debug for Font {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Font")
+            .field("family", &self.family)
+            .field("full_name", &self.full_name)
+            .field("weight", &self.weight)
+            .field("style", &self.style)
+            .field("stretch", &self.stretch)
+            .finish_non_exhaustive()
+    }
+}
+
+impl Font {
+    /// Returns the faces in the font file or collection `data`, described by their own names and
+    /// properties.
+    pub fn load(data: FontData) -> Result<Vec<Font>, &'static str> {
+        let bytes = (*data).as_ref();
+        let count = ttf_parser::fonts_in_collection(bytes).unwrap_or(1);
+        let fonts = (0..count)
+            .filter_map(|index| Font::from_face(data.clone(), index).ok())
+            .collect::<Vec<_>>();
+        match fonts.is_empty() {
+            true => Err("not a TrueType or OpenType font"),
+            false => Ok(fonts),
+        }
+    }
+
+    /// Reads the face at `index` in `data`.
+    fn from_face(data: FontData, index: u32) -> Result<Font, &'static str> {
+        let face = ttf_parser::Face::parse((*data).as_ref(), index)
+            .map_err(|_| "not a TrueType or OpenType font")?;
+        let name = |ids: &[u16]| {
+            ids.iter().find_map(|&id| {
+                face.names()
+                    .into_iter()
+                    .filter(|name| name.name_id == id)
+                    .find_map(|name| name.to_string())
+            })
+        };
+        let family = name(&[
+            ttf_parser::name_id::TYPOGRAPHIC_FAMILY,
+            ttf_parser::name_id::FAMILY,
+        ])
+        .ok_or("font without a family name")?;
+        let weight = face.weight().to_number() as f32;
+        let style = match face.style() {
+            ttf_parser::Style::Normal => FontFaceStyle::Normal,
+            ttf_parser::Style::Italic => FontFaceStyle::Italic,
+            // The angle isn't recorded, so the face covers the default angle of `oblique`.
+            ttf_parser::Style::Oblique => FontFaceStyle::Oblique(14.0, 14.0),
+        };
+        let stretch = match face.width().to_number() {
+            1 => 50.0,
+            2 => 62.5,
+            3 => 75.0,
+            4 => 87.5,
+            6 => 112.5,
+            7 => 125.0,
+            8 => 150.0,
+            9 => 200.0,
+            _ => 100.0,
+        };
+        let glyphs = coverage(&face).into();
+        Ok(Font {
+            full_name: name(&[ttf_parser::name_id::FULL_NAME]),
+            postscript_name: name(&[ttf_parser::name_id::POST_SCRIPT_NAME]),
+            data,
+            index,
+            family,
+            weight: (weight, weight),
+            style,
+            stretch: (stretch, stretch),
+            unicode_range: vec![0..=0x10FFFF],
+            glyphs,
+        })
+    }
+
+    /// Parses the face. Fonts are only created from data that parses.
+    fn face(&self) -> Option<ttf_parser::Face<'_>> {
+        ttf_parser::Face::parse((*self.data).as_ref(), self.index).ok()
+    }
+
+    /// Returns the vertical metrics of the font at `size` px.
+    pub fn metrics(&self, size: f32) -> FontMetrics {
+        let Some(face) = self.face() else {
+            return FontMetrics {
+                ascent: 0.8 * size,
+                descent: 0.2 * size,
+                line_gap: 0.0,
+                x_height: 0.5 * size,
+            };
+        };
+        let scale = size / face.units_per_em() as f32;
+        FontMetrics {
+            ascent: face.ascender() as f32 * scale,
+            descent: -face.descender() as f32 * scale,
+            line_gap: face.line_gap() as f32 * scale,
+            x_height: face
+                .x_height()
+                .map_or(0.5 * size, |x_height| x_height as f32 * scale),
+        }
+    }
+
+    /// Returns true if the font is used for `c` and has a glyph for it.
+    fn covers(&self, c: char) -> bool {
+        let c = c as u32;
+        let i = self.glyphs.partition_point(|range| *range.end() < c);
+        self.unicode_range.iter().any(|range| range.contains(&c))
+            && self.glyphs.get(i).is_some_and(|range| range.contains(&c))
+    }
+}
+
+impl ShapedText {
+    /// Appends `other`, which was shaped from text starting at byte `offset` of this one's.
+    pub fn append(&mut self, other: ShapedText, offset: usize) {
+        let x = self.width;
+        self.runs.extend(other.runs.into_iter().map(|mut run| {
+            run.range = run.range.start + offset..run.range.end + offset;
+            for glyph in &mut run.glyphs {
+                glyph.cluster += offset;
+                glyph.x += x;
+            }
+            run
+        }));
+        self.width += other.width;
+    }
+
+    /// Changes the total advance of the glyphs of the cluster at byte `cluster` to `advance`,
+    /// moving the glyphs after them.
+    pub fn set_advance(&mut self, cluster: usize, advance: f32) {
+        let glyphs = self.runs.iter_mut().flat_map(|run| &mut run.glyphs);
+        let mut delta = None;
+        for glyph in glyphs {
+            match delta {
+                Some(delta) => glyph.x += delta,
+                None if glyph.cluster == cluster => {
+                    delta = Some(advance - glyph.advance);
+                    glyph.advance = advance;
+                }
+                None => {}
+            }
+        }
+        self.width += delta.unwrap_or(0.0);
+    }
+
+    /// Returns the glyphs of the clusters starting in the byte range `range`, positioned
+    /// relative to its start as if it had been shaped on its own.
+    pub fn slice(&self, range: Range<usize>) -> ShapedText {
+        let mut sliced = ShapedText::default();
+        let mut pen = 0.0;
+        let mut start = None;
+        for run in &self.runs {
+            let mut glyphs = Vec::new();
+            for glyph in &run.glyphs {
+                if range.contains(&glyph.cluster) {
+                    let start = *start.get_or_insert(pen);
+                    glyphs.push(Glyph {
+                        cluster: glyph.cluster - range.start,
+                        x: glyph.x - start,
+                        ..*glyph
+                    });
+                    sliced.width += glyph.advance;
+                }
+                pen += glyph.advance;
+            }
+            if !glyphs.is_empty() {
+                let clamp = |offset: usize| offset.clamp(range.start, range.end) - range.start;
+                sliced.runs.push(GlyphRun {
+                    range: clamp(run.range.start)..clamp(run.range.end),
+                    glyphs,
+                    ..*run
+                });
+            }
+        }
+        sliced
+    }
+
+    /// Returns the total advance of the glyphs of the cluster at byte `cluster`.
+    pub fn advance(&self, cluster: usize) -> f32 {
+        self.glyphs()
+            .filter(|(_, glyph)| glyph.cluster == cluster)
+            .map(|(_, glyph)| glyph.advance)
+            .sum()
+    }
+
+    /// Returns the glyphs in order, with the font and size of their runs.
+    pub fn glyphs(&self) -> impl Iterator<Item = (&GlyphRun, &Glyph)> {
+        self.runs
+            .iter()
+            .flat_map(|run| run.glyphs.iter().map(move |glyph| (run, glyph)))
+    }
+}
+
+/// The fonts available to a document: the bundled fonts, local fonts that were loaded and those
+/// of its `@font-face` rules.
+#[derive(Clone)]
+pub struct FontDatabase {
+    fonts: Vec<Font>,
+}
+
+impl Default for FontDatabase {
+    /// Returns a database with just the bundled fonts.
+    fn default() -> FontDatabase {
+        let mut database = FontDatabase { fonts: Vec::new() };
+        for data in [SANS, MONO] {
+            // The bundled fonts are known to parse.
+            if let Ok(fonts) = Font::load(Arc::new(data)) {
+                database.fonts.extend(fonts);
+            }
+        }
+        database
+    }
+}
+
+impl FontDatabase {
+    /// Returns the font with the given ID.
+    pub fn font(&self, id: FontId) -> &Font {
+        &self.fonts[id.0]
+    }
+
+    /// Adds `font`, returning its ID.
+    pub fn add(&mut self, font: Font) -> FontId {
+        self.fonts.push(font);
+        FontId(self.fonts.len() - 1)
+    }
+
+    /// Loads the faces in the font file at `path`.
+    pub fn load_file(&mut self, path: &Path) -> Result<(), &'static str> {
+        let data = fs::read(path).map_err(|_| "failed to read font file")?;
+        self.fonts.extend(Font::load(Arc::new(data))?);
+        Ok(())
+    }
+
+    /// Loads the TrueType and OpenType fonts in the directory at `path` and its subdirectories.
+    /// Files that fail to load are skipped.
+    pub fn load_directory(&mut self, path: &Path) {
+        let Ok(entries) = fs::read_dir(path) else {
+            return;
+        };
+        for path in entries.flatten().map(|entry| entry.path()) {
+            let extension = path.extension().and_then(|extension| extension.to_str());
+            match extension.map(str::to_ascii_lowercase).as_deref() {
+                _ if path.is_dir() => self.load_directory(&path),
+                Some("ttf" | "otf" | "ttc" | "otc") => {
+                    let _ = self.load_file(&path);
+                }
+                _ => {}
+            }
+        }
+    }
+
+    /// Loads the fonts of `font_faces`, the `@font-face` rules of the stylesheet at `base_url`,
+    /// through `loader`. Each uses its first source that loads, and is registered with the
+    /// rule's family and descriptors. Rules none of whose sources load are ignored.
+    pub fn add_font_faces(
+        &mut self,
+        font_faces: &[FontFace],
+        base_url: &str,
+        loader: &dyn ResourceLoader,
+    ) {
+        for font_face in font_faces {
+            let font = font_face.sources.iter().find_map(|source| match source {
+                FontSource::Url { url, format, tech } => {
+                    let supported = format.as_deref().is_none_or(|format| {
+                        matches!(
+                            &*format.to_ascii_lowercase(),
+                            "truetype" | "opentype" | "collection"
+                        )
+                    }) && tech
+                        .iter()
+                        .all(|tech| SUPPORTED_TECH.contains(&tech.as_str()));
+                    if !supported {
+                        return None;
+                    }
+                    let data = loader.load_bytes(&resolve_url(base_url, url)).ok()?;
+                    Font::load(Arc::new(data)).ok()?.into_iter().next()
+                }
+                FontSource::Local(name) => self.local(name).cloned(),
+            });
+            if let Some(font) = font {
+                self.add(Font {
+                    family: font_face.family.clone(),
+                    weight: font_face.weight,
+                    style: font_face.style.clone(),
+                    stretch: font_face.stretch,
+                    unicode_range: font_face.unicode_range.clone(),
+                    ..font
+                });
+            }
+        }
+    }
+
+    /// Returns the loaded face with the given full name or PostScript name.
+    fn local(&self, name: &str) -> Option<&Font> {
+        let matches = |other: &Option<String>| {
+            other
+                .as_deref()
+                .is_some_and(|other| other.eq_ignore_ascii_case(name))
+        };
+        self.fonts
+            .iter()
+            .find(|font| matches(&font.full_name) || matches(&font.postscript_name))
+    }
+
+    /// Returns the fonts for text with the given style, in order of preference: the face that
+    /// best matches the style in each family of `font-family` that has any, as in CSS Fonts 4
+    /// § 5.2, followed by the bundled font for the family's generic kind.
+    pub fn select(&self, style: &ComputedStyle) -> Vec<FontId> {
+        let mut fonts = Vec::new();
+        for family in style.font_family().iter() {
+            if let Some(font) = self.match_family(family_name(family), style) {
+                if !fonts.contains(&font) {
+                    fonts.push(font);
+                }
+            }
+        }
+        let generic = match style.font_family().contains(&FontFamily::Monospace) {
+            true => MONO_FAMILY,
+            false => SANS_FAMILY,
+        };
+        if let Some(font) = self.match_family(generic, style) {
+            if !fonts.contains(&font) {
+                fonts.push(font);
+            }
+        }
+        fonts
+    }
+
+    /// Returns the font text with the given style takes its metrics from: the first one
+    /// `select` returns.
+    pub fn primary_font(&self, style: &ComputedStyle) -> FontId {
+        self.select(style).first().copied().unwrap_or(FontId(0))
+    }
+
+    /// Returns the face of `family` that best matches the width, style and weight of `style`.
+    fn match_family(&self, family: &str, style: &ComputedStyle) -> Option<FontId> {
+        let mut candidates = (0..self.fonts.len())
+            .filter(|&i| self.fonts[i].family.eq_ignore_ascii_case(family))
+            .collect::<Vec<_>>();
+
+        // Narrower widths are preferred for condensed text, and wider ones otherwise.
+        let stretch = style.font_stretch();
+        self.keep_best(&mut candidates, |font| {
+            range_distance(font.stretch, stretch, stretch <= 100.0)
+        });
+
+        let order = match style.font_style() {
+            FontStyle::Italic => [StyleKind::Italic, StyleKind::Oblique, StyleKind::Normal],
+            FontStyle::Oblique => [StyleKind::Oblique, StyleKind::Italic, StyleKind::Normal],
+            FontStyle::Normal => [StyleKind::Normal, StyleKind::Oblique, StyleKind::Italic],
+        };
+        self.keep_best(&mut candidates, |font| {
+            let kind = StyleKind::of(&font.style);
+            (
+                order.iter().position(|&other| other == kind).unwrap_or(0) as u8,
+                0.0,
+            )
+        });
+
+        let weight = style.font_weight() as f32;
+        self.keep_best(&mut candidates, |font| weight_distance(font.weight, weight));
+        candidates.first().map(|&i| FontId(i))
+    }
+
+    /// Keeps the candidates whose key is the lowest.
+    fn keep_best(&self, candidates: &mut Vec<usize>, key: impl Fn(&Font) -> (u8, f32)) {
+        let compare = |a: &(u8, f32), b: &(u8, f32)| a.0.cmp(&b.0).then(a.1.total_cmp(&b.1));
+        let Some(best) = candidates
+            .iter()
+            .map(|&i| key(&self.fonts[i]))
+            .min_by(compare)
+        else {
+            return;
+        };
+        candidates.retain(|&i| compare(&key(&self.fonts[i]), &best) == Ordering::Equal);
+    }
+
+    /// Shapes `text` with the fonts for `style`. Each character is set in the first of them that
+    /// has a glyph for it, or in any other font that does, or else in the primary font.
+    /// Characters without glyphs of their own, such as spaces and combining marks, stay in the
+    /// font of the character before them when it can set them.
+    pub fn shape(&self, text: &str, style: &ComputedStyle) -> ShapedText {
+        let mut fonts = self.select(style);
+        let primary = fonts.first().copied().unwrap_or(FontId(0));
+        let mut shaped = ShapedText::default();
+        let mut run: Option<(FontId, usize)> = None;
+        for (offset, c) in text.char_indices() {
+            let covers = |font: FontId| self.font(font).covers(c);
+            let font = match run {
+                Some((font, _)) if (c.is_whitespace() || is_mark(c)) && covers(font) => font,
+                _ => match fonts.iter().copied().find(|&font| covers(font)) {
+                    Some(font) => font,
+                    None => match (0..self.fonts.len()).map(FontId).find(|&font| covers(font)) {
+                        Some(font) => {
+                            fonts.push(font);
+                            font
+                        }
+                        None => primary,
+                    },
+                },
+            };
+            match run {
+                Some((current, _)) if current == font => {}
+                Some((current, start)) => {
+                    shaped.append(self.shape_run(current, &text[start..offset], style), start);
+                    run = Some((font, offset));
+                }
+                None => run = Some((font, offset)),
+            }
+        }
+        if let Some((font, start)) = run {
+            shaped.append(self.shape_run(font, &text[start..], style), start);
+        }
+        shaped
+    }
+
+    /// Shapes `text` in `font`, applying its kerning, ligatures and other default features.
+    fn shape_run(&self, id: FontId, text: &str, style: &ComputedStyle) -> ShapedText {
+        let size = style.font_size();
+        let font = self.font(id);
+        let Some(face) = rustybuzz::Face::from_slice((*font.data).as_ref(), font.index) else {
+            return ShapedText::default();
+        };
+        let scale = size / face.units_per_em() as f32;
+        let mut buffer = UnicodeBuffer::new();
+        buffer.push_str(text);
+        buffer.guess_segment_properties();
+        let output = rustybuzz::shape(&face, &[], buffer);
+
+        let mut x = 0.0;
+        let glyphs = output
+            .glyph_infos()
+            .iter()
+            .zip(output.glyph_positions())
+            .map(|(info, position)| {
+                let glyph = Glyph {
+                    id: info.glyph_id as u16,
+                    cluster: info.cluster as usize,
+                    x: x + position.x_offset as f32 * scale,
+                    y: -position.y_offset as f32 * scale,
+                    advance: position.x_advance as f32 * scale,
+                };
+                x += glyph.advance;
+                glyph
+            })
+            .collect();
+        ShapedText {
+            runs: vec![GlyphRun {
+                font: id,
+                size,
+                range: 0..text.len(),
+                glyphs,
+            }],
+            width: x,
+        }
+    }
+}
+
+/// Returns the code points `face` has glyphs for, as sorted ranges.
+fn coverage(face: &ttf_parser::Face) -> Vec<RangeInclusive<u32>> {
+    let mut code_points = Vec::new();
+    if let Some(cmap) = face.tables().cmap {
+        for subtable in cmap
+            .subtables
+            .into_iter()
+            .filter(|subtable| subtable.is_unicode())
+        {
+            subtable.codepoints(|c| code_points.push(c));
+        }
+    }
+    code_points.sort_unstable();
+    code_points.dedup();
+    // Subtables may map code points to no glyph, and only the one `glyph_index` uses counts.
+    code_points.retain(|&c| char::from_u32(c).is_some_and(|c| face.glyph_index(c).is_some()));
+    let mut ranges: Vec<RangeInclusive<u32>> = Vec::new();
+    for c in code_points {
+        match ranges.last_mut() {
+            Some(range) if *range.end() + 1 == c => *range = *range.start()..=c,
+            _ => ranges.push(c..=c),
+        }
+    }
+    ranges
+}
+
+/// The kinds of `font-style`, without oblique angles.
+#[derive(Clone, Copy, PartialEq)]
+enum StyleKind {
+    Normal,
+    Italic,
+    Oblique,
+}
+
+impl StyleKind {
+    fn of(style: &FontFaceStyle) -> StyleKind {
+        match style {
+            FontFaceStyle::Normal => StyleKind::Normal,
+            FontFaceStyle::Italic => StyleKind::Italic,
+            FontFaceStyle::Oblique(..) => StyleKind::Oblique,
+        }
+    }
+}
+
+/// Returns the name of the family the database has faces of for `family`.
+fn family_name(family: &FontFamily) -> &str {
+    match family {
+        FontFamily::Named(name) => name,
+        FontFamily::Monospace => MONO_FAMILY,
+        _ => SANS_FAMILY,
+    }
+}
+
+/// Returns how well a face covering `range` matches `desired`, lower being better: faces
+/// covering it come first, then the closest ones on the preferred side, then the closest on the
+/// other.
+fn range_distance(range: (f32, f32), desired: f32, prefer_lower: bool) -> (u8, f32) {
+    let (low, high) = range;
+    if low <= desired && desired <= high {
+        (0, 0.0)
+    } else if high < desired {
+        (if prefer_lower { 1 } else { 2 }, desired - high)
+    } else {
+        (if prefer_lower { 2 } else { 1 }, low - desired)
+    }
+}
+
+/// Returns how well a face covering the weights `range` matches the weight `desired`, lower
+/// being better. Between 400 and 500, heavier weights up to 500 are tried first, then lighter
+/// ones, then those above 500.
+fn weight_distance(range: (f32, f32), desired: f32) -> (u8, f32) {
+    let (low, high) = range;
+    match desired {
+        400.0..=500.0 if low > desired && low <= 500.0 => (1, low - desired),
+        400.0..=500.0 if high < desired => (2, desired - high),
+        400.0..=500.0 if low > desired => (3, low - desired),
+        _ => range_distance(range, desired, desired < 400.0),
+    }
+}
+
+/// Returns true if `c` is a combining mark, which is set in the font of its base character.
+fn is_mark(c: char) -> bool {
+    matches!(c, '\u{0300}'..='\u{036F}' | '\u{1AB0}'..='\u{1AFF}' | '\u{20D0}'..='\u{20FF}')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        css::{self, Value},
+        loader::MemoryLoader,
+        style::PropertyMap,
+    };
+
+    fn style(declarations: &[(&str, &str)]) -> ComputedStyle {
+        let declarations = declarations
+            .iter()
+            .map(|&(name, value)| format!("{}: {};", name, value))
+            .collect::<String>();
+        let stylesheet = css::parse(format!("p {{ {} }}", declarations)).unwrap();
+        let specified = stylesheet.rules[0]
+            .declarations
+            .iter()
+            .map(|declaration| (declaration.name.clone(), declaration.value.clone()))
+            .collect::<PropertyMap>();
+        ComputedStyle::compute(&specified, Some(&ComputedStyle::default()))
+    }
+
+    fn families(database: &FontDatabase, style: &ComputedStyle) -> Vec<String> {
+        database
+            .select(style)
+            .into_iter()
+            .map(|font| database.font(font).family.clone())
+            .collect()
+    }
+
+    #[test]
+    fn test_bundled_fonts() {
+        let database = FontDatabase::default();
+        assert_eq!(families(&database, &style(&[])), [SANS_FAMILY]);
+        assert_eq!(
+            families(&database, &style(&[("font-family", "monospace")])),
+            [MONO_FAMILY]
+        );
+        assert_eq!(
+            families(
+                &database,
+                &style(&[("font-family", "Missing, monospace, serif")])
+            ),
+            [MONO_FAMILY, SANS_FAMILY]
+        );
+
+        let font = database.font(database.primary_font(&style(&[])));
+        assert_eq!(font.full_name.as_deref(), Some("DejaVu Sans"));
+        let metrics = font.metrics(2048.0);
+        assert_eq!((metrics.ascent, metrics.descent), (1901.0, 483.0));
+    }
+
+    #[test]
+    fn test_matches_weight_style_and_stretch() {
+        let mut database = FontDatabase::default();
+        let sans = database.font(FontId(0)).clone();
+        for (weight, style, stretch) in [
+            (300.0, FontFaceStyle::Normal, 100.0),
+            (400.0, FontFaceStyle::Normal, 100.0),
+            (700.0, FontFaceStyle::Normal, 100.0),
+            (400.0, FontFaceStyle::Italic, 100.0),
+            (400.0, FontFaceStyle::Normal, 75.0),
+        ] {
+            database.add(Font {
+                family: String::from("Test"),
+                weight: (weight, weight),
+                style,
+                stretch: (stretch, stretch),
+                ..sans.clone()
+            });
+        }
+        let matched = |declarations: &[(&str, &str)]| {
+            let mut declarations = declarations.to_vec();
+            declarations.push(("font-family", "Test"));
+            let font = database.font(database.primary_font(&style(&declarations)));
+            (font.weight.0, font.style.clone(), font.stretch.0)
+        };
+        assert_eq!(matched(&[]), (400.0, FontFaceStyle::Normal, 100.0));
+        assert_eq!(
+            matched(&[("font-weight", "bold")]),
+            (700.0, FontFaceStyle::Normal, 100.0)
+        );
+        // Lighter weights are preferred below 400, and heavier ones above 500.
+        assert_eq!(
+            matched(&[("font-weight", "350")]),
+            (300.0, FontFaceStyle::Normal, 100.0)
+        );
+        assert_eq!(
+            matched(&[("font-weight", "600")]),
+            (700.0, FontFaceStyle::Normal, 100.0)
+        );
+        // Between 400 and 500, lighter weights are preferred over those above 500.
+        assert_eq!(
+            matched(&[("font-weight", "450")]),
+            (400.0, FontFaceStyle::Normal, 100.0)
+        );
+        // Oblique falls back to italic, and width is matched before style and weight.
+        assert_eq!(
+            matched(&[("font-style", "oblique"), ("font-weight", "bold")]),
+            (400.0, FontFaceStyle::Italic, 100.0)
+        );
+        assert_eq!(
+            matched(&[("font-stretch", "semi-condensed"), ("font-style", "italic")]),
+            (400.0, FontFaceStyle::Normal, 75.0)
+        );
+    }
+
+    #[test]
+    fn test_loads_font_faces() {
+        let mut loader = MemoryLoader::default();
+        loader.insert_bytes("fonts/mono.ttf", MONO);
+        let stylesheet = css::parse(String::from(
+            "@font-face { font-family: Web; src: url(missing.woff2) format(woff2), \
+             url(mono.ttf); unicode-range: U+30-39; } \
+             @font-face { font-family: Local; src: local(\"DejaVu Sans Mono\"); \
+             font-weight: 700; }",
+        ))
+        .unwrap();
+        let mut database = FontDatabase::default();
+        database.add_font_faces(&stylesheet.font_faces, "fonts/style.css", &loader);
+
+        let web = style(&[("font-family", "Web")]);
+        assert_eq!(families(&database, &web), ["Web", SANS_FAMILY]);
+        // The font is only used for the digits in its unicode-range.
+        let shaped = database.shape("a1", &web);
+        let fonts = shaped
+            .runs
+            .iter()
+            .map(|run| database.font(run.font).family.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(fonts, [SANS_FAMILY, "Web"]);
+
+        let local = style(&[("font-family", "Local"), ("font-weight", "bold")]);
+        let font = database.font(database.primary_font(&local));
+        assert_eq!(
+            (font.family.as_str(), font.weight),
+            ("Local", (700.0, 700.0))
+        );
+    }
+
+    #[test]
+    fn test_shape() {
+        let database = FontDatabase::default();
+        let style = style(&[("font-size", "20px")]);
+        let width = |text: &str| database.shape(text, &style).width;
+        // Kerning brings the glyphs closer.
+        assert!(width("AV") < width("A") + width("V"));
+        // A ligature is one glyph for its cluster.
+        let ligature = database.shape("ffi", &style);
+        let glyphs = ligature
+            .glyphs()
+            .map(|(_, glyph)| glyph.cluster)
+            .collect::<Vec<_>>();
+        assert_eq!(glyphs, [0]);
+        let shaped = database.shape("ab c", &style);
+        let clusters = shaped
+            .glyphs()
+            .map(|(_, glyph)| glyph.cluster)
+            .collect::<Vec<_>>();
+        assert_eq!(clusters, [0, 1, 2, 3]);
+        let (run, last) = shaped.glyphs().last().unwrap();
+        assert_eq!(run.size, 20.0);
+        assert_eq!(last.x + last.advance, shaped.width);
+
+        // Characters the fonts for the style lack are taken from any font that has them.
+        let mono = ComputedStyle::compute(
+            &PropertyMap::from_iter([(
+                String::from("font-family"),
+                Value::String(String::from(MONO_FAMILY)),
+            )]),
+            None,
+        );
+        assert_eq!(database.shape("a", &mono).runs[0].font, FontId(1));
+        let shaped = database.shape("a\u{1C4}", &mono);
+        let fonts = shaped.runs.iter().map(|run| run.font).collect::<Vec<_>>();
+        assert_eq!(fonts, [FontId(1), FontId(0)]);
+    }
+
+    #[test]
+    fn test_set_advance() {
+        let database = FontDatabase::default();
+        let mut shaped = database.shape("a\tb", &ComputedStyle::default());
+        let width = shaped.width;
+        let tab = shaped.runs[0].glyphs[1];
+        shaped.set_advance(1, 100.0);
+        assert_eq!(shaped.width, width - tab.advance + 100.0);
+        assert_eq!(shaped.runs[0].glyphs[2].x, tab.x + 100.0);
+    }
+
+    #[test]
+    fn test_slice() {
+        let database = FontDatabase::default();
+        let style = ComputedStyle::default();
+        let shaped = database.shape("ab \u{3b1}\u{3b2}", &style);
+        // The slice is positioned as if it had been shaped on its own.
+        assert_eq!(shaped.slice(3..7), database.shape("\u{3b1}\u{3b2}", &style));
+        assert_eq!(shaped.slice(0..2), database.shape("ab", &style));
+        assert_eq!(shaped.slice(0..7), shaped);
+        assert_eq!(shaped.slice(2..2), ShapedText::default());
+    }
+}
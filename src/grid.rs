@@ -0,0 +1,1629 @@
+use std::{array, collections::HashMap, ops::Range, sync::Arc};
+
+use crate::{
+    computed::{
+        ComputedStyle, ContentAlignment, DisplayInside, GridAutoFlow, GridLine, ItemAlignment,
+        LengthPercentage, LengthPercentageAuto, Overflow, RepeatCount, TemplateAreas, TrackBreadth,
+        TrackListEntry, TrackSize,
+    },
+    flex::distribute,
+    font::FontDatabase,
+    layout::{content_size, ContainingBlock, EdgeSizes, LayoutBox, Rect},
+};
+
+/// The explicit tracks of one axis of a grid, from `grid-template-rows` or
+/// `grid-template-columns` and `grid-template-areas`, with the names of the lines between them.
+struct ExplicitGrid {
+    sizes: Vec<TrackSize>,
+    /// Whether each track was repeated by `repeat(auto-fit, ...)`, so that it collapses if no
+    /// item is placed in it.
+    auto_fit: Vec<bool>,
+    /// The names of each line, of which there is one more than tracks.
+    names: Vec<Vec<Arc<str>>>,
+}
+
+/// The placement of a grid item in one axis: the lines it lies between, numbered from 0 at the
+/// start of the explicit grid, or the number of tracks it spans if it is placed automatically.
+#[derive(Clone, Copy, PartialEq, Debug)]
+enum Placement {
+    Definite(i32, i32),
+    Auto(i32),
+}
+
+/// The tracks of one axis of a grid once its items are placed, numbered from the start of the
+/// implicit grid, and the tracks each item spans.
+struct GridAxis {
+    sizes: Vec<TrackSize>,
+    collapsed: Vec<bool>,
+    spans: Vec<Range<usize>>,
+}
+
+/// A grid track being sized, as in CSS Grid 1 § 11.4. Percentages are resolved, or `auto` if
+/// the size of the grid container is indefinite.
+struct Track {
+    min: TrackBreadth,
+    max: TrackBreadth,
+    /// The limit of a `fit-content()` track.
+    fit_content: Option<f32>,
+    base: f32,
+    /// The growth limit, which is infinite until it is known.
+    limit: f32,
+    /// Whether the track is an empty `auto-fit` track, which is 0 with no gaps around it.
+    collapsed: bool,
+}
+
+/// The outer sizes a grid item contributes to the tracks it spans in one axis.
+struct Contribution {
+    tracks: Range<usize>,
+    /// The size from the item's minimum size, its min-content size if that is `auto`.
+    minimum: f32,
+    min_content: f32,
+    max_content: f32,
+}
+
+/// The margins of a grid item along one axis, or `None` while they are `auto`, and its padding
+/// and borders.
+#[derive(Clone, Copy)]
+struct AxisEdges {
+    margins: (Option<f32>, Option<f32>),
+    edges: f32,
+}
+
+impl LayoutBox<'_> {
+    /// Returns true if the box is a grid container, with `display: grid` or `inline-grid`.
+    pub(crate) fn is_grid_container(&self) -> bool {
+        self.style().display().inside() == Some(DisplayInside::Grid)
+    }
+
+    /// Lays out the children of a grid container as grid items, as in CSS Grid 1 §§ 8 to 11, in
+    /// `containing_block`, its content area. Its height there is the container's definite
+    /// height, if it has one, and `min_height` and `max_height` limit it otherwise. Returns the
+    /// height of the contents.
+    ///
+    /// Absolutely positioned children are given the start of the content area as their static
+    /// position. Baseline alignment falls back to start alignment.
+    pub(crate) fn layout_grid(
+        &mut self,
+        containing_block: ContainingBlock,
+        min_height: f32,
+        max_height: Option<f32>,
+        fonts: &FontDatabase,
+    ) -> f32 {
+        let rect = containing_block.rect;
+        let height = containing_block.height;
+        let LayoutBox {
+            box_type, children, ..
+        } = self;
+        let style = box_type.style();
+        let column_gap = style.column_gap().resolve(rect.width);
+        let row_gap = style.row_gap().resolve(height.unwrap_or(0.0));
+        let justify_content = style.justify_content();
+        let align_content = style.align_content();
+        let justify_items = style.justify_items();
+        let align_items = style.align_items();
+        let available = [Some(rect.width), height.or(max_height)];
+        let clamp_height = |height: f32| {
+            max_height
+                .map_or(height, |max| height.min(max))
+                .max(min_height)
+        };
+
+        let mut items = Vec::new();
+        for child in children {
+            if child.is_absolutely_positioned() {
+                child.set_static_position(rect.x, rect.y);
+                continue;
+            }
+            items.push(child);
+        }
+        // A stable sort keeps items with the same `order` in document order.
+        items.sort_by_key(|item| item.style().order());
+        let lines = items
+            .iter()
+            .map(|item| grid_lines(item.style()))
+            .collect::<Vec<_>>();
+        let [columns, rows] = place_grid(style, &lines, available, [column_gap, row_gap]);
+        let alignments = items
+            .iter()
+            .map(|item| {
+                let style = item.style();
+                let justify = match style.justify_self() {
+                    ItemAlignment::Auto => justify_items,
+                    alignment => alignment,
+                };
+                let align = match style.align_self() {
+                    ItemAlignment::Auto => align_items,
+                    alignment => alignment,
+                };
+                [justify, align].map(|alignment| match alignment {
+                    ItemAlignment::Normal => ItemAlignment::Stretch,
+                    alignment => alignment,
+                })
+            })
+            .collect::<Vec<_>>();
+
+        // Size the columns, which the width of the container is definite for.
+        let mut column_tracks = columns.tracks(Some(rect.width));
+        let contributions = items
+            .iter()
+            .zip(&columns.spans)
+            .map(|(item, span)| item.column_contribution(span.clone(), fonts))
+            .collect::<Vec<_>>();
+        let stretch = |alignment| {
+            matches!(
+                alignment,
+                ContentAlignment::Normal | ContentAlignment::Stretch
+            )
+        };
+        size_tracks(
+            &mut column_tracks,
+            &contributions,
+            column_gap,
+            (Some(rect.width), 0.0, None),
+            stretch(justify_content),
+        );
+        let column_positions = track_positions(
+            &column_tracks,
+            column_gap,
+            rect.x,
+            rect.width,
+            justify_content,
+        );
+
+        // Size the rows, to which the items contribute their heights at the widths of their
+        // areas.
+        let mut row_tracks = rows.tracks(height);
+        let contributions = items
+            .iter_mut()
+            .zip(columns.spans.iter().zip(&rows.spans))
+            .zip(&alignments)
+            .map(|((item, (columns, rows)), &[justify, align])| {
+                let (x, width) = area(&column_tracks, &column_positions, columns.clone());
+                let area = ContainingBlock {
+                    rect: Rect {
+                        x,
+                        y: rect.y,
+                        width,
+                        height: 0.0,
+                    },
+                    height: None,
+                };
+                item.layout_grid_item(area, justify, align, fonts);
+                item.row_contribution(rows.clone(), width)
+            })
+            .collect::<Vec<_>>();
+        size_tracks(
+            &mut row_tracks,
+            &contributions,
+            row_gap,
+            (height, min_height, max_height),
+            stretch(align_content),
+        );
+        let grid_height = height.unwrap_or_else(|| clamp_height(used_size(&row_tracks, row_gap)));
+        let row_positions =
+            track_positions(&row_tracks, row_gap, rect.y, grid_height, align_content);
+
+        for (item, ((columns, rows), &[justify, align])) in items
+            .iter_mut()
+            .zip(columns.spans.iter().zip(&rows.spans).zip(&alignments))
+        {
+            let (x, width) = area(&column_tracks, &column_positions, columns.clone());
+            let (y, height) = area(&row_tracks, &row_positions, rows.clone());
+            let area = ContainingBlock {
+                rect: Rect {
+                    x,
+                    y,
+                    width,
+                    height,
+                },
+                height: Some(height),
+            };
+            item.layout_grid_item(area, justify, align, fonts);
+            item.apply_relative_offset(area);
+        }
+        grid_height
+    }
+
+    /// Lays out a grid item in its grid area `containing_block`, aligned in it by `justify` and
+    /// `align`. If the height of the area isn't known yet, the item is laid out at the top of the
+    /// area with an `auto` height, for its height to be measured.
+    fn layout_grid_item(
+        &mut self,
+        containing_block: ContainingBlock,
+        justify: ItemAlignment,
+        align: ItemAlignment,
+        fonts: &FontDatabase,
+    ) {
+        let rect = containing_block.rect;
+        let style = self.style();
+        let horizontal = AxisEdges::horizontal(style, rect.width);
+        let vertical = AxisEdges::vertical(style, rect.width);
+
+        // An item fills the width of its area if it is stretched, and otherwise fits its
+        // contents, with auto margins taking up the free space before alignment does.
+        let size = content_size(style.box_sizing(), horizontal.edges);
+        let available = rect.width - horizontal.outer();
+        let auto_margins = horizontal.margins.0.is_none() || horizontal.margins.1.is_none();
+        let width = match style.width().resolve(rect.width) {
+            Some(width) => size(width),
+            None if justify == ItemAlignment::Stretch && !auto_margins => available,
+            None => {
+                let (min_content, max_content) = self.intrinsic_widths(fonts);
+                available.min(max_content).max(min_content)
+            }
+        };
+        let min_width = style.min_width().resolve(rect.width).map_or(0.0, size);
+        let max_width = style
+            .max_width()
+            .resolve(rect.width)
+            .map_or(f32::INFINITY, size);
+        let width = width.min(max_width).max(min_width);
+        let free = available - width;
+        let (left, right, x) = align_in_area(horizontal.margins, free, justify);
+
+        let height = match (containing_block.height, vertical.margins) {
+            (Some(height), (Some(_), Some(_)))
+                if align == ItemAlignment::Stretch
+                    && style.height() == LengthPercentageAuto::Auto =>
+            {
+                Some(height - vertical.outer())
+            }
+            _ => None,
+        };
+        let margin = EdgeSizes {
+            left,
+            right,
+            top: vertical.margins.0.unwrap_or(0.0),
+            bottom: vertical.margins.1.unwrap_or(0.0),
+        };
+        self.layout_item(
+            containing_block,
+            (rect.x + x, rect.y),
+            margin,
+            (width, height),
+            fonts,
+        );
+        if containing_block.height.is_some() {
+            let free = rect.height - self.dimensions.margin_box().height;
+            let (top, bottom, y) = align_in_area(vertical.margins, free, align);
+            self.dimensions.margin.top = top;
+            self.dimensions.margin.bottom = bottom;
+            self.translate(0.0, top - margin.top + y);
+        }
+    }
+
+    /// Returns the contribution of a grid item spanning `tracks` to the sizes of the columns.
+    fn column_contribution(&self, tracks: Range<usize>, fonts: &FontDatabase) -> Contribution {
+        let style = self.style();
+        let (min_content, max_content) = self.intrinsic_outer_widths(fonts);
+        let edges = AxisEdges::horizontal(style, 0.0);
+        let size = content_size(style.box_sizing(), edges.edges);
+        Contribution {
+            tracks,
+            minimum: minimum_contribution(style.min_width(), style, min_content, edges, size),
+            min_content,
+            max_content,
+        }
+    }
+
+    /// Returns the contribution of a grid item spanning `tracks` to the sizes of the rows, once
+    /// it is laid out in an area `width` wide.
+    fn row_contribution(&self, tracks: Range<usize>, width: f32) -> Contribution {
+        let style = self.style();
+        let height = self.dimensions.margin_box().height;
+        let edges = AxisEdges::vertical(style, width);
+        let size = content_size(style.box_sizing(), edges.edges);
+        Contribution {
+            tracks,
+            minimum: minimum_contribution(style.min_height(), style, height, edges, size),
+            min_content: height,
+            max_content: height,
+        }
+    }
+
+    /// Returns the first baseline of a grid container, which is that of the first item with one
+    /// among the items at the top of its first row.
+    pub(crate) fn grid_first_baseline(&self) -> Option<f32> {
+        let mut items = self
+            .children
+            .iter()
+            .filter(|child| !child.is_absolutely_positioned())
+            .collect::<Vec<_>>();
+        items.sort_by_key(|item| item.style().order());
+        let top = items
+            .iter()
+            .map(|item| item.dimensions.margin_box().y)
+            .fold(f32::INFINITY, f32::min);
+        items
+            .into_iter()
+            .filter(|item| item.dimensions.margin_box().y == top)
+            .find_map(LayoutBox::first_baseline)
+    }
+
+    /// Returns the min-content and max-content widths of the contents of a grid container: the
+    /// widths of its columns sized under a min-content and a max-content constraint. Tracks
+    /// repeated to fill the container are repeated once.
+    pub(crate) fn grid_intrinsic_widths(&self, fonts: &FontDatabase) -> (f32, f32) {
+        let style = self.style();
+        let gap = style.column_gap().resolve(0.0);
+        let mut items = self
+            .children
+            .iter()
+            .filter(|child| !child.is_absolutely_positioned())
+            .collect::<Vec<_>>();
+        items.sort_by_key(|item| item.style().order());
+        let lines = items
+            .iter()
+            .map(|item| grid_lines(item.style()))
+            .collect::<Vec<_>>();
+        let [columns, _] = place_grid(style, &lines, [None, None], [gap, 0.0]);
+        let contributions = items
+            .iter()
+            .zip(&columns.spans)
+            .map(|(item, span)| item.column_contribution(span.clone(), fonts))
+            .collect::<Vec<_>>();
+
+        let mut tracks = columns.tracks(None);
+        resolve_intrinsic_sizes(&mut tracks, &contributions, gap);
+        let min_content = used_size(&tracks, gap);
+        size_tracks(&mut tracks, &contributions, gap, (None, 0.0, None), false);
+        (min_content, used_size(&tracks, gap))
+    }
+}
+
+impl ExplicitGrid {
+    /// Expands the track list `list` of one axis, where `repeat(auto-fill)` and
+    /// `repeat(auto-fit)` repeat their tracks as many times as fit in `available` space with
+    /// `gap` between tracks, or once if it is indefinite. Named areas in `areas`, along the
+    /// columns if `columns` and the rows otherwise, add tracks sized by `auto_sizes` if the list
+    /// has fewer, and name the lines at their edges.
+    fn new(
+        list: &[TrackListEntry],
+        auto_sizes: &[TrackSize],
+        areas: Option<&TemplateAreas>,
+        columns: bool,
+        available: Option<f32>,
+        gap: f32,
+    ) -> ExplicitGrid {
+        let repetitions = auto_repetitions(list, available, gap);
+        let mut grid = ExplicitGrid {
+            sizes: Vec::new(),
+            auto_fit: Vec::new(),
+            names: vec![Vec::new()],
+        };
+        for entry in list {
+            match entry {
+                TrackListEntry::Repeat(count, entries) => {
+                    let (count, auto_fit) = match *count {
+                        RepeatCount::Count(count) => (count, false),
+                        RepeatCount::AutoFill => (repetitions, false),
+                        RepeatCount::AutoFit => (repetitions, true),
+                    };
+                    for _ in 0..count {
+                        for entry in entries.iter() {
+                            grid.push(entry, auto_fit);
+                        }
+                    }
+                }
+                entry => grid.push(entry, false),
+            }
+        }
+
+        let Some(areas) = areas else {
+            return grid;
+        };
+        let count = match columns {
+            true => areas.columns,
+            false => areas.rows.len(),
+        };
+        for size in auto_sizes
+            .iter()
+            .cycle()
+            .take(count.saturating_sub(grid.sizes.len()))
+        {
+            grid.push(&TrackListEntry::Track(*size), false);
+        }
+        for area in &areas.areas {
+            let (start, end) = match columns {
+                true => area.columns,
+                false => area.rows,
+            };
+            let name = &area.name;
+            grid.names[start as usize - 1].push(Arc::from(format!("{}-start", name)));
+            grid.names[end as usize - 1].push(Arc::from(format!("{}-end", name)));
+        }
+        grid
+    }
+
+    /// Adds the line names or track of `entry`, which isn't a `repeat()`, to the grid.
+    fn push(&mut self, entry: &TrackListEntry, auto_fit: bool) {
+        match entry {
+            TrackListEntry::LineNames(names) => {
+                // `names` always has a last line.
+                let line = self.names.last_mut().unwrap();
+                line.extend(names.iter().cloned());
+            }
+            TrackListEntry::Track(size) => {
+                self.sizes.push(*size);
+                self.auto_fit.push(auto_fit);
+                self.names.push(Vec::new());
+            }
+            TrackListEntry::Repeat(..) => (),
+        }
+    }
+
+    /// Returns the explicit lines named `name`, in order.
+    fn lines_named(&self, name: &str) -> Vec<i32> {
+        (0..self.names.len() as i32)
+            .filter(|&line| self.names[line as usize].iter().any(|n| &**n == name))
+            .collect()
+    }
+
+    /// Returns the `n`th line named `name`, counting from the end if `n` is negative. All the
+    /// implicit lines are taken to have the name if there aren't enough explicit ones.
+    fn named_line(&self, name: &str, n: i32) -> i32 {
+        let lines = self.lines_named(name);
+        let count = lines.len() as i32;
+        let last = self.sizes.len() as i32;
+        match n > 0 {
+            true if count >= n => lines[n as usize - 1],
+            true => last + n - count,
+            false if count >= -n => lines[(count + n) as usize],
+            false => -(-n - count),
+        }
+    }
+
+    /// Returns the line `n` lines named `name` away from `line`, towards the end if `forward`.
+    /// All the implicit lines are taken to have the name if there aren't enough explicit ones.
+    fn span_to(&self, name: &str, n: i32, line: i32, forward: bool) -> i32 {
+        let lines = self.lines_named(name);
+        let last = self.sizes.len() as i32;
+        match forward {
+            true => {
+                let after = lines.into_iter().filter(|&l| l > line).collect::<Vec<_>>();
+                match after.get(n as usize - 1) {
+                    Some(&l) => l,
+                    None => line.max(last) + n - after.len() as i32,
+                }
+            }
+            false => {
+                let before = lines
+                    .into_iter()
+                    .filter(|&l| l < line)
+                    .rev()
+                    .collect::<Vec<_>>();
+                match before.get(n as usize - 1) {
+                    Some(&l) => l,
+                    None => line.min(0) - (n - before.len() as i32),
+                }
+            }
+        }
+    }
+
+    /// Returns the line `line` refers to at the start of an item, or at its end if `end`, or
+    /// `None` for `auto` and spans.
+    fn line(&self, line: &GridLine, end: bool) -> Option<i32> {
+        let count = self.sizes.len() as i32;
+        match line {
+            GridLine::Auto | GridLine::Span(..) => None,
+            GridLine::Line(n, None) if *n > 0 => Some(n - 1),
+            GridLine::Line(n, None) => Some(count + 1 + n),
+            GridLine::Line(n, Some(name)) => Some(self.named_line(name, *n)),
+            // A name refers to the edge of the area with that name, through the lines named
+            // after it, or else to the first line with the name.
+            GridLine::Name(name) => {
+                let edge = format!("{}-{}", name, if end { "end" } else { "start" });
+                match self.lines_named(&edge).first() {
+                    Some(&line) => Some(line),
+                    None => Some(self.named_line(name, 1)),
+                }
+            }
+        }
+    }
+
+    /// Resolves the placement of an item between the lines `start` and `end`, as in CSS Grid 1
+    /// § 8.3.1.
+    fn placement(&self, start: &GridLine, end: &GridLine) -> Placement {
+        match (self.line(start, false), self.line(end, true)) {
+            (Some(start), Some(end)) if start == end => Placement::Definite(start, start + 1),
+            (Some(start), Some(end)) => Placement::Definite(start.min(end), start.max(end)),
+            (Some(start), None) => match end {
+                GridLine::Span(n, None) => Placement::Definite(start, start + n),
+                GridLine::Span(n, Some(name)) => {
+                    Placement::Definite(start, self.span_to(name, *n, start, true))
+                }
+                _ => Placement::Definite(start, start + 1),
+            },
+            (None, Some(end)) => match start {
+                GridLine::Span(n, None) => Placement::Definite(end - n, end),
+                GridLine::Span(n, Some(name)) => {
+                    Placement::Definite(self.span_to(name, *n, end, false), end)
+                }
+                _ => Placement::Definite(end - 1, end),
+            },
+            // A named span of an automatically placed item spans one track.
+            (None, None) => match (start, end) {
+                (GridLine::Span(n, None), _) => Placement::Auto(*n),
+                (GridLine::Span(..), _) => Placement::Auto(1),
+                (_, GridLine::Span(n, None)) => Placement::Auto(*n),
+                _ => Placement::Auto(1),
+            },
+        }
+    }
+}
+
+impl GridAxis {
+    /// Returns the tracks of the axis to be sized, resolving percentages against `reference`.
+    fn tracks(&self, reference: Option<f32>) -> Vec<Track> {
+        self.sizes
+            .iter()
+            .zip(&self.collapsed)
+            .map(|(&size, &collapsed)| Track::new(size, reference, collapsed))
+            .collect()
+    }
+}
+
+impl Track {
+    fn new(size: TrackSize, reference: Option<f32>, collapsed: bool) -> Track {
+        let resolve = |breadth| match (breadth, reference) {
+            (TrackBreadth::Percent(percent), Some(reference)) => {
+                TrackBreadth::Px(reference * percent / 100.0)
+            }
+            (TrackBreadth::Percent(_), None) => TrackBreadth::Auto,
+            (breadth, _) => breadth,
+        };
+        let (min, max) = match collapsed {
+            true => (TrackBreadth::Px(0.0), TrackBreadth::Px(0.0)),
+            false => (resolve(size.min()), resolve(size.max())),
+        };
+        let fit_content = match (size, reference) {
+            _ if collapsed => None,
+            (TrackSize::FitContent(LengthPercentage::Percent(_)), None) => None,
+            (TrackSize::FitContent(limit), reference) => {
+                Some(limit.resolve(reference.unwrap_or(0.0)))
+            }
+            _ => None,
+        };
+        let base = match min {
+            TrackBreadth::Px(px) => px,
+            _ => 0.0,
+        };
+        let limit = match max {
+            TrackBreadth::Px(px) => px.max(base),
+            _ => f32::INFINITY,
+        };
+        Track {
+            min,
+            max,
+            fit_content,
+            base,
+            limit,
+            collapsed,
+        }
+    }
+
+    /// Returns the flex factor of a flexible track, or `None` for other tracks.
+    fn flex(&self) -> Option<f32> {
+        match self.max {
+            TrackBreadth::Fr(fr) => Some(fr),
+            _ => None,
+        }
+    }
+
+    fn intrinsic_min(&self) -> bool {
+        matches!(
+            self.min,
+            TrackBreadth::MinContent | TrackBreadth::MaxContent | TrackBreadth::Auto
+        )
+    }
+
+    fn intrinsic_max(&self) -> bool {
+        matches!(
+            self.max,
+            TrackBreadth::MinContent | TrackBreadth::MaxContent | TrackBreadth::Auto
+        )
+    }
+}
+
+impl AxisEdges {
+    /// Returns the horizontal edges of a box with `style`, resolving percentages against
+    /// `reference`, the width of its containing block.
+    fn horizontal(style: &ComputedStyle, reference: f32) -> AxisEdges {
+        AxisEdges {
+            margins: (
+                style.margin_left().resolve(reference),
+                style.margin_right().resolve(reference),
+            ),
+            edges: style.padding_left().resolve(reference)
+                + style.padding_right().resolve(reference)
+                + style.border_left_width()
+                + style.border_right_width(),
+        }
+    }
+
+    /// Returns the vertical edges of a box with `style`, resolving percentages against
+    /// `reference`, the width of its containing block.
+    fn vertical(style: &ComputedStyle, reference: f32) -> AxisEdges {
+        AxisEdges {
+            margins: (
+                style.margin_top().resolve(reference),
+                style.margin_bottom().resolve(reference),
+            ),
+            edges: style.padding_top().resolve(reference)
+                + style.padding_bottom().resolve(reference)
+                + style.border_top_width()
+                + style.border_bottom_width(),
+        }
+    }
+
+    /// Returns the sum of the margins, padding and borders, with `auto` margins as 0.
+    fn outer(&self) -> f32 {
+        let (start, end) = self.margins;
+        self.edges + start.unwrap_or(0.0) + end.unwrap_or(0.0)
+    }
+}
+
+/// Returns the lines a grid item with `style` is placed between in the column and row axes.
+fn grid_lines(style: &ComputedStyle) -> [(GridLine, GridLine); 2] {
+    [
+        (style.grid_column_start(), style.grid_column_end()),
+        (style.grid_row_start(), style.grid_row_end()),
+    ]
+}
+
+/// Builds the grid of a container with `style` whose items are placed between `lines` in the
+/// column and row axes, given the space `available` for the tracks of each axis and the `gaps`
+/// between them. Returns the tracks of the columns and of the rows, with the tracks each item
+/// spans.
+fn place_grid(
+    style: &ComputedStyle,
+    lines: &[[(GridLine, GridLine); 2]],
+    available: [Option<f32>; 2],
+    gaps: [f32; 2],
+) -> [GridAxis; 2] {
+    let areas = style.grid_template_areas();
+    let auto_sizes = [style.grid_auto_columns(), style.grid_auto_rows()];
+    let explicit = [
+        (style.grid_template_columns(), true),
+        (style.grid_template_rows(), false),
+    ];
+    let explicit: [ExplicitGrid; 2] = array::from_fn(|axis| {
+        let (list, columns) = &explicit[axis];
+        ExplicitGrid::new(
+            list,
+            &auto_sizes[axis],
+            areas.as_deref(),
+            *columns,
+            available[axis],
+            gaps[axis],
+        )
+    });
+    let mut placements = lines
+        .iter()
+        .map(|lines| {
+            array::from_fn(|axis| {
+                let (start, end) = &lines[axis];
+                explicit[axis].placement(start, end)
+            })
+        })
+        .collect::<Vec<_>>();
+    let counts = explicit.each_ref().map(|grid| grid.sizes.len() as i32);
+    let offsets = place_items(&mut placements, counts, style.grid_auto_flow());
+
+    array::from_fn(|axis| {
+        let explicit = &explicit[axis];
+        let auto_sizes = &auto_sizes[axis];
+        let offset = offsets[axis];
+        let spans = placements
+            .iter()
+            .map(|placement| match placement[axis] {
+                Placement::Definite(start, end) => start as usize..end as usize,
+                Placement::Auto(_) => unreachable!("placed items have definite placements"),
+            })
+            .collect::<Vec<_>>();
+        let count = spans
+            .iter()
+            .map(|span| span.end)
+            .fold(offset + explicit.sizes.len(), usize::max);
+        // Implicit tracks repeat the auto sizes away from the explicit grid on either side.
+        let sizes = (0..count)
+            .map(|track| match track.checked_sub(offset) {
+                Some(i) if i < explicit.sizes.len() => explicit.sizes[i],
+                Some(i) => auto_sizes[(i - explicit.sizes.len()) % auto_sizes.len()],
+                None => {
+                    let before = offset - 1 - track;
+                    auto_sizes[auto_sizes.len() - 1 - before % auto_sizes.len()]
+                }
+            })
+            .collect();
+        let collapsed = (0..count)
+            .map(|track| {
+                let auto_fit = track
+                    .checked_sub(offset)
+                    .and_then(|i| explicit.auto_fit.get(i).copied())
+                    .unwrap_or(false);
+                auto_fit && !spans.iter().any(|span| span.contains(&track))
+            })
+            .collect();
+        GridAxis {
+            sizes,
+            collapsed,
+            spans,
+        }
+    })
+}
+
+/// Returns the number of times `repeat(auto-fill)` or `repeat(auto-fit)` in the track list
+/// `list` repeats its tracks: as many as fit in `available` space with `gap` between tracks
+/// sized by their fixed sizes, or once if that is indefinite, as in CSS Grid 1 § 7.2.3.2.
+fn auto_repetitions(list: &[TrackListEntry], available: Option<f32>, gap: f32) -> u32 {
+    let Some(available) = available else {
+        return 1;
+    };
+    let fixed = |size: TrackSize| {
+        let breadth = match size.max() {
+            max @ (TrackBreadth::Px(_) | TrackBreadth::Percent(_)) => max,
+            _ => size.min(),
+        };
+        match breadth {
+            TrackBreadth::Px(px) => px,
+            TrackBreadth::Percent(percent) => available * percent / 100.0,
+            _ => 0.0,
+        }
+    };
+    let (mut other_size, mut other_count) = (0.0, 0);
+    let (mut repeated_size, mut repeated_count) = (0.0, 0);
+    for entry in list {
+        match entry {
+            TrackListEntry::Track(size) => {
+                other_size += fixed(*size);
+                other_count += 1;
+            }
+            TrackListEntry::Repeat(RepeatCount::Count(count), entries) => {
+                for entry in entries.iter() {
+                    if let TrackListEntry::Track(size) = entry {
+                        other_size += fixed(*size) * *count as f32;
+                        other_count += *count;
+                    }
+                }
+            }
+            TrackListEntry::Repeat(_, entries) => {
+                for entry in entries.iter() {
+                    if let TrackListEntry::Track(size) = entry {
+                        repeated_size += fixed(*size);
+                        repeated_count += 1;
+                    }
+                }
+            }
+            TrackListEntry::LineNames(_) => (),
+        }
+    }
+    // The tracks and gaps fit if other_size + n * repeated_size + (other_count + n *
+    // repeated_count - 1) * gap <= available.
+    let repetition = repeated_size + repeated_count as f32 * gap;
+    let space = available - other_size - (other_count as f32 - 1.0) * gap;
+    match repetition > 0.0 {
+        true => ((space / repetition).floor() as u32).max(1),
+        false => 1,
+    }
+}
+
+/// Places grid items automatically as in CSS Grid 1 § 8.5, given their `placements` in the
+/// column and row axes, which are all definite afterwards, and the number of tracks in each
+/// axis of the explicit grid. Returns the number of implicit tracks before the explicit grid in
+/// each axis, from where the lines of the placements are numbered afterwards.
+fn place_items(
+    placements: &mut [[Placement; 2]],
+    explicit: [i32; 2],
+    flow: GridAutoFlow,
+) -> [usize; 2] {
+    // The auto-placement cursor moves along the `inner` axis, the columns for `row`, and wraps
+    // to the next track of the `outer` one.
+    let (inner, outer) = match flow {
+        GridAutoFlow::Row | GridAutoFlow::RowDense => (0, 1),
+        GridAutoFlow::Column | GridAutoFlow::ColumnDense => (1, 0),
+    };
+    let dense = matches!(flow, GridAutoFlow::RowDense | GridAutoFlow::ColumnDense);
+
+    // Number the lines from the start of the implicit grid.
+    let mut offsets = [0; 2];
+    for (axis, offset) in offsets.iter_mut().enumerate() {
+        let start = placements
+            .iter()
+            .filter_map(|placement| match placement[axis] {
+                Placement::Definite(start, _) => Some(start),
+                Placement::Auto(_) => None,
+            })
+            .fold(0, i32::min);
+        *offset = -start;
+        for placement in placements.iter_mut() {
+            if let Placement::Definite(start, end) = &mut placement[axis] {
+                *start += *offset;
+                *end += *offset;
+            }
+        }
+    }
+
+    let area = |placement: &[Placement; 2]| match *placement {
+        [Placement::Definite(a, b), Placement::Definite(c, d)] => Some([(a, b), (c, d)]),
+        _ => None,
+    };
+    let mut occupied = placements.iter().filter_map(area).collect::<Vec<_>>();
+    let overlaps = |occupied: &[[(i32, i32); 2]], area: [(i32, i32); 2]| {
+        occupied.iter().any(|other| {
+            (0..2).all(|axis| other[axis].0 < area[axis].1 && area[axis].0 < other[axis].1)
+        })
+    };
+    let place = |occupied: &mut Vec<_>, placement: &mut [Placement; 2], area: [(i32, i32); 2]| {
+        occupied.push(area);
+        *placement = area.map(|(start, end)| Placement::Definite(start, end));
+    };
+
+    // Place the items locked to a track of the outer axis, after the items placed in it before
+    // unless the placement is dense.
+    let mut cursors = HashMap::new();
+    for placement in placements.iter_mut() {
+        let (Placement::Auto(span), Placement::Definite(start, end)) =
+            (placement[inner], placement[outer])
+        else {
+            continue;
+        };
+        let mut position = match dense {
+            true => 0,
+            false => cursors.get(&start).copied().unwrap_or(0),
+        };
+        let mut area = [(0, 0); 2];
+        area[outer] = (start, end);
+        loop {
+            area[inner] = (position, position + span);
+            if !overlaps(&occupied, area) {
+                break;
+            }
+            position += 1;
+        }
+        cursors.insert(start, position + span);
+        place(&mut occupied, placement, area);
+    }
+
+    // The implicit grid has enough tracks in the inner axis for the definite placements and the
+    // widest automatic span.
+    let inner_count = placements
+        .iter()
+        .map(|placement| match placement[inner] {
+            Placement::Definite(_, end) => end,
+            Placement::Auto(span) => span,
+        })
+        .fold(explicit[inner] + offsets[inner], i32::max);
+
+    let mut cursor = [0; 2];
+    for placement in placements.iter_mut() {
+        let mut area = [(0, 0); 2];
+        match (placement[inner], placement[outer]) {
+            (_, Placement::Definite(..)) => continue,
+            (Placement::Definite(start, end), Placement::Auto(span)) => {
+                if dense {
+                    cursor[outer] = 0;
+                } else if start < cursor[inner] {
+                    cursor[outer] += 1;
+                }
+                cursor[inner] = start;
+                area[inner] = (start, end);
+                loop {
+                    area[outer] = (cursor[outer], cursor[outer] + span);
+                    if !overlaps(&occupied, area) {
+                        break;
+                    }
+                    cursor[outer] += 1;
+                }
+            }
+            (Placement::Auto(inner_span), Placement::Auto(outer_span)) => {
+                if dense {
+                    cursor = [0; 2];
+                }
+                loop {
+                    if cursor[inner] + inner_span > inner_count {
+                        cursor[inner] = 0;
+                        cursor[outer] += 1;
+                        continue;
+                    }
+                    area[inner] = (cursor[inner], cursor[inner] + inner_span);
+                    area[outer] = (cursor[outer], cursor[outer] + outer_span);
+                    if !overlaps(&occupied, area) {
+                        break;
+                    }
+                    cursor[inner] += 1;
+                }
+            }
+        }
+        place(&mut occupied, placement, area);
+    }
+    offsets.map(|offset| offset as usize)
+}
+
+/// Returns the minimum contribution of a grid item with `style` whose minimum size in an axis is
+/// `min_size`, its min-content contribution is `min_content`, and whose `edges` along the axis
+/// turn a specified size into a content size with `size`. An `auto` minimum size is the
+/// min-content size unless the item's overflow isn't visible.
+fn minimum_contribution(
+    min_size: LengthPercentageAuto,
+    style: &ComputedStyle,
+    min_content: f32,
+    edges: AxisEdges,
+    size: impl Fn(f32) -> f32,
+) -> f32 {
+    match min_size {
+        LengthPercentageAuto::Px(px) => size(px) + edges.outer(),
+        LengthPercentageAuto::Auto if style.overflow() == Overflow::Visible => min_content,
+        _ => edges.outer(),
+    }
+}
+
+/// Returns the margins at the start and end of an axis of a grid item with `margins` and `free`
+/// space left in its area, and the offset of its margin box from the start of the area: `auto`
+/// margins take up the free space, and otherwise the item is aligned by `alignment`.
+fn align_in_area(
+    margins: (Option<f32>, Option<f32>),
+    free: f32,
+    alignment: ItemAlignment,
+) -> (f32, f32, f32) {
+    let positive = free.max(0.0);
+    match margins {
+        (None, None) => (positive / 2.0, positive / 2.0, 0.0),
+        (None, Some(end)) => (positive, end, 0.0),
+        (Some(start), None) => (start, positive, 0.0),
+        (Some(start), Some(end)) => {
+            let offset = match alignment {
+                ItemAlignment::End | ItemAlignment::FlexEnd => free,
+                ItemAlignment::Center => free / 2.0,
+                _ => 0.0,
+            };
+            (start, end, offset)
+        }
+    }
+}
+
+/// Returns the number of gaps between the tracks that aren't collapsed.
+fn gap_count(tracks: &[Track]) -> usize {
+    tracks
+        .iter()
+        .filter(|track| !track.collapsed)
+        .count()
+        .saturating_sub(1)
+}
+
+/// Returns the sum of the base sizes of `tracks` and the gaps of size `gap` between them.
+fn used_size(tracks: &[Track], gap: f32) -> f32 {
+    tracks.iter().map(|track| track.base).sum::<f32>() + gap * gap_count(tracks) as f32
+}
+
+/// Returns the start of each of `tracks`, with `gap` between them, aligned by `alignment` in a
+/// grid `size` long from `start`.
+fn track_positions(
+    tracks: &[Track],
+    gap: f32,
+    start: f32,
+    size: f32,
+    alignment: ContentAlignment,
+) -> Vec<f32> {
+    let free = size - used_size(tracks, gap);
+    let count = tracks.iter().filter(|track| !track.collapsed).count();
+    let (offset, spacing) = distribute(alignment, free, count);
+    let mut position = start + offset;
+    let mut remaining = count;
+    tracks
+        .iter()
+        .map(|track| {
+            let track_start = position;
+            position += track.base;
+            if !track.collapsed {
+                remaining -= 1;
+                if remaining > 0 {
+                    position += gap + spacing;
+                }
+            }
+            track_start
+        })
+        .collect()
+}
+
+/// Returns the start and size of the area spanning `span` of `tracks`, which start at
+/// `positions`.
+fn area(tracks: &[Track], positions: &[f32], span: Range<usize>) -> (f32, f32) {
+    let start = positions[span.start];
+    let last = span.end - 1;
+    (start, positions[last] + tracks[last].base - start)
+}
+
+/// Sizes the tracks of one axis of a grid as in CSS Grid 1 § 11.3, given the `contributions`
+/// of the items and the `gap` between tracks. `space` is the available space, if definite, and
+/// the minimum and maximum sizes of the grid container. Tracks with an `auto` maximum are
+/// stretched to fill the free space if `stretch`.
+fn size_tracks(
+    tracks: &mut [Track],
+    contributions: &[Contribution],
+    gap: f32,
+    space: (Option<f32>, f32, Option<f32>),
+    stretch: bool,
+) {
+    let (available, min_size, max_size) = space;
+    resolve_intrinsic_sizes(tracks, contributions, gap);
+
+    // Maximize the tracks, up to the available space if it is definite, and otherwise up to the
+    // maximum size of the container.
+    let indices = (0..tracks.len()).collect::<Vec<_>>();
+    match available.or(max_size) {
+        Some(space) => {
+            let free = space - used_size(tracks, gap);
+            if free > 0.0 {
+                grow_equally(tracks, &indices, free, false, |track| track.limit);
+            }
+        }
+        None => {
+            for track in tracks.iter_mut() {
+                track.base = track.limit;
+            }
+        }
+    }
+
+    expand_flexible_tracks(tracks, contributions, gap, (available, min_size, max_size));
+
+    if stretch {
+        let free = available.unwrap_or(min_size) - used_size(tracks, gap);
+        let auto = (0..tracks.len())
+            .filter(|&i| tracks[i].max == TrackBreadth::Auto && !tracks[i].collapsed)
+            .collect::<Vec<_>>();
+        if free > 0.0 && !auto.is_empty() {
+            grow_equally(tracks, &auto, free, false, |_| f32::INFINITY);
+        }
+    }
+}
+
+/// Resolves the base sizes and growth limits of intrinsically sized tracks from the
+/// `contributions` of the items spanning them, as in CSS Grid 1 § 11.5, with `gap` between the
+/// tracks.
+fn resolve_intrinsic_sizes(tracks: &mut [Track], contributions: &[Contribution], gap: f32) {
+    let flexible = |tracks: &[Track], span: &Range<usize>| {
+        tracks[span.clone()]
+            .iter()
+            .any(|track| track.flex().is_some())
+    };
+
+    // Items spanning a single track that isn't flexible size it directly.
+    for contribution in contributions {
+        let span = &contribution.tracks;
+        if span.len() != 1 || flexible(tracks, span) {
+            continue;
+        }
+        let track = &mut tracks[span.start];
+        track.base = match track.min {
+            TrackBreadth::MinContent => track.base.max(contribution.min_content),
+            TrackBreadth::MaxContent => track.base.max(contribution.max_content),
+            TrackBreadth::Auto => track.base.max(contribution.minimum),
+            _ => track.base,
+        };
+        let limit = match track.max {
+            TrackBreadth::MinContent => contribution.min_content,
+            TrackBreadth::MaxContent | TrackBreadth::Auto => match track.fit_content {
+                Some(fit_content) => contribution.max_content.min(fit_content),
+                None => contribution.max_content,
+            },
+            _ => continue,
+        };
+        track.limit = match track.limit.is_finite() {
+            true => track.limit.max(limit),
+            false => limit,
+        };
+    }
+    for track in tracks.iter_mut() {
+        track.limit = track.limit.max(track.base);
+    }
+
+    // Items spanning several tracks, none of them flexible, distribute their contributions
+    // among the tracks, from the items spanning the fewest tracks.
+    let mut spanning = contributions
+        .iter()
+        .filter(|contribution| {
+            contribution.tracks.len() > 1 && !flexible(tracks, &contribution.tracks)
+        })
+        .collect::<Vec<_>>();
+    spanning.sort_by_key(|contribution| contribution.tracks.len());
+    for contribution in spanning {
+        let span = contribution.tracks.clone();
+        distribute_extra_space(
+            tracks,
+            span.clone(),
+            contribution.minimum,
+            gap,
+            false,
+            |track| track.intrinsic_min(),
+        );
+        distribute_extra_space(
+            tracks,
+            span.clone(),
+            contribution.min_content,
+            gap,
+            false,
+            |track| {
+                matches!(
+                    track.min,
+                    TrackBreadth::MinContent | TrackBreadth::MaxContent
+                )
+            },
+        );
+        distribute_extra_space(
+            tracks,
+            span.clone(),
+            contribution.max_content,
+            gap,
+            false,
+            |track| track.min == TrackBreadth::MaxContent,
+        );
+        for track in &mut tracks[span.clone()] {
+            track.limit = track.limit.max(track.base);
+        }
+        distribute_extra_space(
+            tracks,
+            span.clone(),
+            contribution.min_content,
+            gap,
+            true,
+            |track| track.intrinsic_max(),
+        );
+        distribute_extra_space(tracks, span, contribution.max_content, gap, true, |track| {
+            matches!(track.max, TrackBreadth::MaxContent | TrackBreadth::Auto)
+        });
+    }
+
+    // Items spanning flexible tracks distribute their minimum contributions among those of them
+    // with an intrinsic minimum.
+    for contribution in contributions {
+        let span = contribution.tracks.clone();
+        if flexible(tracks, &span) {
+            distribute_extra_space(tracks, span, contribution.minimum, gap, false, |track| {
+                track.flex().is_some() && track.intrinsic_min()
+            });
+        }
+    }
+
+    for track in tracks.iter_mut() {
+        if track.limit.is_infinite() {
+            track.limit = track.base;
+        }
+    }
+}
+
+/// Grows the base sizes, or the growth limits if `limits`, of the tracks in `span` that are
+/// `affected` so that the tracks and the gaps between them add up to at least `size`, as in CSS
+/// Grid 1 § 11.5.1. The space is shared equally, up to the growth limits of the base sizes or
+/// the limits of `fit-content()` tracks first.
+fn distribute_extra_space(
+    tracks: &mut [Track],
+    span: Range<usize>,
+    size: f32,
+    gap: f32,
+    limits: bool,
+    affected: impl Fn(&Track) -> bool,
+) {
+    let spanned = &tracks[span.clone()];
+    let used = spanned
+        .iter()
+        .map(|track| match limits && track.limit.is_finite() {
+            true => track.limit,
+            false => track.base,
+        })
+        .sum::<f32>()
+        + gap * gap_count(spanned) as f32;
+    let space = size - used;
+    let indices = span
+        .filter(|&i| affected(&tracks[i]) && !tracks[i].collapsed)
+        .collect::<Vec<_>>();
+    if space <= 0.0 || indices.is_empty() {
+        return;
+    }
+    let left = grow_equally(tracks, &indices, space, limits, |track| match limits {
+        true => track.fit_content.unwrap_or(f32::INFINITY),
+        false => track.limit,
+    });
+    if left > 0.0 {
+        grow_equally(tracks, &indices, left, limits, |_| f32::INFINITY);
+    }
+}
+
+/// Grows the base sizes, or the growth limits if `limits`, of the tracks at `indices` equally
+/// by `space` in total, freezing each track when it reaches its `cap`. An infinite growth limit
+/// grows from the base size. Returns the space that is left over.
+fn grow_equally(
+    tracks: &mut [Track],
+    indices: &[usize],
+    mut space: f32,
+    limits: bool,
+    cap: impl Fn(&Track) -> f32,
+) -> f32 {
+    let size = |track: &Track| match limits && track.limit.is_finite() {
+        true => track.limit,
+        false => track.base,
+    };
+    let mut increases = vec![0.0; indices.len()];
+    loop {
+        let growable = (0..indices.len())
+            .filter(|&i| {
+                let track = &tracks[indices[i]];
+                size(track) + increases[i] < cap(track) - EPSILON
+            })
+            .collect::<Vec<_>>();
+        if space <= EPSILON || growable.is_empty() {
+            break;
+        }
+        let share = space / growable.len() as f32;
+        for i in growable {
+            let track = &tracks[indices[i]];
+            let increase = share.min(cap(track) - size(track) - increases[i]);
+            increases[i] += increase;
+            space -= increase;
+        }
+    }
+    for (&i, increase) in indices.iter().zip(increases) {
+        let track = &mut tracks[i];
+        let grown = size(track) + increase;
+        match limits {
+            true => track.limit = grown,
+            false => track.base = grown,
+        }
+    }
+    space.max(0.0)
+}
+
+/// The tolerance below which free space is taken to be used up.
+const EPSILON: f32 = 0.001;
+
+/// Expands the flexible tracks to their share of the free space, as in CSS Grid 1 § 11.7. If the
+/// available space in `space` is indefinite, the share of an `fr` is found from the
+/// contributions of the items spanning the flexible tracks, and then limited by the minimum
+/// and maximum sizes of the container.
+fn expand_flexible_tracks(
+    tracks: &mut [Track],
+    contributions: &[Contribution],
+    gap: f32,
+    space: (Option<f32>, f32, Option<f32>),
+) {
+    if tracks.iter().all(|track| track.flex().is_none()) {
+        return;
+    }
+    let (available, min_size, max_size) = space;
+    let gaps = gap * gap_count(tracks) as f32;
+    let all = 0..tracks.len();
+    let fraction = match available {
+        Some(available) => fr_size(tracks, all, available - gaps),
+        None => {
+            let tracks = &*tracks;
+            let fraction = tracks
+                .iter()
+                .filter_map(|track| {
+                    let flex = track.flex()?;
+                    Some(match flex > 1.0 {
+                        true => track.base / flex,
+                        false => track.base,
+                    })
+                })
+                .chain(contributions.iter().filter_map(|contribution| {
+                    let span = contribution.tracks.clone();
+                    let spanned = &tracks[span.clone()];
+                    spanned.iter().any(|track| track.flex().is_some()).then(|| {
+                        let gaps = gap * gap_count(spanned) as f32;
+                        fr_size(tracks, span, contribution.max_content - gaps)
+                    })
+                }))
+                .fold(0.0, f32::max);
+            let size = tracks
+                .iter()
+                .map(|track| match track.flex() {
+                    Some(flex) => track.base.max(fraction * flex),
+                    None => track.base,
+                })
+                .sum::<f32>()
+                + gaps;
+            match max_size {
+                Some(max) if size > max => fr_size(tracks, all, max - gaps),
+                _ if size < min_size => fr_size(tracks, all, min_size - gaps),
+                _ => fraction,
+            }
+        }
+    };
+    for track in tracks.iter_mut() {
+        if let Some(flex) = track.flex() {
+            track.base = track.base.max(fraction * flex);
+            track.limit = track.limit.max(track.base);
+        }
+    }
+}
+
+/// Returns the size of an `fr` that fills `space` with the tracks in `span`, as in CSS Grid 1
+/// § 11.7.1. Flexible tracks whose base sizes are larger than their share are treated as
+/// inflexible.
+fn fr_size(tracks: &[Track], span: Range<usize>, space: f32) -> f32 {
+    let tracks = &tracks[span];
+    let mut inflexible = tracks
+        .iter()
+        .map(|track| track.flex().is_none())
+        .collect::<Vec<_>>();
+    loop {
+        let leftover = space
+            - tracks
+                .iter()
+                .zip(&inflexible)
+                .filter(|(_, &inflexible)| inflexible)
+                .map(|(track, _)| track.base)
+                .sum::<f32>();
+        let flex = tracks
+            .iter()
+            .zip(&inflexible)
+            .filter(|(_, &inflexible)| !inflexible)
+            .filter_map(|(track, _)| track.flex())
+            .sum::<f32>();
+        // Flex factors that add up to less than 1 take only that fraction of the space.
+        let fraction = leftover / flex.max(1.0);
+        let mut changed = false;
+        for (track, inflexible) in tracks.iter().zip(&mut inflexible) {
+            if let Some(flex) = track.flex().filter(|_| !*inflexible) {
+                if fraction * flex < track.base {
+                    *inflexible = true;
+                    changed = true;
+                }
+            }
+        }
+        if !changed {
+            return fraction.max(0.0);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        layout::tests::{border_boxes, with_monospace_layout, CH},
+        position::StackingContext,
+    };
+
+    /// Returns the x and y of the border boxes of the children of `layout_box`.
+    fn positions(layout_box: &LayoutBox) -> Vec<(f32, f32)> {
+        border_boxes(layout_box)
+            .into_iter()
+            .map(|(x, y, _, _)| (x, y))
+            .collect()
+    }
+
+    #[test]
+    fn test_fixed_and_flexible_tracks() {
+        let css = "div { display: grid; grid-template-columns: 100px 1fr 3fr; column-gap: 10px; } \
+                   p { height: 20px; }";
+        with_monospace_layout(
+            "<html><div><p></p><p></p><p></p></div></html>",
+            css,
+            |root| {
+                let div = &root.children[0];
+                assert_eq!(
+                    border_boxes(div),
+                    [
+                        (0.0, 0.0, 100.0, 20.0),
+                        (110.0, 0.0, 170.0, 20.0),
+                        (290.0, 0.0, 510.0, 20.0)
+                    ]
+                );
+                assert_eq!(div.dimensions.content.height, 20.0);
+            },
+        );
+
+        // An item's min-content width is the minimum of an `fr` track, unless the track has
+        // another minimum.
+        let css = "div { display: grid; width: 100px; } .a { grid-template-columns: 1fr 1fr; } \
+                   .b { grid-template-columns: minmax(0, 1fr) 1fr; }";
+        let html = "<html><div class=\"a\"><p>xxxxxxxxxxxxxxxxxxxx</p><p></p></div>\
+                    <div class=\"b\"><p>xxxxxxxxxxxxxxxxxxxx</p><p></p></div></html>";
+        with_monospace_layout(html, css, |root| {
+            let widths = |div: &LayoutBox| {
+                border_boxes(div)
+                    .into_iter()
+                    .map(|(_, _, width, _)| width)
+                    .collect::<Vec<_>>()
+            };
+            assert_eq!(widths(&root.children[0]), [20.0 * CH, 0.0]);
+            assert_eq!(widths(&root.children[1]), [50.0, 50.0]);
+        });
+    }
+
+    #[test]
+    fn test_intrinsic_tracks() {
+        let css = "div { display: grid; grid-template-columns: minmax(50px, 100px) auto \
+                   fit-content(30px) min-content max-content; }";
+        let html = "<html><div><p></p><p>xx xx</p><p>xx xx xx xx</p><p>xx xx</p><p>xx xx</p>\
+                    </div></html>";
+        with_monospace_layout(html, css, |root| {
+            let widths = border_boxes(&root.children[0])
+                .into_iter()
+                .map(|(_, _, width, _)| width)
+                .collect::<Vec<_>>();
+            // The `auto` track is stretched to fill the free space once the others have grown
+            // to their limits.
+            assert_eq!(widths, [100.0, 670.0 - 7.0 * CH, 30.0, 2.0 * CH, 5.0 * CH]);
+        });
+
+        // Rows with `fr` sizes in a grid of indefinite height are sized so that each item fits.
+        let css = "div { display: grid; grid-template-rows: 1fr 2fr; } p { height: 10px; }";
+        with_monospace_layout("<html><div><p></p><p></p></div></html>", css, |root| {
+            let div = &root.children[0];
+            assert_eq!(positions(div), [(0.0, 0.0), (0.0, 10.0)]);
+            assert_eq!(div.dimensions.content.height, 30.0);
+        });
+    }
+
+    #[test]
+    fn test_repeat_to_fill() {
+        let css = "div { display: grid; column-gap: 10px; justify-content: center; } \
+                   .fill { grid-template-columns: repeat(auto-fill, 100px); } \
+                   .fit { grid-template-columns: repeat(auto-fit, 100px); } \
+                   .flexible { grid-template-columns: repeat(auto-fit, minmax(100px, 1fr)); }";
+        let html = "<html><div class=\"fill\"><p></p><p></p></div>\
+                    <div class=\"fit\"><p></p><p></p></div>\
+                    <div class=\"flexible\"><p></p><p></p></div></html>";
+        with_monospace_layout(html, css, |root| {
+            // Seven tracks fit, of which `auto-fit` collapses the empty ones.
+            assert_eq!(positions(&root.children[0]), [(20.0, 0.0), (130.0, 0.0)]);
+            assert_eq!(positions(&root.children[1]), [(295.0, 0.0), (405.0, 0.0)]);
+            assert_eq!(
+                border_boxes(&root.children[2]),
+                [(0.0, 0.0, 395.0, 0.0), (405.0, 0.0, 395.0, 0.0)]
+            );
+        });
+    }
+
+    #[test]
+    fn test_line_placement() {
+        let css = "div { display: grid; grid-template-columns: [a] 100px [b] 100px [c] 100px [d]; \
+                   grid-template-rows: 50px 50px; } \
+                   #x { grid-column-start: b; grid-column-end: d; } \
+                   #y { grid-row-start: 2; grid-column-start: 3; } \
+                   #z { grid-column-start: -2; grid-row-start: span 2; } \
+                   #w { grid-column-end: a; grid-column-start: span 2; grid-row-start: 1; }";
+        let html = "<html><div><p id=\"x\"></p><p id=\"y\"></p><p id=\"z\"></p><p id=\"w\"></p>\
+                    </div></html>";
+        with_monospace_layout(html, css, |root| {
+            // `#w` ends at the first line, so two implicit columns are added before it, which
+            // take the free space as `auto` tracks.
+            assert_eq!(
+                border_boxes(&root.children[0]),
+                [
+                    (600.0, 0.0, 200.0, 50.0),
+                    (700.0, 50.0, 100.0, 50.0),
+                    (700.0, 100.0, 100.0, 0.0),
+                    (0.0, 0.0, 500.0, 50.0)
+                ]
+            );
+        });
+    }
+
+    #[test]
+    fn test_placement_shorthands() {
+        let css = "div { display: grid; grid-template-columns: repeat(3, 100px); \
+                   grid-template-rows: 50px 50px; } \
+                   #x { grid-column: 2 / 4; } #y { grid-area: 2 / 1 / 3 / 2; } \
+                   #z { grid-row: 2; grid-column: span 2 / -1; }";
+        let html = "<html><div><p id=\"x\"></p><p id=\"y\"></p><p id=\"z\"></p></div></html>";
+        with_monospace_layout(html, css, |root| {
+            assert_eq!(
+                border_boxes(&root.children[0]),
+                [
+                    (100.0, 0.0, 200.0, 50.0),
+                    (0.0, 50.0, 100.0, 50.0),
+                    (100.0, 50.0, 200.0, 50.0)
+                ]
+            );
+        });
+    }
+
+    #[test]
+    fn test_template_areas() {
+        let css = "div { display: grid; grid-template-areas: \"head head\" \"side main\"; \
+                   grid-template-columns: 100px 1fr; grid-template-rows: 30px 200px; } \
+                   #h { grid-area: head; } #s { grid-area: side; } #m { grid-area: main; } \
+                   #f { grid-column: main; grid-row-start: main-end; }";
+        let html = "<html><div><p id=\"m\"></p><p id=\"s\"></p><p id=\"h\"></p><p id=\"f\"></p>\
+                    </div></html>";
+        with_monospace_layout(html, css, |root| {
+            assert_eq!(
+                border_boxes(&root.children[0]),
+                [
+                    (100.0, 30.0, 700.0, 200.0),
+                    (0.0, 30.0, 100.0, 200.0),
+                    (0.0, 0.0, 800.0, 30.0),
+                    (100.0, 230.0, 700.0, 0.0)
+                ]
+            );
+        });
+    }
+
+    #[test]
+    fn test_auto_placement() {
+        let css = "div { display: grid; grid-template-columns: repeat(3, 100px); \
+                   grid-auto-rows: 10px; } \
+                   .dense { grid-auto-flow: dense; } .wide { grid-column-end: span 2; } \
+                   .column { grid-auto-flow: column; grid-template-rows: repeat(2, 10px); \
+                   grid-template-columns: none; grid-auto-columns: 50px; }";
+        let html = "<html><div><p class=\"wide\"></p><p class=\"wide\"></p><p></p></div>\
+                    <div class=\"dense\"><p class=\"wide\"></p><p class=\"wide\"></p><p></p></div>\
+                    <div class=\"column\"><p></p><p></p><p></p></div></html>";
+        with_monospace_layout(html, css, |root| {
+            // Sparse placement never goes back to fill the hole left in the first row.
+            assert_eq!(
+                positions(&root.children[0]),
+                [(0.0, 0.0), (0.0, 10.0), (200.0, 10.0)]
+            );
+            assert_eq!(
+                positions(&root.children[1]),
+                [(0.0, 20.0), (0.0, 30.0), (200.0, 20.0)]
+            );
+            assert_eq!(
+                positions(&root.children[2]),
+                [(0.0, 40.0), (0.0, 50.0), (50.0, 40.0)]
+            );
+        });
+    }
+
+    #[test]
+    fn test_alignment() {
+        let css = "div { display: grid; grid-template-columns: 100px 100px; \
+                   grid-template-rows: 50px; height: 100px; \
+                   justify-content: center; \
+                   align-content: end; justify-items: center; align-items: end; } \
+                   p { width: 20px; height: 10px; grid-row-start: 1; } \
+                   #a, #c { grid-column-start: 1; } #b { grid-column-start: 2; } \
+                   #b { justify-self: start; align-self: stretch; height: auto; } \
+                   #c { margin: auto; }";
+        let html = "<html><div><p id=\"a\"></p><p id=\"b\"></p><p id=\"c\"></p></div></html>";
+        with_monospace_layout(html, css, |root| {
+            assert_eq!(
+                border_boxes(&root.children[0]),
+                [
+                    (340.0, 90.0, 20.0, 10.0),
+                    (400.0, 50.0, 20.0, 50.0),
+                    (340.0, 70.0, 20.0, 10.0)
+                ]
+            );
+        });
+
+        let css = "div { display: grid; grid-template-columns: repeat(3, 100px); \
+                   justify-content: space-between; } \
+                   p { z-index: 1; }";
+        with_monospace_layout(
+            "<html><div><p></p><p></p><p></p></div></html>",
+            css,
+            |root| {
+                let div = &root.children[0];
+                assert_eq!(positions(div), [(0.0, 0.0), (350.0, 0.0), (700.0, 0.0)]);
+                // `z-index` applies to grid items, which aren't positioned.
+                let context = StackingContext::new(root);
+                assert_eq!(context.children.len(), 3);
+            },
+        );
+    }
+
+    #[test]
+    fn test_inline_grid() {
+        let css = "p { display: block; } \
+                   span { display: inline-grid; grid-template-columns: auto auto; \
+                   column-gap: 5px; }";
+        with_monospace_layout(
+            "<html><p>a<span><b>bc</b><i>def</i></span></p></html>",
+            css,
+            |root| {
+                let span = &root.children[0].children[1];
+                assert_eq!(span.dimensions.content.width, 5.0 * CH + 5.0);
+                let (b, i) = (&span.children[0], &span.children[1]);
+                assert_eq!(b.dimensions.content.x, CH);
+                assert_eq!(i.dimensions.content.x, 3.0 * CH + 5.0);
+                // The grid's baseline is that of its first row, which is the paragraph's.
+                assert_eq!(b.lines[0].baseline, root.children[0].lines[0].baseline);
+            },
+        );
+    }
+}
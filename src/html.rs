@@ -1,10 +1,49 @@
-use std::collections::HashMap;
+use std::{collections::HashMap, mem};
 
 use crate::{
-    dom::{elem, text, AttrMap, Node},
+    dom::{comment, doctype, elem, processing_instruction, text, AttrMap, Node, NodeType},
     parser::Parser,
 };
 
+/// The elements that make up the structure of a table. Inside a table, anything else is moved out
+/// in front of it.
+const TABLE_ELEMENTS: [&str; 9] = [
+    "caption", "colgroup", "col", "tbody", "thead", "tfoot", "tr", "td", "th",
+];
+
+/// The elements whose end tag may be omitted: they are closed by the end tag of an ancestor, by a
+/// start tag that cannot be their child, or by the end of input.
+const OPTIONAL_END_TAGS: [&str; 8] = [
+    "caption", "colgroup", "tbody", "thead", "tfoot", "tr", "td", "th",
+];
+
+/// Returns whether the children of `parent` are table structure, with other content fostered out
+/// of the table.
+fn is_table_context(parent: &str) -> bool {
+    matches!(parent, "table" | "tbody" | "thead" | "tfoot" | "tr")
+}
+
+/// Returns whether a start tag named `tag` implicitly closes an open `parent` element.
+fn implicitly_closes(parent: &str, tag: &str) -> bool {
+    match parent {
+        "td" | "th" => matches!(
+            tag,
+            "td" | "th" | "tr" | "tbody" | "thead" | "tfoot" | "caption" | "col" | "colgroup"
+        ),
+        "tr" => matches!(
+            tag,
+            "tr" | "tbody" | "thead" | "tfoot" | "caption" | "col" | "colgroup"
+        ),
+        "tbody" | "thead" | "tfoot" => matches!(
+            tag,
+            "tbody" | "thead" | "tfoot" | "caption" | "col" | "colgroup"
+        ),
+        "caption" => TABLE_ELEMENTS.contains(&tag),
+        "colgroup" => tag != "col",
+        _ => false,
+    }
+}
+
 /// A struct representing a parser for HTML.
 #[derive(Debug, PartialEq)]
 struct HtmlParser {
@@ -12,6 +51,8 @@ struct HtmlParser {
     current_position: usize,
     /// The input string being parsed.
     input: String,
+    /// Nodes misplaced inside the table being parsed, to be inserted in front of the table.
+    fostered: Vec<Node>,
 }
 
 impl Parser for HtmlParser {
@@ -28,7 +69,6 @@ impl Parser for HtmlParser {
     }
 }
 
-// TODO: Implement comment parsing
 impl HtmlParser {
     /// Parses the tag name from the input stream.
     ///
@@ -46,12 +86,50 @@ impl HtmlParser {
     /// with an error message on failure.
     fn parse_node(&mut self) -> Result<Node, &'static str> {
         match self.next_char() {
+            Ok('<') if self.starts_with(b"<!--")? => self.parse_comment(),
+            Ok('<') if self.starts_with(b"<!")? => self.parse_doctype(),
+            Ok('<') if self.starts_with(b"<?")? => self.parse_processing_instruction(),
             Ok('<') => self.parse_element(),
             Ok(_) => self.parse_text(),
             Err(_) => Err("Failed to get next character"),
         }
     }
 
+    /// Parses a comment, e.g. `<!-- note -->`. An unterminated comment runs to the end of input.
+    fn parse_comment(&mut self) -> Result<Node, &'static str> {
+        self.set_current_position(self.current_position() + "<!--".len());
+        let start = self.current_position();
+        let end = self.input()[start..]
+            .find("-->")
+            .map_or(self.input().len(), |i| start + i);
+        let data = self.input()[start..end].to_string();
+        self.set_current_position((end + "-->".len()).min(self.input().len()));
+        Ok(comment(data))
+    }
+
+    /// Parses a doctype, e.g. `<!DOCTYPE html>`, keeping only its name.
+    fn parse_doctype(&mut self) -> Result<Node, &'static str> {
+        self.set_current_position(self.current_position() + "<!".len());
+        let keyword = self.parse_tag_name()?;
+        if !keyword.eq_ignore_ascii_case("doctype") {
+            return Err("Expected a doctype");
+        }
+        self.consume_whitespace()?;
+        let name = self.parse_tag_name()?.to_ascii_lowercase();
+        self.consume_while(|c| c != '>')?;
+        self.consume_char()?; // Consume '>'
+        Ok(doctype(name))
+    }
+
+    /// Parses a processing instruction, e.g. `<?xml version="1.0"?>`.
+    fn parse_processing_instruction(&mut self) -> Result<Node, &'static str> {
+        self.set_current_position(self.current_position() + "<?".len());
+        let data = self.consume_while(|c| c != '>')?;
+        self.consume_char()?; // Consume '>'
+        let data = data.strip_suffix('?').unwrap_or(&data).to_string();
+        Ok(processing_instruction(data))
+    }
+
     /// Parses the text content of an HTML node.
     ///
     /// # Returns
@@ -74,8 +152,30 @@ impl HtmlParser {
         let (tag_name, attrs) = self.parse_opening_tag()?;
         let tag_name = tag_name.to_ascii_lowercase();
 
+        // `col` is a void element; tolerate a redundant end tag.
+        if tag_name == "col" {
+            if self.starts_with_end_tag("col")? {
+                self.parse_closing_tag()?;
+            }
+            return Ok(elem(tag_name, attrs, Vec::new()));
+        }
+
+        self.parse_element_contents(tag_name, attrs)
+    }
+
+    /// Parses the children and end tag of an element whose start tag has been parsed, or which is
+    /// implied by the table structure.
+    fn parse_element_contents(
+        &mut self,
+        tag_name: String,
+        attrs: AttrMap,
+    ) -> Result<Node, &'static str> {
         // Contents
-        let children = self.parse_nodes()?;
+        let children = self.parse_children(Some(&tag_name))?;
+
+        if OPTIONAL_END_TAGS.contains(&tag_name.as_str()) && !self.starts_with_end_tag(&tag_name)? {
+            return Ok(elem(tag_name, attrs, children));
+        }
 
         let closing_tag_name = self.parse_closing_tag()?;
         let closing_tag_name = closing_tag_name.to_ascii_lowercase();
@@ -87,6 +187,33 @@ impl HtmlParser {
         Ok(elem(tag_name, attrs, children))
     }
 
+    /// Returns the lowercase name of the tag that starts `offset` bytes after the current
+    /// position, without consuming anything.
+    fn peek_tag_name(&mut self, offset: usize) -> Result<String, &'static str> {
+        let position = self.current_position();
+        self.set_current_position(position + offset);
+        let tag_name = self.parse_tag_name();
+        self.set_current_position(position);
+        Ok(tag_name?.to_ascii_lowercase())
+    }
+
+    /// Returns the lowercase name of the start tag at the current position, if there is one.
+    fn peek_start_tag(&mut self) -> Result<Option<String>, &'static str> {
+        let rest = &self.input()[self.current_position()..];
+        if !rest.starts_with('<') || !rest[1..].starts_with(|c: char| c.is_ascii_alphabetic()) {
+            return Ok(None);
+        }
+        self.peek_tag_name(1).map(Some)
+    }
+
+    /// Returns whether the input continues with the end tag of `tag_name`.
+    fn starts_with_end_tag(&mut self, tag_name: &str) -> Result<bool, &'static str> {
+        if self.eof() || !self.starts_with(b"</")? {
+            return Ok(false);
+        }
+        Ok(self.peek_tag_name(2)? == tag_name)
+    }
+
     /// Parses an opening tag and returns the tag name and its attributes.
     ///
     /// # Returns
@@ -100,6 +227,9 @@ impl HtmlParser {
         self.consume_char()?; // Consume '<'
         let tag_name = self.parse_tag_name()?;
         let attrs = self.parse_attributes()?;
+        if self.next_char()? == '/' {
+            self.consume_char()?; // Consume the '/' of a self-closing tag
+        }
         self.consume_char()?; // Consume '>'
 
         Ok((tag_name, attrs))
@@ -221,6 +351,21 @@ impl HtmlParser {
     /// assert_eq!(nodes[0].name, "html");
     /// ```
     fn parse_nodes(&mut self) -> Result<Vec<Node>, &'static str> {
+        self.parse_children(None)
+    }
+
+    /// Parses the children of an open `parent` element, or the top-level nodes without one.
+    ///
+    /// Inside tables this follows the table insertion modes of the HTML parser: missing
+    /// `tbody`, `tr` and `colgroup` elements are implied, elements with optional end tags are
+    /// closed where the table structure requires it, and content that does not belong in the
+    /// table structure is moved out in front of the table.
+    fn parse_children(&mut self, parent: Option<&str>) -> Result<Vec<Node>, &'static str> {
+        let in_table = parent.is_some_and(is_table_context);
+        let outer_fostered = match in_table {
+            true => Vec::new(),
+            false => mem::take(&mut self.fostered),
+        };
         let mut nodes = Vec::new();
 
         loop {
@@ -230,9 +375,40 @@ impl HtmlParser {
                 break;
             }
 
-            nodes.push(self.parse_node()?);
+            let tag_name = self.peek_start_tag()?;
+            let node = match (parent, tag_name.as_deref()) {
+                (Some(parent), Some(tag_name)) if implicitly_closes(parent, tag_name) => break,
+                (Some("colgroup"), None) if !self.starts_with(b"<!--")? => break,
+                (Some("table"), Some("tr" | "td" | "th")) => {
+                    self.parse_element_contents("tbody".to_string(), HashMap::new())?
+                }
+                (Some("table"), Some("col")) => {
+                    self.parse_element_contents("colgroup".to_string(), HashMap::new())?
+                }
+                (Some("tbody" | "thead" | "tfoot"), Some("td" | "th")) => {
+                    self.parse_element_contents("tr".to_string(), HashMap::new())?
+                }
+                _ => self.parse_node()?,
+            };
+
+            let belongs = match &node.node_type {
+                NodeType::Element(data) => TABLE_ELEMENTS.contains(&data.tag_name.as_str()),
+                NodeType::Text(_) => false,
+                _ => true,
+            };
+            if in_table && !belongs {
+                self.fostered.push(node);
+                continue;
+            }
+            if !in_table {
+                nodes.append(&mut self.fostered);
+            }
+            nodes.push(node);
         }
 
+        if !in_table {
+            self.fostered = outer_fostered;
+        }
         Ok(nodes)
     }
 
@@ -249,6 +425,7 @@ impl HtmlParser {
         let mut parser = HtmlParser {
             current_position: 0,
             input: source,
+            fostered: Vec::new(),
         };
 
         let mut nodes = parser.parse_nodes()?;
@@ -261,16 +438,21 @@ impl HtmlParser {
     }
 }
 
+/// Parse a whole HTML document and return the root node.
+pub fn parse(source: String) -> Result<Node, &'static str> {
+    HtmlParser::parse(source)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
 
-
     #[test]
     fn test_parse_tag_name() {
         let mut parser = HtmlParser {
             current_position: 0,
             input: String::from("div"),
+            fostered: Vec::new(),
         };
 
         assert_eq!(parser.parse_tag_name(), Ok(String::from("div")));
@@ -331,6 +513,7 @@ mod tests {
         let mut parser = HtmlParser {
             current_position: 0,
             input: String::from("hello"),
+            fostered: Vec::new(),
         };
 
         assert_eq!(parser.parse_text(), Ok(text("hello".to_string())));
@@ -342,6 +525,7 @@ mod tests {
         let mut parser = HtmlParser {
             current_position: 0,
             input: String::from("<div></div>"),
+            fostered: Vec::new(),
         };
 
         assert_eq!(
@@ -404,6 +588,7 @@ mod tests {
         let mut parser = HtmlParser {
             current_position: 0,
             input: String::from("<div>"),
+            fostered: Vec::new(),
         };
 
         assert_eq!(
@@ -433,6 +618,7 @@ mod tests {
         let mut parser = HtmlParser {
             current_position: 0,
             input: String::from("</div>"),
+            fostered: Vec::new(),
         };
 
         assert_eq!(parser.parse_closing_tag(), Ok("div".to_string()));
@@ -444,6 +630,7 @@ mod tests {
         let mut parser = HtmlParser {
             current_position: 0,
             input: String::from("class=\"example\""),
+            fostered: Vec::new(),
         };
 
         let mut attrs = HashMap::new();
@@ -465,6 +652,7 @@ mod tests {
         let mut parser = HtmlParser {
             current_position: 0,
             input: String::from("class=\"example\""),
+            fostered: Vec::new(),
         };
 
         assert_eq!(
@@ -487,6 +675,7 @@ mod tests {
         let mut parser = HtmlParser {
             current_position: 0,
             input: String::from("\"example\""),
+            fostered: Vec::new(),
         };
 
         assert_eq!(parser.parse_attr_value(), Ok("example".to_string()));
@@ -498,6 +687,7 @@ mod tests {
         let mut parser = HtmlParser {
             current_position: 0,
             input: String::from("<html><body><h1>Hello, world!</h1></body></html>"),
+            fostered: Vec::new(),
         };
 
         let nodes = vec![elem(
@@ -538,4 +728,100 @@ mod tests {
 
         assert_eq!(HtmlParser::parse(source), Ok(nodes));
     }
+
+    #[test]
+    fn test_parse_comments_doctypes_and_processing_instructions() {
+        let source = String::from(
+            "<!DOCTYPE html><?xml-stylesheet href=\"a.css\"?>\
+             <div><!-- a <b>comment</b> -->hello</div><!-- unterminated",
+        );
+
+        let nodes = elem(
+            "html".to_string(),
+            HashMap::new(),
+            vec![
+                doctype("html".to_string()),
+                processing_instruction("xml-stylesheet href=\"a.css\"".to_string()),
+                elem(
+                    "div".to_string(),
+                    HashMap::new(),
+                    vec![
+                        comment(" a <b>comment</b> ".to_string()),
+                        text("hello".to_string()),
+                    ],
+                ),
+                comment(" unterminated".to_string()),
+            ],
+        );
+
+        assert_eq!(HtmlParser::parse(source), Ok(nodes));
+    }
+
+    #[test]
+    fn test_parse_table() {
+        let source = String::from(
+            "<table><caption>c</caption><col span=\"2\"><tr><td>a<td>b</tr>\
+             <thead><tr><th>h</table>",
+        );
+        let element = |name: &str, children| elem(name.to_string(), HashMap::new(), children);
+        let mut span = HashMap::new();
+        span.insert("span".to_string(), "2".to_string());
+
+        let nodes = element(
+            "table",
+            vec![
+                element("caption", vec![text("c".to_string())]),
+                element("colgroup", vec![elem("col".to_string(), span, Vec::new())]),
+                element(
+                    "tbody",
+                    vec![element(
+                        "tr",
+                        vec![
+                            element("td", vec![text("a".to_string())]),
+                            element("td", vec![text("b".to_string())]),
+                        ],
+                    )],
+                ),
+                element(
+                    "thead",
+                    vec![element(
+                        "tr",
+                        vec![element("th", vec![text("h".to_string())])],
+                    )],
+                ),
+            ],
+        );
+
+        assert_eq!(HtmlParser::parse(source), Ok(nodes));
+    }
+
+    #[test]
+    fn test_parse_table_fosters_misplaced_content() {
+        let source = String::from(
+            "<div><table>x<tr><p>y</p><td>z<table><td>w</td>v</table></td></tr></table></div>",
+        );
+        let element = |name: &str, children| elem(name.to_string(), HashMap::new(), children);
+        let table = |rows| element("table", vec![element("tbody", rows)]);
+
+        let inner = table(vec![element(
+            "tr",
+            vec![element("td", vec![text("w".to_string())])],
+        )]);
+        let nodes = element(
+            "div",
+            vec![
+                text("x".to_string()),
+                element("p", vec![text("y".to_string())]),
+                table(vec![element(
+                    "tr",
+                    vec![element(
+                        "td",
+                        vec![text("z".to_string()), text("v".to_string()), inner],
+                    )],
+                )]),
+            ],
+        );
+
+        assert_eq!(HtmlParser::parse(source), Ok(nodes));
+    }
 }
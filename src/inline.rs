@@ -0,0 +1,1489 @@
+use std::ops::Range;
+
+use unicode_linebreak::{linebreaks, BreakOpportunity};
+
+use crate::{
+    computed::{
+        ComputedStyle, LineHeight, OverflowWrap, TextAlign, VerticalAlign, WhiteSpace, WordBreak,
+    },
+    dom::NodeType,
+    float::FloatContext,
+    font::{FontDatabase, ShapedText},
+    layout::{BoxType, ContainingBlock, LayoutBox, Rect},
+    position::relative_offset,
+    style::StyledNode,
+};
+
+/// The character standing in for an atomic inline in the text of a paragraph. UAX #14 allows
+/// breaks on both sides of it.
+const OBJECT_REPLACEMENT: char = '\u{FFFC}';
+
+/// The number of spaces a tab advances by.
+const TAB_SIZE: f32 = 8.0;
+
+/// A row of inline-level content in an inline formatting context.
+pub struct LineBox<'a> {
+    pub rect: Rect,
+    /// The y coordinate of the baseline of the line.
+    pub baseline: f32,
+    /// The pieces of content on the line, in painting order: an inline box comes before its
+    /// contents.
+    pub fragments: Vec<Fragment<'a>>,
+}
+
+/// The part of an inline box, text run or atomic inline that is on one line.
+pub struct Fragment<'a> {
+    /// The border box of an inline box or atomic inline, or the content area of text.
+    pub rect: Rect,
+    /// The node the content comes from.
+    pub node: &'a StyledNode<'a>,
+    pub kind: FragmentKind,
+}
+
+#[derive(Clone, PartialEq, Debug)]
+pub enum FragmentKind {
+    /// Text, after white space processing, and its glyphs. The glyphs are positioned relative to
+    /// the start of the text on its baseline, which is the ascent of its primary font below the
+    /// top of the fragment.
+    Text { text: String, glyphs: ShapedText },
+    /// The part of an inline box on the line, which has its left edges only if the box starts on
+    /// the line, and its right edges only if it ends on it.
+    InlineBox { first: bool, last: bool },
+    /// An atomic inline, such as an inline block, which is laid out as a box of the box tree.
+    Atomic,
+}
+
+impl LineBox<'_> {
+    /// Moves the line and its fragments by `dx` and `dy`.
+    pub fn translate(&mut self, dx: f32, dy: f32) {
+        self.rect.x += dx;
+        self.rect.y += dy;
+        self.baseline += dy;
+        for fragment in &mut self.fragments {
+            fragment.rect.x += dx;
+            fragment.rect.y += dy;
+        }
+    }
+}
+
+/// Lays out `children`, the inline-level boxes and out-of-flow boxes of a block container whose
+/// style is `style`, into line boxes stacked from the top of `containing_block`, which is the
+/// container's content area.
+///
+/// Atomic inlines among them are laid out and moved into place, and relatively positioned inline
+/// boxes and atomic inlines are moved by their offsets. Floats are placed in `floats` when the
+/// line their anchor is on is laid out: beside its content if there is room, or below it. Each
+/// line box is shortened to fit beside the floats it meets, and moves down past them if its
+/// content doesn't fit. Absolutely positioned boxes get the static position of their anchor, to
+/// be laid out with their containing block.
+pub(crate) fn layout_lines<'a>(
+    style: &ComputedStyle,
+    children: &mut [LayoutBox<'a>],
+    containing_block: ContainingBlock,
+    floats: &mut FloatContext,
+    fonts: &FontDatabase,
+) -> Vec<LineBox<'a>> {
+    for_each_atomic(children, &mut |atomic| {
+        atomic.layout_atomic(containing_block, fonts)
+    });
+    let rect = containing_block.rect;
+    let (left, right) = (rect.x, rect.x + rect.width);
+    let mut paragraph = Paragraph::new(children, style, rect.width, &|atomic| {
+        let margin_box = atomic.dimensions.margin_box();
+        let baseline = match atomic.inline_block_baseline() {
+            Some(baseline) => baseline - margin_box.y,
+            None => margin_box.height,
+        };
+        (margin_box.width, margin_box.height, baseline)
+    });
+    let anchors = paragraph
+        .items
+        .iter()
+        .filter_map(|item| match *item {
+            Item::Float { offset } => Some(offset),
+            _ => None,
+        })
+        .collect::<Vec<_>>();
+    let mut float_boxes = Vec::new();
+    let mut absolute_boxes = Vec::new();
+    collect_out_of_flow(children, &mut float_boxes, &mut absolute_boxes);
+    for float in &mut float_boxes {
+        float.layout_atomic(containing_block, fonts);
+    }
+    let mut float_boxes = float_boxes.into_iter().zip(anchors).peekable();
+    if paragraph.is_empty() {
+        for (float, _) in float_boxes {
+            float.place_float(containing_block, rect.y, floats);
+        }
+        for absolute in absolute_boxes {
+            absolute.set_static_position(rect.x, rect.y);
+        }
+        return Vec::new();
+    }
+    paragraph.measure(fonts);
+
+    let mut builder = LineBuilder {
+        paragraph: &paragraph,
+        fonts,
+        item: 0,
+        open: Vec::new(),
+        atomics: Vec::new(),
+        static_positions: Vec::new(),
+    };
+    // The floats a line meets are found from its top down to the height of the container's
+    // strut, as the line's own height isn't known until it's laid out.
+    let strut = Metrics::new(style, fonts).line_height;
+    let breaks = paragraph.break_opportunities();
+    let mut lines = Vec::new();
+    let mut y = rect.y;
+    let mut start = 0;
+    loop {
+        // Floats anchored before the line go beside it.
+        while let Some((float, _)) = float_boxes.next_if(|&(_, anchor)| anchor <= start) {
+            float.place_float(containing_block, y, floats);
+        }
+        let line = loop {
+            let (band_left, band_right) = floats.band(y, strut, left, right);
+            let width = band_right - band_left;
+            let Some(line) = paragraph.break_line(&breaks, start, width) else {
+                break None;
+            };
+            let overflows = paragraph.trimmed_width(line.range.clone()) > width;
+            match floats.next_bottom(y) {
+                Some(bottom) if overflows && width < rect.width => y = bottom,
+                _ => break Some(line),
+            }
+        };
+        let Some(line) = line else {
+            break;
+        };
+
+        // Floats anchored on the line go beside it if they fit next to its content.
+        let last_line = line.range.end == paragraph.text.len();
+        let mut used = paragraph.trimmed_width(line.range.clone());
+        let mut below = Vec::new();
+        while let Some((float, _)) =
+            float_boxes.next_if(|&(_, anchor)| anchor < line.range.end || last_line)
+        {
+            let width = float.dimensions.margin_box().width;
+            let (band_left, band_right) = floats.band(y, strut, left, right);
+            match used + width <= band_right - band_left {
+                true => {
+                    float.place_float(containing_block, y, floats);
+                    used += width;
+                }
+                false => below.push(float),
+            }
+        }
+
+        let (band_left, band_right) = floats.band(y, strut, left, right);
+        let line_box = builder.build(&line, band_left, y, band_right - band_left);
+        y += line_box.rect.height;
+        lines.push(line_box);
+        start = line.range.end;
+        for float in below {
+            float.place_float(containing_block, y, floats);
+        }
+    }
+    for (float, _) in float_boxes {
+        float.place_float(containing_block, y, floats);
+    }
+
+    let static_positions = builder.static_positions.into_iter();
+    for (absolute, (x, y)) in absolute_boxes
+        .into_iter()
+        .zip(static_positions.chain(std::iter::repeat((left, y))))
+    {
+        absolute.set_static_position(x, y);
+    }
+    let mut positions = builder.atomics.into_iter();
+    for_each_atomic(children, &mut |atomic| {
+        if let Some((x, y)) = positions.next() {
+            let margin_box = atomic.dimensions.margin_box();
+            atomic.translate(x - margin_box.x, y - margin_box.y);
+        }
+    });
+    lines
+}
+
+/// Returns the min-content and max-content widths of `children`, the inline-level boxes of a
+/// block container whose style is `style`.
+pub(crate) fn intrinsic_widths(
+    style: &ComputedStyle,
+    children: &[LayoutBox],
+    fonts: &FontDatabase,
+) -> (f32, f32) {
+    let mut min_content = Paragraph::new(children, style, 0.0, &|atomic| {
+        (atomic.intrinsic_outer_widths(fonts).0, 0.0, 0.0)
+    });
+    let mut max_content = Paragraph::new(children, style, 0.0, &|atomic| {
+        (atomic.intrinsic_outer_widths(fonts).1, 0.0, 0.0)
+    });
+    min_content.measure(fonts);
+    max_content.measure(fonts);
+    (
+        min_content.min_content_width(),
+        max_content.max_content_width(),
+    )
+}
+
+/// Returns true if `children`, the boxes of a block container whose style is `style`, include
+/// inline-level content that generates line boxes.
+pub(crate) fn has_lines(style: &ComputedStyle, children: &[LayoutBox]) -> bool {
+    !Paragraph::new(children, style, 0.0, &|_| (0.0, 0.0, 0.0)).is_empty()
+}
+
+/// Calls `f` on each atomic inline among `boxes` and their descendants in an inline formatting
+/// context, in document order.
+fn for_each_atomic<'a>(boxes: &mut [LayoutBox<'a>], f: &mut impl FnMut(&mut LayoutBox<'a>)) {
+    for child in boxes {
+        if child.is_atomic_inline() {
+            f(child);
+        } else if !child.is_out_of_flow() {
+            for_each_atomic(&mut child.children, f);
+        }
+    }
+}
+
+/// Appends the floats and absolutely positioned boxes among `boxes` and their descendants in an
+/// inline formatting context to `floats` and `absolutes`, in document order.
+fn collect_out_of_flow<'b, 'a>(
+    boxes: &'b mut [LayoutBox<'a>],
+    floats: &mut Vec<&'b mut LayoutBox<'a>>,
+    absolutes: &mut Vec<&'b mut LayoutBox<'a>>,
+) {
+    for child in boxes {
+        if child.is_float() {
+            floats.push(child);
+        } else if child.is_absolutely_positioned() {
+            absolutes.push(child);
+        } else if !child.is_atomic_inline() {
+            collect_out_of_flow(&mut child.children, floats, absolutes);
+        }
+    }
+}
+
+/// The inline-level content of a block container, as one string of text for line breaking.
+struct Paragraph<'a, 's> {
+    /// The text after white space processing, with each atomic inline replaced by U+FFFC.
+    text: String,
+    items: Vec<Item<'a>>,
+    /// The style of the block container.
+    style: &'s ComputedStyle,
+    /// The width percentages of inline boxes' margins and padding resolve against.
+    reference: f32,
+    /// The x coordinate of each byte offset of `text` if all of the content were on one line,
+    /// counting the edges of the inline boxes that end at or before it and start before it. An
+    /// offset inside a cluster of characters shaped together is at the end of the cluster.
+    positions: Vec<f32>,
+    /// The glyphs of each item that is text, shaped once for both breaking lines and laying
+    /// them out, and empty for the other items.
+    shaped: Vec<ShapedText>,
+}
+
+/// A piece of the content of a `Paragraph`, in document order.
+enum Item<'a> {
+    /// A run of text from a text node, at `range` in the text.
+    Text {
+        node: &'a StyledNode<'a>,
+        range: Range<usize>,
+    },
+    /// The start of an inline box, whose left margin, border and padding add up to `edge`.
+    Start {
+        node: &'a StyledNode<'a>,
+        offset: usize,
+        edge: f32,
+    },
+    /// The end of an inline box, whose right margin, border and padding add up to `edge`.
+    End {
+        node: &'a StyledNode<'a>,
+        offset: usize,
+        edge: f32,
+    },
+    /// An atomic inline, with the width and height of its margin box and the distance from its
+    /// top to its baseline.
+    Atomic {
+        node: &'a StyledNode<'a>,
+        offset: usize,
+        width: f32,
+        height: f32,
+        baseline: f32,
+    },
+    /// The anchor of a float, which takes no room on the line.
+    Float { offset: usize },
+    /// The anchor of an absolutely positioned box, which takes no room on the line.
+    Absolute { offset: usize },
+}
+
+/// The size of an atomic inline for a paragraph: the width and height of its margin box and the
+/// distance from its top to its baseline.
+type AtomicSize<'f> = &'f dyn Fn(&LayoutBox) -> (f32, f32, f32);
+
+/// A line of a paragraph, before it's laid out.
+struct Line {
+    range: Range<usize>,
+    /// Whether the line ends at a forced break or the end of the paragraph.
+    forced: bool,
+}
+
+impl<'a, 's> Paragraph<'a, 's> {
+    fn new(
+        children: &[LayoutBox<'a>],
+        style: &'s ComputedStyle,
+        reference: f32,
+        atomic_size: AtomicSize,
+    ) -> Paragraph<'a, 's> {
+        let mut paragraph = Paragraph {
+            text: String::new(),
+            items: Vec::new(),
+            style,
+            reference,
+            positions: Vec::new(),
+            shaped: Vec::new(),
+        };
+        // Collapsible spaces at the start of the paragraph are removed.
+        let mut after_space = true;
+        paragraph.push_boxes(children, atomic_size, &mut after_space);
+        paragraph
+    }
+
+    /// Appends the content of `boxes`. `after_space` tracks whether the text ends with a
+    /// collapsible space, which collapsible spaces that follow collapse into.
+    fn push_boxes(
+        &mut self,
+        boxes: &[LayoutBox<'a>],
+        atomic_size: AtomicSize,
+        after_space: &mut bool,
+    ) {
+        for child in boxes {
+            let BoxType::InlineNode(node) = child.box_type else {
+                let offset = self.text.len();
+                if child.is_float() {
+                    self.items.push(Item::Float { offset });
+                } else if child.is_absolutely_positioned() {
+                    self.items.push(Item::Absolute { offset });
+                }
+                continue;
+            };
+            if let NodeType::Text(ref text) = node.node.node_type {
+                self.push_text(node, text, after_space);
+            } else if child.is_atomic_inline() {
+                let (width, height, baseline) = atomic_size(child);
+                self.items.push(Item::Atomic {
+                    node,
+                    offset: self.text.len(),
+                    width,
+                    height,
+                    baseline,
+                });
+                self.text.push(OBJECT_REPLACEMENT);
+                *after_space = false;
+            } else {
+                let style = &node.computed;
+                let reference = self.reference;
+                let edge = style.margin_left().resolve(reference).unwrap_or(0.0)
+                    + style.border_left_width()
+                    + style.padding_left().resolve(reference);
+                self.items.push(Item::Start {
+                    node,
+                    offset: self.text.len(),
+                    edge,
+                });
+                self.push_boxes(&child.children, atomic_size, after_space);
+                let edge = style.margin_right().resolve(reference).unwrap_or(0.0)
+                    + style.border_right_width()
+                    + style.padding_right().resolve(reference);
+                self.items.push(Item::End {
+                    node,
+                    offset: self.text.len(),
+                    edge,
+                });
+            }
+        }
+    }
+
+    /// Appends the text of a text node, processing its white space as in CSS Text 3 § 4.1.1.
+    fn push_text(&mut self, node: &'a StyledNode<'a>, text: &str, after_space: &mut bool) {
+        let white_space = node.computed.white_space();
+        let start = self.text.len();
+        for c in text.chars() {
+            match c {
+                '\n' if white_space.preserves_newlines() => {
+                    // Collapsible spaces around a preserved newline are removed.
+                    if white_space.collapses_spaces() {
+                        let trimmed = self.text[start..].trim_end_matches(' ').len();
+                        self.text.truncate(start + trimmed);
+                        *after_space = true;
+                    }
+                    self.text.push('\n');
+                }
+                '\r' if !white_space.collapses_spaces() => {}
+                ' ' | '\t' | '\n' | '\r' | '\x0c' if white_space.collapses_spaces() => {
+                    if !*after_space {
+                        self.text.push(' ');
+                        *after_space = true;
+                    }
+                }
+                c => {
+                    self.text.push(c);
+                    *after_space = false;
+                }
+            }
+        }
+        if self.text.len() > start {
+            self.items.push(Item::Text {
+                node,
+                range: start..self.text.len(),
+            });
+        }
+    }
+
+    /// Shapes the text to fill in `shaped` and `positions`.
+    fn measure(&mut self, fonts: &FontDatabase) {
+        let mut positions = vec![0.0; self.text.len() + 1];
+        let mut filled = 0;
+        let mut x = 0.0;
+        let mut shaped_items = Vec::with_capacity(self.items.len());
+        let mut fill = |offset: usize, x: f32| {
+            while filled <= offset {
+                positions[filled] = x;
+                filled += 1;
+            }
+        };
+        for item in &self.items {
+            match *item {
+                Item::Text { node, ref range } => {
+                    let shaped = shape(fonts, &self.text[range.clone()], &node.computed);
+                    let mut pen = x;
+                    for (_, glyph) in shaped.glyphs() {
+                        fill(range.start + glyph.cluster, pen);
+                        pen += glyph.advance;
+                    }
+                    x += shaped.width;
+                    shaped_items.push(shaped);
+                    continue;
+                }
+                Item::Start { offset, edge, .. } => {
+                    fill(offset, x);
+                    x += edge;
+                }
+                Item::End { edge, .. } => x += edge,
+                Item::Atomic { offset, width, .. } => {
+                    fill(offset, x);
+                    x += width;
+                }
+                Item::Float { .. } | Item::Absolute { .. } => {}
+            }
+            shaped_items.push(ShapedText::default());
+        }
+        fill(self.text.len(), x);
+        self.positions = positions;
+        self.shaped = shaped_items;
+    }
+
+    /// Returns true if the paragraph generates no line boxes, because it has no text, atomic
+    /// inlines or inline box edges.
+    fn is_empty(&self) -> bool {
+        self.text.is_empty()
+            && self.items.iter().all(|item| match *item {
+                Item::Start { edge, .. } | Item::End { edge, .. } => edge == 0.0,
+                Item::Float { .. } | Item::Absolute { .. } => true,
+                _ => false,
+            })
+    }
+
+    /// Returns the style of the text at `offset`, or the container's style if it isn't text.
+    fn style_at(&self, offset: usize) -> &ComputedStyle {
+        let i = self.items.partition_point(|item| match *item {
+            Item::Text { ref range, .. } => range.end <= offset,
+            Item::Start { offset: start, .. }
+            | Item::End { offset: start, .. }
+            | Item::Atomic { offset: start, .. }
+            | Item::Float { offset: start }
+            | Item::Absolute { offset: start } => start <= offset,
+        });
+        match self.items.get(i) {
+            Some(Item::Text { node, range }) if range.contains(&offset) => &node.computed,
+            _ => self.style,
+        }
+    }
+
+    /// Returns the offsets where lines may or must break, in order, as in UAX #14 as modified by
+    /// `white-space` and `word-break`.
+    fn break_opportunities(&self) -> Vec<(usize, BreakOpportunity)> {
+        let mut breaks = vec![None; self.text.len() + 1];
+        for (offset, opportunity) in linebreaks(&self.text) {
+            breaks[offset] = Some(opportunity);
+        }
+
+        let mut chars = self.text.char_indices().peekable();
+        while let Some((offset, c)) = chars.next() {
+            let end = offset + c.len_utf8();
+            let style = self.style_at(offset);
+            let next = chars.peek().map(|&(_, next)| next);
+            let allowed = &mut breaks[end];
+            if *allowed == Some(BreakOpportunity::Mandatory) {
+                continue;
+            }
+            if !style.white_space().wraps() {
+                *allowed = None;
+                continue;
+            }
+            let is_letter = |c: char| c.is_alphanumeric();
+            match style.word_break() {
+                WordBreak::BreakAll if is_letter(c) && next.is_some_and(is_letter) => {
+                    *allowed = Some(BreakOpportunity::Allowed)
+                }
+                WordBreak::KeepAll if is_letter(c) && next.is_some_and(is_letter) => {
+                    *allowed = None
+                }
+                _ => {}
+            }
+            // Every preserved space is a break opportunity with `break-spaces`.
+            if c == ' ' && style.white_space() == WhiteSpace::BreakSpaces {
+                *allowed = Some(BreakOpportunity::Allowed);
+            }
+        }
+        breaks
+            .into_iter()
+            .enumerate()
+            .skip(1)
+            .filter_map(|(offset, opportunity)| Some((offset, opportunity?)))
+            .collect()
+    }
+
+    /// Returns the width of the content in `range`, without the spaces at its end that hang or
+    /// are removed at the end of a line.
+    fn trimmed_width(&self, range: Range<usize>) -> f32 {
+        let end = self.trimmed_end(range.clone());
+        self.positions[end] - self.positions[range.start]
+    }
+
+    /// Returns the end of `range` without the spaces at its end that hang or are removed at the
+    /// end of a line, and a newline ending it.
+    fn trimmed_end(&self, range: Range<usize>) -> usize {
+        let text = &self.text[range.clone()];
+        let mut end = range.end;
+        for c in text.chars().rev() {
+            let trimmed = match c {
+                '\n' => true,
+                ' ' | '\t' => !matches!(
+                    self.style_at(end - 1).white_space(),
+                    WhiteSpace::Pre | WhiteSpace::BreakSpaces
+                ),
+                _ => false,
+            };
+            if !trimmed {
+                break;
+            }
+            end -= c.len_utf8();
+        }
+        end
+    }
+
+    /// Breaks the paragraph into lines no wider than `width` where possible, filling each line
+    /// with as much content as fits.
+    fn break_lines(&self, width: f32) -> Vec<Line> {
+        let breaks = self.break_opportunities();
+        let mut lines = Vec::new();
+        let mut start = 0;
+        while let Some(line) = self.break_line(&breaks, start, width) {
+            start = line.range.end;
+            lines.push(line);
+        }
+        lines
+    }
+
+    /// Returns the line starting at `start`, filled with as much content as fits in `width`, or
+    /// `None` at the end of the paragraph. `breaks` are the paragraph's break opportunities.
+    fn break_line(
+        &self,
+        breaks: &[(usize, BreakOpportunity)],
+        start: usize,
+        width: f32,
+    ) -> Option<Line> {
+        // The last opportunity on the line up to which its content fits.
+        let mut fits = None;
+        let first = breaks.partition_point(|&(offset, _)| offset <= start);
+        for &(offset, opportunity) in &breaks[first..] {
+            if self.trimmed_width(start..offset) > width {
+                if let Some(end) = fits.or_else(|| self.emergency_break(start..offset, width)) {
+                    return Some(Line {
+                        range: start..end,
+                        forced: false,
+                    });
+                }
+            }
+            match opportunity {
+                BreakOpportunity::Mandatory => {
+                    return Some(Line {
+                        range: start..offset,
+                        forced: true,
+                    })
+                }
+                BreakOpportunity::Allowed => fits = Some(offset),
+            }
+        }
+        None
+    }
+
+    /// Returns where to break `range`, which has no break opportunity and is too wide for a line
+    /// of its own, if `overflow-wrap` or `word-break` allows breaking it anywhere.
+    fn emergency_break(&self, range: Range<usize>, width: f32) -> Option<usize> {
+        let style = self.style_at(range.start);
+        if style.overflow_wrap() == OverflowWrap::Normal
+            && style.word_break() != WordBreak::BreakAll
+        {
+            return None;
+        }
+        let mut boundaries = self.text[range.clone()]
+            .char_indices()
+            .map(|(i, c)| range.start + i + c.len_utf8())
+            .filter(|&end| end < range.end);
+        // At least one character goes on the line.
+        let first = boundaries.next()?;
+        let end = boundaries
+            .take_while(|&end| self.trimmed_width(range.start..end) <= width)
+            .last();
+        Some(end.unwrap_or(first))
+    }
+
+    /// Returns the width of the widest line when lines only break where they must.
+    fn max_content_width(&self) -> f32 {
+        self.break_lines(f32::INFINITY)
+            .into_iter()
+            .map(|line| self.trimmed_width(line.range))
+            .fold(0.0, f32::max)
+    }
+
+    /// Returns the width of the widest piece of content between break opportunities, taking
+    /// every opportunity. `overflow-wrap: anywhere` allows breaks between any characters.
+    fn min_content_width(&self) -> f32 {
+        let mut start = 0;
+        let mut widest = 0.0f32;
+        for (offset, _) in self.break_opportunities() {
+            let width = match self.style_at(start).overflow_wrap() {
+                OverflowWrap::Anywhere => self.text[start..offset]
+                    .char_indices()
+                    .map(|(i, c)| {
+                        let i = start + i;
+                        self.trimmed_width(i..i + c.len_utf8())
+                    })
+                    .fold(0.0, f32::max),
+                _ => self.trimmed_width(start..offset),
+            };
+            widest = widest.max(width);
+            start = offset;
+        }
+        widest
+    }
+}
+
+/// The font metrics and line height of an inline box, in px.
+#[derive(Clone, Copy)]
+struct Metrics {
+    font_size: f32,
+    ascent: f32,
+    descent: f32,
+    x_height: f32,
+    /// Half of the difference between the line height and the height of the content area, added
+    /// above and below it.
+    half_leading: f32,
+    line_height: f32,
+}
+
+impl Metrics {
+    /// Returns the metrics of an inline box, which come from its primary font.
+    fn new(style: &ComputedStyle, fonts: &FontDatabase) -> Metrics {
+        let font_size = style.font_size();
+        let font = fonts.font(fonts.primary_font(style)).metrics(font_size);
+        let (ascent, descent) = (font.ascent, font.descent);
+        let line_height = match style.line_height() {
+            LineHeight::Normal => ascent + descent + font.line_gap,
+            LineHeight::Number(number) => number * font_size,
+            LineHeight::Px(px) => px,
+        };
+        Metrics {
+            font_size,
+            ascent,
+            descent,
+            x_height: font.x_height,
+            half_leading: (line_height - ascent - descent) / 2.0,
+            line_height,
+        }
+    }
+
+    /// Returns the metrics of an atomic inline of the given height whose baseline is `baseline`
+    /// below its top.
+    fn atomic(style: &ComputedStyle, height: f32, baseline: f32) -> Metrics {
+        Metrics {
+            font_size: style.font_size(),
+            ascent: baseline,
+            descent: height - baseline,
+            x_height: 0.0,
+            half_leading: 0.0,
+            line_height: height,
+        }
+    }
+
+    /// Returns the top and bottom of the box's layout bounds relative to its baseline.
+    fn bounds(&self) -> (f32, f32) {
+        (
+            -self.ascent - self.half_leading,
+            self.descent + self.half_leading,
+        )
+    }
+}
+
+/// Returns how far below its parent's baseline the baseline of a box with the given metrics is
+/// shifted by `vertical-align`, as in CSS 2.1 § 10.8.1. `top` and `bottom` are aligned with the
+/// line box afterwards.
+fn baseline_shift(align: VerticalAlign, metrics: &Metrics, parent: &Metrics) -> f32 {
+    match align {
+        VerticalAlign::Baseline | VerticalAlign::Top | VerticalAlign::Bottom => 0.0,
+        VerticalAlign::Sub => parent.font_size / 5.0,
+        VerticalAlign::Super => -parent.font_size / 3.0,
+        VerticalAlign::TextTop => -parent.ascent + metrics.ascent + metrics.half_leading,
+        VerticalAlign::TextBottom => parent.descent - metrics.descent - metrics.half_leading,
+        VerticalAlign::Middle => -parent.x_height / 2.0 - (metrics.descent - metrics.ascent) / 2.0,
+        VerticalAlign::Px(px) => -px,
+        VerticalAlign::Percent(percent) => -metrics.line_height * percent / 100.0,
+    }
+}
+
+/// Shapes `text` with the fonts for `style`. Preserved tabs advance by `TAB_SIZE` spaces, and
+/// preserved newlines have no width.
+fn shape(fonts: &FontDatabase, text: &str, style: &ComputedStyle) -> ShapedText {
+    let controls = ['\t', '\n'];
+    // Both are shaped as spaces, which are as long in UTF-8.
+    let mut shaped = fonts.shape(&text.replace(controls, " "), style);
+    for (offset, c) in text.match_indices(controls) {
+        // A tab is shaped as the space it advances by multiples of.
+        let advance = match c {
+            "\t" => TAB_SIZE * shaped.advance(offset),
+            _ => 0.0,
+        };
+        shaped.set_advance(offset, advance);
+    }
+    shaped
+}
+
+/// An inline box open on the line being built.
+struct OpenBox<'a> {
+    node: &'a StyledNode<'a>,
+    /// The index of its fragment on the line.
+    fragment: usize,
+    /// Whether it started on this line.
+    first: bool,
+    metrics: Metrics,
+    /// How far its baseline is below the line's.
+    shift: f32,
+    /// The top- or bottom-aligned box it is in, as an index into `LineState::groups`.
+    group: Option<usize>,
+    /// How far it and its contents are moved by its own and its ancestors' relative offsets.
+    offset: (f32, f32),
+}
+
+/// Builds the line boxes of a paragraph one line at a time.
+struct LineBuilder<'p, 'a, 's> {
+    paragraph: &'p Paragraph<'a, 's>,
+    fonts: &'p FontDatabase,
+    /// The index of the first item not yet placed.
+    item: usize,
+    /// The inline boxes open at the end of the last line, outermost first.
+    open: Vec<OpenBox<'a>>,
+    /// The position of the margin box of each atomic inline placed so far.
+    atomics: Vec<(f32, f32)>,
+    /// The static position of each absolutely positioned box anchored in the lines so far.
+    static_positions: Vec<(f32, f32)>,
+}
+
+/// The vertical extent of the content placed on a line so far.
+struct LineState<'a> {
+    fragments: Vec<Fragment<'a>>,
+    /// For each fragment, how far its baseline is below the line's, and the top- or
+    /// bottom-aligned box it is in.
+    shifts: Vec<(f32, Option<usize>)>,
+    /// For each fragment, how far it is moved by relative positioning once the line is laid out.
+    offsets: Vec<(f32, f32)>,
+    /// The top and bottom of the content that isn't in a top- or bottom-aligned box, relative
+    /// to the baseline.
+    top: f32,
+    bottom: f32,
+    /// The boxes aligned with the top or bottom of the line, with the extent of their contents
+    /// relative to the baseline.
+    groups: Vec<(VerticalAlign, f32, f32)>,
+}
+
+impl<'a> LineState<'a> {
+    /// Extends the line or `group` to include a box spanning `top` to `bottom` relative to the
+    /// baseline.
+    fn extend(&mut self, group: Option<usize>, top: f32, bottom: f32) {
+        match group {
+            Some(group) => {
+                let (_, group_top, group_bottom) = &mut self.groups[group];
+                *group_top = group_top.min(top);
+                *group_bottom = group_bottom.max(bottom);
+            }
+            None => {
+                self.top = self.top.min(top);
+                self.bottom = self.bottom.max(bottom);
+            }
+        }
+    }
+
+    fn push(
+        &mut self,
+        fragment: Fragment<'a>,
+        shift: f32,
+        group: Option<usize>,
+        offset: (f32, f32),
+    ) {
+        self.fragments.push(fragment);
+        self.shifts.push((shift, group));
+        self.offsets.push(offset);
+    }
+}
+
+impl<'a> LineBuilder<'_, 'a, '_> {
+    /// Lays out `line` in a line box at `(x, y)` that is `width` wide.
+    fn build(&mut self, line: &Line, x: f32, y: f32, width: f32) -> LineBox<'a> {
+        let paragraph = self.paragraph;
+        let style = paragraph.style;
+        let range = line.range.clone();
+        let end = paragraph.trimmed_end(range.clone());
+        let content_width = paragraph.trimmed_width(range.clone());
+        let free = width - content_width;
+
+        let left = x;
+        let mut x = x;
+        let mut justification = 0.0;
+        match style.text_align() {
+            TextAlign::Start | TextAlign::Left => {}
+            TextAlign::End | TextAlign::Right => x += free.max(0.0),
+            TextAlign::Center => x += (free / 2.0).max(0.0),
+            TextAlign::Justify if !line.forced && free > 0.0 => {
+                let spaces = paragraph.text[range.start..end].matches(' ').count();
+                if spaces > 0 {
+                    justification = free / spaces as f32;
+                }
+            }
+            TextAlign::Justify => {}
+        }
+
+        let root = Metrics::new(style, self.fonts);
+        let (top, bottom) = root.bounds();
+        let mut state = LineState {
+            fragments: Vec::new(),
+            shifts: Vec::new(),
+            offsets: Vec::new(),
+            top,
+            bottom,
+            groups: Vec::new(),
+        };
+
+        // Reopen the boxes that continue from the last line.
+        let mut open = std::mem::take(&mut self.open);
+        for i in 0..open.len() {
+            let (parent, parent_shift, parent_group) = match i {
+                0 => (root, 0.0, None),
+                _ => (open[i - 1].metrics, open[i - 1].shift, open[i - 1].group),
+            };
+            let node = open[i].node;
+            let (shift, group) = self.align(
+                &mut state,
+                node,
+                &open[i].metrics,
+                &parent,
+                parent_shift,
+                parent_group,
+            );
+            open[i].fragment = state.fragments.len();
+            open[i].first = false;
+            open[i].shift = shift;
+            open[i].group = group;
+            state.push(
+                Fragment {
+                    rect: Rect {
+                        x,
+                        ..Rect::default()
+                    },
+                    node,
+                    kind: FragmentKind::InlineBox {
+                        first: false,
+                        last: false,
+                    },
+                },
+                shift,
+                group,
+                open[i].offset,
+            );
+        }
+
+        let last_line = range.end == paragraph.text.len();
+        while let Some(item) = paragraph.items.get(self.item) {
+            let (parent, parent_shift, parent_group, parent_offset) = match open.last() {
+                Some(open) => (open.metrics, open.shift, open.group, open.offset),
+                None => (root, 0.0, None, (0.0, 0.0)),
+            };
+            // Relative offsets of inline boxes resolve against the width of the container, and
+            // percentages of its height are 0.
+            let offset_of = |node: &StyledNode| {
+                let (dx, dy) = relative_offset(&node.computed, paragraph.reference, None);
+                (parent_offset.0 + dx, parent_offset.1 + dy)
+            };
+            match *item {
+                Item::Start { node, offset, edge } if offset < range.end || last_line => {
+                    let metrics = Metrics::new(&node.computed, self.fonts);
+                    let (shift, group) = self.align(
+                        &mut state,
+                        node,
+                        &metrics,
+                        &parent,
+                        parent_shift,
+                        parent_group,
+                    );
+                    let margin = node.computed.margin_left().resolve(paragraph.reference);
+                    let offset = offset_of(node);
+                    open.push(OpenBox {
+                        node,
+                        fragment: state.fragments.len(),
+                        first: true,
+                        metrics,
+                        shift,
+                        group,
+                        offset,
+                    });
+                    state.push(
+                        Fragment {
+                            rect: Rect {
+                                x: x + margin.unwrap_or(0.0),
+                                ..Rect::default()
+                            },
+                            node,
+                            kind: FragmentKind::InlineBox {
+                                first: true,
+                                last: false,
+                            },
+                        },
+                        shift,
+                        group,
+                        offset,
+                    );
+                    x += edge;
+                }
+                Item::End { node, offset, edge } if offset <= range.end => {
+                    let margin = node.computed.margin_right().resolve(paragraph.reference);
+                    if let Some(open_box) = open.pop() {
+                        let right = x + edge - margin.unwrap_or(0.0);
+                        self.close(&mut state, &open_box, right, true);
+                    }
+                    x += edge;
+                }
+                Item::Text { node, ref range } if range.start < line.range.end => {
+                    let text_end = range.end.min(end);
+                    let start = range.start.max(line.range.start);
+                    if start < text_end {
+                        let (ascent, descent) = (parent.ascent, parent.descent);
+                        let shaped = &paragraph.shaped[self.item];
+                        // The glyphs of the text at `slice` of the paragraph, cut from those of
+                        // the whole text node.
+                        let push_text = |state: &mut LineState<'a>, slice: Range<usize>, x: f32| {
+                            let glyphs =
+                                shaped.slice(slice.start - range.start..slice.end - range.start);
+                            let width = glyphs.width;
+                            state.push(
+                                Fragment {
+                                    rect: Rect {
+                                        x,
+                                        y: -ascent,
+                                        width,
+                                        height: ascent + descent,
+                                    },
+                                    node,
+                                    kind: FragmentKind::Text {
+                                        text: paragraph.text[slice].to_string(),
+                                        glyphs,
+                                    },
+                                },
+                                parent_shift,
+                                parent_group,
+                                parent_offset,
+                            );
+                            width
+                        };
+                        let text = &paragraph.text[start..text_end];
+                        if justification > 0.0 {
+                            // Each word is a fragment of its own, with the spaces between words
+                            // stretched.
+                            let mut offset = start;
+                            for word in text.split(' ') {
+                                let word_end = offset + word.len();
+                                if !word.is_empty() {
+                                    x += push_text(&mut state, offset..word_end, x);
+                                }
+                                if word_end < text_end {
+                                    let positions = &paragraph.positions;
+                                    x += positions[word_end + 1] - positions[word_end]
+                                        + justification;
+                                }
+                                offset = word_end + 1;
+                            }
+                        } else {
+                            x += push_text(&mut state, start..text_end, x);
+                        }
+                    }
+                    if range.end > line.range.end {
+                        break;
+                    }
+                }
+                Item::Atomic {
+                    node,
+                    offset,
+                    width,
+                    height,
+                    baseline,
+                } if offset < range.end => {
+                    let metrics = Metrics::atomic(&node.computed, height, baseline);
+                    let (shift, group) = self.align(
+                        &mut state,
+                        node,
+                        &metrics,
+                        &parent,
+                        parent_shift,
+                        parent_group,
+                    );
+                    state.push(
+                        Fragment {
+                            rect: Rect {
+                                x,
+                                y: -baseline,
+                                width,
+                                height,
+                            },
+                            node,
+                            kind: FragmentKind::Atomic,
+                        },
+                        shift,
+                        group,
+                        offset_of(node),
+                    );
+                    x += width;
+                }
+                Item::Float { offset } if offset < range.end || last_line => {}
+                Item::Absolute { offset } if offset < range.end || last_line => {
+                    self.static_positions.push((x, y));
+                }
+                _ => break,
+            }
+            self.item += 1;
+        }
+
+        // Close the boxes that continue on the next line.
+        for open_box in open.iter().rev() {
+            self.close(&mut state, open_box, x, false);
+        }
+        self.open = open;
+
+        self.finish(
+            state,
+            Rect {
+                x: left,
+                y,
+                width,
+                height: 0.0,
+            },
+        )
+    }
+
+    /// Computes the baseline shift of an inline box or atomic inline whose parent is shifted by
+    /// `parent_shift` and in `parent_group`, and extends the line to include it. Returns its
+    /// shift and group.
+    fn align(
+        &self,
+        state: &mut LineState,
+        node: &StyledNode,
+        metrics: &Metrics,
+        parent: &Metrics,
+        parent_shift: f32,
+        parent_group: Option<usize>,
+    ) -> (f32, Option<usize>) {
+        let align = node.computed.vertical_align();
+        let (top, bottom) = metrics.bounds();
+        let (shift, group) = match align {
+            VerticalAlign::Top | VerticalAlign::Bottom => {
+                state.groups.push((align, top, bottom));
+                (0.0, Some(state.groups.len() - 1))
+            }
+            _ => (
+                parent_shift + baseline_shift(align, metrics, parent),
+                parent_group,
+            ),
+        };
+        state.extend(group, shift + top, shift + bottom);
+        (shift, group)
+    }
+
+    /// Sets the extent of the fragment of `open_box` on the line, whose right border edge is at
+    /// `right`.
+    fn close(&self, state: &mut LineState, open_box: &OpenBox, right: f32, last: bool) {
+        let style = &open_box.node.computed;
+        let reference = self.paragraph.reference;
+        let metrics = open_box.metrics;
+        let fragment = &mut state.fragments[open_box.fragment];
+        let top =
+            metrics.ascent + style.padding_top().resolve(reference) + style.border_top_width();
+        let bottom = metrics.descent
+            + style.padding_bottom().resolve(reference)
+            + style.border_bottom_width();
+        fragment.rect.y = -top;
+        fragment.rect.height = top + bottom;
+        fragment.rect.width = right - fragment.rect.x;
+        fragment.kind = FragmentKind::InlineBox {
+            first: open_box.first,
+            last,
+        };
+    }
+
+    /// Sizes the line box from its content, aligns the top- and bottom-aligned boxes, and moves
+    /// the fragments to their final positions in `rect`, whose height is left to compute, and then
+    /// by their relative offsets. Atomic inlines are recorded in `atomics`.
+    fn finish(&mut self, state: LineState<'a>, rect: Rect) -> LineBox<'a> {
+        let top = state.top;
+        let mut bottom = state.bottom;
+        for &(_, group_top, group_bottom) in &state.groups {
+            let height = group_bottom - group_top;
+            // The line grows downwards to fit a top- or bottom-aligned box taller than the rest of
+            // its content.
+            bottom = bottom.max(top + height);
+        }
+        let deltas = state
+            .groups
+            .iter()
+            .map(|&(align, group_top, group_bottom)| match align {
+                VerticalAlign::Top => top - group_top,
+                _ => bottom - group_bottom,
+            })
+            .collect::<Vec<_>>();
+
+        let baseline = rect.y - top;
+        let mut fragments = state.fragments;
+        for ((fragment, (shift, group)), (dx, dy)) in
+            fragments.iter_mut().zip(state.shifts).zip(state.offsets)
+        {
+            let delta = group.map_or(0.0, |group| deltas[group]);
+            fragment.rect.x += dx;
+            fragment.rect.y += baseline + shift + delta + dy;
+            if fragment.kind == FragmentKind::Atomic {
+                self.atomics.push((fragment.rect.x, fragment.rect.y));
+            }
+        }
+        LineBox {
+            rect: Rect {
+                height: bottom - top,
+                ..rect
+            },
+            baseline,
+            fragments,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::layout::tests::{with_monospace_layout, CH};
+
+    /// The ascent and descent of the bundled monospace font at 10px.
+    const ASCENT: f32 = 1901.0 / 2048.0 * 10.0;
+    const DESCENT: f32 = 483.0 / 2048.0 * 10.0;
+
+    /// Returns the text on each line.
+    fn lines(layout_box: &LayoutBox) -> Vec<String> {
+        layout_box
+            .lines
+            .iter()
+            .map(|line| {
+                line.fragments
+                    .iter()
+                    .filter_map(|fragment| match fragment.kind {
+                        FragmentKind::Text { ref text, .. } => Some(text.as_str()),
+                        _ => None,
+                    })
+                    .collect()
+            })
+            .collect()
+    }
+
+    fn text_lines(html: &str, css: &str) -> Vec<String> {
+        let mut text = Vec::new();
+        with_monospace_layout(html, css, |root| text = lines(root));
+        text
+    }
+
+    #[test]
+    fn test_collapses_white_space() {
+        assert_eq!(text_lines("<p>  a \n\t b  <b> c </b> </p>", ""), ["a b c"]);
+        assert_eq!(
+            text_lines("<p>a  b\n c </p>", "p { white-space: pre; }"),
+            ["a  b", " c "]
+        );
+        assert_eq!(
+            text_lines("<p>a  b \n c</p>", "p { white-space: pre-line; }"),
+            ["a b", "c"]
+        );
+    }
+
+    #[test]
+    fn test_breaks_lines() {
+        let html = "<p>aaa bbb ccc ddd</p>";
+        assert_eq!(
+            text_lines(html, "p { width: 50px; }"),
+            ["aaa bbb", "ccc ddd"]
+        );
+        assert_eq!(
+            text_lines(html, "p { width: 50px; white-space: nowrap; }"),
+            ["aaa bbb ccc ddd"]
+        );
+        // A word too long for the line overflows it.
+        assert_eq!(
+            text_lines("<p>a bbbbbbbbbbbb c</p>", "p { width: 50px; }"),
+            ["a", "bbbbbbbbbbbb", "c"]
+        );
+        assert_eq!(
+            text_lines(
+                "<p>aa   bb</p>",
+                "p { width: 25px; white-space: break-spaces; }"
+            ),
+            ["aa  ", " bb"]
+        );
+    }
+
+    #[test]
+    fn test_breaks_words() {
+        let html = "<p>abcdefghijkl</p>";
+        assert_eq!(text_lines(html, "p { width: 31px; }"), ["abcdefghijkl"]);
+        assert_eq!(
+            text_lines(html, "p { width: 31px; overflow-wrap: anywhere; }"),
+            ["abcde", "fghij", "kl"]
+        );
+        assert_eq!(
+            text_lines(html, "p { width: 31px; word-break: break-all; }"),
+            ["abcde", "fghij", "kl"]
+        );
+    }
+
+    #[test]
+    fn test_text_align() {
+        let x = |css: &str| {
+            let mut x = Vec::new();
+            with_monospace_layout("<p>aaa bbb ccc ddd</p>", css, |root| {
+                x = root
+                    .lines
+                    .iter()
+                    .flat_map(|line| line.fragments.iter().map(|fragment| fragment.rect.x))
+                    .collect();
+            });
+            x
+        };
+        // Each line is seven characters wide, not counting the space at its end.
+        let free = 50.0 - 7.0 * CH;
+        assert_eq!(x("p { width: 50px; text-align: right; }"), [free, free]);
+        assert_eq!(
+            x("p { width: 50px; text-align: center; }"),
+            [free / 2.0, free / 2.0]
+        );
+        // The last line isn't justified.
+        assert_eq!(
+            x("p { width: 50px; text-align: justify; }"),
+            [0.0, 4.0 * CH + free, 0.0]
+        );
+    }
+
+    #[test]
+    fn test_line_height() {
+        let css = "p { line-height: 20px; white-space: pre-line; }";
+        with_monospace_layout("<p>a\nb</p>", css, |root| {
+            let line = &root.lines[0];
+            assert_eq!((line.rect.y, line.rect.height), (0.0, 20.0));
+            // The half-leading is above the ascent.
+            let half_leading = (20.0 - ASCENT - DESCENT) / 2.0;
+            assert_eq!(line.baseline, half_leading + ASCENT);
+            assert_eq!(line.fragments[0].rect.y, half_leading);
+            assert_eq!(root.lines[1].rect.y, 20.0);
+            assert_eq!(root.dimensions.content.height, 40.0);
+        });
+    }
+
+    #[test]
+    fn test_vertical_align() {
+        let css = "p { line-height: 10px; } span { font-size: 20px; line-height: 20px; }";
+        with_monospace_layout("<p>a<span>b</span></p>", css, |root| {
+            // The taller span extends the line above the root's ascent.
+            let line = &root.lines[0];
+            let half_leading = (20.0 - 2.0 * (ASCENT + DESCENT)) / 2.0;
+            assert_eq!(
+                (line.baseline, line.rect.height),
+                (half_leading + 2.0 * ASCENT, 20.0)
+            );
+        });
+        let css = "p { line-height: 10px; } span { vertical-align: 4px; }";
+        with_monospace_layout("<p>a<span>b</span></p>", css, |root| {
+            let line = &root.lines[0];
+            let half_leading = (10.0 - ASCENT - DESCENT) / 2.0;
+            assert_eq!(
+                (line.baseline, line.rect.height),
+                (half_leading + ASCENT + 4.0, 14.0)
+            );
+            assert_eq!(line.fragments[2].rect.y, half_leading);
+        });
+    }
+
+    #[test]
+    fn test_shapes_text() {
+        let css = "p { font-family: sans-serif; }";
+        with_monospace_layout("<p>ffi AV</p>", css, |root| {
+            let fragment = &root.lines[0].fragments[0];
+            let FragmentKind::Text { ref glyphs, .. } = fragment.kind else {
+                panic!("expected text");
+            };
+            // "ffi" is a ligature, and "AV" is kerned.
+            let clusters = glyphs.glyphs().map(|(_, glyph)| glyph.cluster);
+            assert_eq!(clusters.collect::<Vec<_>>(), [0, 3, 4, 5]);
+            assert_eq!(fragment.rect.width, glyphs.width);
+            let fonts = FontDatabase::default();
+            let unkerned = ["ffi A", "V"].map(|text| fonts.shape(text, root.style()).width);
+            assert!(glyphs.width < unkerned[0] + unkerned[1]);
+        });
+    }
+
+    #[test]
+    fn test_flows_around_floats() {
+        let line_positions = |root: &LayoutBox| {
+            root.lines
+                .iter()
+                .map(|line| (line.rect.x, line.rect.y, line.rect.width))
+                .collect::<Vec<_>>()
+        };
+        let css = "p { width: 100px; } span { float: left; width: 30px; height: 15px; }";
+        with_monospace_layout(
+            "<p><span></span>aaa bbb ccc ddd eee fff ggg hhh</p>",
+            css,
+            |root| {
+                assert_eq!(lines(root), ["aaa bbb ccc", "ddd eee fff", "ggg hhh"]);
+                let height = root.lines[0].rect.height;
+                assert_eq!(
+                    line_positions(root),
+                    [
+                        (30.0, 0.0, 70.0),
+                        (30.0, height, 70.0),
+                        (0.0, 2.0 * height, 100.0)
+                    ]
+                );
+            },
+        );
+        // A line whose content doesn't fit beside a float moves below it.
+        let css = "p { width: 100px; } span { float: right; width: 90px; height: 15px; }";
+        with_monospace_layout("<p><span></span>aaa bbb</p>", css, |root| {
+            assert_eq!(line_positions(root), [(0.0, 15.0, 100.0)]);
+            let span = root.children[0].dimensions.margin_box();
+            assert_eq!((span.x, span.y), (10.0, 0.0));
+        });
+        // A float anchored in a line goes beside it if there is room, and below it if not.
+        let css = "p { width: 50px; } span { float: left; width: 8px; height: 5px; }";
+        with_monospace_layout("<p>aaa<span></span> bbb<span></span></p>", css, |root| {
+            assert_eq!(root.lines[0].rect.x, 8.0);
+            let position = |i: usize| {
+                let margin_box = root.children[i].dimensions.margin_box();
+                (margin_box.x, margin_box.y)
+            };
+            assert_eq!(position(1), (0.0, 0.0));
+            assert_eq!(position(3), (0.0, root.lines[0].rect.height));
+        });
+    }
+
+    #[test]
+    fn test_inline_box_edges() {
+        let css = "span { padding-left: 5px; border-right-width: 2px; \
+                   border-right-style: solid; }";
+        with_monospace_layout("<p>a<span>bb</span>c</p>", css, |root| {
+            let fragments = &root.lines[0].fragments;
+            let rects = fragments
+                .iter()
+                .map(|fragment| (fragment.rect.x, fragment.rect.width))
+                .collect::<Vec<_>>();
+            assert_eq!(
+                rects,
+                [
+                    (0.0, CH),
+                    (CH, 2.0 * CH + 7.0),
+                    (CH + 5.0, 2.0 * CH),
+                    (3.0 * CH + 7.0, CH)
+                ]
+            );
+            assert_eq!(
+                fragments[1].kind,
+                FragmentKind::InlineBox {
+                    first: true,
+                    last: true
+                }
+            );
+        });
+    }
+
+    #[test]
+    fn test_inline_block() {
+        let css = "p { line-height: 10px; } \
+                   span { display: inline-block; width: 30px; height: 20px; margin: 1px; }";
+        with_monospace_layout("<p>ab<span></span>cd</p>", css, |root| {
+            let line = &root.lines[0];
+            // With no line boxes, the bottom margin edge is on the baseline.
+            let below = DESCENT + (10.0 - ASCENT - DESCENT) / 2.0;
+            assert_eq!((line.baseline, line.rect.height), (22.0, 22.0 + below));
+            let span = &root.children[1];
+            assert_eq!(
+                span.dimensions.content,
+                Rect {
+                    x: 2.0 * CH + 1.0,
+                    y: 1.0,
+                    width: 30.0,
+                    height: 20.0,
+                }
+            );
+            assert_eq!(line.fragments[2].rect.x, 2.0 * CH + 32.0);
+        });
+        // An inline block without a width shrinks to fit its contents, and its last line box
+        // gives its baseline.
+        let css = "p { line-height: 10px; white-space: pre-line; } \
+                   span { display: inline-block; }";
+        with_monospace_layout("<p>a<span>bb\nc</span></p>", css, |root| {
+            let span = &root.children[1];
+            assert_eq!(span.dimensions.content.width, 2.0 * CH);
+            assert_eq!(root.lines[0].baseline, span.lines[1].baseline);
+        });
+    }
+
+    #[test]
+    fn test_positioned_inline_content() {
+        let css = "span { position: relative; left: 3px; top: 2px; } \
+                   i { display: inline-block; position: relative; left: 1px; } \
+                   b { position: absolute; }";
+        with_monospace_layout("<p>a<span>bb</span><i>x</i>c<b></b></p>", css, |root| {
+            let fragments = &root.lines[0].fragments;
+            let rects = fragments
+                .iter()
+                .map(|fragment| (fragment.rect.x, fragment.rect.y - fragments[0].rect.y))
+                .collect::<Vec<_>>();
+            // Relative offsets move an inline box and its contents without moving what follows.
+            assert_eq!(rects[1].0, CH + 3.0);
+            assert_eq!(rects[2], (CH + 3.0, 2.0));
+            assert_eq!(rects[3].0, 3.0 * CH + 1.0);
+            assert_eq!(rects[4], (4.0 * CH, 0.0));
+            assert_eq!(root.children[2].dimensions.content.x, 3.0 * CH + 1.0);
+            // An absolutely positioned box stays where its anchor is in the text.
+            let b = &root.children[4];
+            assert_eq!(b.dimensions.content.x, 5.0 * CH);
+            assert_eq!(b.dimensions.content.y, root.lines[0].rect.y);
+        });
+    }
+}
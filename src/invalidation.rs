@@ -0,0 +1,407 @@
+use std::{collections::HashSet, sync::Arc};
+
+use crate::{
+    computed::ComputedStyle,
+    css::{Condition, Rule, Selector, SimpleSelector, Stylesheet},
+    dom::{ElementData, Node, NodeType},
+    style::{matches_simple_selector, PropertyMap},
+};
+
+/// The ids and classes that selectors in a stylesheet depend on, split by whether they can affect
+/// the element that has them or its descendants.
+///
+/// Changing an id or class that isn't in the map can't change any style, so it needs no restyle.
+#[derive(Default, Debug)]
+pub struct InvalidationMap {
+    /// Ids in the rightmost compound of a selector.
+    pub self_ids: HashSet<String>,
+    /// Classes in the rightmost compound of a selector.
+    pub self_classes: HashSet<String>,
+    /// Ids that an ancestor of the matched element must have, or that a scoping root or limit
+    /// must have.
+    pub descendant_ids: HashSet<String>,
+    /// Classes that an ancestor of the matched element must have, or that a scoping root or limit
+    /// must have.
+    pub descendant_classes: HashSet<String>,
+}
+
+/// A change to the DOM since it was last styled. Nodes are identified by the indices of the
+/// children leading to them from the root, so `[]` is the root itself.
+#[derive(Clone, PartialEq, Debug)]
+pub enum Change {
+    /// An attribute of the element at `path` was set or removed. `old_value` is its previous
+    /// value, or `None` if it didn't have the attribute.
+    Attribute {
+        path: Vec<usize>,
+        name: String,
+        old_value: Option<String>,
+    },
+    /// The text of the text node at `path` changed.
+    Text { path: Vec<usize> },
+    /// Children of the node at `path` were inserted, removed or replaced.
+    Children { path: Vec<usize> },
+}
+
+/// The parts of a retained style that need recomputing.
+#[derive(Clone, Copy, Default, PartialEq, Debug)]
+pub struct DirtyBits {
+    /// The node's own style needs recomputing.
+    pub restyle: bool,
+    /// The styles of the node and all of its descendants need recomputing.
+    pub restyle_subtree: bool,
+    /// Some descendant has dirty bits set.
+    pub dirty_descendants: bool,
+}
+
+/// The styles of a tree from a previous styling pass, detached from the DOM so that the DOM can be
+/// changed and then restyled incrementally with `style::restyle`.
+#[derive(Clone, Debug)]
+pub struct RetainedStyle {
+    pub specified_values: Arc<PropertyMap>,
+    pub computed: Arc<ComputedStyle>,
+    /// The styles of the children, lined up with the children in the DOM. Children that weren't
+    /// styled, like comments and collapsed whitespace, are `None`.
+    pub children: Vec<Option<RetainedStyle>>,
+    pub dirty: DirtyBits,
+    /// The laid-out content-box size of the node if it is a query container that was styled
+    /// with one, which `@container` rules are evaluated against again on restyle.
+    pub container_size: Option<(f32, f32)>,
+}
+
+impl InvalidationMap {
+    /// Builds the invalidation map of `stylesheet`.
+    pub fn new(stylesheet: &Stylesheet) -> InvalidationMap {
+        let mut map = InvalidationMap::default();
+        for rule in &stylesheet.rules {
+            for selector in &rule.selectors {
+                map.add_selector(selector, false);
+            }
+            for condition in &rule.conditions {
+                if let Condition::Scope(scope) = condition {
+                    for selector in scope.root.iter().chain(&scope.limit) {
+                        map.add_selector(selector, true);
+                    }
+                }
+            }
+        }
+        map
+    }
+
+    /// Adds the ids and classes of `selector`. If `descendants` is true, they all affect
+    /// descendants of the elements that have them.
+    fn add_selector(&mut self, selector: &Selector, descendants: bool) {
+        match selector {
+            Selector::Simple(simple) => self.add_simple_selector(simple, descendants),
+            Selector::Complex(complex) => {
+                self.add_simple_selector(&complex.subject.simple, descendants);
+                for selector in complex.subject.nesting.iter().flatten() {
+                    self.add_selector(selector, descendants);
+                }
+                for (compound, _) in &complex.ancestors {
+                    self.add_simple_selector(&compound.simple, true);
+                    for selector in compound.nesting.iter().flatten() {
+                        self.add_selector(selector, true);
+                    }
+                }
+            }
+        }
+    }
+
+    fn add_simple_selector(&mut self, simple: &SimpleSelector, descendants: bool) {
+        let (ids, classes) = match descendants {
+            true => (&mut self.descendant_ids, &mut self.descendant_classes),
+            false => (&mut self.self_ids, &mut self.self_classes),
+        };
+        ids.extend(simple.id.iter().cloned());
+        classes.extend(simple.class.iter().cloned());
+    }
+
+    /// Returns the dirty bits that changing the ids and classes in `changed` calls for.
+    fn dirty_bits(&self, ids: &HashSet<&str>, classes: &HashSet<&str>) -> DirtyBits {
+        DirtyBits {
+            restyle: ids.iter().any(|&id| self.self_ids.contains(id))
+                || classes
+                    .iter()
+                    .any(|&class| self.self_classes.contains(class)),
+            restyle_subtree: ids.iter().any(|&id| self.descendant_ids.contains(id))
+                || classes
+                    .iter()
+                    .any(|&class| self.descendant_classes.contains(class)),
+            dirty_descendants: false,
+        }
+    }
+}
+
+impl RetainedStyle {
+    /// Returns true if the style or any descendant's style needs recomputing.
+    pub fn is_dirty(&self) -> bool {
+        let dirty = self.dirty;
+        dirty.restyle || dirty.restyle_subtree || dirty.dirty_descendants
+    }
+
+    /// Marks the styles that `change` can affect as dirty. `root` is the DOM after the change.
+    ///
+    /// Returns an error if `path` doesn't lead to a node of the right kind, in which case nothing
+    /// is marked.
+    pub fn invalidate(
+        &mut self,
+        root: &Node,
+        change: &Change,
+        map: &InvalidationMap,
+    ) -> Result<(), &'static str> {
+        let path = match change {
+            Change::Attribute { path, .. } | Change::Text { path } | Change::Children { path } => {
+                path
+            }
+        };
+
+        let mut node = root;
+        for &i in path {
+            node = node.children.get(i).ok_or("no node at path")?;
+        }
+        let bits = match (change, &node.node_type) {
+            (
+                Change::Attribute {
+                    name, old_value, ..
+                },
+                NodeType::Element(elem),
+            ) => attribute_dirty_bits(elem, name, old_value.as_deref(), map),
+            (Change::Text { .. }, NodeType::Text(_)) => DirtyBits {
+                restyle: true,
+                ..DirtyBits::default()
+            },
+            (Change::Children { .. }, _) => DirtyBits {
+                restyle_subtree: true,
+                ..DirtyBits::default()
+            },
+            _ => return Err("change doesn't apply to the node at path"),
+        };
+        if bits == DirtyBits::default() {
+            return Ok(());
+        }
+
+        let mut style = Some(self);
+        for &i in path {
+            let parent = style.ok_or("no retained style at path")?;
+            parent.dirty.dirty_descendants = true;
+            style = parent
+                .children
+                .get_mut(i)
+                .ok_or("no retained style at path")?
+                .as_mut();
+        }
+        // A node that wasn't styled gets styled afresh when its parent restyles its children.
+        if let Some(style) = style {
+            style.dirty.restyle |= bits.restyle;
+            style.dirty.restyle_subtree |= bits.restyle_subtree;
+        }
+        Ok(())
+    }
+
+    /// Marks the styles of the elements that could be affected by changing `old` into `new` as
+    /// dirty. `root` is the DOM the styles belong to.
+    ///
+    /// Only the rules that differ between the stylesheets, other than by where they are in the
+    /// source, are considered: an element is marked if the rightmost compound of one of their
+    /// selectors could match it. Changing the layer order marks everything.
+    pub fn invalidate_stylesheet(&mut self, root: &Node, old: &Stylesheet, new: &Stylesheet) {
+        if old.layers != new.layers {
+            self.dirty.restyle_subtree = true;
+            return;
+        }
+
+        let prefix = old
+            .rules
+            .iter()
+            .zip(&new.rules)
+            .take_while(|(a, b)| a.eq_ignoring_source(b))
+            .count();
+        let suffix = old.rules[prefix..]
+            .iter()
+            .rev()
+            .zip(new.rules[prefix..].iter().rev())
+            .take_while(|(a, b)| a.eq_ignoring_source(b))
+            .count();
+        let changed = old.rules[prefix..old.rules.len() - suffix]
+            .iter()
+            .chain(&new.rules[prefix..new.rules.len() - suffix])
+            .collect::<Vec<_>>();
+        if !changed.is_empty() {
+            self.invalidate_rules(root, &changed);
+        }
+    }
+
+    /// Marks the elements that the rightmost compound of a selector in `rules` could match.
+    /// Returns true if anything was marked.
+    fn invalidate_rules(&mut self, node: &Node, rules: &[&Rule]) -> bool {
+        if let NodeType::Element(ref elem) = node.node_type {
+            if rules.iter().any(|rule| could_match(elem, rule)) {
+                self.dirty.restyle = true;
+            }
+        }
+        let mut dirty_descendants = false;
+        for (child, style) in node.children.iter().zip(&mut self.children) {
+            if let Some(style) = style {
+                dirty_descendants |= style.invalidate_rules(child, rules);
+            }
+        }
+        self.dirty.dirty_descendants |= dirty_descendants;
+        self.is_dirty()
+    }
+}
+
+/// Returns true if the rightmost compound of a selector of `rule` matches `elem`.
+fn could_match(elem: &ElementData, rule: &Rule) -> bool {
+    rule.selectors
+        .iter()
+        .any(|selector| matches_simple_selector(elem, selector.subject()))
+}
+
+/// Returns the dirty bits for changing the attribute `name` of `elem` from `old_value`.
+///
+/// Selectors can only test ids and classes, so other attributes don't affect styles.
+fn attribute_dirty_bits(
+    elem: &ElementData,
+    name: &str,
+    old_value: Option<&str>,
+    map: &InvalidationMap,
+) -> DirtyBits {
+    let new_value = elem.attributes.get(name).map(String::as_str);
+    let mut ids = HashSet::new();
+    let mut classes = HashSet::new();
+    match name {
+        "id" if old_value != new_value => ids.extend(old_value.into_iter().chain(new_value)),
+        "class" => {
+            fn words(value: Option<&str>) -> HashSet<&str> {
+                value
+                    .unwrap_or("")
+                    .split(' ')
+                    .filter(|word| !word.is_empty())
+                    .collect()
+            }
+            classes.extend(words(old_value).symmetric_difference(&words(new_value)));
+        }
+        _ => {}
+    }
+    map.dirty_bits(&ids, &classes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{css, html};
+
+    fn retained(node: &Node) -> RetainedStyle {
+        RetainedStyle {
+            specified_values: Arc::new(PropertyMap::new()),
+            computed: Arc::default(),
+            children: node
+                .children
+                .iter()
+                .map(|child| Some(retained(child)))
+                .collect(),
+            dirty: DirtyBits::default(),
+            container_size: None,
+        }
+    }
+
+    fn child(style: &RetainedStyle, i: usize) -> &RetainedStyle {
+        style.children[i].as_ref().unwrap()
+    }
+
+    fn set_attribute(root: &mut Node, path: &[usize], name: &str, value: &str) -> Change {
+        let mut node = root;
+        for &i in path {
+            node = &mut node.children[i];
+        }
+        let NodeType::Element(ref mut elem) = node.node_type else {
+            panic!("expected an element");
+        };
+        let old_value = elem
+            .attributes
+            .insert(String::from(name), String::from(value));
+        Change::Attribute {
+            path: path.to_vec(),
+            name: String::from(name),
+            old_value,
+        }
+    }
+
+    #[test]
+    fn test_invalidation_map() {
+        let stylesheet = css::parse(String::from(
+            ".a { color: a; } #main .b > p.c { color: b; } .d { &.e { color: c; } } \
+             @scope (.f) to (#g) { p { color: d; } }",
+        ))
+        .unwrap();
+        let map = InvalidationMap::new(&stylesheet);
+        let set = |names: &[&str]| names.iter().map(|&s| String::from(s)).collect();
+        assert_eq!(map.self_classes, set(&["a", "c", "d", "e"]));
+        assert_eq!(map.self_ids, set(&[]));
+        assert_eq!(map.descendant_classes, set(&["b", "f"]));
+        assert_eq!(map.descendant_ids, set(&["main", "g"]));
+    }
+
+    #[test]
+    fn test_invalidate_attribute_changes() {
+        let mut root = html::parse(String::from(
+            "<div class=\"a\"><p class=\"x\"></p><p></p></div>",
+        ))
+        .unwrap();
+        let stylesheet = css::parse(String::from(".a p { color: a; } .b { color: b; }")).unwrap();
+        let map = InvalidationMap::new(&stylesheet);
+
+        let mut style = retained(&root);
+        let change = set_attribute(&mut root, &[0], "class", "x b");
+        style.invalidate(&root, &change, &map).unwrap();
+        assert!(style.dirty.dirty_descendants);
+        assert!(!style.dirty.restyle);
+        assert!(child(&style, 0).dirty.restyle);
+        assert!(!child(&style, 1).is_dirty());
+
+        let mut style = retained(&root);
+        let change = set_attribute(&mut root, &[], "class", "");
+        style.invalidate(&root, &change, &map).unwrap();
+        assert!(style.dirty.restyle_subtree);
+
+        // Attributes selectors can't see, and classes no selector uses, need no restyle.
+        let mut style = retained(&root);
+        let change = set_attribute(&mut root, &[1], "title", "hello");
+        style.invalidate(&root, &change, &map).unwrap();
+        let change = set_attribute(&mut root, &[1], "class", "unused");
+        style.invalidate(&root, &change, &map).unwrap();
+        assert!(!style.is_dirty());
+
+        let change = Change::Text { path: vec![1] };
+        assert!(style.invalidate(&root, &change, &map).is_err());
+    }
+
+    #[test]
+    fn test_invalidate_stylesheet() {
+        let root = html::parse(String::from(
+            "<div><p class=\"a\"></p><p class=\"b\"></p><span></span></div>",
+        ))
+        .unwrap();
+        let old = css::parse(String::from(
+            "p { color: p; } .a { color: a; } span { color: span; }",
+        ))
+        .unwrap();
+        let new = css::parse(String::from(
+            "p { color: p; } .a { color: changed; } span { color: span; }",
+        ))
+        .unwrap();
+
+        let mut style = retained(&root);
+        style.invalidate_stylesheet(&root, &old, &new);
+        assert!(style.dirty.dirty_descendants);
+        assert!(!style.dirty.restyle);
+        assert!(child(&style, 0).dirty.restyle);
+        assert!(!child(&style, 1).is_dirty());
+        assert!(!child(&style, 2).is_dirty());
+
+        let layered = css::parse(String::from("@layer a; p { color: p; }")).unwrap();
+        let mut style = retained(&root);
+        style.invalidate_stylesheet(&root, &old, &layered);
+        assert!(style.dirty.restyle_subtree);
+    }
+}
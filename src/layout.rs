@@ -0,0 +1,1722 @@
+use std::sync::Arc;
+
+use crate::{
+    computed::{
+        serialize_property, BoxSizing, ComputedStyle, Display, DisplayInside, DisplayOutside,
+        Float, LengthPercentageAuto, LengthPercentageNone, Overflow, Position,
+    },
+    dom::{Node, NodeType},
+    float::{FloatContext, Side},
+    font::FontDatabase,
+    inline::{self, LineBox},
+    media::Environment,
+    rule_index::RuleIndex,
+    style::{style_tree_with_container_sizes, ContainerSizes, PropertyMap, StyledNode},
+    table,
+};
+
+/// The position and size of a rectangle, in px.
+#[derive(Clone, Copy, Default, PartialEq, Debug)]
+pub struct Rect {
+    pub x: f32,
+    pub y: f32,
+    pub width: f32,
+    pub height: f32,
+}
+
+/// The sizes of the four edges of a box, in px.
+#[derive(Clone, Copy, Default, PartialEq, Debug)]
+pub struct EdgeSizes {
+    pub left: f32,
+    pub right: f32,
+    pub top: f32,
+    pub bottom: f32,
+}
+
+/// The content area of a box and the sizes of the areas around it.
+#[derive(Clone, Copy, Default, PartialEq, Debug)]
+pub struct Dimensions {
+    /// The position of the content area relative to the document origin, and its size.
+    pub content: Rect,
+    pub padding: EdgeSizes,
+    pub border: EdgeSizes,
+    pub margin: EdgeSizes,
+}
+
+/// The rectangle a box is sized and positioned against.
+#[derive(Clone, Copy, Debug)]
+pub(crate) struct ContainingBlock {
+    /// The content area of the containing block.
+    pub(crate) rect: Rect,
+    /// The height of the containing block if it is known before its contents are laid out, which
+    /// percentage heights need.
+    pub(crate) height: Option<f32>,
+}
+
+/// A set of adjoining vertical margins, which collapse into a single margin: the largest positive
+/// margin plus the most negative one, as in CSS 2.1 § 8.3.1.
+#[derive(Clone, Copy, Default, PartialEq, Debug)]
+pub(crate) struct CollapsedMargin {
+    positive: f32,
+    negative: f32,
+}
+
+impl CollapsedMargin {
+    fn new(margin: f32) -> CollapsedMargin {
+        CollapsedMargin {
+            positive: margin.max(0.0),
+            negative: margin.min(0.0),
+        }
+    }
+
+    /// Returns the set of the margins in both sets.
+    fn adjoin(self, other: CollapsedMargin) -> CollapsedMargin {
+        CollapsedMargin {
+            positive: self.positive.max(other.positive),
+            negative: self.negative.min(other.negative),
+        }
+    }
+
+    /// Returns the width of the collapsed margin.
+    fn resolve(self) -> f32 {
+        self.positive + self.negative
+    }
+}
+
+/// A node of the box tree.
+pub struct LayoutBox<'a> {
+    pub dimensions: Dimensions,
+    pub box_type: BoxType<'a>,
+    pub children: Vec<LayoutBox<'a>>,
+    /// The line boxes of a block container whose children are inline-level, which they are laid
+    /// out in.
+    pub lines: Vec<LineBox<'a>>,
+    /// The style of a table wrapper box, which takes the properties of the table element that
+    /// position the table and leaves the rest to the table box inside it.
+    wrapper_style: Option<Arc<ComputedStyle>>,
+    /// The used `left`, `right`, `top` and `bottom` of a relatively or absolutely positioned box,
+    /// once it has been laid out.
+    pub(crate) insets: Option<EdgeSizes>,
+}
+
+/// The kind of box a `LayoutBox` is, and what generated it.
+#[derive(Clone)]
+pub enum BoxType<'a> {
+    /// A block-level box generated by an element.
+    BlockNode(&'a StyledNode<'a>),
+    /// An inline-level box generated by an element or a text node.
+    InlineNode(&'a StyledNode<'a>),
+    /// A block box wrapping a run of inline-level boxes whose siblings are block-level, with the
+    /// style it inherits from its parent. Anonymous table objects are anonymous blocks too, with
+    /// the display type of their role in the table.
+    AnonymousBlock(Arc<ComputedStyle>),
+    /// The table box inside a table wrapper box, which contains the rows and columns of the table
+    /// and has the properties of the table element that the wrapper box doesn't.
+    TableBox(Arc<ComputedStyle>),
+}
+
+impl Rect {
+    /// Returns the rectangle grown by `edge` on each side.
+    pub fn expanded_by(self, edge: EdgeSizes) -> Rect {
+        Rect {
+            x: self.x - edge.left,
+            y: self.y - edge.top,
+            width: self.width + edge.left + edge.right,
+            height: self.height + edge.top + edge.bottom,
+        }
+    }
+}
+
+impl Dimensions {
+    /// Returns the area covered by the content area plus its padding.
+    pub fn padding_box(self) -> Rect {
+        self.content.expanded_by(self.padding)
+    }
+
+    /// Returns the area covered by the content area plus padding and borders.
+    pub fn border_box(self) -> Rect {
+        self.padding_box().expanded_by(self.border)
+    }
+
+    /// Returns the area covered by the content area plus padding, borders and margin.
+    pub fn margin_box(self) -> Rect {
+        self.border_box().expanded_by(self.margin)
+    }
+}
+
+impl<'a> LayoutBox<'a> {
+    pub(crate) fn new(box_type: BoxType<'a>) -> LayoutBox<'a> {
+        let wrapper_style = match box_type {
+            BoxType::BlockNode(styled) | BoxType::InlineNode(styled)
+                if styled.display().inside() == Some(DisplayInside::Table) =>
+            {
+                Some(Arc::new(table::wrapper_style(&styled.computed)))
+            }
+            _ => None,
+        };
+        LayoutBox {
+            dimensions: Dimensions::default(),
+            box_type,
+            children: Vec::new(),
+            lines: Vec::new(),
+            wrapper_style,
+            insets: None,
+        }
+    }
+
+    /// Returns the computed style of the box.
+    pub fn style(&self) -> &ComputedStyle {
+        match self.wrapper_style {
+            Some(ref style) => style,
+            None => self.box_type.style(),
+        }
+    }
+
+    /// Returns the styled node that generated the box, or `None` for an anonymous box.
+    pub fn styled_node(&self) -> Option<&'a StyledNode<'a>> {
+        match self.box_type {
+            BoxType::BlockNode(styled) | BoxType::InlineNode(styled) => Some(styled),
+            BoxType::AnonymousBlock(_) | BoxType::TableBox(_) => None,
+        }
+    }
+
+    /// Returns true if the box is block-level.
+    pub fn is_block_level(&self) -> bool {
+        !matches!(self.box_type, BoxType::InlineNode(_))
+    }
+
+    /// Returns true if the box is floated, which takes it out of flow.
+    pub fn is_float(&self) -> bool {
+        match self.box_type {
+            BoxType::BlockNode(styled) => styled.computed.float() != Float::None,
+            _ => false,
+        }
+    }
+
+    /// Returns true if the box is absolutely positioned, with `position: absolute` or `fixed`,
+    /// which takes it out of flow.
+    pub fn is_absolutely_positioned(&self) -> bool {
+        match self.box_type {
+            BoxType::BlockNode(styled) => matches!(
+                styled.computed.position(),
+                Position::Absolute | Position::Fixed
+            ),
+            _ => false,
+        }
+    }
+
+    /// Returns true if the box is floated or absolutely positioned, and so takes no part in the
+    /// layout of its siblings in normal flow.
+    pub fn is_out_of_flow(&self) -> bool {
+        self.is_float() || self.is_absolutely_positioned()
+    }
+
+    /// Returns true if the box is an atomic inline, such as an inline block, which takes part in
+    /// an inline formatting context as a single opaque box.
+    pub fn is_atomic_inline(&self) -> bool {
+        match self.box_type {
+            BoxType::InlineNode(styled) => {
+                !matches!(styled.node.node_type, NodeType::Text(_))
+                    && styled.display().inside() != Some(DisplayInside::Flow)
+            }
+            _ => false,
+        }
+    }
+
+    /// Returns the box generated by `node` among the box and its descendants, or the first of them
+    /// if `node` generates several.
+    pub fn find(&self, node: &Node) -> Option<&LayoutBox<'a>> {
+        match self.styled_node() {
+            Some(styled) if std::ptr::eq(styled.node, node) => Some(self),
+            _ => self.children.iter().find_map(|child| child.find(node)),
+        }
+    }
+
+    /// Returns the resolved value of the property `name` for the element that generated the box,
+    /// serialized like `getComputedStyle` does, or `None` if the property is unknown.
+    ///
+    /// The widths, heights, margins and paddings of boxes that aren't inline boxes, and the insets
+    /// of relatively and absolutely positioned boxes, resolve to their used values in px, with
+    /// `width` and `height` of the border box if `box-sizing` is `border-box`. Other properties
+    /// resolve as `StyledNode::property_value` does.
+    pub fn property_value(&self, name: &str) -> Option<String> {
+        let styled = self.styled_node();
+        serialize_property(name, |longhand| match self.used_value(longhand) {
+            // Adding 0 turns -0 into 0, which serializes without a sign.
+            Some(used) => Some(format!("{}px", used + 0.0)),
+            None => match styled {
+                Some(styled) => styled.property_value(longhand),
+                None => self.style().property_value(longhand),
+            },
+        })
+    }
+
+    /// Returns the used value of the longhand `name` if it is a size, margin, padding or inset
+    /// that layout gave the box.
+    fn used_value(&self, name: &str) -> Option<f32> {
+        if !self.is_block_level() && !self.is_atomic_inline() {
+            return None;
+        }
+        // The table box has the table's size and padding, and the wrapper box its margins.
+        let sized = match self.is_table_wrapper() {
+            true => self.table_box()?,
+            false => self,
+        };
+        let margin = self.dimensions.margin;
+        let Dimensions {
+            content, padding, ..
+        } = sized.dimensions;
+        let size = match sized.style().box_sizing() {
+            BoxSizing::ContentBox => content,
+            BoxSizing::BorderBox => sized.dimensions.border_box(),
+        };
+        Some(match name {
+            "width" => size.width,
+            "height" => size.height,
+            "margin-top" => margin.top,
+            "margin-right" => margin.right,
+            "margin-bottom" => margin.bottom,
+            "margin-left" => margin.left,
+            "padding-top" => padding.top,
+            "padding-right" => padding.right,
+            "padding-bottom" => padding.bottom,
+            "padding-left" => padding.left,
+            "top" => self.insets?.top,
+            "right" => self.insets?.right,
+            "bottom" => self.insets?.bottom,
+            "left" => self.insets?.left,
+            _ => return None,
+        })
+    }
+
+    /// Moves the box and everything in it by `dx` and `dy`.
+    pub fn translate(&mut self, dx: f32, dy: f32) {
+        self.dimensions.content.x += dx;
+        self.dimensions.content.y += dy;
+        for line in &mut self.lines {
+            line.translate(dx, dy);
+        }
+        for child in &mut self.children {
+            child.translate(dx, dy);
+        }
+    }
+}
+
+impl BoxType<'_> {
+    /// Returns the computed style of the box.
+    pub fn style(&self) -> &ComputedStyle {
+        match *self {
+            BoxType::BlockNode(styled) | BoxType::InlineNode(styled) => &styled.computed,
+            BoxType::AnonymousBlock(ref style) | BoxType::TableBox(ref style) => style,
+        }
+    }
+}
+
+/// Builds the box tree of the styled tree rooted at `root` and lays it out for `viewport`, setting
+/// text in `fonts`. Returns `None` if the root has `display: none`.
+///
+/// `viewport` is the visible area of the document, so its position is how far the document is
+/// scrolled. The initial containing block is the size of the viewport at the document origin.
+/// Fixed positioned boxes are placed in the viewport, and sticky positioned boxes are shifted to
+/// stay in it.
+pub fn layout_tree<'a>(
+    root: &'a StyledNode<'a>,
+    viewport: Rect,
+    fonts: &FontDatabase,
+) -> Option<LayoutBox<'a>> {
+    let mut root_box = build_layout_tree(root)?;
+    let initial = Rect {
+        x: 0.0,
+        y: 0.0,
+        ..viewport
+    };
+    let containing_block = ContainingBlock {
+        rect: initial,
+        height: Some(initial.height),
+    };
+    // The margins of the root box don't collapse.
+    root_box.calculate_block_width(containing_block, fonts);
+    root_box.calculate_block_position(containing_block, initial.y);
+    let y = initial.y + root_box.dimensions.margin.top;
+    root_box.layout_block(containing_block, y, None, fonts);
+    root_box.apply_relative_offset(containing_block);
+    if root_box.style().position() == Position::Static {
+        root_box.layout_absolute_descendants(initial, fonts);
+    }
+    root_box.layout_fixed_descendants(viewport, fonts);
+    let content = root_box.dimensions.content;
+    root_box.apply_sticky_offsets(content, viewport);
+    Some(root_box)
+}
+
+/// Styles the tree rooted at `root` with the rules of `index` in `environment`, and returns it
+/// for `layout_tree` to lay out for `viewport` with text set in `fonts`.
+///
+/// `@container` rules are evaluated against the laid-out sizes of query containers: if the tree
+/// has any, it is laid out and styled again with the content-box sizes layout gave them, so that
+/// containers sized by `auto`, percentages, or flex and grid layout match size queries too. The
+/// containers keep the sizes of the first layout, where the rules that depend on them didn't
+/// apply yet, even if those rules change how big their contents are.
+pub fn style_tree_for_layout<'a>(
+    root: &'a Node,
+    index: &RuleIndex,
+    environment: &Environment,
+    viewport: Rect,
+    fonts: &FontDatabase,
+) -> StyledNode<'a> {
+    let styled = style_tree_with_container_sizes(root, index, environment, Default::default());
+    if !has_query_containers(&styled) {
+        return styled;
+    }
+    let sizes = match layout_tree(&styled, viewport, fonts) {
+        Some(root_box) => root_box.container_sizes().in_tree(root),
+        None => return styled,
+    };
+    style_tree_with_container_sizes(root, index, environment, sizes)
+}
+
+/// Returns true if `styled` or one of its descendants is a query container.
+fn has_query_containers(styled: &StyledNode) -> bool {
+    styled.is_query_container() || styled.children.iter().any(has_query_containers)
+}
+
+impl<'a> LayoutBox<'a> {
+    /// Returns the content-box sizes of the query containers among the box and its descendants.
+    pub fn container_sizes(&self) -> ContainerSizes<'a> {
+        fn collect<'a>(layout_box: &LayoutBox<'a>, sizes: &mut ContainerSizes<'a>) {
+            // Only boxes whose size doesn't come from their line boxes can be queried.
+            let sized = layout_box.is_block_level() || layout_box.is_atomic_inline();
+            if let Some(styled) = layout_box.styled_node().filter(|_| sized) {
+                if styled.is_query_container() {
+                    let content = layout_box.dimensions.content;
+                    sizes.insert(styled.node, content.width, content.height);
+                }
+            }
+            for child in &layout_box.children {
+                collect(child, sizes);
+            }
+        }
+
+        let mut sizes = ContainerSizes::default();
+        collect(self, &mut sizes);
+        sizes
+    }
+    /// Lays out an in-flow block-level box and its descendants in `containing_block`, in the block
+    /// formatting context whose floats are `floats`, with the top of its border box at `y`.
+    ///
+    /// A box that establishes a new block formatting context moves down past the floats it
+    /// doesn't fit beside. Returns the top of its border box and the margins adjoining its bottom
+    /// margin edge, which may collapse with the margins that follow. Neither counts the offset of
+    /// a relatively positioned box, which doesn't affect the boxes around it.
+    fn layout(
+        &mut self,
+        containing_block: ContainingBlock,
+        y: f32,
+        floats: &mut FloatContext,
+        fonts: &FontDatabase,
+    ) -> (f32, CollapsedMargin) {
+        let (y, margin) = match self.establishes_block_formatting_context() {
+            true => self.layout_beside_floats(containing_block, y, floats, fonts),
+            false => (
+                y,
+                self.layout_block(containing_block, y, Some(floats), fonts),
+            ),
+        };
+        self.apply_relative_offset(containing_block);
+        (y, margin)
+    }
+
+    /// Lays out a block-level box that establishes a new block formatting context, whose border
+    /// box must not overlap the margin boxes of `floats`, as in CSS 2.1 § 9.5. It is narrowed to
+    /// fit beside them at `y`, or moved down until it does. Returns the top of its border box and
+    /// its bottom margin.
+    fn layout_beside_floats(
+        &mut self,
+        containing_block: ContainingBlock,
+        y: f32,
+        floats: &FloatContext,
+        fonts: &FontDatabase,
+    ) -> (f32, CollapsedMargin) {
+        let rect = containing_block.rect;
+        let (left, right) = (rect.x, rect.x + rect.width);
+        let mut y = y;
+        loop {
+            let (band_left, band_right) = floats.band(y, 0.0, left, right);
+            let narrowed = ContainingBlock {
+                rect: Rect {
+                    x: band_left,
+                    width: band_right - band_left,
+                    ..rect
+                },
+                ..containing_block
+            };
+            let margin = self.layout_block(narrowed, y, None, fonts);
+            let border_box = self.dimensions.border_box();
+            let (band_left, band_right) = floats.band(y, border_box.height, left, right);
+            let fits = border_box.x >= band_left && border_box.x + border_box.width <= band_right;
+            match floats.next_bottom(y) {
+                Some(bottom) if !fits => y = bottom,
+                _ => return (y, margin),
+            }
+        }
+    }
+
+    /// Lays out a block-level box in normal flow, as in CSS 2.1 §§ 10.3.3 and 10.6.3, stacking
+    /// its block-level children with their adjoining margins collapsed and placing its floats.
+    ///
+    /// `floats` are the floats of the block formatting context the box's contents are in, or
+    /// `None` if it establishes a new one. Its margins then don't collapse with its children's,
+    /// and its height grows to contain its floats.
+    pub(crate) fn layout_block(
+        &mut self,
+        containing_block: ContainingBlock,
+        y: f32,
+        floats: Option<&mut FloatContext>,
+        fonts: &FontDatabase,
+    ) -> CollapsedMargin {
+        self.calculate_block_width(containing_block, fonts);
+        self.calculate_block_position(containing_block, y);
+        let sizes = self.vertical_sizes(containing_block);
+        self.layout_contents(sizes, floats, fonts)
+    }
+
+    /// Lays out the contents of a block container whose width and edges are known, and computes
+    /// its height from `sizes`, the content heights given by `vertical_sizes`. Returns the
+    /// margins adjoining its bottom margin edge, as `layout_block` does.
+    ///
+    /// A positioned box then lays out the absolutely positioned boxes it is the containing block
+    /// of.
+    pub(crate) fn layout_contents(
+        &mut self,
+        sizes: (Option<f32>, f32, Option<f32>),
+        floats: Option<&mut FloatContext>,
+        fonts: &FontDatabase,
+    ) -> CollapsedMargin {
+        let independent = floats.is_none();
+        let mut own_floats = FloatContext::default();
+        let floats = floats.unwrap_or(&mut own_floats);
+        let (height, min_height, max_height) = sizes;
+        let clamp = |height: f32| {
+            max_height
+                .map_or(height, |max| height.min(max))
+                .max(min_height)
+        };
+        let height = height.map(clamp);
+        let content = self.dimensions.content;
+        let children_block = ContainingBlock {
+            rect: Rect {
+                height: height.unwrap_or(0.0),
+                ..content
+            },
+            height,
+        };
+
+        let mut cursor = content.y;
+        // The margins collapsed since the bottom border edge of the last child, or the top of the
+        // content area.
+        let mut pending = CollapsedMargin::default();
+        // Whether the margins so far collapsed with this box's top margin, and so were already
+        // accounted for when positioning it.
+        let mut leading = !independent && self.top_margin_collapses_with_children();
+        if self.is_flex_container() {
+            cursor += self.layout_flex(children_block, min_height, max_height, fonts);
+        } else if self.is_grid_container() {
+            cursor += self.layout_grid(children_block, min_height, max_height, fonts);
+        } else if self.is_table_wrapper() {
+            cursor += self.layout_table_wrapper(children_block, fonts);
+        } else if self.children.iter().any(|child| !child.is_block_level()) {
+            // The children are inline-level or floats, and are laid out in line boxes.
+            let LayoutBox {
+                box_type,
+                children,
+                lines,
+                ..
+            } = self;
+            *lines =
+                inline::layout_lines(box_type.style(), children, children_block, floats, fonts);
+            if let Some(last) = lines.last() {
+                cursor = last.rect.y + last.rect.height;
+            }
+        } else {
+            for child in self.children.iter_mut() {
+                // An out-of-flow box goes where the next in-flow box would if it had no top
+                // margin. An absolutely positioned one is laid out with its containing block.
+                let next_y = match leading {
+                    true => cursor,
+                    false => cursor + pending.resolve(),
+                };
+                if child.is_float() {
+                    child.layout_atomic(children_block, fonts);
+                    child.place_float(children_block, next_y, floats);
+                    continue;
+                }
+                if child.is_absolutely_positioned() {
+                    child.set_static_position(children_block.rect.x, next_y);
+                    continue;
+                }
+                let (top, collapses_through) = child.collapsed_top_margin(children_block, fonts);
+                let mut child_y = match leading {
+                    true => cursor,
+                    false => cursor + pending.adjoin(top).resolve(),
+                };
+                // A box that clears floats above where it would be otherwise moves below them,
+                // as in CSS 2.1 § 9.5.2, and its margins no longer collapse with those before it.
+                let clearance = floats
+                    .clearance(child.style().clear())
+                    .filter(|&bottom| bottom > child_y);
+                if let Some(bottom) = clearance {
+                    child_y = bottom;
+                }
+                let (child_y, bottom) = child.layout(children_block, child_y, floats, fonts);
+                if collapses_through && clearance.is_none() {
+                    // All of the child's margins adjoin, and `top` already holds them.
+                    if !leading {
+                        pending = pending.adjoin(top);
+                    }
+                } else {
+                    leading = false;
+                    cursor = child_y + child.dimensions.border_box().height;
+                    pending = bottom;
+                }
+            }
+        }
+
+        let margin_bottom = CollapsedMargin::new(self.dimensions.margin.bottom);
+        let collapse_bottom = !independent && self.bottom_margin_collapses_with_children();
+        let mut content_height = match collapse_bottom {
+            true => cursor - content.y,
+            false => cursor + pending.resolve() - content.y,
+        };
+        if independent {
+            if let Some(bottom) = floats.bottom() {
+                content_height = content_height.max(bottom - content.y);
+            }
+        }
+        self.dimensions.content.height = height.unwrap_or_else(|| clamp(content_height));
+        if self.style().position() != Position::Static {
+            let padding_box = self.dimensions.padding_box();
+            self.layout_absolute_descendants(padding_box, fonts);
+        }
+        match collapse_bottom {
+            true => pending.adjoin(margin_bottom),
+            false => margin_bottom,
+        }
+    }
+
+    /// Returns the margins adjoining the top margin edge of a block-level box, and whether its
+    /// margins collapse through it because it is empty, in which case all of them adjoin.
+    ///
+    /// This needs the vertical edges of the box, so it computes them, along with its width.
+    fn collapsed_top_margin(
+        &mut self,
+        containing_block: ContainingBlock,
+        fonts: &FontDatabase,
+    ) -> (CollapsedMargin, bool) {
+        self.calculate_block_width(containing_block, fonts);
+        self.calculate_block_position(containing_block, 0.0);
+        let mut margin = CollapsedMargin::new(self.dimensions.margin.top);
+        if self.establishes_block_formatting_context() || !self.top_margin_collapses_with_children()
+        {
+            return (margin, false);
+        }
+
+        let children_block = ContainingBlock {
+            rect: self.dimensions.content,
+            height: None,
+        };
+        // Line boxes separate the margins, unless there are none, as when the only content is
+        // collapsible white space.
+        if inline::has_lines(self.style(), &self.children) {
+            return (margin, false);
+        }
+        for child in self
+            .children
+            .iter_mut()
+            .filter(|child| child.is_block_level() && !child.is_out_of_flow())
+        {
+            let (top, collapses_through) = child.collapsed_top_margin(children_block, fonts);
+            margin = margin.adjoin(top);
+            if !collapses_through {
+                return (margin, false);
+            }
+        }
+
+        let style = self.style();
+        let empty = self.bottom_margin_collapses_with_children()
+            && matches!(style.height().resolve(0.0), None | Some(0.0))
+            && matches!(style.min_height().resolve(0.0), None | Some(0.0));
+        match empty {
+            true => (
+                margin.adjoin(CollapsedMargin::new(self.dimensions.margin.bottom)),
+                true,
+            ),
+            false => (margin, false),
+        }
+    }
+
+    /// Returns true if the top margin of a block box adjoins the top margin of its first child,
+    /// i.e. it has no top border or padding.
+    fn top_margin_collapses_with_children(&self) -> bool {
+        self.dimensions.border.top == 0.0 && self.dimensions.padding.top == 0.0
+    }
+
+    /// Returns true if the bottom margin of a block box adjoins the bottom margin of its last
+    /// child, i.e. it has no bottom border or padding and its height depends on its contents.
+    fn bottom_margin_collapses_with_children(&self) -> bool {
+        let style = self.style();
+        self.dimensions.border.bottom == 0.0
+            && self.dimensions.padding.bottom == 0.0
+            && style.height() == LengthPercentageAuto::Auto
+            && matches!(style.min_height().resolve(0.0), None | Some(0.0))
+    }
+
+    /// Returns true if a block-level box establishes a new block formatting context for its
+    /// contents, which isolates their margins from its own.
+    fn establishes_block_formatting_context(&self) -> bool {
+        if self.is_table_wrapper() {
+            return true;
+        }
+        let BoxType::BlockNode(styled) = self.box_type else {
+            return false;
+        };
+        let style = &styled.computed;
+        style.display().inside() != Some(DisplayInside::Flow)
+            || style.float() != Float::None
+            || matches!(style.position(), Position::Absolute | Position::Fixed)
+            || style.overflow() != Overflow::Visible
+    }
+
+    /// Computes the used width and horizontal margins, padding and borders of a block-level box,
+    /// solving the constraint that its margin box is as wide as its containing block for the
+    /// `auto` values.
+    ///
+    /// The tentative width is then limited by `max-width` and `min-width`, in that order, by
+    /// solving the constraint again with the limit as the width. The width of a table wrapper box
+    /// is that of its table, which the table layout algorithm determines instead.
+    fn calculate_block_width(&mut self, containing_block: ContainingBlock, fonts: &FontDatabase) {
+        let style = self.style();
+        let reference = containing_block.rect.width;
+        let padding_left = style.padding_left().resolve(reference);
+        let padding_right = style.padding_right().resolve(reference);
+        let border_left = style.border_left_width();
+        let border_right = style.border_right_width();
+        let edges = padding_left + padding_right + border_left + border_right;
+        let content_size = content_size(style.box_sizing(), edges);
+
+        let margin_left = style.margin_left().resolve(reference);
+        let margin_right = style.margin_right().resolve(reference);
+        let width = match self.is_table_wrapper() {
+            true => {
+                let margins = margin_left.unwrap_or(0.0) + margin_right.unwrap_or(0.0);
+                Some(self.table_used_width(reference, reference - margins, fonts))
+            }
+            false => style.width().resolve(reference).map(content_size),
+        };
+        let min_width = style
+            .min_width()
+            .resolve(reference)
+            .map_or(0.0, content_size);
+        let max_width = style.max_width().resolve(reference).map(content_size);
+
+        let available = reference - edges;
+        let shrinks_to_fit = self.shrinks_to_fit();
+        let solve = |width: Option<f32>| match shrinks_to_fit {
+            // Auto margins are 0, and an auto width is the shrink-to-fit width of CSS 2.1
+            // § 10.3.5: the available width limited to the min-content and max-content widths.
+            true => {
+                let margin_left = margin_left.unwrap_or(0.0);
+                let margin_right = margin_right.unwrap_or(0.0);
+                let width = width.unwrap_or_else(|| {
+                    let (min_content, max_content) = self.intrinsic_widths(fonts);
+                    (available - margin_left - margin_right)
+                        .max(min_content)
+                        .min(max_content)
+                });
+                (margin_left, width, margin_right)
+            }
+            false => solve_width(width, margin_left, margin_right, available),
+        };
+        let mut used = solve(width);
+        if let Some(max_width) = max_width.filter(|&max_width| used.1 > max_width) {
+            used = solve(Some(max_width));
+        }
+        if used.1 < min_width {
+            used = solve(Some(min_width));
+        }
+
+        let dimensions = &mut self.dimensions;
+        (
+            dimensions.margin.left,
+            dimensions.content.width,
+            dimensions.margin.right,
+        ) = used;
+        dimensions.padding.left = padding_left;
+        dimensions.padding.right = padding_right;
+        dimensions.border.left = border_left;
+        dimensions.border.right = border_right;
+    }
+
+    /// Returns true if an `auto` width of the box shrinks to fit its contents rather than filling
+    /// its containing block.
+    fn shrinks_to_fit(&self) -> bool {
+        !self.is_block_level() || self.is_out_of_flow()
+    }
+
+    /// Returns the min-content and max-content widths of the content box, as in CSS Sizing 3
+    /// § 5.1. Percentages are treated as `auto`, and those of padding as 0.
+    pub(crate) fn intrinsic_widths(&self, fonts: &FontDatabase) -> (f32, f32) {
+        let style = self.style();
+        let edges = style.padding_left().resolve(0.0)
+            + style.padding_right().resolve(0.0)
+            + style.border_left_width()
+            + style.border_right_width();
+        let content_size = content_size(style.box_sizing(), edges);
+        let (mut min_content, mut max_content) = match style.width() {
+            LengthPercentageAuto::Px(width) => (content_size(width), content_size(width)),
+            _ => self.contents_intrinsic_widths(fonts),
+        };
+        if let LengthPercentageNone::Px(max_width) = style.max_width() {
+            min_content = min_content.min(content_size(max_width));
+            max_content = max_content.min(content_size(max_width));
+        }
+        if let LengthPercentageAuto::Px(min_width) = style.min_width() {
+            min_content = min_content.max(content_size(min_width));
+            max_content = max_content.max(content_size(min_width));
+        }
+        (min_content, max_content)
+    }
+
+    /// Returns the min-content and max-content widths of the contents of the box, regardless of
+    /// its own sizing properties.
+    pub(crate) fn contents_intrinsic_widths(&self, fonts: &FontDatabase) -> (f32, f32) {
+        if self.is_flex_container() {
+            self.flex_intrinsic_widths(fonts)
+        } else if self.is_grid_container() {
+            self.grid_intrinsic_widths(fonts)
+        } else if self.is_table_wrapper() {
+            self.table_intrinsic_widths(fonts)
+        } else if self.children.iter().any(|child| !child.is_block_level()) {
+            inline::intrinsic_widths(self.style(), &self.children, fonts)
+        } else {
+            self.children
+                .iter()
+                .filter(|child| !child.is_absolutely_positioned())
+                .map(|child| child.intrinsic_outer_widths(fonts))
+                .fold((0.0, 0.0), |(min, max), (child_min, child_max)| {
+                    (f32::max(min, child_min), f32::max(max, child_max))
+                })
+        }
+    }
+
+    /// Returns the min-content and max-content widths of the margin box.
+    pub(crate) fn intrinsic_outer_widths(&self, fonts: &FontDatabase) -> (f32, f32) {
+        let style = self.style();
+        let edges = style.margin_left().resolve(0.0).unwrap_or(0.0)
+            + style.margin_right().resolve(0.0).unwrap_or(0.0)
+            + style.padding_left().resolve(0.0)
+            + style.padding_right().resolve(0.0)
+            + style.border_left_width()
+            + style.border_right_width();
+        let (min_content, max_content) = self.intrinsic_widths(fonts);
+        (min_content + edges, max_content + edges)
+    }
+
+    /// Lays out an atomic inline or a float with the top left corner of its margin box at the top
+    /// left corner of `containing_block`, to be moved into place once its size is known.
+    pub(crate) fn layout_atomic(
+        &mut self,
+        containing_block: ContainingBlock,
+        fonts: &FontDatabase,
+    ) {
+        self.layout_block(containing_block, containing_block.rect.y, None, fonts);
+        let margin_box = self.dimensions.margin_box();
+        self.translate(
+            containing_block.rect.x - margin_box.x,
+            containing_block.rect.y - margin_box.y,
+        );
+    }
+
+    /// Moves a float laid out by `layout_atomic` to its place in `floats`, no higher than `y` and
+    /// below the floats it clears, and then by its offset if it is relatively positioned.
+    pub(crate) fn place_float(
+        &mut self,
+        containing_block: ContainingBlock,
+        y: f32,
+        floats: &mut FloatContext,
+    ) {
+        let style = self.style();
+        let side = Side::of(style.float()).unwrap_or(Side::Left);
+        let y = floats
+            .clearance(style.clear())
+            .map_or(y, |bottom| bottom.max(y));
+        let rect = containing_block.rect;
+        let margin_box = self.dimensions.margin_box();
+        let (x, y) = floats.place(
+            side,
+            margin_box.width,
+            margin_box.height,
+            y,
+            rect.x,
+            rect.x + rect.width,
+        );
+        self.translate(x - margin_box.x, y - margin_box.y);
+        self.apply_relative_offset(containing_block);
+    }
+
+    /// Returns the y coordinate of the baseline of an inline block, which is the baseline of its
+    /// last line box, or `None` if it has none or its overflow isn't visible, in which case its
+    /// bottom margin edge is aligned instead, as in CSS 2.1 § 10.8.1. That of an inline flex or
+    /// grid container or an inline table is its first baseline.
+    pub(crate) fn inline_block_baseline(&self) -> Option<f32> {
+        if self.is_flex_container() || self.is_grid_container() || self.is_table_wrapper() {
+            return self.first_baseline();
+        }
+        if self.style().overflow() != Overflow::Visible {
+            return None;
+        }
+        self.last_baseline()
+    }
+
+    /// Returns the baseline of the first line box in the box and its in-flow block descendants,
+    /// or that of a flex or grid container's first item with one, or that of a table's first row.
+    pub(crate) fn first_baseline(&self) -> Option<f32> {
+        if self.is_table_wrapper() {
+            return self.table_first_baseline();
+        }
+        if self.is_flex_container() {
+            return self.flex_first_baseline();
+        }
+        if self.is_grid_container() {
+            return self.grid_first_baseline();
+        }
+        match self.lines.first() {
+            Some(line) => Some(line.baseline),
+            None => self
+                .children
+                .iter()
+                .filter(|child| child.is_block_level() && !child.is_out_of_flow())
+                .find_map(LayoutBox::first_baseline),
+        }
+    }
+
+    /// Returns the baseline of the last line box in the box and its in-flow block descendants.
+    fn last_baseline(&self) -> Option<f32> {
+        match self.lines.last() {
+            Some(line) => Some(line.baseline),
+            None => self
+                .children
+                .iter()
+                .rev()
+                .filter(|child| child.is_block_level() && !child.is_out_of_flow())
+                .find_map(LayoutBox::last_baseline),
+        }
+    }
+
+    /// Computes the vertical margins, padding and borders of a block-level box, and positions its
+    /// content area in `containing_block` so that the top of its border box is at `y`.
+    pub(crate) fn calculate_block_position(&mut self, containing_block: ContainingBlock, y: f32) {
+        let style = self.style();
+        // Vertical margins and padding resolve against the width of the containing block too.
+        let reference = containing_block.rect.width;
+        let margin_top = style.margin_top().resolve(reference).unwrap_or(0.0);
+        let margin_bottom = style.margin_bottom().resolve(reference).unwrap_or(0.0);
+        let padding_top = style.padding_top().resolve(reference);
+        let padding_bottom = style.padding_bottom().resolve(reference);
+        let border_top = style.border_top_width();
+        let border_bottom = style.border_bottom_width();
+
+        let dimensions = &mut self.dimensions;
+        dimensions.margin.top = margin_top;
+        dimensions.margin.bottom = margin_bottom;
+        dimensions.padding.top = padding_top;
+        dimensions.padding.bottom = padding_bottom;
+        dimensions.border.top = border_top;
+        dimensions.border.bottom = border_bottom;
+        dimensions.content.x = containing_block.rect.x
+            + dimensions.margin.left
+            + dimensions.border.left
+            + dimensions.padding.left;
+        dimensions.content.y = y + border_top + padding_top;
+    }
+
+    /// Returns the content heights given by `height`, `min-height` and `max-height`, once the
+    /// vertical padding and borders are known. `None` is `auto` for the height and `none` for the
+    /// maximum.
+    ///
+    /// Percentages of a containing block whose height depends on its contents behave as `auto`,
+    /// or as `0` and `none` for the minimum and maximum.
+    pub(crate) fn vertical_sizes(
+        &self,
+        containing_block: ContainingBlock,
+    ) -> (Option<f32>, f32, Option<f32>) {
+        let style = self.style();
+        let dimensions = &self.dimensions;
+        let edges = dimensions.padding.top
+            + dimensions.padding.bottom
+            + dimensions.border.top
+            + dimensions.border.bottom;
+        let content_size = content_size(style.box_sizing(), edges);
+        let reference = containing_block.height;
+        let resolve = |size: LengthPercentageAuto| match (size, reference) {
+            (LengthPercentageAuto::Percent(_), None) => None,
+            (size, reference) => size.resolve(reference.unwrap_or(0.0)),
+        };
+
+        let height = resolve(style.height()).map(content_size);
+        let min_height = resolve(style.min_height()).map_or(0.0, content_size);
+        let max_height = match (style.max_height(), reference) {
+            (LengthPercentageNone::Percent(_), None) => None,
+            (size, reference) => size.resolve(reference.unwrap_or(0.0)),
+        };
+        (height, min_height, max_height.map(content_size))
+    }
+}
+
+/// Returns a function converting a size given by a sizing property to the size of the content
+/// box, where `edges` is the padding and borders along the same axis.
+pub(crate) fn content_size(box_sizing: BoxSizing, edges: f32) -> impl Fn(f32) -> f32 + Copy {
+    move |size| match box_sizing {
+        BoxSizing::ContentBox => size,
+        BoxSizing::BorderBox => (size - edges).max(0.0),
+    }
+}
+
+/// Solves `margin_left + width + margin_right = available` for the values that are `None`, i.e.
+/// `auto`, as in CSS 2.1 § 10.3.3. Returns the used margin-left, width and margin-right.
+///
+/// If nothing is `auto`, the constraint is over-constrained and margin-right gives way, as it does
+/// in left-to-right text.
+fn solve_width(
+    width: Option<f32>,
+    margin_left: Option<f32>,
+    margin_right: Option<f32>,
+    available: f32,
+) -> (f32, f32, f32) {
+    let (mut margin_left, mut margin_right) = (margin_left, margin_right);
+    // Auto margins can't make room for a box that is already too wide.
+    if let Some(width) = width {
+        if margin_left.unwrap_or(0.0) + width + margin_right.unwrap_or(0.0) > available {
+            margin_left.get_or_insert(0.0);
+            margin_right.get_or_insert(0.0);
+        }
+    }
+    match (width, margin_left, margin_right) {
+        (Some(width), Some(left), _) => (left, width, available - left - width),
+        (Some(width), None, Some(right)) => (available - width - right, width, right),
+        (Some(width), None, None) => {
+            let margin = (available - width) / 2.0;
+            (margin, width, margin)
+        }
+        // Any other `auto` values become 0, and the width can't be negative.
+        (None, left, right) => {
+            let left = left.unwrap_or(0.0);
+            let right = right.unwrap_or(0.0);
+            let width = (available - left - right).max(0.0);
+            (left, width, available - left - width)
+        }
+    }
+}
+
+/// Builds the box tree of the styled tree rooted at `root`, or returns `None` if the root has
+/// `display: none`.
+///
+/// Elements with `display: none` generate no boxes, and those with `display: contents` are
+/// replaced by their children's boxes. A block container whose children are a mix of block-level
+/// and inline-level boxes has each run of inline-level boxes wrapped in an anonymous block, and
+/// an inline box containing a block-level box is split around it, as in CSS 2.1 § 9.2.1.1. Tables
+/// get the anonymous table objects of CSS 2.1 § 17.2.1.
+///
+/// The dimensions of the boxes are left zeroed for layout to fill in.
+pub fn build_layout_tree<'a>(root: &'a StyledNode<'a>) -> Option<LayoutBox<'a>> {
+    match root.display() {
+        Display::None => None,
+        // The root element is always blockified, and `contents` behaves as `block` on it.
+        _ => {
+            let mut root_box = LayoutBox::new(BoxType::BlockNode(root));
+            let children = build_children(root);
+            root_box.children = build_contents(&root_box, children);
+            Some(root_box)
+        }
+    }
+}
+
+/// Returns the boxes generated by `styled` and its descendants, which are more than one for
+/// `display: contents` or an inline box split around block-level descendants.
+fn build_boxes<'a>(styled: &'a StyledNode<'a>) -> Vec<LayoutBox<'a>> {
+    if let NodeType::Text(_) = styled.node.node_type {
+        return vec![LayoutBox::new(BoxType::InlineNode(styled))];
+    }
+    let display = styled.display();
+    match display {
+        Display::None => Vec::new(),
+        Display::Contents => build_children(styled),
+        _ if display.outside() == Some(DisplayOutside::Inline) => {
+            let mut inline = LayoutBox::new(BoxType::InlineNode(styled));
+            match inline.is_atomic_inline() {
+                // An atomic inline is a block container on the inside.
+                true => {
+                    let children = build_children(styled);
+                    inline.children = build_contents(&inline, children);
+                    vec![inline]
+                }
+                false => split_inline(inline, build_children(styled)),
+            }
+        }
+        // Run-in boxes are laid out as blocks.
+        _ => {
+            let mut block = LayoutBox::new(BoxType::BlockNode(styled));
+            let children = build_children(styled);
+            block.children = build_contents(&block, children);
+            vec![block]
+        }
+    }
+}
+
+/// Returns the boxes generated by the children of `styled`.
+fn build_children<'a>(styled: &'a StyledNode<'a>) -> Vec<LayoutBox<'a>> {
+    styled.children.iter().flat_map(build_boxes).collect()
+}
+
+/// Returns `children` as the children of `parent`, with the anonymous boxes its display type
+/// needs around them: the anonymous table objects of a table or a part of one, and otherwise an
+/// anonymous table around each run of misparented table parts, and anonymous blocks.
+pub(crate) fn build_contents<'a>(
+    parent: &LayoutBox<'a>,
+    children: Vec<LayoutBox<'a>>,
+) -> Vec<LayoutBox<'a>> {
+    match table::is_table_part(parent) {
+        true => table::build_table_part(parent, children),
+        false => wrap_inline_runs(parent, table::wrap_misparented(parent, children)),
+    }
+}
+
+/// Gives `inline` the boxes in `children`, splitting it into several boxes around those that are
+/// block-level, which are returned between the pieces.
+fn split_inline<'a>(inline: LayoutBox<'a>, children: Vec<LayoutBox<'a>>) -> Vec<LayoutBox<'a>> {
+    let mut boxes = Vec::new();
+    let mut piece = LayoutBox::new(inline.box_type.clone());
+    for child in children {
+        if child.is_block_level() && !child.is_out_of_flow() {
+            let next = LayoutBox::new(inline.box_type.clone());
+            boxes.push(std::mem::replace(&mut piece, next));
+            boxes.push(child);
+        } else {
+            piece.children.push(child);
+        }
+    }
+    boxes.push(piece);
+    boxes
+}
+
+/// Returns the children of the block container `parent`, with each run of inline-level boxes
+/// wrapped in an anonymous block if any of them is block-level.
+///
+/// Out-of-flow boxes don't need wrapping, but one after inline-level content joins its anonymous
+/// block to stay anchored in its text. The children of a flex or grid container are its items,
+/// so each run of inline-level boxes in it is always wrapped, and nothing else joins it.
+fn wrap_inline_runs<'a>(
+    parent: &LayoutBox<'a>,
+    children: Vec<LayoutBox<'a>>,
+) -> Vec<LayoutBox<'a>> {
+    let items = parent.is_flex_container() || parent.is_grid_container();
+    if !items
+        && children
+            .iter()
+            .all(|child| !child.is_block_level() || child.is_out_of_flow())
+    {
+        return children;
+    }
+    let mut wrapped: Vec<LayoutBox> = Vec::new();
+    for child in children {
+        let last_anonymous = wrapped.last_mut().filter(|last| {
+            matches!(last.box_type, BoxType::AnonymousBlock(_)) && !last.is_table_wrapper()
+        });
+        match last_anonymous {
+            Some(last) if !child.is_block_level() || (child.is_out_of_flow() && !items) => {
+                last.children.push(child)
+            }
+            _ if child.is_block_level() => wrapped.push(child),
+            _ => {
+                let style = ComputedStyle::compute(&PropertyMap::new(), Some(parent.style()));
+                let mut anonymous = LayoutBox::new(BoxType::AnonymousBlock(Arc::new(style)));
+                anonymous.children.push(child);
+                wrapped.push(anonymous);
+            }
+        }
+    }
+    wrapped
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use crate::{
+        css, html,
+        invalidation::{Change, InvalidationMap},
+        media::Environment,
+        style::{restyle, style_tree},
+    };
+
+    /// Describes the box tree as nested tags: `b` for block boxes, `a` for anonymous blocks, `t`
+    /// for table boxes, `i` for inline boxes and the text of text boxes.
+    fn describe(layout_box: &LayoutBox) -> String {
+        let children = layout_box.children.iter().map(describe).collect::<String>();
+        match layout_box.box_type {
+            BoxType::BlockNode(_) => format!("b({})", children),
+            BoxType::AnonymousBlock(_) => format!("a({})", children),
+            BoxType::TableBox(_) => format!("t({})", children),
+            BoxType::InlineNode(styled) => match styled.node.node_type {
+                NodeType::Text(ref text) => text.clone(),
+                _ => format!("i({})", children),
+            },
+        }
+    }
+
+    fn layout_description(html: &str, css: &str) -> Option<String> {
+        let root = html::parse(String::from(html)).unwrap();
+        let stylesheet = css::parse(String::from(css)).unwrap();
+        let styled = style_tree(&root, &stylesheet, &Environment::default());
+        build_layout_tree(&styled).map(|root| describe(&root))
+    }
+
+    const CSS: &str = "div, p { display: block; } .none { display: none; } \
+                       .contents { display: contents; }";
+
+    #[test]
+    fn test_wraps_inline_runs_next_to_blocks() {
+        assert_eq!(
+            layout_description("<div>a<span>b</span><p>c</p>d</div>", CSS).as_deref(),
+            Some("b(a(ai(b))b(c)a(d))")
+        );
+        assert_eq!(
+            layout_description("<div><span>a</span>b</div>", CSS).as_deref(),
+            Some("b(i(a)b)")
+        );
+    }
+
+    #[test]
+    fn test_omits_display_none() {
+        assert_eq!(
+            layout_description(r#"<div><div class="none">a<p>b</p></div>c</div>"#, CSS).as_deref(),
+            Some("b(c)")
+        );
+        assert_eq!(layout_description(r#"<div class="none"></div>"#, CSS), None);
+    }
+
+    #[test]
+    fn test_replaces_display_contents_with_children() {
+        assert_eq!(
+            layout_description(r#"<div><span class="contents"><p>a</p>b</span></div>"#, CSS)
+                .as_deref(),
+            Some("b(b(a)a(b))")
+        );
+    }
+
+    #[test]
+    fn test_splits_inline_around_blocks() {
+        assert_eq!(
+            layout_description("<div><span>a<p>b</p>c</span></div>", CSS).as_deref(),
+            Some("b(a(i(a))b(b)a(i(c)))")
+        );
+    }
+
+    #[test]
+    fn test_generates_anonymous_table_objects() {
+        let css = "div, p { display: block; } .t { display: table; } \
+                   .cap { display: table-caption; } .r { display: table-row; } \
+                   .c { display: table-cell; }";
+        assert_eq!(
+            layout_description(
+                r#"<div><span class="t"><span class="c">a</span>b</span></div>"#,
+                css
+            )
+            .as_deref(),
+            Some("b(b(t(a(b(a)a(b)))))")
+        );
+        assert_eq!(
+            layout_description(
+                r#"<div class="t"><span class="r">a</span><span class="cap">b</span></div>"#,
+                css
+            )
+            .as_deref(),
+            Some("b(b(b)t(b(a(a))))")
+        );
+        assert_eq!(
+            layout_description(r#"<div><span class="c">a</span><p>b</p></div>"#, css).as_deref(),
+            Some("b(a(t(a(b(a))))b(b))")
+        );
+    }
+
+    #[test]
+    fn test_anonymous_blocks_inherit_from_parent() {
+        let root = html::parse(String::from("<div>a<p>b</p></div>")).unwrap();
+        let stylesheet = css::parse(String::from(
+            "div { color: red; border-width: 2px; } p { display: block; }",
+        ))
+        .unwrap();
+        let styled = style_tree(&root, &stylesheet, &Environment::default());
+        let root_box = build_layout_tree(&styled).unwrap();
+        let anonymous = &root_box.children[0];
+        assert!(anonymous.styled_node().is_none());
+        assert_eq!(anonymous.style().color(), root_box.style().color());
+        assert_eq!(anonymous.style().border_top_width(), 0.0);
+    }
+
+    /// The unscrolled 800x600 viewport that layout tests lay out in.
+    pub(crate) const VIEWPORT: Rect = Rect {
+        x: 0.0,
+        y: 0.0,
+        width: 800.0,
+        height: 600.0,
+    };
+
+    /// The style that sets text in the bundled monospace font at 10px, for tests that measure
+    /// text.
+    pub(crate) const MONOSPACE: &str = "* { font-family: monospace; font-size: 10px; }";
+
+    /// The advance of each character of the bundled monospace font at 10px.
+    pub(crate) const CH: f32 = 1233.0 / 2048.0 * 10.0;
+
+    /// Lays out `html` styled with `prefix` followed by `css` in `viewport`, and passes the root
+    /// box to `f`.
+    pub(crate) fn with_layout_in(
+        viewport: Rect,
+        prefix: &str,
+        html: &str,
+        css: &str,
+        f: impl FnOnce(&LayoutBox),
+    ) {
+        let root = html::parse(String::from(html)).unwrap();
+        let stylesheet = css::parse(format!("{} {}", prefix, css)).unwrap();
+        let styled = style_tree(&root, &stylesheet, &Environment::default());
+        f(&layout_tree(&styled, viewport, &FontDatabase::default()).unwrap());
+    }
+
+    /// Lays out `html` styled with `css` in the viewport, where text is set in the bundled
+    /// monospace font at 10px, and passes the root box to `f`.
+    pub(crate) fn with_monospace_layout(html: &str, css: &str, f: impl FnOnce(&LayoutBox)) {
+        with_layout_in(VIEWPORT, MONOSPACE, html, css, f);
+    }
+
+    /// Returns the x, y, width and height of the border boxes of the children of `layout_box`.
+    pub(crate) fn border_boxes(layout_box: &LayoutBox) -> Vec<(f32, f32, f32, f32)> {
+        layout_box
+            .children
+            .iter()
+            .map(|child| {
+                let rect = child.dimensions.border_box();
+                (rect.x, rect.y, rect.width, rect.height)
+            })
+            .collect()
+    }
+
+    /// Lays out `html` styled with `css` in the viewport, and passes the root box to `f`.
+    fn with_layout(html: &str, css: &str, f: impl FnOnce(&LayoutBox)) {
+        with_layout_in(VIEWPORT, "", html, css, f);
+    }
+
+    fn horizontal(layout_box: &LayoutBox) -> (f32, f32, f32, f32) {
+        let dimensions = layout_box.dimensions;
+        (
+            dimensions.content.x,
+            dimensions.margin.left,
+            dimensions.content.width,
+            dimensions.margin.right,
+        )
+    }
+
+    #[test]
+    fn test_solve_width() {
+        assert_eq!(
+            solve_width(None, Some(10.0), None, 100.0),
+            (10.0, 90.0, 0.0)
+        );
+        assert_eq!(
+            solve_width(Some(50.0), None, None, 100.0),
+            (25.0, 50.0, 25.0)
+        );
+        assert_eq!(
+            solve_width(Some(50.0), None, Some(10.0), 100.0),
+            (40.0, 50.0, 10.0)
+        );
+        // Over-constrained, so margin-right gives way.
+        assert_eq!(
+            solve_width(Some(50.0), Some(10.0), Some(10.0), 100.0),
+            (10.0, 50.0, 40.0)
+        );
+        // Too wide, so auto margins are 0.
+        assert_eq!(
+            solve_width(Some(150.0), None, None, 100.0),
+            (0.0, 150.0, -50.0)
+        );
+        assert_eq!(
+            solve_width(None, Some(60.0), Some(60.0), 100.0),
+            (60.0, 0.0, 40.0)
+        );
+    }
+
+    #[test]
+    fn test_block_widths() {
+        let css = "div { display: block; } \
+                   .a { width: 400px; margin-left: auto; margin-right: auto; } \
+                   .b { margin: 10px; padding: 5px; border-style: solid; border-width: 1px; } \
+                   .c { width: 50%; margin-left: auto; }";
+        let html =
+            r#"<main><div class="a"></div><div class="b"></div><div class="c"></div></main>"#;
+        with_layout(html, css, |root| {
+            assert_eq!(horizontal(root), (0.0, 0.0, 800.0, 0.0));
+            assert_eq!(horizontal(&root.children[0]), (200.0, 200.0, 400.0, 200.0));
+            assert_eq!(horizontal(&root.children[1]), (16.0, 10.0, 768.0, 10.0));
+            assert_eq!(horizontal(&root.children[2]), (400.0, 400.0, 400.0, 0.0));
+        });
+    }
+
+    #[test]
+    fn test_min_and_max_sizes() {
+        let css = "div { display: block; } \
+                   .a { max-width: 300px; min-width: 400px; } \
+                   .b { max-width: 300px; margin-left: auto; margin-right: auto; } \
+                   .c { width: 100px; padding: 10px; box-sizing: border-box; min-height: 50px; } \
+                   .d { height: 10px; max-height: 5px; min-height: 8px; }";
+        let html = r#"<main><div class="a"></div><div class="b"></div><div class="c"></div><div class="d"></div></main>"#;
+        with_layout(html, css, |root| {
+            // The minimum wins over the maximum.
+            assert_eq!(root.children[0].dimensions.content.width, 400.0);
+            assert_eq!(horizontal(&root.children[1]), (250.0, 250.0, 300.0, 250.0));
+            let c = root.children[2].dimensions;
+            assert_eq!((c.content.width, c.border_box().width), (80.0, 100.0));
+            assert_eq!((c.content.height, c.border_box().height), (30.0, 50.0));
+            assert_eq!(root.children[3].dimensions.content.height, 8.0);
+        });
+    }
+
+    #[test]
+    fn test_stacks_children_vertically() {
+        let css = "main { height: 400px; } div, section { display: block; } \
+                   .a { height: 50px; margin-top: 10px; margin-bottom: 5px; } \
+                   .b { height: 25%; padding-top: 2px; } \
+                   .c { height: 20px; } \
+                   .c div { height: 50%; }";
+        let html = r#"<main><div class="a"></div><div class="b"></div><section class="c"><div></div></section><div><div class="b"></div></div></main>"#;
+        with_layout(html, css, |root| {
+            assert_eq!(
+                border_boxes(root),
+                [
+                    (0.0, 10.0, 800.0, 50.0),
+                    (0.0, 65.0, 800.0, 102.0),
+                    (0.0, 167.0, 800.0, 20.0),
+                    (0.0, 187.0, 800.0, 2.0)
+                ]
+            );
+            assert_eq!(root.children[2].children[0].dimensions.content.height, 10.0);
+            // The last box's child has a percentage height of an auto height, so it's auto too.
+            assert_eq!(root.children[3].children[0].dimensions.content.height, 0.0);
+            assert_eq!(root.dimensions.content.height, 400.0);
+        });
+    }
+
+    #[test]
+    fn test_collapsed_margin() {
+        let margins = [10.0, -4.0, 20.0, -8.0].map(CollapsedMargin::new);
+        let collapsed = margins
+            .into_iter()
+            .fold(CollapsedMargin::default(), CollapsedMargin::adjoin);
+        assert_eq!(collapsed.resolve(), 12.0);
+        assert_eq!(margins[1].adjoin(margins[3]).resolve(), -8.0);
+    }
+
+    /// Returns the top of the border box and the height of the content area of each child of
+    /// `layout_box`.
+    fn vertical(layout_box: &LayoutBox) -> Vec<(f32, f32)> {
+        layout_box
+            .children
+            .iter()
+            .map(|child| {
+                (
+                    child.dimensions.border_box().y,
+                    child.dimensions.content.height,
+                )
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_collapses_sibling_margins() {
+        let css = "div { display: block; height: 10px; } \
+                   .a { margin-bottom: 20px; } .b { margin-top: 30px; margin-bottom: -5px; } \
+                   .c { margin-top: -10px; margin-bottom: 10px; }";
+        let html =
+            r#"<main><div class="a"></div><div class="b"></div><div class="c"></div></main>"#;
+        with_layout(html, css, |root| {
+            // max(20, 30), then 0 + min(-5, -10).
+            assert_eq!(
+                vertical(root),
+                vec![(0.0, 10.0), (40.0, 10.0), (40.0, 10.0)]
+            );
+            // The last margin stays inside the root, whose margins don't collapse.
+            assert_eq!(root.dimensions.content.height, 60.0);
+        });
+    }
+
+    #[test]
+    fn test_stacks_lines_between_blocks() {
+        let css = "main, div { display: block; } div { margin: 10px; } \
+                   * { font-size: 10px; line-height: 15px; }";
+        let html = "<main><div></div><span> </span><div></div>text<div></div></main>";
+        with_layout(html, css, |root| {
+            // The white space between the first two blocks generates no line boxes, so the
+            // margins collapse through it, but the line of text separates them from the last one.
+            assert_eq!(
+                vertical(root),
+                vec![
+                    (10.0, 0.0),
+                    (10.0, 0.0),
+                    (10.0, 0.0),
+                    (10.0, 15.0),
+                    (35.0, 0.0)
+                ]
+            );
+        });
+    }
+
+    #[test]
+    fn test_collapses_parent_and_child_margins() {
+        let css = "section, div { display: block; } \
+                   section { margin-top: 10px; margin-bottom: 10px; } \
+                   div { height: 10px; margin-top: 20px; margin-bottom: 20px; } \
+                   .bordered { border-top-style: solid; border-top-width: 1px; }";
+        let html = "<main><section><div></div></section><section><div></div></section></main>";
+        with_layout(html, css, |root| {
+            // The first section's top margin collapses with its child's, and its bottom margin
+            // with its child's and the next section's top margin.
+            assert_eq!(vertical(root), vec![(20.0, 10.0), (50.0, 10.0)]);
+            assert_eq!(vertical(&root.children[0]), vec![(20.0, 10.0)]);
+            assert_eq!(root.dimensions.content.height, 80.0);
+        });
+        let html = r#"<main><section class="bordered"><div></div></section></main>"#;
+        with_layout(html, css, |root| {
+            assert_eq!(vertical(root), vec![(10.0, 30.0)]);
+            assert_eq!(vertical(&root.children[0]), vec![(31.0, 10.0)]);
+        });
+    }
+
+    #[test]
+    fn test_collapses_through_empty_blocks() {
+        let css = "div, section { display: block; } .full { height: 10px; } \
+                   .empty { margin-top: 15px; margin-bottom: 25px; } .empty div { margin-top: -5px; } \
+                   .a { margin-bottom: 10px; } .b { margin-top: 5px; }";
+        let html = r#"<main><div class="full a"></div><section class="empty"><div></div></section><div class="full b"></div></main>"#;
+        with_layout(html, css, |root| {
+            // 10, 15, -5, 25 and 5 all adjoin.
+            assert_eq!(vertical(root)[2], (30.0, 10.0));
+            assert_eq!(root.children[1].dimensions.content.height, 0.0);
+        });
+    }
+
+    #[test]
+    fn test_no_collapsing_across_formatting_contexts() {
+        let css = "div { display: block; margin-top: 10px; margin-bottom: 10px; } \
+                   .root { display: flow-root; } .hidden { overflow: hidden; } \
+                   .leaf { height: 10px; }";
+        let html = r#"<main><div class="root"><div class="leaf"></div></div><div class="hidden"><div class="leaf"></div></div></main>"#;
+        with_layout(html, css, |root| {
+            assert_eq!(vertical(root), vec![(10.0, 30.0), (50.0, 30.0)]);
+            assert_eq!(vertical(&root.children[0]), vec![(20.0, 10.0)]);
+        });
+    }
+
+    /// Returns the position of the margin box of each child of `layout_box`.
+    fn margin_positions(layout_box: &LayoutBox) -> Vec<(f32, f32)> {
+        layout_box
+            .children
+            .iter()
+            .map(|child| {
+                let margin_box = child.dimensions.margin_box();
+                (margin_box.x, margin_box.y)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_places_floats() {
+        let css = "div { display: block; } .l { float: left; width: 100px; height: 50px; } \
+                   .r { float: inline-end; width: 50px; height: 20px; } \
+                   .wide { float: left; width: 595px; height: 10px; margin-left: 5px; }";
+        let html = r#"<main><div class="l"></div><div class="r"></div><div class="l"></div><div class="wide"></div></main>"#;
+        with_layout(html, css, |root| {
+            // The wide float doesn't fit beside the right float, so it goes below it.
+            assert_eq!(
+                margin_positions(root),
+                vec![(0.0, 0.0), (750.0, 0.0), (100.0, 0.0), (200.0, 20.0)]
+            );
+            assert_eq!(root.children[3].dimensions.content.x, 205.0);
+            // The root establishes a block formatting context, so it contains its floats.
+            assert_eq!(root.dimensions.content.height, 50.0);
+        });
+    }
+
+    #[test]
+    fn test_clearance() {
+        let css = "div { display: block; } .a { height: 10px; margin-bottom: 30px; } \
+                   .f { float: left; width: 10px; height: 50px; } \
+                   .c { clear: both; height: 10px; margin-top: 20px; } .far { margin-top: 100px; }";
+        let html =
+            r#"<main><div class="a"></div><div class="f"></div><div class="c"></div></main>"#;
+        with_layout(html, css, |root| {
+            // The float goes below the collapsed margins, and the clearing box below the float.
+            assert_eq!(margin_positions(root)[1], (0.0, 40.0));
+            assert_eq!(vertical(root)[2], (90.0, 10.0));
+            assert_eq!(root.dimensions.content.height, 100.0);
+        });
+        // A box already below the floats has no clearance, and its margins collapse as usual.
+        let html =
+            r#"<main><div class="a"></div><div class="f"></div><div class="c far"></div></main>"#;
+        with_layout(html, css, |root| {
+            assert_eq!(vertical(root)[2], (110.0, 10.0));
+        });
+    }
+
+    #[test]
+    fn test_formatting_contexts_and_floats() {
+        let css = "div { display: block; } .f { float: left; width: 100px; height: 50px; } \
+                   .bfc { overflow: hidden; } .wide { width: 750px; }";
+        let html = r#"<main><div class="f"></div><div class="bfc"><div class="f"></div></div><div class="bfc wide"></div><div><div class="f"></div></div></main>"#;
+        with_layout(html, css, |root| {
+            // A box establishing a block formatting context is narrowed to fit beside the float,
+            // and contains its own floats.
+            let bfc = &root.children[1];
+            assert_eq!(horizontal(bfc), (100.0, 0.0, 700.0, 0.0));
+            assert_eq!(vertical(root)[1], (0.0, 50.0));
+            // One too wide to fit beside it moves below it.
+            assert_eq!(vertical(root)[2], (50.0, 0.0));
+            // A box in the same block formatting context doesn't contain its floats.
+            assert_eq!(vertical(root)[3], (50.0, 0.0));
+            assert_eq!(margin_positions(&root.children[3]), vec![(0.0, 50.0)]);
+        });
+    }
+
+    #[test]
+    fn test_property_value_resolves_used_values() {
+        let css = "div, p { display: block; } \
+                   div { position: relative; width: 50%; top: 10%; left: 5px; padding: 1%; } \
+                   p { box-sizing: border-box; width: 50%; margin: 0 auto; padding: 0 10px; \
+                   border: 2px solid; height: 20px; --gap: 4px; } \
+                   #a { position: absolute; left: 0; right: 25%; bottom: 0; height: 5px; }";
+        let html = r#"<main><div><p></p><p id="a"></p></div></main>"#;
+        with_layout(html, css, |root| {
+            let div = &root.children[0];
+            let value = |layout_box: &LayoutBox, name| layout_box.property_value(name);
+            assert_eq!(value(div, "width").as_deref(), Some("400px"));
+            assert_eq!(value(div, "padding").as_deref(), Some("8px"));
+            // `top` is a percentage of an auto height, so the box isn't moved vertically.
+            assert_eq!(value(div, "top").as_deref(), Some("0px"));
+            assert_eq!(value(div, "inset").as_deref(), Some("0px -5px 0px 5px"));
+            assert_eq!(value(div, "color").as_deref(), Some("rgb(0, 0, 0)"));
+
+            let p = &div.children[0];
+            assert_eq!(value(p, "width").as_deref(), Some("200px"));
+            assert_eq!(value(p, "height").as_deref(), Some("20px"));
+            assert_eq!(value(p, "margin").as_deref(), Some("0px 100px"));
+            assert_eq!(value(p, "margin-left").as_deref(), Some("100px"));
+            // Static boxes have no used insets.
+            assert_eq!(value(p, "top").as_deref(), Some("auto"));
+            assert_eq!(value(p, "--gap").as_deref(), Some("4px"));
+
+            let a = &div.children[1];
+            assert_eq!(value(a, "inset").as_deref(), Some("31px 104px 0px 0px"));
+
+            let node = p.styled_node().unwrap().node;
+            assert!(std::ptr::eq(root.find(node).unwrap(), p));
+        });
+    }
+
+    #[test]
+    fn test_container_queries_use_laid_out_sizes() {
+        let mut root = html::parse(String::from(
+            r#"<main><div class="c"><p></p></div><div class="c narrow"><p></p></div></main>"#,
+        ))
+        .unwrap();
+        let stylesheet = css::parse(String::from(
+            "div, p { display: block; } \
+             .c { container-type: inline-size; width: 50%; padding: 0 10px; } \
+             .narrow { width: 35%; } \
+             @container (min-width: 300px) { p { height: 10px; } } \
+             .x { color: red; }",
+        ))
+        .unwrap();
+        let index = RuleIndex::new(&stylesheet);
+        let environment = Environment::default();
+        let fonts = FontDatabase::default();
+        let heights = |styled: &StyledNode| {
+            let root_box = layout_tree(styled, VIEWPORT, &fonts).unwrap();
+            border_boxes(&root_box)
+                .into_iter()
+                .map(|(_, _, _, height)| height)
+                .collect::<Vec<_>>()
+        };
+
+        // Before layout, a container with a percentage width has no width to query.
+        let styled = style_tree(&root, &stylesheet, &environment);
+        assert_eq!(heights(&styled), [0.0, 0.0]);
+        // After it, the query sees the width of the content box: 400px, but 280px for the
+        // narrow one, whose border box is 300px wide.
+        let styled = style_tree_for_layout(&root, &index, &environment, VIEWPORT, &fonts);
+        assert_eq!(heights(&styled), [10.0, 0.0]);
+
+        // Restyling evaluates the rules again against the sizes the containers were styled with.
+        let mut retained = styled.retain();
+        let NodeType::Element(ref mut p) = root.children[0].children[0].node_type else {
+            unreachable!()
+        };
+        p.attributes
+            .insert(String::from("class"), String::from("x"));
+        let change = Change::Attribute {
+            path: vec![0, 0],
+            name: String::from("class"),
+            old_value: None,
+        };
+        let map = InvalidationMap::new(&stylesheet);
+        retained.invalidate(&root, &change, &map).unwrap();
+        assert!(
+            retained.children[0].as_ref().unwrap().children[0]
+                .as_ref()
+                .unwrap()
+                .dirty
+                .restyle
+        );
+        let styled = restyle(&root, retained, &index, &environment);
+        assert_eq!(heights(&styled), [10.0, 0.0]);
+    }
+
+    #[test]
+    fn test_dimensions_boxes() {
+        let edge = |size| EdgeSizes {
+            left: size,
+            right: size,
+            top: size,
+            bottom: size,
+        };
+        let dimensions = Dimensions {
+            content: Rect {
+                x: 10.0,
+                y: 10.0,
+                width: 100.0,
+                height: 50.0,
+            },
+            padding: edge(1.0),
+            border: edge(2.0),
+            margin: edge(3.0),
+        };
+        assert_eq!(dimensions.padding_box().x, 9.0);
+        assert_eq!(dimensions.border_box().width, 106.0);
+        assert_eq!(
+            dimensions.margin_box(),
+            Rect {
+                x: 4.0,
+                y: 4.0,
+                width: 112.0,
+                height: 62.0
+            }
+        );
+    }
+}
@@ -0,0 +1,345 @@
+use std::{collections::HashMap, fs, path::PathBuf};
+
+use crate::css::{self, Condition, Stylesheet};
+
+/// The maximum nesting depth of `@import` rules. Deeper imports are ignored.
+pub const MAX_IMPORT_DEPTH: usize = 16;
+
+/// Fetches resources such as imported stylesheets on behalf of the engine.
+///
+/// Embedders implement this to control where resources come from.
+pub trait ResourceLoader {
+    /// Returns the contents of the resource at `url`.
+    ///
+    /// `url` has already been resolved against the URL of the resource that referenced it.
+    fn load(&self, url: &str) -> Result<String, &'static str>;
+
+    /// Returns the contents of the binary resource at `url`, such as a font.
+    ///
+    /// Defaults to the bytes of the text `load` returns.
+    fn load_bytes(&self, url: &str) -> Result<Vec<u8>, &'static str> {
+        self.load(url).map(String::into_bytes)
+    }
+}
+
+/// A `ResourceLoader` that reads resources from the filesystem, treating URLs as paths relative
+/// to a root directory.
+pub struct FileLoader {
+    /// The directory URLs are resolved against.
+    pub root: PathBuf,
+}
+
+impl ResourceLoader for FileLoader {
+    fn load(&self, url: &str) -> Result<String, &'static str> {
+        fs::read_to_string(self.root.join(url.trim_start_matches('/')))
+            .map_err(|_| "failed to read resource")
+    }
+
+    fn load_bytes(&self, url: &str) -> Result<Vec<u8>, &'static str> {
+        fs::read(self.root.join(url.trim_start_matches('/'))).map_err(|_| "failed to read resource")
+    }
+}
+
+/// A `ResourceLoader` that serves resources from memory. Useful for tests.
+#[derive(Default)]
+pub struct MemoryLoader {
+    /// The contents of each resource, keyed by URL.
+    pub resources: HashMap<String, Vec<u8>>,
+}
+
+impl MemoryLoader {
+    /// Adds a resource with the given URL and contents.
+    pub fn insert(&mut self, url: &str, contents: &str) {
+        self.insert_bytes(url, contents.as_bytes());
+    }
+
+    /// Adds a binary resource with the given URL and contents.
+    pub fn insert_bytes(&mut self, url: &str, contents: &[u8]) {
+        self.resources.insert(url.to_string(), contents.to_vec());
+    }
+}
+
+impl ResourceLoader for MemoryLoader {
+    fn load(&self, url: &str) -> Result<String, &'static str> {
+        String::from_utf8(self.load_bytes(url)?).map_err(|_| "resource is not valid UTF-8")
+    }
+
+    fn load_bytes(&self, url: &str) -> Result<Vec<u8>, &'static str> {
+        self.resources.get(url).cloned().ok_or("resource not found")
+    }
+}
+
+/// Loads and parses the stylesheet at `url`, resolving its `@import` rules through `loader`.
+pub fn load_stylesheet(url: &str, loader: &dyn ResourceLoader) -> Result<Stylesheet, &'static str> {
+    let stylesheet = css::parse(loader.load(url)?)?;
+    Ok(resolve_imports(
+        stylesheet,
+        url,
+        loader,
+        &mut vec![url.to_string()],
+    ))
+}
+
+/// Replaces the `@import` rules of `stylesheet`, whose URL is `url`, with the rules and layers of
+/// the stylesheets they import. Rules that don't have a source URL yet get `url`.
+///
+/// `stack` holds the URLs of the stylesheets currently being imported. Imports that would form a
+/// cycle, exceed `MAX_IMPORT_DEPTH`, fail to load or whose supports condition is false are
+/// ignored, like browsers do.
+pub fn resolve_imports(
+    mut stylesheet: Stylesheet,
+    url: &str,
+    loader: &dyn ResourceLoader,
+    stack: &mut Vec<String>,
+) -> Stylesheet {
+    for rule in &mut stylesheet.rules {
+        rule.source.url.get_or_insert_with(|| url.to_string());
+    }
+    let mut rules = Vec::new();
+    let mut font_faces = Vec::new();
+    let mut keyframes = Vec::new();
+    let mut pages = Vec::new();
+    let mut imported_layers = Vec::new();
+
+    for import in std::mem::take(&mut stylesheet.imports) {
+        let import_url = resolve_url(url, &import.url);
+        if stack.contains(&import_url)
+            || stack.len() >= MAX_IMPORT_DEPTH
+            || !import.supports.as_ref().is_none_or(|c| c.evaluate())
+        {
+            continue;
+        }
+        let Ok(imported) = loader.load(&import_url).and_then(css::parse) else {
+            continue;
+        };
+
+        stack.push(import_url.clone());
+        let imported = resolve_imports(imported, &import_url, loader, stack);
+        stack.pop();
+
+        // Imported rules and layers are nested inside the import's layer.
+        let nest = |name: &str| match import.layer {
+            Some(ref layer) => format!("{}.{}", layer, name),
+            None => name.to_string(),
+        };
+        for mut rule in imported.rules {
+            rule.layer = match rule.layer {
+                Some(ref name) => Some(nest(name)),
+                None => import.layer.clone(),
+            };
+            if !import.media.0.is_empty() {
+                rule.conditions
+                    .insert(0, Condition::Media(import.media.clone()));
+            }
+            rules.push(rule);
+        }
+        font_faces.extend(imported.font_faces);
+        keyframes.extend(imported.keyframes);
+        pages.extend(imported.pages);
+        imported_layers.extend(
+            imported
+                .layers
+                .iter()
+                .map(|name| (import.layer_position, nest(name))),
+        );
+    }
+
+    // Imports come before everything else, so the imported rules go first.
+    rules.append(&mut stylesheet.rules);
+    stylesheet.rules = rules;
+    font_faces.append(&mut stylesheet.font_faces);
+    stylesheet.font_faces = font_faces;
+    keyframes.append(&mut stylesheet.keyframes);
+    stylesheet.keyframes = keyframes;
+    pages.append(&mut stylesheet.pages);
+    stylesheet.pages = pages;
+
+    // Each imported layer goes where its import was; a layer keeps its first position.
+    let mut layers: Vec<String> = Vec::new();
+    let mut imported_layers = imported_layers.into_iter().peekable();
+    for (i, layer) in stylesheet.layers.iter().enumerate() {
+        while let Some((_, imported)) = imported_layers.next_if(|&(position, _)| position <= i) {
+            if !layers.contains(&imported) {
+                layers.push(imported);
+            }
+        }
+        if !layers.contains(layer) {
+            layers.push(layer.clone());
+        }
+    }
+    for (_, imported) in imported_layers {
+        if !layers.contains(&imported) {
+            layers.push(imported);
+        }
+    }
+    stylesheet.layers = layers;
+
+    stylesheet
+}
+
+/// Resolves `url` against the URL `base` of the resource referencing it.
+///
+/// URLs with a scheme or starting with `/` are returned as-is. Relative URLs are resolved
+/// against the directory of `base`, and `.` and `..` segments are removed.
+pub fn resolve_url(base: &str, url: &str) -> String {
+    if url.contains("://") || url.starts_with('/') {
+        return url.to_string();
+    }
+
+    // Keep any scheme and host of `base` out of the path normalization.
+    let path_start = match base.find("://") {
+        Some(i) => base[i + 3..].find('/').map_or(base.len(), |j| i + 3 + j),
+        None => 0,
+    };
+    let (origin, path) = base.split_at(path_start);
+    let directory = match path.rfind('/') {
+        Some(i) => &path[..=i],
+        None => "",
+    };
+
+    let mut segments: Vec<&str> = Vec::new();
+    let joined = format!("{}{}", directory, url);
+    for segment in joined.split('/') {
+        match segment {
+            "." => {}
+            ".." if segments.last().is_some_and(|s| !s.is_empty() && *s != "..") => {
+                segments.pop();
+            }
+            _ => segments.push(segment),
+        }
+    }
+    format!("{}{}", origin, segments.join("/"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::css::Value;
+
+    fn colors(stylesheet: &Stylesheet) -> Vec<String> {
+        stylesheet
+            .rules
+            .iter()
+            .map(|rule| match rule.declarations[0].value {
+                Value::Keyword(ref keyword) => keyword.clone(),
+                _ => panic!("expected a keyword"),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_resolve_url() {
+        assert_eq!(
+            resolve_url("styles/main.css", "base.css"),
+            "styles/base.css"
+        );
+        assert_eq!(resolve_url("styles/main.css", "../a/./b.css"), "a/b.css");
+        assert_eq!(resolve_url("main.css", "/abs.css"), "/abs.css");
+        assert_eq!(
+            resolve_url("https://example.com/css/main.css", "../x.css"),
+            "https://example.com/x.css"
+        );
+        assert_eq!(
+            resolve_url("main.css", "https://cdn.test/a.css"),
+            "https://cdn.test/a.css"
+        );
+    }
+
+    #[test]
+    fn test_load_stylesheet_splices_imports() {
+        let mut loader = MemoryLoader::default();
+        loader.insert(
+            "css/main.css",
+            "@import \"reset.css\"; @import url(parts/theme.css) print; p { color: main; }",
+        );
+        loader.insert("css/reset.css", "a { color: reset; }");
+        loader.insert(
+            "css/parts/theme.css",
+            "@import '../reset.css'; b { color: theme; }",
+        );
+
+        let stylesheet = load_stylesheet("css/main.css", &loader).unwrap();
+        assert_eq!(colors(&stylesheet), vec!["reset", "reset", "theme", "main"]);
+        assert!(stylesheet.rules[0].conditions.is_empty());
+        assert!(matches!(
+            stylesheet.rules[1].conditions[..],
+            [Condition::Media(_)]
+        ));
+        assert!(stylesheet.imports.is_empty());
+
+        let urls = stylesheet
+            .rules
+            .iter()
+            .map(|rule| rule.source.url.as_deref().unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            urls,
+            vec![
+                "css/reset.css",
+                "css/reset.css",
+                "css/parts/theme.css",
+                "css/main.css"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_load_stylesheet_imports_into_layers() {
+        let mut loader = MemoryLoader::default();
+        loader.insert(
+            "main.css",
+            "@layer first; @import url(framework.css) layer(framework); @layer last; \
+             p { color: main; }",
+        );
+        loader.insert(
+            "framework.css",
+            "@layer base { a { color: base; } } b { color: framework; }",
+        );
+
+        let stylesheet = load_stylesheet("main.css", &loader).unwrap();
+        assert_eq!(
+            stylesheet.layers,
+            vec!["first", "framework", "framework.base", "last"]
+        );
+        let layers = stylesheet
+            .rules
+            .iter()
+            .map(|rule| rule.layer.as_deref())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            layers,
+            vec![Some("framework.base"), Some("framework"), None]
+        );
+    }
+
+    #[test]
+    fn test_load_stylesheet_skips_cycles_and_failures() {
+        let mut loader = MemoryLoader::default();
+        loader.insert(
+            "a.css",
+            "@import url(b.css); @import url(missing.css); \
+             @import url(c.css) supports(display: masonry); a { color: a; }",
+        );
+        loader.insert("b.css", "@import url(a.css); b { color: b; }");
+        loader.insert("c.css", "c { color: c; }");
+
+        let stylesheet = load_stylesheet("a.css", &loader).unwrap();
+        assert_eq!(colors(&stylesheet), vec!["b", "a"]);
+
+        assert!(load_stylesheet("missing.css", &loader).is_err());
+    }
+
+    #[test]
+    fn test_load_stylesheet_limits_depth() {
+        let mut loader = MemoryLoader::default();
+        for i in 0..MAX_IMPORT_DEPTH + 5 {
+            loader.insert(
+                &format!("{}.css", i),
+                &format!("@import url({}.css); p {{ color: c{}; }}", i + 1, i),
+            );
+        }
+
+        let stylesheet = load_stylesheet("0.css", &loader).unwrap();
+        assert_eq!(stylesheet.rules.len(), MAX_IMPORT_DEPTH);
+    }
+}
@@ -1,8 +1,26 @@
+pub mod at_rules;
+pub mod bloom;
+pub mod computed;
+pub mod css;
 pub mod dom;
+pub mod dump;
+pub mod flex;
+pub mod float;
+pub mod font;
+pub mod grid;
 pub mod html;
-pub mod css;
+pub mod inline;
+pub mod invalidation;
+pub mod layout;
+pub mod loader;
+pub mod media;
 pub mod parser;
+pub mod position;
+pub mod rule_index;
+pub mod sharing;
 pub mod style;
+pub mod supports;
+pub mod table;
 
 fn main() {
     println!("Hello, world!");
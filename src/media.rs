@@ -0,0 +1,462 @@
+use crate::css::{ResolutionUnit, Unit, Value};
+
+/// A comma-separated list of media queries, as found in the prelude of an `@media` rule.
+///
+/// The list matches if any of its queries match. An empty list matches everything.
+#[derive(Clone, PartialEq, Debug)]
+pub struct MediaQueryList(pub Vec<MediaQuery>);
+
+/// A single media query, e.g. `only screen and (min-width: 400px)`.
+#[derive(Clone, PartialEq, Debug)]
+pub struct MediaQuery {
+    /// The optional `not` or `only` keyword in front of the media type.
+    pub qualifier: Option<Qualifier>,
+    /// The media type the query applies to.
+    pub media_type: MediaType,
+    /// The condition following the media type, if any.
+    pub condition: Option<MediaCondition>,
+}
+
+/// The keyword that can precede the media type of a media query.
+#[derive(Clone, PartialEq, Debug)]
+pub enum Qualifier {
+    /// Negates the result of the whole query.
+    Not,
+    /// Hides the query from legacy user agents; has no effect on evaluation.
+    Only,
+}
+
+/// The media type of a media query or of the environment it is evaluated against.
+#[derive(Clone, PartialEq, Debug)]
+pub enum MediaType {
+    All,
+    Screen,
+    Print,
+    /// Any other media type. These never match.
+    Other(String),
+}
+
+/// A boolean combination of media features.
+#[derive(Clone, PartialEq, Debug)]
+pub enum MediaCondition {
+    /// A single media feature test.
+    Feature(MediaFeature),
+    /// A negated condition.
+    Not(Box<MediaCondition>),
+    /// Conditions joined with `and`.
+    And(Vec<MediaCondition>),
+    /// Conditions joined with `or`.
+    Or(Vec<MediaCondition>),
+    /// Parenthesized content that isn't a valid condition. Always evaluates to unknown.
+    Unknown,
+}
+
+/// A media feature test, e.g. `(orientation: landscape)` or `(400px <= width < 800px)`.
+#[derive(Clone, PartialEq, Debug)]
+pub struct MediaFeature {
+    /// The name of the feature, without any `min-` or `max-` prefix.
+    pub name: String,
+    /// The test performed on the feature.
+    pub test: FeatureTest,
+}
+
+/// The test performed on a media feature.
+#[derive(Clone, PartialEq, Debug)]
+pub enum FeatureTest {
+    /// `(name)`: the feature is evaluated in a boolean context.
+    Boolean,
+    /// `(name: value)`: the feature must equal the value.
+    Plain(Value),
+    /// Range syntax and `min-`/`max-` prefixes, normalized so that each comparison reads
+    /// `feature <op> value`.
+    Range(Vec<(Comparison, Value)>),
+}
+
+/// A comparison operator in a media feature range.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Comparison {
+    Lt,
+    Le,
+    Gt,
+    Ge,
+    Eq,
+}
+
+/// A description of the device and user preferences that media queries are evaluated against.
+#[derive(Clone, PartialEq, Debug)]
+pub struct Environment {
+    /// The media type of the device.
+    pub media_type: MediaType,
+    /// The width of the viewport in px.
+    pub width: f32,
+    /// The height of the viewport in px.
+    pub height: f32,
+    /// The device pixel ratio, in dppx.
+    pub resolution: f32,
+    /// The value of `prefers-color-scheme`.
+    pub color_scheme: ColorScheme,
+    /// Whether `prefers-reduced-motion` is `reduce`.
+    pub reduced_motion: bool,
+    /// Whether the primary pointing device can hover.
+    pub hover: bool,
+    /// The accuracy of the primary pointing device.
+    pub pointer: Pointer,
+}
+
+/// The user's preferred color scheme.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum ColorScheme {
+    Light,
+    Dark,
+}
+
+/// The accuracy of a pointing device.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Pointer {
+    None,
+    Coarse,
+    Fine,
+}
+
+impl Default for Environment {
+    /// An 800x600 screen with a mouse and no user preferences.
+    fn default() -> Self {
+        Environment {
+            media_type: MediaType::Screen,
+            width: 800.0,
+            height: 600.0,
+            resolution: 1.0,
+            color_scheme: ColorScheme::Light,
+            reduced_motion: false,
+            hover: true,
+            pointer: Pointer::Fine,
+        }
+    }
+}
+
+impl MediaQueryList {
+    /// Returns true if any query in the list matches `environment`.
+    pub fn evaluate(&self, environment: &Environment) -> bool {
+        self.0.is_empty() || self.0.iter().any(|query| query.evaluate(environment))
+    }
+}
+
+impl MediaQuery {
+    /// The query `not all`, which is what an invalid media query evaluates as.
+    pub fn not_all() -> MediaQuery {
+        MediaQuery {
+            qualifier: Some(Qualifier::Not),
+            media_type: MediaType::All,
+            condition: None,
+        }
+    }
+
+    /// Returns true if the query matches `environment`.
+    pub fn evaluate(&self, environment: &Environment) -> bool {
+        let type_matches = match self.media_type {
+            MediaType::All => true,
+            MediaType::Other(_) => false,
+            ref media_type => *media_type == environment.media_type,
+        };
+        let matches = type_matches
+            && self
+                .condition
+                .as_ref()
+                .is_none_or(|condition| condition.evaluate(environment) == Some(true));
+        match self.qualifier {
+            Some(Qualifier::Not) => !matches,
+            _ => matches,
+        }
+    }
+}
+
+impl MediaCondition {
+    /// Evaluates the condition using three-valued logic, where `None` means unknown.
+    pub fn evaluate(&self, environment: &Environment) -> Option<bool> {
+        self.evaluate_with(&|feature| feature.evaluate(environment))
+    }
+
+    /// Evaluates the condition using `evaluate_feature` to evaluate each feature test.
+    fn evaluate_with(
+        &self,
+        evaluate_feature: &dyn Fn(&MediaFeature) -> Option<bool>,
+    ) -> Option<bool> {
+        match self {
+            MediaCondition::Feature(feature) => evaluate_feature(feature),
+            MediaCondition::Not(condition) => condition.evaluate_with(evaluate_feature).map(|b| !b),
+            MediaCondition::And(conditions) => {
+                let results = conditions.iter().map(|c| c.evaluate_with(evaluate_feature));
+                let mut result = Some(true);
+                for r in results {
+                    match r {
+                        Some(false) => return Some(false),
+                        None => result = None,
+                        Some(true) => {}
+                    }
+                }
+                result
+            }
+            MediaCondition::Or(conditions) => {
+                let results = conditions.iter().map(|c| c.evaluate_with(evaluate_feature));
+                let mut result = Some(false);
+                for r in results {
+                    match r {
+                        Some(true) => return Some(true),
+                        None => result = None,
+                        Some(false) => {}
+                    }
+                }
+                result
+            }
+            MediaCondition::Unknown => None,
+        }
+    }
+}
+
+/// A container query, as found in the prelude of an `@container` rule.
+#[derive(Clone, PartialEq, Debug)]
+pub struct ContainerQuery {
+    /// The container name the query is restricted to, if any.
+    pub name: Option<String>,
+    /// The size condition. Container queries share their syntax with media conditions.
+    pub condition: MediaCondition,
+}
+
+/// The size of a query container, in px. A size that isn't known yet is `None`.
+#[derive(Clone, Copy, PartialEq, Debug, Default)]
+pub struct ContainerSize {
+    pub width: Option<f32>,
+    pub height: Option<f32>,
+}
+
+impl ContainerQuery {
+    /// Returns true if the query matches a container of the given size.
+    pub fn evaluate(&self, size: &ContainerSize) -> bool {
+        let evaluate_feature = |feature: &MediaFeature| match &*feature.name {
+            "width" | "inline-size" => feature.evaluate_range(size.width?, length),
+            "height" | "block-size" => feature.evaluate_range(size.height?, length),
+            "orientation" => {
+                let portrait = size.height? >= size.width?;
+                feature.evaluate_keyword(if portrait { "portrait" } else { "landscape" }, true)
+            }
+            _ => None,
+        };
+        self.condition.evaluate_with(&evaluate_feature) == Some(true)
+    }
+}
+
+impl MediaFeature {
+    /// Evaluates the feature against `environment`, returning `None` if the feature or its
+    /// value isn't understood.
+    pub fn evaluate(&self, environment: &Environment) -> Option<bool> {
+        match &*self.name {
+            "width" => self.evaluate_range(environment.width, length),
+            "height" => self.evaluate_range(environment.height, length),
+            "resolution" => self.evaluate_range(environment.resolution, resolution),
+            "orientation" => {
+                let portrait = environment.height >= environment.width;
+                self.evaluate_keyword(if portrait { "portrait" } else { "landscape" }, true)
+            }
+            "prefers-color-scheme" => match environment.color_scheme {
+                ColorScheme::Light => self.evaluate_keyword("light", true),
+                ColorScheme::Dark => self.evaluate_keyword("dark", true),
+            },
+            "prefers-reduced-motion" => match environment.reduced_motion {
+                true => self.evaluate_keyword("reduce", true),
+                false => self.evaluate_keyword("no-preference", false),
+            },
+            "hover" | "any-hover" => match environment.hover {
+                true => self.evaluate_keyword("hover", true),
+                false => self.evaluate_keyword("none", false),
+            },
+            "pointer" | "any-pointer" => match environment.pointer {
+                Pointer::None => self.evaluate_keyword("none", false),
+                Pointer::Coarse => self.evaluate_keyword("coarse", true),
+                Pointer::Fine => self.evaluate_keyword("fine", true),
+            },
+            _ => None,
+        }
+    }
+
+    /// Evaluates a range feature whose current value is `actual`. `convert` turns a CSS value
+    /// into the same units as `actual`.
+    fn evaluate_range(&self, actual: f32, convert: fn(&Value) -> Option<f32>) -> Option<bool> {
+        match &self.test {
+            FeatureTest::Boolean => Some(actual != 0.0),
+            FeatureTest::Plain(value) => Some(actual == convert(value)?),
+            FeatureTest::Range(comparisons) => {
+                let mut result = true;
+                for (comparison, value) in comparisons {
+                    let value = convert(value)?;
+                    result &= match comparison {
+                        Comparison::Lt => actual < value,
+                        Comparison::Le => actual <= value,
+                        Comparison::Gt => actual > value,
+                        Comparison::Ge => actual >= value,
+                        Comparison::Eq => actual == value,
+                    };
+                }
+                Some(result)
+            }
+        }
+    }
+
+    /// Evaluates a discrete feature whose current value is the keyword `actual`. `truthy` is
+    /// the result of evaluating the feature in a boolean context.
+    fn evaluate_keyword(&self, actual: &str, truthy: bool) -> Option<bool> {
+        match &self.test {
+            FeatureTest::Boolean => Some(truthy),
+            FeatureTest::Plain(Value::Keyword(keyword)) => {
+                Some(keyword.eq_ignore_ascii_case(actual))
+            }
+            _ => None,
+        }
+    }
+}
+
+/// Converts a length value to px.
+fn length(value: &Value) -> Option<f32> {
+    match *value {
+        Value::Length(length, Unit::Px) => Some(length),
+        Value::Integer(0) => Some(0.0),
+        _ => None,
+    }
+}
+
+/// Converts a resolution value to dppx.
+fn resolution(value: &Value) -> Option<f32> {
+    match value {
+        Value::Resolution(dots, ResolutionUnit::Dppx) => Some(*dots),
+        Value::Resolution(dots, ResolutionUnit::Dpi) => Some(dots / 96.0),
+        Value::Resolution(dots, ResolutionUnit::Dpcm) => Some(dots * 2.54 / 96.0),
+        Value::Keyword(keyword) if keyword == "infinite" => Some(f32::INFINITY),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn feature(name: &str, test: FeatureTest) -> MediaCondition {
+        MediaCondition::Feature(MediaFeature {
+            name: String::from(name),
+            test,
+        })
+    }
+
+    fn query(media_type: MediaType, condition: Option<MediaCondition>) -> MediaQuery {
+        MediaQuery {
+            qualifier: None,
+            media_type,
+            condition,
+        }
+    }
+
+    #[test]
+    fn test_evaluate_media_type() {
+        let environment = Environment::default();
+        assert!(query(MediaType::All, None).evaluate(&environment));
+        assert!(query(MediaType::Screen, None).evaluate(&environment));
+        assert!(!query(MediaType::Print, None).evaluate(&environment));
+        assert!(!query(MediaType::Other(String::from("tv")), None).evaluate(&environment));
+        assert!(!MediaQuery::not_all().evaluate(&environment));
+
+        let mut not_print = query(MediaType::Print, None);
+        not_print.qualifier = Some(Qualifier::Not);
+        assert!(not_print.evaluate(&environment));
+    }
+
+    #[test]
+    fn test_evaluate_range() {
+        let environment = Environment::default();
+        let range = |comparisons: Vec<(Comparison, f32)>| {
+            feature(
+                "width",
+                FeatureTest::Range(
+                    comparisons
+                        .into_iter()
+                        .map(|(c, v)| (c, Value::Length(v, Unit::Px)))
+                        .collect(),
+                ),
+            )
+        };
+
+        let condition = range(vec![(Comparison::Ge, 400.0), (Comparison::Lt, 800.0)]);
+        assert_eq!(condition.evaluate(&environment), Some(false));
+        let condition = range(vec![(Comparison::Ge, 400.0), (Comparison::Le, 800.0)]);
+        assert_eq!(condition.evaluate(&environment), Some(true));
+
+        let condition = feature(
+            "width",
+            FeatureTest::Plain(Value::Keyword(String::from("a"))),
+        );
+        assert_eq!(condition.evaluate(&environment), None);
+    }
+
+    #[test]
+    fn test_evaluate_discrete_features() {
+        let mut environment = Environment::default();
+        let keyword = |name: &str, value: &str| {
+            feature(
+                name,
+                FeatureTest::Plain(Value::Keyword(String::from(value))),
+            )
+        };
+
+        assert_eq!(
+            keyword("orientation", "landscape").evaluate(&environment),
+            Some(true)
+        );
+        assert_eq!(
+            keyword("prefers-color-scheme", "dark").evaluate(&environment),
+            Some(false)
+        );
+        assert_eq!(
+            feature("prefers-reduced-motion", FeatureTest::Boolean).evaluate(&environment),
+            Some(false)
+        );
+        assert_eq!(
+            feature("hover", FeatureTest::Boolean).evaluate(&environment),
+            Some(true)
+        );
+
+        environment.color_scheme = ColorScheme::Dark;
+        environment.pointer = Pointer::Coarse;
+        assert_eq!(
+            keyword("prefers-color-scheme", "dark").evaluate(&environment),
+            Some(true)
+        );
+        assert_eq!(
+            keyword("pointer", "fine").evaluate(&environment),
+            Some(false)
+        );
+        assert_eq!(
+            keyword("pointer", "coarse").evaluate(&environment),
+            Some(true)
+        );
+    }
+
+    #[test]
+    fn test_evaluate_three_valued_logic() {
+        let environment = Environment::default();
+        let yes = feature("hover", FeatureTest::Boolean);
+        let no = feature("prefers-reduced-motion", FeatureTest::Boolean);
+
+        let and = MediaCondition::And(vec![yes.clone(), MediaCondition::Unknown]);
+        assert_eq!(and.evaluate(&environment), None);
+        let and = MediaCondition::And(vec![no.clone(), MediaCondition::Unknown]);
+        assert_eq!(and.evaluate(&environment), Some(false));
+        let or = MediaCondition::Or(vec![yes.clone(), MediaCondition::Unknown]);
+        assert_eq!(or.evaluate(&environment), Some(true));
+        let not = MediaCondition::Not(Box::new(MediaCondition::Unknown));
+        assert_eq!(not.evaluate(&environment), None);
+
+        // A query whose condition is unknown doesn't match.
+        let unknown = query(MediaType::All, Some(MediaCondition::Unknown));
+        assert!(!unknown.evaluate(&environment));
+        assert!(
+            MediaQueryList(vec![unknown, query(MediaType::All, Some(yes))]).evaluate(&environment)
+        );
+    }
+}
@@ -37,9 +37,7 @@ pub trait Parser {
             return Err("No more characters in input string");
         }
 
-        Ok(self.input()[self.current_position()..]
-            .as_bytes()
-            .starts_with(s))
+        Ok(self.input().as_bytes()[self.current_position()..].starts_with(s))
     }
 
     /// Returns true if the current position is at the end of the input.
@@ -0,0 +1,636 @@
+use crate::{
+    computed::{ComputedStyle, LengthPercentageAuto, Position},
+    dom::NodeType,
+    font::FontDatabase,
+    layout::{content_size, ContainingBlock, Dimensions, EdgeSizes, LayoutBox, Rect},
+};
+
+/// Returns how far a box with style `style` is moved from its place in flow by relative
+/// positioning, as in CSS 2.1 § 9.4.3, in a containing block `width` wide and `height` high, if
+/// its height is known. `left` wins over `right`, and `top` over `bottom`.
+pub(crate) fn relative_offset(
+    style: &ComputedStyle,
+    width: f32,
+    height: Option<f32>,
+) -> (f32, f32) {
+    if style.position() != Position::Relative {
+        return (0.0, 0.0);
+    }
+    let vertical = |inset: LengthPercentageAuto| match (inset, height) {
+        (LengthPercentageAuto::Percent(_), None) => None,
+        (inset, height) => inset.resolve(height.unwrap_or(0.0)),
+    };
+    let dx = match (style.left().resolve(width), style.right().resolve(width)) {
+        (Some(left), _) => left,
+        (None, Some(right)) => -right,
+        (None, None) => 0.0,
+    };
+    let dy = match (vertical(style.top()), vertical(style.bottom())) {
+        (Some(top), _) => top,
+        (None, Some(bottom)) => -bottom,
+        (None, None) => 0.0,
+    };
+    (dx, dy)
+}
+
+/// The values along one axis of the constraint an absolutely positioned box's size and position
+/// satisfy, as in CSS 2.1 §§ 10.3.7 and 10.6.4: the inset from the start of the containing block,
+/// the margins, the content size and the inset from the end. `None` is `auto`.
+#[derive(Clone, Copy, Debug)]
+struct Insets {
+    start: Option<f32>,
+    margin_start: Option<f32>,
+    size: Option<f32>,
+    margin_end: Option<f32>,
+    end: Option<f32>,
+}
+
+/// Solves `start + margin_start + size + margin_end + end = available` for the `auto` values of
+/// `insets`, where `available` is the size of the containing block less the box's padding and
+/// borders. Returns the used start, margin-start, size and margin-end.
+///
+/// If both insets are `auto`, the box stays at `static_start`, the start of its margin box had it
+/// been in flow. An `auto` size shrinks to fit: `auto_size` returns it given the space there is.
+/// If nothing is `auto`, the end gives way, as it does in left-to-right text.
+fn solve_absolute(
+    insets: Insets,
+    available: f32,
+    static_start: f32,
+    auto_size: impl Fn(f32) -> f32,
+) -> (f32, f32, f32, f32) {
+    let Insets {
+        start,
+        margin_start,
+        size,
+        margin_end,
+        end,
+    } = insets;
+    if let (Some(start), Some(size), Some(end)) = (start, size, end) {
+        let space = available - start - size - end;
+        return match (margin_start, margin_end) {
+            // Auto margins share the space equally, unless that would make them negative.
+            (None, None) if space < 0.0 => (start, 0.0, size, space),
+            (None, None) => (start, space / 2.0, size, space / 2.0),
+            (None, Some(margin_end)) => (start, space - margin_end, size, margin_end),
+            (Some(margin_start), _) => (start, margin_start, size, margin_end.unwrap_or(0.0)),
+        };
+    }
+    // Otherwise auto margins are 0.
+    let margin_start = margin_start.unwrap_or(0.0);
+    let margin_end = margin_end.unwrap_or(0.0);
+    let space = available - margin_start - margin_end;
+    let (start, size) = match (start, size, end) {
+        (None, None, None) => (static_start, auto_size(space - static_start)),
+        (None, None, Some(end)) => {
+            let size = auto_size(space - end);
+            (space - end - size, size)
+        }
+        (None, Some(size), None) => (static_start, size),
+        (None, Some(size), Some(end)) => (space - end - size, size),
+        (Some(start), None, None) => (start, auto_size(space - start)),
+        (Some(start), None, Some(end)) => (start, (space - start - end).max(0.0)),
+        (Some(start), Some(size), _) => (start, size),
+    };
+    (start, margin_start, size, margin_end)
+}
+
+impl LayoutBox<'_> {
+    /// Moves a relatively positioned box laid out in `containing_block` by its offset, and
+    /// records its used insets.
+    pub(crate) fn apply_relative_offset(&mut self, containing_block: ContainingBlock) {
+        let (dx, dy) = relative_offset(
+            self.style(),
+            containing_block.rect.width,
+            containing_block.height,
+        );
+        if self.style().position() == Position::Relative {
+            self.insets = Some(EdgeSizes {
+                left: dx,
+                right: -dx,
+                top: dy,
+                bottom: -dy,
+            });
+        }
+        if dx != 0.0 || dy != 0.0 {
+            self.translate(dx, dy);
+        }
+    }
+
+    /// Records where the margin box of an absolutely positioned box would start had it been in
+    /// flow, which `auto` insets fall back to, until it is laid out with its containing block.
+    pub(crate) fn set_static_position(&mut self, x: f32, y: f32) {
+        self.dimensions = Dimensions {
+            content: Rect {
+                x,
+                y,
+                ..Rect::default()
+            },
+            ..Dimensions::default()
+        };
+    }
+
+    /// Lays out the boxes with `position: absolute` that a positioned box is the containing block
+    /// of, which is `rect`, its padding box.
+    ///
+    /// Positioned inline boxes that aren't atomic are not containing blocks here, so their
+    /// absolutely positioned descendants are positioned against the nearest positioned block.
+    pub(crate) fn layout_absolute_descendants(&mut self, rect: Rect, fonts: &FontDatabase) {
+        let mut boxes = Vec::new();
+        collect_positioned(&mut self.children, Position::Absolute, &mut boxes);
+        for layout_box in boxes {
+            layout_box.layout_absolute(rect, fonts);
+        }
+    }
+
+    /// Lays out the boxes with `position: fixed` in the box and its descendants against
+    /// `viewport`.
+    pub(crate) fn layout_fixed_descendants(&mut self, viewport: Rect, fonts: &FontDatabase) {
+        let mut boxes = Vec::new();
+        collect_positioned(&mut self.children, Position::Fixed, &mut boxes);
+        for layout_box in boxes {
+            layout_box.layout_absolute(viewport, fonts);
+            layout_box.layout_fixed_descendants(viewport, fonts);
+        }
+    }
+
+    /// Lays out an absolutely positioned box in `rect`, the padding box of its containing block,
+    /// as in CSS 2.1 §§ 10.3.7 and 10.6.4, from the static position recorded by
+    /// `set_static_position`.
+    ///
+    /// The width is solved first, limited by `max-width` and `min-width` as for boxes in flow.
+    /// The contents are then laid out, and an `auto` height is the height of the contents unless
+    /// both `top` and `bottom` are given, in which case the box stretches between them.
+    fn layout_absolute(&mut self, rect: Rect, fonts: &FontDatabase) {
+        let (static_x, static_y) = (self.dimensions.content.x, self.dimensions.content.y);
+        let containing_block = ContainingBlock {
+            rect,
+            height: Some(rect.height),
+        };
+
+        let style = self.style();
+        let reference = rect.width;
+        let padding_left = style.padding_left().resolve(reference);
+        let padding_right = style.padding_right().resolve(reference);
+        let border_left = style.border_left_width();
+        let border_right = style.border_right_width();
+        let edges = padding_left + padding_right + border_left + border_right;
+        let content_size = content_size(style.box_sizing(), edges);
+        let width = style.width().resolve(reference).map(content_size);
+        let min_width = style
+            .min_width()
+            .resolve(reference)
+            .map_or(0.0, content_size);
+        let max_width = style.max_width().resolve(reference).map(content_size);
+        let (min_content, max_content) = self.intrinsic_widths(fonts);
+        let solve = |width: Option<f32>| {
+            let insets = Insets {
+                start: style.left().resolve(reference),
+                margin_start: style.margin_left().resolve(reference),
+                size: width,
+                margin_end: style.margin_right().resolve(reference),
+                end: style.right().resolve(reference),
+            };
+            solve_absolute(insets, rect.width - edges, static_x - rect.x, |space| {
+                space.max(min_content).min(max_content)
+            })
+        };
+        let mut used = solve(width);
+        if let Some(max_width) = max_width.filter(|&max_width| used.2 > max_width) {
+            used = solve(Some(max_width));
+        }
+        if used.2 < min_width {
+            used = solve(Some(min_width));
+        }
+        let (left, margin_left, width, margin_right) = used;
+        let right = rect.width - edges - left - margin_left - width - margin_right;
+        let top = style.top().resolve(rect.height);
+        let bottom = style.bottom().resolve(rect.height);
+        let margin_top = style.margin_top().resolve(reference);
+        let margin_bottom = style.margin_bottom().resolve(reference);
+
+        let dimensions = &mut self.dimensions;
+        dimensions.margin.left = margin_left;
+        dimensions.margin.right = margin_right;
+        dimensions.padding.left = padding_left;
+        dimensions.padding.right = padding_right;
+        dimensions.border.left = border_left;
+        dimensions.border.right = border_right;
+        dimensions.content.width = width;
+
+        // Lay out the contents at the top of the containing block, then move the box into place
+        // once its height is known.
+        self.calculate_block_position(containing_block, rect.y);
+        self.dimensions.content.x = rect.x + left + margin_left + border_left + padding_left;
+        let (height, min_height, max_height) = self.vertical_sizes(containing_block);
+        let dimensions = self.dimensions;
+        let edges = dimensions.padding.top
+            + dimensions.padding.bottom
+            + dimensions.border.top
+            + dimensions.border.bottom;
+        let height = match (height, top, bottom) {
+            (None, Some(top), Some(bottom)) => {
+                let space = rect.height
+                    - edges
+                    - top
+                    - bottom
+                    - margin_top.unwrap_or(0.0)
+                    - margin_bottom.unwrap_or(0.0);
+                let height = space.max(0.0);
+                let height = max_height.map_or(height, |max_height| height.min(max_height));
+                Some(height.max(min_height))
+            }
+            (height, _, _) => height,
+        };
+        self.layout_contents((height, min_height, max_height), None, fonts);
+
+        let insets = Insets {
+            start: top,
+            margin_start: margin_top,
+            size: Some(self.dimensions.content.height),
+            margin_end: margin_bottom,
+            end: bottom,
+        };
+        let (top, margin_top, _, margin_bottom) =
+            solve_absolute(insets, rect.height - edges, static_y - rect.y, |size| size);
+        self.dimensions.margin.top = margin_top;
+        self.dimensions.margin.bottom = margin_bottom;
+        let border_top = self.dimensions.border_box().y;
+        self.translate(0.0, rect.y + top + margin_top - border_top);
+        let height = self.dimensions.margin_box().height;
+        self.insets = Some(EdgeSizes {
+            left,
+            right,
+            top,
+            bottom: rect.height - top - height,
+        });
+    }
+
+    /// Moves the boxes with `position: sticky` among the descendants of a block container whose
+    /// content area is `container` so that they stay in `viewport` as far as their containing
+    /// blocks allow, as in CSS Positioned Layout 3 § 3.4. Only block-level boxes are moved.
+    pub(crate) fn apply_sticky_offsets(&mut self, container: Rect, viewport: Rect) {
+        for child in &mut self.children {
+            if child.is_block_level() && child.style().position() == Position::Sticky {
+                let (dx, dy) = child.sticky_offset(container, viewport);
+                child.translate(dx, dy);
+            }
+            let container = match child.is_block_level() || child.is_atomic_inline() {
+                true => child.dimensions.content,
+                false => container,
+            };
+            child.apply_sticky_offsets(container, viewport);
+        }
+    }
+
+    /// Returns how far a sticky positioned box must move for its border box to be inside
+    /// `viewport` less its insets, without its margin box leaving `container`. `top` wins over
+    /// `bottom`, and `left` over `right`.
+    fn sticky_offset(&self, container: Rect, viewport: Rect) -> (f32, f32) {
+        let style = self.style();
+        let border_box = self.dimensions.border_box();
+        let margin_box = self.dimensions.margin_box();
+        let offset = |start: Option<f32>,
+                      end: Option<f32>,
+                      (view_start, view_size): (f32, f32),
+                      (box_start, box_size): (f32, f32),
+                      (margin_start, margin_size): (f32, f32),
+                      (container_start, container_size): (f32, f32)| {
+            let mut offset: f32 = 0.0;
+            if let Some(end) = end {
+                offset = offset.min(view_start + view_size - end - (box_start + box_size));
+            }
+            if let Some(start) = start {
+                offset = offset.max(view_start + start - box_start);
+            }
+            let min = container_start - margin_start;
+            let max = container_start + container_size - (margin_start + margin_size);
+            offset.clamp(min.min(0.0), max.max(0.0))
+        };
+        let dx = offset(
+            style.left().resolve(viewport.width),
+            style.right().resolve(viewport.width),
+            (viewport.x, viewport.width),
+            (border_box.x, border_box.width),
+            (margin_box.x, margin_box.width),
+            (container.x, container.width),
+        );
+        let dy = offset(
+            style.top().resolve(viewport.height),
+            style.bottom().resolve(viewport.height),
+            (viewport.y, viewport.height),
+            (border_box.y, border_box.height),
+            (margin_box.y, margin_box.height),
+            (container.y, container.height),
+        );
+        (dx, dy)
+    }
+
+    /// Returns true if the box establishes a stacking context: the root, a positioned box or
+    /// flex or grid item with an integer `z-index`, a fixed or sticky positioned box, or a box
+    /// with `opacity` below 1.
+    fn establishes_stacking_context(&self, item: bool) -> bool {
+        let style = self.style();
+        let positioned = match style.position() {
+            Position::Static => item && style.z_index().is_some(),
+            Position::Relative | Position::Absolute => style.z_index().is_some(),
+            Position::Fixed | Position::Sticky => true,
+        };
+        positioned || style.opacity() < 1.0
+    }
+}
+
+/// Appends the boxes with `position`, either `absolute` or `fixed`, among `boxes` and their
+/// descendants to `positioned`, in document order, leaving out those in a box that lays them out
+/// itself: a positioned block or atomic inline for absolute boxes, or a fixed box for fixed ones.
+fn collect_positioned<'b, 'a>(
+    boxes: &'b mut [LayoutBox<'a>],
+    position: Position,
+    positioned: &mut Vec<&'b mut LayoutBox<'a>>,
+) {
+    for child in boxes {
+        let child_position = child.style().position();
+        if child.is_absolutely_positioned() && child_position == position {
+            positioned.push(child);
+        } else if position == Position::Fixed
+            || child_position == Position::Static
+            || !(child.is_block_level() || child.is_atomic_inline())
+        {
+            collect_positioned(&mut child.children, position, positioned);
+        }
+    }
+}
+
+/// A stacking context of a laid out box tree, as in CSS 2.1 Appendix E, which a paint stage
+/// paints as a unit.
+pub struct StackingContext<'b, 'a> {
+    /// The box that establishes it, or a positioned box with `z-index: auto`, which is painted as
+    /// if it did but whose positioned descendants belong to its parent.
+    pub layout_box: &'b LayoutBox<'a>,
+    /// The stack level within its parent, which is 0 for `auto`.
+    pub z_index: i32,
+    /// The stacking contexts and positioned boxes in it, in painting order: those with negative
+    /// stack levels are painted before the box's own content in flow, and the others after it.
+    /// Those at the same level are in document order.
+    pub children: Vec<StackingContext<'b, 'a>>,
+}
+
+impl<'b, 'a> StackingContext<'b, 'a> {
+    /// Builds the tree of stacking contexts of the box tree rooted at `root`, which establishes
+    /// the root stacking context.
+    pub fn new(root: &'b LayoutBox<'a>) -> StackingContext<'b, 'a> {
+        StackingContext::with_z_index(root, 0)
+    }
+
+    fn with_z_index(layout_box: &'b LayoutBox<'a>, z_index: i32) -> StackingContext<'b, 'a> {
+        let mut children = Vec::new();
+        let items = layout_box.is_flex_container() || layout_box.is_grid_container();
+        collect_stacking_contexts(&layout_box.children, items, &mut children);
+        // A stable sort keeps each stack level in document order.
+        children.sort_by_key(|child| child.z_index);
+        StackingContext {
+            layout_box,
+            z_index,
+            children,
+        }
+    }
+}
+
+/// Appends the stacking contexts and positioned boxes among `boxes` and their descendants that
+/// belong to the enclosing stacking context to `contexts`, in document order. `items` is whether
+/// `boxes` are the items of a flex or grid container, which `z-index` applies to.
+fn collect_stacking_contexts<'b, 'a>(
+    boxes: &'b [LayoutBox<'a>],
+    items: bool,
+    contexts: &mut Vec<StackingContext<'b, 'a>>,
+) {
+    for child in boxes {
+        // Text takes the style of its parent, but never establishes a stacking context itself.
+        if matches!(child.styled_node(), Some(styled) if matches!(styled.node.node_type, NodeType::Text(_)))
+        {
+            continue;
+        }
+        let style = child.style();
+        if child.establishes_stacking_context(items) {
+            let z_index = match style.position() {
+                Position::Static if !items => 0,
+                _ => style.z_index().unwrap_or(0),
+            };
+            contexts.push(StackingContext::with_z_index(child, z_index));
+        } else {
+            if style.position() != Position::Static {
+                contexts.push(StackingContext {
+                    layout_box: child,
+                    z_index: 0,
+                    children: Vec::new(),
+                });
+            }
+            let items = child.is_flex_container() || child.is_grid_container();
+            collect_stacking_contexts(&child.children, items, contexts);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::layout::tests::{border_boxes, with_layout_in, VIEWPORT};
+
+    #[test]
+    fn test_solve_absolute() {
+        let insets = Insets {
+            start: None,
+            margin_start: None,
+            size: None,
+            margin_end: Some(5.0),
+            end: None,
+        };
+        let shrink = |space: f32| space.min(50.0);
+        assert_eq!(
+            solve_absolute(insets, 200.0, 30.0, shrink),
+            (30.0, 0.0, 50.0, 5.0)
+        );
+        // Right aligned, shrinking to fit.
+        let insets = Insets {
+            end: Some(10.0),
+            ..insets
+        };
+        assert_eq!(
+            solve_absolute(insets, 200.0, 30.0, shrink),
+            (135.0, 0.0, 50.0, 5.0)
+        );
+        // Stretched between the insets.
+        let insets = Insets {
+            start: Some(20.0),
+            ..insets
+        };
+        assert_eq!(
+            solve_absolute(insets, 200.0, 30.0, shrink),
+            (20.0, 0.0, 165.0, 5.0)
+        );
+        // Centered by auto margins.
+        let insets = Insets {
+            start: Some(0.0),
+            margin_start: None,
+            size: Some(100.0),
+            margin_end: None,
+            end: Some(0.0),
+        };
+        assert_eq!(
+            solve_absolute(insets, 200.0, 30.0, shrink),
+            (0.0, 50.0, 100.0, 50.0)
+        );
+        // Over-constrained, so the end gives way.
+        let insets = Insets {
+            margin_start: Some(10.0),
+            margin_end: Some(10.0),
+            ..insets
+        };
+        assert_eq!(
+            solve_absolute(insets, 200.0, 30.0, shrink),
+            (0.0, 10.0, 100.0, 10.0)
+        );
+    }
+
+    /// Lays out `html` styled with `css` in the viewport scrolled to `scroll`, and calls `f`
+    /// with the root box.
+    fn with_layout(html: &str, css: &str, scroll: (f32, f32), f: impl FnOnce(&LayoutBox)) {
+        let viewport = Rect {
+            x: scroll.0,
+            y: scroll.1,
+            ..VIEWPORT
+        };
+        with_layout_in(viewport, "", html, css, f);
+    }
+
+    #[test]
+    fn test_relative_offsets() {
+        with_layout(
+            r#"<html><div><p id="a"></p><p></p><p id="b"></p></div></html>"#,
+            "div { display: block; }
+             p { display: block; height: 10px; }
+             #a { position: relative; left: 5px; right: 100px; bottom: 3px; }
+             #b { position: relative; top: 50%; }",
+            (0.0, 0.0),
+            |root| {
+                // The boxes around a relatively positioned box are laid out as if it weren't
+                // moved, and percentages of an auto height are 0.
+                assert_eq!(
+                    border_boxes(&root.children[0]),
+                    vec![
+                        (5.0, -3.0, 800.0, 10.0),
+                        (0.0, 10.0, 800.0, 10.0),
+                        (0.0, 20.0, 800.0, 10.0),
+                    ]
+                );
+            },
+        );
+    }
+
+    #[test]
+    fn test_absolute_positioning() {
+        with_layout(
+            "<html><div><p></p><i></i><b></b><span></span><em></em></div></html>",
+            "div { display: block; position: relative; margin: 10px; padding: 5px; height: 100px; }
+             p { display: block; height: 20px; }
+             i { position: absolute; inset: 0; }
+             b { position: absolute; right: 10px; bottom: 10px; width: 30px; height: 30px; }
+             span { position: absolute; left: 0; right: 0; width: 100px; height: 10px; margin: auto; }
+             em { position: absolute; height: 10px; }",
+            (0.0, 0.0),
+            |root| {
+                let div = &root.children[0];
+                // The containing block is the padding box, from (10, 10) to (790, 120).
+                assert_eq!(
+                    border_boxes(div)[1..],
+                    [
+                        (10.0, 10.0, 780.0, 110.0),
+                        (750.0, 80.0, 30.0, 30.0),
+                        (350.0, 35.0, 100.0, 10.0),
+                        // At its static position, after the paragraph, with no width to shrink to.
+                        (15.0, 35.0, 0.0, 10.0),
+                    ]
+                );
+                // Absolutely positioned boxes take no room in flow.
+                assert_eq!(div.dimensions.content.height, 100.0);
+            },
+        );
+    }
+
+    #[test]
+    fn test_fixed_positioning() {
+        with_layout(
+            "<html><div><p></p></div></html>",
+            "div { display: block; position: relative; height: 2000px; }
+             p { position: fixed; top: 10px; left: 20px; width: 50px; height: 50px; }",
+            (0.0, 500.0),
+            |root| {
+                // Fixed boxes are placed in the scrolled viewport, not their positioned ancestor.
+                assert_eq!(border_boxes(&root.children[0]), [(20.0, 510.0, 50.0, 50.0)]);
+            },
+        );
+    }
+
+    #[test]
+    fn test_sticky_positioning() {
+        let css = "b { display: block; height: 100px; }
+                   div { display: block; height: 300px; }
+                   p { display: block; position: sticky; top: 10px; height: 50px; }";
+        let html = "<html><b></b><div><p></p></div></html>";
+        let sticky_top = |scroll: f32| {
+            let mut top = 0.0;
+            with_layout(html, css, (0.0, scroll), |root| {
+                top = root.children[1].children[0].dimensions.border_box().y;
+            });
+            top
+        };
+        assert_eq!(sticky_top(0.0), 100.0);
+        // It sticks 10px below the top of the viewport...
+        assert_eq!(sticky_top(150.0), 160.0);
+        // ...until it reaches the bottom of its containing block.
+        assert_eq!(sticky_top(380.0), 350.0);
+    }
+
+    #[test]
+    fn test_stacking_contexts() {
+        with_layout(
+            "<html><a></a><b><i></i></b><p><em></em></p><s></s><u></u></html>",
+            "a { position: relative; z-index: 2; }
+             b { position: relative; }
+             i { position: absolute; z-index: -1; }
+             p { opacity: 0.5; }
+             em { position: relative; z-index: 5; }
+             s { position: fixed; }
+             u { position: relative; z-index: -1; }",
+            (0.0, 0.0),
+            |root| {
+                let context = StackingContext::new(root);
+                let describe = |context: &StackingContext| {
+                    let name = |layout_box: &LayoutBox| match layout_box.styled_node() {
+                        Some(styled) => match styled.node.node_type {
+                            NodeType::Element(ref element) => element.tag_name.clone(),
+                            _ => String::new(),
+                        },
+                        None => String::new(),
+                    };
+                    context
+                        .children
+                        .iter()
+                        .map(|child| {
+                            let children = child
+                                .children
+                                .iter()
+                                .map(|child| name(child.layout_box))
+                                .collect::<Vec<_>>();
+                            format!("{}{}{:?}", name(child.layout_box), child.z_index, children)
+                        })
+                        .collect::<Vec<_>>()
+                };
+                // The child of `b`, whose z-index is auto, belongs to the root stacking context.
+                assert_eq!(
+                    describe(&context),
+                    ["i-1[]", "u-1[]", "b0[]", "p0[\"em\"]", "s0[]", "a2[]"]
+                );
+            },
+        );
+    }
+}
@@ -0,0 +1,266 @@
+use std::collections::HashMap;
+
+use crate::{
+    bloom::{class_hash, id_hash, tag_hash, CountingBloomFilter},
+    css::{Rule, Selector, Specificity, Stylesheet},
+    dom::ElementData,
+};
+
+/// The maximum number of ancestor hashes stored for a selector.
+const ANCESTOR_HASHES: usize = 4;
+
+/// The rules of a stylesheet, with their selectors bucketed by the rightmost id, class or tag they
+/// require, so that matching an element only has to look at selectors that could match it.
+///
+/// The index doesn't depend on the environment, so it can be built once per stylesheet.
+pub struct RuleIndex<'a> {
+    /// The rules in source order, each with the rank of its cascade layer.
+    rules: Vec<(usize, &'a Rule)>,
+    by_id: HashMap<&'a str, Vec<IndexedSelector<'a>>>,
+    by_class: HashMap<&'a str, Vec<IndexedSelector<'a>>>,
+    by_tag: HashMap<&'a str, Vec<IndexedSelector<'a>>>,
+    /// Selectors whose rightmost compound has no id, class or tag.
+    universal: Vec<IndexedSelector<'a>>,
+}
+
+/// A selector in a `RuleIndex`, along with the information needed to cascade its rule.
+#[derive(Clone, Copy, Debug)]
+pub struct IndexedSelector<'a> {
+    pub selector: &'a Selector,
+    pub specificity: Specificity,
+    /// The index of the selector's rule in the stylesheet, i.e. its source order.
+    pub source_order: usize,
+    /// Hashes of tag names, ids and classes some ancestor must have for the selector to match,
+    /// padded with zeros.
+    pub ancestor_hashes: [u32; ANCESTOR_HASHES],
+}
+
+impl IndexedSelector<'_> {
+    /// Returns false if the selector can't match an element whose ancestors are in `ancestors`,
+    /// because one of the keys it requires of them is missing.
+    pub fn may_match(&self, ancestors: &CountingBloomFilter) -> bool {
+        self.ancestor_hashes
+            .iter()
+            .all(|&hash| hash == 0 || ancestors.might_contain(hash))
+    }
+}
+
+/// The key a selector is bucketed under.
+#[derive(PartialEq, Debug)]
+enum BucketKey<'a> {
+    Id(&'a str),
+    Class(&'a str),
+    Tag(&'a str),
+    Universal,
+}
+
+impl<'a> RuleIndex<'a> {
+    /// Builds an index of the rules of `stylesheet`.
+    pub fn new(stylesheet: &'a Stylesheet) -> RuleIndex<'a> {
+        let layers = layer_ranks(stylesheet);
+        let mut index = RuleIndex {
+            rules: Vec::with_capacity(stylesheet.rules.len()),
+            by_id: HashMap::new(),
+            by_class: HashMap::new(),
+            by_tag: HashMap::new(),
+            universal: Vec::new(),
+        };
+
+        for (source_order, rule) in stylesheet.rules.iter().enumerate() {
+            let layer = match rule.layer {
+                Some(ref name) => layers[name.as_str()],
+                None => stylesheet.layers.len(),
+            };
+            index.rules.push((layer, rule));
+
+            for selector in &rule.selectors {
+                let entry = IndexedSelector {
+                    selector,
+                    specificity: selector.specificity(),
+                    source_order,
+                    ancestor_hashes: ancestor_hashes(selector),
+                };
+                match bucket_key(selector) {
+                    BucketKey::Id(id) => index.by_id.entry(id).or_default().push(entry),
+                    BucketKey::Class(class) => index.by_class.entry(class).or_default().push(entry),
+                    BucketKey::Tag(tag) => index.by_tag.entry(tag).or_default().push(entry),
+                    BucketKey::Universal => index.universal.push(entry),
+                }
+            }
+        }
+        index
+    }
+
+    /// Returns the rules in source order, each with the rank of its cascade layer.
+    pub fn rules(&self) -> &[(usize, &'a Rule)] {
+        &self.rules
+    }
+
+    /// Returns the selectors that could match `elem`: those whose bucket key is its id, one of
+    /// its classes, its tag name, or universal.
+    ///
+    /// The selectors still have to be matched against the element. A rule may appear more than
+    /// once if several of its selectors are candidates.
+    pub fn candidates<'s>(
+        &'s self,
+        elem: &'s ElementData,
+    ) -> impl Iterator<Item = &'s IndexedSelector<'a>> + 's {
+        let by_id = elem.id().and_then(|id| self.by_id.get(id.as_str()));
+        let by_class = elem
+            .classes()
+            .into_iter()
+            .filter_map(|class| self.by_class.get(class));
+        let by_tag = self.by_tag.get(elem.tag_name.as_str());
+        by_id
+            .into_iter()
+            .chain(by_class)
+            .chain(by_tag)
+            .flatten()
+            .chain(&self.universal)
+    }
+}
+
+/// Returns the key to bucket `selector` under: the id, first class or tag of its rightmost
+/// compound selector, in that order of preference.
+fn bucket_key(selector: &Selector) -> BucketKey<'_> {
+    let simple = selector.subject();
+    if let Some(ref id) = simple.id {
+        BucketKey::Id(id)
+    } else if let Some(class) = simple.class.first() {
+        BucketKey::Class(class)
+    } else if let Some(ref tag) = simple.tag_name {
+        BucketKey::Tag(tag)
+    } else {
+        BucketKey::Universal
+    }
+}
+
+/// Returns the hashes of the first tag names, ids and classes that `selector` requires of the
+/// ancestors of the element it matches, rightmost first.
+///
+/// The keys of `&` are left out, since an ancestor only has to match one of the selectors it
+/// stands for.
+fn ancestor_hashes(selector: &Selector) -> [u32; ANCESTOR_HASHES] {
+    let mut hashes = [0; ANCESTOR_HASHES];
+    let Selector::Complex(complex) = selector else {
+        return hashes;
+    };
+    let keys = complex.ancestors.iter().rev().flat_map(|(compound, _)| {
+        let simple = &compound.simple;
+        simple
+            .id
+            .iter()
+            .map(|id| id_hash(id))
+            .chain(simple.class.iter().map(|class| class_hash(class)))
+            .chain(simple.tag_name.iter().map(|tag| tag_hash(tag)))
+    });
+    for (slot, hash) in hashes.iter_mut().zip(keys) {
+        *slot = hash;
+    }
+    hashes
+}
+
+/// Ranks the cascade layers of `stylesheet`, lowest priority first.
+///
+/// A layer's sub-layers rank below the rules directly inside it, and all layers rank below
+/// unlayered rules, which get the rank `stylesheet.layers.len()`.
+fn layer_ranks(stylesheet: &Stylesheet) -> HashMap<&str, usize> {
+    fn rank_children<'s>(
+        layers: &'s [String],
+        parent: Option<&str>,
+        ranks: &mut HashMap<&'s str, usize>,
+    ) {
+        let children = layers
+            .iter()
+            .filter(|layer| layer.rsplit_once('.').map(|(parent, _)| parent) == parent);
+        for layer in children {
+            rank_children(layers, Some(layer), ranks);
+            ranks.insert(layer, ranks.len());
+        }
+    }
+
+    let mut ranks = HashMap::new();
+    rank_children(&stylesheet.layers, None, &mut ranks);
+    ranks
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{css, dom};
+
+    #[test]
+    fn test_bucket_key() {
+        let stylesheet = css::parse(String::from(
+            "p#main.a { color: a; } p.a.b { color: b; } main > p { color: c; } \
+             p * { color: d; } .card { & { color: e; } }",
+        ))
+        .unwrap();
+        let keys = stylesheet
+            .rules
+            .iter()
+            .map(|rule| bucket_key(&rule.selectors[0]))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            keys,
+            vec![
+                BucketKey::Id("main"),
+                BucketKey::Class("a"),
+                BucketKey::Tag("p"),
+                BucketKey::Universal,
+                BucketKey::Class("card"),
+                BucketKey::Universal,
+            ]
+        );
+    }
+
+    #[test]
+    fn test_ancestor_hashes() {
+        let stylesheet = css::parse(String::from(
+            "p { color: a; } main#top > .a.b div p { color: b; } .card { & p { color: c; } }",
+        ))
+        .unwrap();
+        let hashes = stylesheet
+            .rules
+            .iter()
+            .map(|rule| ancestor_hashes(&rule.selectors[0]))
+            .collect::<Vec<_>>();
+        assert_eq!(hashes[0], [0; 4]);
+        assert_eq!(
+            hashes[1],
+            [
+                tag_hash("div"),
+                class_hash("a"),
+                class_hash("b"),
+                id_hash("top")
+            ]
+        );
+        assert_eq!(hashes[3], [0; 4]);
+    }
+
+    #[test]
+    fn test_candidates() {
+        let stylesheet = css::parse(String::from(
+            "#main { color: a; } .a { color: b; } .b, div { color: c; } p { color: d; } \
+             * { color: e; } #other { color: f; }",
+        ))
+        .unwrap();
+        let index = RuleIndex::new(&stylesheet);
+        assert_eq!(index.rules().len(), 6);
+
+        let mut attributes = HashMap::new();
+        attributes.insert(String::from("id"), String::from("main"));
+        attributes.insert(String::from("class"), String::from("a b"));
+        let node = dom::elem(String::from("p"), attributes, Vec::new());
+        let dom::NodeType::Element(ref elem) = node.node_type else {
+            unreachable!()
+        };
+
+        let mut candidates = index
+            .candidates(elem)
+            .map(|candidate| candidate.source_order)
+            .collect::<Vec<_>>();
+        candidates.sort();
+        assert_eq!(candidates, vec![0, 1, 2, 3, 4]);
+    }
+}
@@ -0,0 +1,126 @@
+use std::{collections::VecDeque, sync::Arc};
+
+use crate::{dom::ElementData, style::PropertyMap};
+
+/// The number of recently styled elements the cache remembers.
+pub const STYLE_SHARING_CACHE_SIZE: usize = 8;
+
+/// A small LRU cache of recently styled elements, so that siblings that provably get the same
+/// style can share it instead of matching rules again.
+///
+/// Selectors only look at an element's tag name, id and classes, and at its ancestors. Two
+/// children of the same parent with the same tag name, id and classes therefore match the same
+/// rules under the same conditions.
+///
+/// The shared style is the specified values by default, but can be anything derived from them
+/// and the parent's style alone.
+pub struct StyleSharingCache<'a, T = Arc<PropertyMap>> {
+    /// The cached elements, most recently used first.
+    entries: VecDeque<Entry<'a, T>>,
+}
+
+struct Entry<'a, T> {
+    parent: Option<&'a ElementData>,
+    elem: &'a ElementData,
+    values: T,
+}
+
+impl<T> Default for StyleSharingCache<'_, T> {
+    fn default() -> Self {
+        StyleSharingCache {
+            entries: VecDeque::new(),
+        }
+    }
+}
+
+impl<'a, T: Clone> StyleSharingCache<'a, T> {
+    /// Returns the style of a cached sibling of `elem` that is known to get the same style.
+    pub fn lookup(&mut self, parent: Option<&'a ElementData>, elem: &'a ElementData) -> Option<T> {
+        let position = self
+            .entries
+            .iter()
+            .position(|entry| same_element(entry.parent, parent) && can_share(entry.elem, elem))?;
+        let entry = self.entries.remove(position)?;
+        let values = entry.values.clone();
+        self.entries.push_front(entry);
+        Some(values)
+    }
+
+    /// Remembers the style of `elem`, evicting the least recently used entry if the cache is full.
+    pub fn insert(&mut self, parent: Option<&'a ElementData>, elem: &'a ElementData, values: T) {
+        self.entries.truncate(STYLE_SHARING_CACHE_SIZE - 1);
+        self.entries.push_front(Entry {
+            parent,
+            elem,
+            values,
+        });
+    }
+}
+
+/// Returns true if `a` and `b` are the same element, or both absent.
+fn same_element(a: Option<&ElementData>, b: Option<&ElementData>) -> bool {
+    match (a, b) {
+        (Some(a), Some(b)) => std::ptr::eq(a, b),
+        (None, None) => true,
+        _ => false,
+    }
+}
+
+/// Returns true if every selector matches `a` and `b` alike, given that they have the same
+/// ancestors.
+fn can_share(a: &ElementData, b: &ElementData) -> bool {
+    a.tag_name == b.tag_name && a.id() == b.id() && a.classes() == b.classes()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dom::{self, NodeType};
+    use std::collections::HashMap;
+
+    fn element(tag_name: &str, class: &str) -> ElementData {
+        let mut attributes = HashMap::new();
+        attributes.insert(String::from("class"), String::from(class));
+        let NodeType::Element(elem) =
+            dom::elem(String::from(tag_name), attributes, Vec::new()).node_type
+        else {
+            unreachable!()
+        };
+        elem
+    }
+
+    #[test]
+    fn test_lookup() {
+        let parent = element("ul", "");
+        let other_parent = element("ul", "");
+        let first = element("li", "a b");
+        let second = element("li", "b a");
+        let different = element("li", "a");
+
+        let mut cache = StyleSharingCache::default();
+        let values = Arc::new(PropertyMap::new());
+        cache.insert(Some(&parent), &first, values.clone());
+
+        let shared = cache.lookup(Some(&parent), &second).unwrap();
+        assert!(Arc::ptr_eq(&shared, &values));
+        assert!(cache.lookup(Some(&parent), &different).is_none());
+        assert!(cache.lookup(Some(&other_parent), &second).is_none());
+    }
+
+    #[test]
+    fn test_evicts_least_recently_used() {
+        let parent = element("ul", "");
+        let items = (0..=STYLE_SHARING_CACHE_SIZE)
+            .map(|i| element("li", &format!("item{}", i)))
+            .collect::<Vec<_>>();
+
+        let mut cache = StyleSharingCache::default();
+        for item in &items {
+            cache.insert(Some(&parent), item, Arc::new(PropertyMap::new()));
+            // Keep the first item in use.
+            assert!(cache.lookup(Some(&parent), &items[0]).is_some());
+        }
+        assert!(cache.lookup(Some(&parent), &items[1]).is_none());
+        assert!(cache.lookup(Some(&parent), &items[2]).is_some());
+    }
+}
@@ -1,32 +1,118 @@
 use itertools::Itertools;
+use rayon::prelude::*;
 
 use crate::{
-    css::{Rule, Selector, SimpleSelector, Specificity, Stylesheet, Value},
+    bloom::CountingBloomFilter,
+    computed::{expand_declaration, ComputedStyle, WhiteSpace},
+    css::{
+        Combinator, CompoundSelector, Condition, Rule, Scope, Selector, SimpleSelector,
+        Specificity, Stylesheet, Unit, Value,
+    },
     dom::{ElementData, Node, NodeType},
+    invalidation::{DirtyBits, RetainedStyle},
+    media::{ContainerQuery, ContainerSize, Environment},
+    rule_index::RuleIndex,
+    sharing::StyleSharingCache,
 };
-use std::collections::HashMap;
+use std::{
+    cmp::Reverse,
+    collections::HashMap,
+    hash::{Hash, Hasher},
+    sync::Arc,
+};
+
+pub use crate::computed::Display;
 
 pub type PropertyMap = HashMap<String, Value>;
 
+/// The content-box widths and heights that layout gave query containers, which `@container`
+/// rules are evaluated against when the tree is styled again.
+///
+/// Containers are identified by their DOM node, which the sizes borrow, so they only apply to the
+/// DOM they were laid out from. `StyledNode::retain` keeps them across changes to the DOM.
+#[derive(Clone, Default, Debug)]
+pub struct ContainerSizes<'a>(HashMap<NodeRef<'a>, (f32, f32)>);
+
+impl<'a> ContainerSizes<'a> {
+    /// Records the content-box size of the container generated by `node`.
+    pub fn insert(&mut self, node: &'a Node, width: f32, height: f32) {
+        self.0.insert(NodeRef(node), (width, height));
+    }
+
+    /// Returns the content-box width and height of the container generated by `node`, if it was
+    /// laid out.
+    pub fn get(&self, node: &Node) -> Option<(f32, f32)> {
+        self.0.get(&NodeRef(node)).copied()
+    }
+
+    /// Returns the sizes of the containers among `root` and its descendants, borrowing the DOM
+    /// for as long as `root` is borrowed rather than as long as these sizes are.
+    pub fn in_tree<'b>(&self, root: &'b Node) -> ContainerSizes<'b> {
+        fn collect<'b>(node: &'b Node, from: &ContainerSizes, sizes: &mut ContainerSizes<'b>) {
+            if let Some((width, height)) = from.get(node) {
+                sizes.insert(node, width, height);
+            }
+            for child in &node.children {
+                collect(child, from, sizes);
+            }
+        }
+
+        let mut sizes = ContainerSizes::default();
+        collect(root, self, &mut sizes);
+        sizes
+    }
+
+    /// Records the sizes that `retained` kept for the query containers among `node` and its
+    /// descendants. Children whose retained styles don't line up with them are skipped.
+    fn insert_retained(&mut self, node: &'a Node, retained: &RetainedStyle) {
+        if let Some((width, height)) = retained.container_size {
+            self.insert(node, width, height);
+        }
+        if node.children.len() != retained.children.len() {
+            return;
+        }
+        for (child, retained) in node.children.iter().zip(&retained.children) {
+            if let Some(retained) = retained {
+                self.insert_retained(child, retained);
+            }
+        }
+    }
+}
+
+/// A DOM node that is compared and hashed by its identity rather than its contents.
+#[derive(Clone, Copy, Debug)]
+struct NodeRef<'a>(&'a Node);
+
+impl PartialEq for NodeRef<'_> {
+    fn eq(&self, other: &Self) -> bool {
+        std::ptr::eq(self.0, other.0)
+    }
+}
+
+impl Eq for NodeRef<'_> {}
+
+impl Hash for NodeRef<'_> {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        std::ptr::hash(self.0, state);
+    }
+}
+
+/// The specified and computed values of a node, which may be shared with other nodes.
+type SharedStyle = (Arc<PropertyMap>, Arc<ComputedStyle>);
+
 /// A struct representing a styled node in the DOM tree.
 pub struct StyledNode<'a> {
     /// The node being styled.
     pub node: &'a Node,
-    /// The specified values for the node's properties.
-    pub specified_values: PropertyMap,
+    /// The specified values for the node's properties, which may be shared with other nodes.
+    pub specified_values: Arc<PropertyMap>,
+    /// The computed values of the node's properties, which may be shared with other nodes.
+    pub computed: Arc<ComputedStyle>,
     /// The styled children of the node.
     pub children: Vec<StyledNode<'a>>,
-}
-
-/// An enum representing the display property of an HTML element.
-#[derive(PartialEq)]
-pub enum Display {
-    /// The element is displayed inline.
-    Inline,
-    /// The element is displayed as a block.
-    Block,
-    /// The element is not displayed.
-    None,
+    /// The laid-out content-box width and height `@container` rules were evaluated against, if
+    /// the node is a query container that was styled with them.
+    pub container_size: Option<(f32, f32)>,
 }
 
 impl<'a> StyledNode<'a> {
@@ -42,80 +128,757 @@ impl<'a> StyledNode<'a> {
             .unwrap_or_else(|| self.value(fallback_name).unwrap_or_else(|| default.clone()))
     }
 
-    /// Returns the `Display` value of the style.
+    /// Returns the computed `display` of the node.
     pub fn display(&self) -> Display {
-        match self.value("display") {
-            Some(Value::Keyword(s)) => match s.as_ref() {
-                "block" => Display::Block,
-                "none" => Display::None,
-                _ => Display::Inline,
-            },
-            _ => Display::Inline,
+        self.computed.display()
+    }
+
+    /// Returns true if the node is a query container with a size along some axis, which
+    /// `@container` rules in its subtree can query.
+    pub fn is_query_container(&self) -> bool {
+        query_container(&self.specified_values, None).is_some()
+    }
+
+    /// Returns the resolved value of the property `name` for the node, serialized like
+    /// `getComputedStyle` does, or `None` if the property is unknown.
+    ///
+    /// Values that depend on layout are returned as computed, and `LayoutBox::property_value`
+    /// resolves them once the node has a box.
+    pub fn property_value(&self, name: &str) -> Option<String> {
+        self.computed.property_value(name)
+    }
+
+    /// Returns the styled node for `node` in this subtree, if `node` is styled.
+    pub fn find(&self, node: &Node) -> Option<&StyledNode<'a>> {
+        if std::ptr::eq(self.node, node) {
+            return Some(self);
+        }
+        self.children.iter().find_map(|child| child.find(node))
+    }
+}
+
+impl StyledNode<'_> {
+    /// Returns the styles of the tree, detached from the DOM, so that they can be invalidated and
+    /// passed to `restyle` after the DOM changes.
+    ///
+    /// The retained children line up with the node's children in the DOM, with `None` for those
+    /// that aren't in the styled tree.
+    pub fn retain(&self) -> RetainedStyle {
+        let mut styled = self.children.iter().peekable();
+        let children = self
+            .node
+            .children
+            .iter()
+            .map(|child| {
+                styled
+                    .next_if(|styled| std::ptr::eq(styled.node, child))
+                    .map(StyledNode::retain)
+            })
+            .collect();
+        RetainedStyle {
+            specified_values: self.specified_values.clone(),
+            computed: self.computed.clone(),
+            children,
+            dirty: DirtyBits::default(),
+            container_size: self.container_size,
         }
     }
 }
 
-pub fn style_tree<'a>(root: &'a Node, stylesheet: &'a Stylesheet) -> StyledNode<'a> {
+/// Applies `stylesheet` to the tree rooted at `root`, evaluating conditional rules against
+/// `environment`.
+pub fn style_tree<'a>(
+    root: &'a Node,
+    stylesheet: &Stylesheet,
+    environment: &Environment,
+) -> StyledNode<'a> {
+    style_tree_with_index(root, &RuleIndex::new(stylesheet), environment)
+}
+
+/// Like `style_tree`, but with an index of the stylesheet's rules that has already been built,
+/// e.g. to style several documents with the same stylesheet.
+pub fn style_tree_with_index<'a>(
+    root: &'a Node,
+    index: &RuleIndex,
+    environment: &Environment,
+) -> StyledNode<'a> {
+    style_tree_with_container_sizes(root, index, environment, ContainerSizes::default())
+}
+
+/// Like `style_tree_with_index`, but evaluates `@container` rules against the laid-out sizes of
+/// the query containers in `sizes`, rather than against the sizes they specify.
+pub fn style_tree_with_container_sizes<'a>(
+    root: &'a Node,
+    index: &RuleIndex,
+    environment: &Environment,
+    sizes: ContainerSizes<'a>,
+) -> StyledNode<'a> {
+    let rules = ActiveRules::new(index, environment);
+    let mut ancestors = Ancestors {
+        container_sizes: Arc::new(sizes),
+        ..Ancestors::default()
+    };
+    style_subtree(
+        root,
+        &rules,
+        &mut ancestors,
+        &mut StyleSharingCache::default(),
+    )
+}
+
+/// Returns true if every condition enclosing `rule` that doesn't depend on the element being
+/// matched holds in `environment`.
+fn conditions_hold(rule: &Rule, environment: &Environment) -> bool {
+    rule.conditions.iter().all(|condition| match condition {
+        Condition::Media(media) => media.evaluate(environment),
+        Condition::Supports(supports) => supports.evaluate(),
+        Condition::Container(_) | Condition::Scope(_) => true,
+    })
+}
+
+/// A node of a styled tree, along with the rules that matched it.
+pub struct CascadeTrace<'a> {
+    pub styled: &'a StyledNode<'a>,
+    /// The rules that matched the node in cascade order, lowest precedence first, each with the
+    /// specificity it matched with. Empty for nodes that aren't elements.
+    pub rules: Vec<(Specificity, &'a Rule)>,
+    pub children: Vec<CascadeTrace<'a>>,
+}
+
+/// Matches the rules of `index` against every element of `styled` again, to explain where its
+/// specified values come from.
+///
+/// `styled` must have been styled with `index` in `environment`.
+pub fn trace_cascade<'a>(
+    styled: &'a StyledNode<'a>,
+    index: &'a RuleIndex<'a>,
+    environment: &Environment,
+) -> CascadeTrace<'a> {
+    fn trace_subtree<'a>(
+        styled: &'a StyledNode<'a>,
+        rules: &ActiveRules<'_, 'a>,
+        ancestors: &mut Ancestors<'a>,
+    ) -> CascadeTrace<'a> {
+        let matched = match styled.node.node_type {
+            NodeType::Element(ref elem) => matching_rules(elem, rules, ancestors)
+                .into_iter()
+                .sorted_by(|&(a, _), &(b, _)| a.cmp(&b))
+                .map(|((_, specificity, _), rule)| (specificity, rule))
+                .collect(),
+            _ => Vec::new(),
+        };
+        let style = (styled.specified_values.clone(), styled.computed.clone());
+        let is_container = ancestors.enter(styled.node, &style);
+        let children = styled
+            .children
+            .iter()
+            .map(|child| trace_subtree(child, rules, ancestors))
+            .collect();
+        ancestors.leave(styled.node, is_container);
+        CascadeTrace {
+            styled,
+            rules: matched,
+            children,
+        }
+    }
+
+    let rules = ActiveRules::new(index, environment);
+    trace_subtree(styled, &rules, &mut Ancestors::default())
+}
+
+/// Restyles the tree rooted at `root` incrementally: the styles in `retained` whose dirty bits are
+/// clear are reused, and only the dirty ones are recomputed.
+///
+/// `retained` must come from styling `root` before the changes it was invalidated for. If a
+/// node's children no longer line up with their retained styles, they are all recomputed.
+/// `@container` rules are evaluated against the laid-out sizes the retained query containers
+/// were styled with, if any.
+pub fn restyle<'a>(
+    root: &'a Node,
+    retained: RetainedStyle,
+    index: &RuleIndex,
+    environment: &Environment,
+) -> StyledNode<'a> {
+    let rules = ActiveRules::new(index, environment);
+    let mut sizes = ContainerSizes::default();
+    sizes.insert_retained(root, &retained);
+    let mut ancestors = Ancestors {
+        container_sizes: Arc::new(sizes),
+        ..Ancestors::default()
+    };
+    restyle_subtree(
+        root,
+        retained,
+        &rules,
+        &mut ancestors,
+        &mut StyleSharingCache::default(),
+    )
+}
+
+/// Like `style_tree`, but styles sibling subtrees in parallel on rayon's thread pool.
+///
+/// The result is the same as `style_tree`'s. To control the number of threads, call it inside
+/// `rayon::ThreadPool::install`.
+pub fn par_style_tree<'a>(
+    root: &'a Node,
+    stylesheet: &Stylesheet,
+    environment: &Environment,
+) -> StyledNode<'a> {
+    par_style_tree_with_index(root, &RuleIndex::new(stylesheet), environment)
+}
+
+/// Like `par_style_tree`, but with an index of the stylesheet's rules that has already been built.
+pub fn par_style_tree_with_index<'a>(
+    root: &'a Node,
+    index: &RuleIndex,
+    environment: &Environment,
+) -> StyledNode<'a> {
+    let rules = ActiveRules::new(index, environment);
+    par_style_subtree(
+        root,
+        &rules,
+        &mut Ancestors::default(),
+        &mut StyleSharingCache::default(),
+    )
+}
+
+/// The rules of a stylesheet, along with whether their conditions hold in the current
+/// environment.
+struct ActiveRules<'r, 'a> {
+    index: &'r RuleIndex<'a>,
+    /// Whether each rule is active, in source order.
+    active: Vec<bool>,
+}
+
+impl<'r, 'a> ActiveRules<'r, 'a> {
+    fn new(index: &'r RuleIndex<'a>, environment: &Environment) -> Self {
+        ActiveRules {
+            index,
+            active: index
+                .rules()
+                .iter()
+                .map(|&(_, rule)| conditions_hold(rule, environment))
+                .collect(),
+        }
+    }
+}
+
+/// The elements enclosing the node being styled.
+#[derive(Clone, Default)]
+struct Ancestors<'a> {
+    /// The ancestor elements, outermost first.
+    elements: Vec<&'a ElementData>,
+    /// The ancestors that are query containers, outermost first.
+    containers: Vec<QueryContainer>,
+    /// The tag names, ids and classes of the ancestor elements.
+    bloom: CountingBloomFilter,
+    /// The styles of the ancestors, outermost first.
+    styles: Vec<SharedStyle>,
+    /// The laid-out sizes of the query containers, if the tree has been laid out.
+    container_sizes: Arc<ContainerSizes<'a>>,
+}
+
+/// An element that `@container` rules can query.
+#[derive(Clone, PartialEq)]
+struct QueryContainer {
+    /// The names given by `container-name`.
+    names: Vec<String>,
+    /// The size of the container along the axes given by `container-type`.
+    size: ContainerSize,
+}
+
+impl<'a> Ancestors<'a> {
+    /// Makes `node`, whose style is `style`, the innermost ancestor. Returns whether it is a
+    /// query container, which `leave` needs.
+    fn enter(&mut self, node: &'a Node, style: &SharedStyle) -> bool {
+        self.styles.push(style.clone());
+        let container = self.query_container(node, &style.0);
+        let is_container = container.is_some();
+        self.containers.extend(container);
+        if let NodeType::Element(ref elem) = node.node_type {
+            self.elements.push(elem);
+            self.bloom.insert_element(elem);
+        }
+        is_container
+    }
+
+    /// Undoes `enter` for `node`.
+    fn leave(&mut self, node: &'a Node, is_container: bool) {
+        if let NodeType::Element(ref elem) = node.node_type {
+            self.elements.pop();
+            self.bloom.remove_element(elem);
+        }
+        if is_container {
+            self.containers.pop();
+        }
+        self.styles.pop();
+    }
+
+    /// Returns the query container established by `node` if its specified values are `values`,
+    /// sized by layout if it has been laid out.
+    fn query_container(&self, node: &Node, values: &PropertyMap) -> Option<QueryContainer> {
+        query_container(values, self.container_sizes.get(node))
+    }
+
+    /// Returns the computed style of the innermost ancestor.
+    fn parent_style(&self) -> Option<&ComputedStyle> {
+        self.styles.last().map(|(_, computed)| &**computed)
+    }
+
+    /// Returns the `white-space` of the innermost ancestor.
+    fn white_space(&self) -> WhiteSpace {
+        self.parent_style()
+            .map_or(WhiteSpace::Normal, ComputedStyle::white_space)
+    }
+}
+
+fn style_subtree<'a>(
+    root: &'a Node,
+    rules: &ActiveRules,
+    ancestors: &mut Ancestors<'a>,
+    cache: &mut StyleSharingCache<'a, SharedStyle>,
+) -> StyledNode<'a> {
+    let style = style_node(root, rules, ancestors, cache);
+    let is_container = ancestors.enter(root, &style);
+    let children = style_children(root, ancestors, |_, child, ancestors| {
+        style_subtree(child, rules, ancestors, cache)
+    });
+    ancestors.leave(root, is_container);
+
+    let (specified_values, computed) = style;
     StyledNode {
         node: root,
-        specified_values: match root.node_type {
-            NodeType::Element(ref elem) => specified_values(elem, stylesheet),
-            NodeType::Text(_) => HashMap::new(),
-            NodeType::Comment(_) => todo!(),
-        },
-        children: root
+        specified_values,
+        computed,
+        children,
+        container_size: ancestors.container_sizes.get(root),
+    }
+}
+
+/// Like `style_subtree`, but reuses the clean styles in `retained`.
+fn restyle_subtree<'a>(
+    root: &'a Node,
+    retained: RetainedStyle,
+    rules: &ActiveRules,
+    ancestors: &mut Ancestors<'a>,
+    cache: &mut StyleSharingCache<'a, SharedStyle>,
+) -> StyledNode<'a> {
+    if retained.dirty.restyle_subtree {
+        return style_subtree(root, rules, ancestors, cache);
+    }
+    let old_values = retained.specified_values;
+    let old_computed = retained.computed;
+    let (specified_values, computed) = match retained.dirty.restyle {
+        true => style_node(root, rules, ancestors, cache),
+        // The parent's computed style may have changed even if the node's rules didn't.
+        false => {
+            let computed = ComputedStyle::compute(&old_values, ancestors.parent_style());
+            (old_values.clone(), Arc::new(computed))
+        }
+    };
+    let computed_changed = *computed != *old_computed;
+    let computed = match computed_changed {
+        true => computed,
+        false => old_computed,
+    };
+    let changed = old_values != specified_values || computed_changed;
+    let mut retained_children = retained.children;
+    if !changed && !retained.dirty.dirty_descendants {
+        return StyledNode {
+            node: root,
+            children: reattach_children(root, &mut retained_children),
+            specified_values,
+            computed,
+            container_size: retained.container_size,
+        };
+    }
+
+    // Container queries in the subtree depend on the container's own style.
+    let restyle_children = retained_children.len() != root.children.len()
+        || ancestors.query_container(root, &old_values)
+            != ancestors.query_container(root, &specified_values);
+    let style = (specified_values, computed);
+    let is_container = ancestors.enter(root, &style);
+    let children = style_children(root, ancestors, |i, child, ancestors| {
+        match retained_children.get_mut(i).and_then(Option::take) {
+            // Text nodes take their parent's style, which may have changed.
+            Some(retained)
+                if !restyle_children && !matches!(child.node_type, NodeType::Text(_)) =>
+            {
+                restyle_subtree(child, retained, rules, ancestors, cache)
+            }
+            _ => style_subtree(child, rules, ancestors, cache),
+        }
+    });
+    ancestors.leave(root, is_container);
+
+    let (specified_values, computed) = style;
+    StyledNode {
+        node: root,
+        specified_values,
+        computed,
+        children,
+        container_size: ancestors.container_sizes.get(root),
+    }
+}
+
+/// Reattaches the clean retained styles of the children of `root`, which line up with its
+/// children in the DOM.
+fn reattach_children<'a>(
+    root: &'a Node,
+    retained: &mut [Option<RetainedStyle>],
+) -> Vec<StyledNode<'a>> {
+    root.children
+        .iter()
+        .zip(retained)
+        .filter_map(|(child, retained)| {
+            let mut retained = retained.take()?;
+            Some(StyledNode {
+                node: child,
+                children: reattach_children(child, &mut retained.children),
+                specified_values: retained.specified_values,
+                computed: retained.computed,
+                container_size: retained.container_size,
+            })
+        })
+        .collect()
+}
+
+/// Like `style_subtree`, but styles the children of each node in parallel.
+///
+/// Each rayon job gets its own copy of the ancestors and its own style sharing cache, so the
+/// result doesn't depend on how the work is split.
+fn par_style_subtree<'a>(
+    root: &'a Node,
+    rules: &ActiveRules,
+    ancestors: &mut Ancestors<'a>,
+    cache: &mut StyleSharingCache<'a, SharedStyle>,
+) -> StyledNode<'a> {
+    let style = style_node(root, rules, ancestors, cache);
+    let is_container = ancestors.enter(root, &style);
+    let children = {
+        let ancestors = &*ancestors;
+        let children = root
             .children
-            .iter()
-            .map(|child| style_tree(child, stylesheet))
-            .collect(),
+            .par_iter()
+            .filter(|child| is_styled(child))
+            .map_init(
+                || (ancestors.clone(), StyleSharingCache::default()),
+                |(ancestors, cache), child| par_style_subtree(child, rules, ancestors, cache),
+            )
+            .collect();
+        collapse_whitespace(children, ancestors.white_space())
+    };
+    ancestors.leave(root, is_container);
+
+    let (specified_values, computed) = style;
+    StyledNode {
+        node: root,
+        specified_values,
+        computed,
+        children,
+        container_size: ancestors.container_sizes.get(root),
     }
 }
 
+/// Returns true if `node` takes part in styling and rendering. Comments, processing
+/// instructions and doctypes don't.
+pub fn is_styled(node: &Node) -> bool {
+    matches!(node.node_type, NodeType::Element(_) | NodeType::Text(_))
+}
+
+/// Styles the children of `node` that take part in styling with `style_child`, which gets the
+/// index of each child among all the children, and collapses whitespace among them.
+///
+/// `ancestors` must have been entered for `node`.
+fn style_children<'a>(
+    node: &'a Node,
+    ancestors: &mut Ancestors<'a>,
+    mut style_child: impl FnMut(usize, &'a Node, &mut Ancestors<'a>) -> StyledNode<'a>,
+) -> Vec<StyledNode<'a>> {
+    let children = node
+        .children
+        .iter()
+        .enumerate()
+        .filter(|(_, child)| is_styled(child))
+        .map(|(i, child)| style_child(i, child, ancestors))
+        .collect();
+    collapse_whitespace(children, ancestors.white_space())
+}
+
+/// Drops the whitespace-only text nodes among `children`, the styled children of an element whose
+/// `white-space` is `white_space`, that collapse away: those at the start or end of the element,
+/// next to a block, or following other collapsible whitespace.
+fn collapse_whitespace<'a>(
+    children: Vec<StyledNode<'a>>,
+    white_space: WhiteSpace,
+) -> Vec<StyledNode<'a>> {
+    let collapsible = |child: &StyledNode| match child.node.node_type {
+        NodeType::Text(ref text) if text.chars().all(|c| matches!(c, ' ' | '\t' | '\n' | '\r')) => {
+            match white_space {
+                WhiteSpace::Normal | WhiteSpace::Nowrap => true,
+                WhiteSpace::PreLine => !text.contains('\n'),
+                _ => false,
+            }
+        }
+        _ => false,
+    };
+    let is_block = |child: &StyledNode| {
+        matches!(child.node.node_type, NodeType::Element(_)) && child.display().is_block_level()
+    };
+
+    let mut kept: Vec<StyledNode> = Vec::with_capacity(children.len());
+    for child in children {
+        if collapsible(&child)
+            && kept
+                .last()
+                .is_none_or(|last| is_block(last) || collapsible(last))
+        {
+            continue;
+        }
+        if is_block(&child) {
+            while kept.last().is_some_and(collapsible) {
+                kept.pop();
+            }
+        }
+        kept.push(child);
+    }
+    while kept.last().is_some_and(collapsible) {
+        kept.pop();
+    }
+    kept
+}
+
+/// Returns the specified and computed values of `node`, from `cache` if a sibling with the same
+/// style has already been styled.
+///
+/// Text nodes inherit their parent's style.
+fn style_node<'a>(
+    node: &'a Node,
+    rules: &ActiveRules,
+    ancestors: &Ancestors<'a>,
+    cache: &mut StyleSharingCache<'a, SharedStyle>,
+) -> SharedStyle {
+    match node.node_type {
+        NodeType::Element(ref elem) => {
+            let parent = ancestors.elements.last().copied();
+            match cache.lookup(parent, elem) {
+                Some(style) => style,
+                None => {
+                    let values = specified_values(elem, rules, ancestors);
+                    let computed = ComputedStyle::compute(&values, ancestors.parent_style());
+                    let style = (Arc::new(values), Arc::new(computed));
+                    cache.insert(parent, elem, style.clone());
+                    style
+                }
+            }
+        }
+        NodeType::Text(_) => ancestors.styles.last().cloned().unwrap_or_default(),
+        // Nodes that aren't styled only get here as the root of the tree.
+        NodeType::Comment(_) | NodeType::ProcessingInstruction(_) | NodeType::Doctype(_) => {
+            SharedStyle::default()
+        }
+    }
+}
+
+/// Returns the query container established by an element with the given values, if any.
+/// `laid_out` is the width and height of its content box, if it has been laid out.
+///
+/// Before layout, a container's size is only known along an axis whose size is specified as an
+/// absolute length, so a container whose size is a percentage or `auto` doesn't match size
+/// queries until the tree is styled again with the sizes layout gave it.
+fn query_container(values: &PropertyMap, laid_out: Option<(f32, f32)>) -> Option<QueryContainer> {
+    let length = |name, laid_out| match (values.get(name), laid_out) {
+        (_, Some(length)) => Some(length),
+        (Some(Value::Length(length, Unit::Px)), None) => Some(*length),
+        _ => None,
+    };
+    let width = length("width", laid_out.map(|(width, _)| width));
+    let height = length("height", laid_out.map(|(_, height)| height));
+    let size = match values.get("container-type") {
+        Some(Value::Keyword(keyword)) if keyword == "size" => ContainerSize { width, height },
+        Some(Value::Keyword(keyword)) if keyword == "inline-size" => ContainerSize {
+            width,
+            height: None,
+        },
+        _ => return None,
+    };
+    let names = match values.get("container-name") {
+        Some(Value::Keyword(name)) if name != "none" => vec![name.clone()],
+        _ => Vec::new(),
+    };
+    Some(QueryContainer { names, size })
+}
+
 /// Apply styles to an element based on the rules in the stylesheet.
-fn specified_values(elem: &ElementData, stylesheet: &Stylesheet) -> PropertyMap {
-    let rules = matching_rules(elem, stylesheet);
+fn specified_values(elem: &ElementData, rules: &ActiveRules, ancestors: &Ancestors) -> PropertyMap {
+    let rules = matching_rules(elem, rules, ancestors);
 
-    // Iterate over the rules in order of specificity, highest first. Then 
-    rules
+    // Sort the rules by precedence, lowest first, so that declarations from higher-precedence
+    // rules overwrite those from lower ones. The sort is stable, so later rules win ties.
+    // Shorthands are expanded as they are met, so that they overwrite earlier longhands too.
+    let rules = rules
         .into_iter()
         .sorted_by(|&(a, _), &(b, _)| a.cmp(&b))
+        .collect::<Vec<_>>();
+    let normal = rules
+        .iter()
         .flat_map(|(_, rule)| rule.declarations.iter())
-        .map(|declaration| (declaration.name.clone(), declaration.value.clone()))
+        .filter(|declaration| !declaration.important);
+    // Important declarations come after all normal ones. Among them, earlier layers win, and
+    // unlayered rules lose to layered ones, as in CSS Cascade 5 § 6.4.
+    let important = rules
+        .iter()
+        .sorted_by_key(|&&((layer, ..), _)| Reverse(layer))
+        .flat_map(|(_, rule)| rule.declarations.iter())
+        .filter(|declaration| declaration.important);
+    normal
+        .chain(important)
+        .flat_map(|declaration| expand_declaration(&declaration.name, &declaration.value))
         .collect::<HashMap<_, _>>()
 }
 
-type MatchedRule<'a> = (Specificity, &'a Rule);
+/// The precedence of a matched rule in the cascade: its layer rank, then the specificity of the
+/// matching selector, then the proximity of its scoping root (closer is higher).
+type Precedence = (usize, Specificity, Reverse<usize>);
 
-/// Find all CSS rules that match the given element.
-fn matching_rules<'a>(elem: &ElementData, stylesheet: &'a Stylesheet) -> Vec<MatchedRule<'a>> {
-    // For now, we just do a linear scan of all the rules.  For large
-    // documents, it would be more efficient to store the rules in hash tables
-    // based on tag name, id, class, etc.
-    stylesheet
-        .rules
-        .iter()
-        .filter_map(|rule| match_rule(elem, rule))
+type MatchedRule<'a> = (Precedence, &'a Rule);
+
+/// Find all CSS rules that match the given element, in source order.
+fn matching_rules<'a>(
+    elem: &ElementData,
+    rules: &ActiveRules<'_, 'a>,
+    ancestors: &Ancestors,
+) -> Vec<MatchedRule<'a>> {
+    let mut candidates = rules
+        .index
+        .candidates(elem)
+        .filter(|candidate| rules.active[candidate.source_order])
+        .filter(|candidate| candidate.may_match(&ancestors.bloom))
+        .filter(|candidate| matches(elem, &ancestors.elements, candidate.selector))
+        .collect::<Vec<_>>();
+
+    // A rule matches with the specificity of its most specific matching selector.
+    candidates
+        .sort_unstable_by_key(|candidate| (candidate.source_order, Reverse(candidate.specificity)));
+    candidates.dedup_by_key(|candidate| candidate.source_order);
+
+    candidates
+        .into_iter()
+        .filter_map(|candidate| {
+            let (layer, rule) = rules.index.rules()[candidate.source_order];
+            let proximity = rule_proximity(elem, rule, ancestors)?;
+            Some(((layer, candidate.specificity, Reverse(proximity)), rule))
+        })
         .collect()
 }
 
-/// If `elem` matches `selector`, return a `MatchedRule`. Otherwise, return `None`.
-fn match_rule<'a>(elem: &ElementData, rule: &'a Rule) -> Option<MatchedRule<'a>> {
-    // Find the first (highest-specificity) matching selector in `rule`.
-    rule.selectors
+/// Checks the conditions enclosing `rule` that depend on the element being matched, and returns
+/// the proximity of its scoping root, or `None` if a condition doesn't hold.
+///
+/// Rules outside of any `@scope` have infinite proximity.
+fn rule_proximity(elem: &ElementData, rule: &Rule, ancestors: &Ancestors) -> Option<usize> {
+    let mut proximity = usize::MAX;
+    for condition in &rule.conditions {
+        match condition {
+            Condition::Scope(scope) => {
+                proximity = scope_proximity(scope, elem, &ancestors.elements)?;
+            }
+            Condition::Container(query) if !container_matches(query, &ancestors.containers) => {
+                return None;
+            }
+            _ => {}
+        }
+    }
+    Some(proximity)
+}
+
+/// Returns the number of generations between `elem` and the nearest scoping root of `scope`
+/// that it is in scope of, or `None` if it isn't in scope.
+///
+/// A scope without root selectors is rooted at the root element.
+fn scope_proximity(scope: &Scope, elem: &ElementData, ancestors: &[&ElementData]) -> Option<usize> {
+    let chain = ancestors
         .iter()
-        .find(|selector| matches(elem, selector))
-        .map(|selector| (selector.specificity(), rule))
+        .copied()
+        .chain(std::iter::once(elem))
+        .collect::<Vec<_>>();
+    let matches_any = |i: usize, selectors: &[Selector]| {
+        selectors
+            .iter()
+            .any(|selector| matches(chain[i], &chain[..i], selector))
+    };
+    let is_root = |i: usize| match scope.root.is_empty() {
+        true => i == 0,
+        false => matches_any(i, &scope.root),
+    };
+
+    (0..chain.len())
+        .rev()
+        .find(|&i| is_root(i) && !(i + 1..chain.len()).any(|j| matches_any(j, &scope.limit)))
+        .map(|i| chain.len() - 1 - i)
+}
+
+/// Returns true if the nearest ancestor container with the name `query` asks for matches it.
+fn container_matches(query: &ContainerQuery, containers: &[QueryContainer]) -> bool {
+    containers
+        .iter()
+        .rev()
+        .find(|container| match query.name {
+            Some(ref name) => container.names.contains(name),
+            None => true,
+        })
+        .is_some_and(|container| query.evaluate(&container.size))
 }
 
 /// Selector matching: see https://drafts.csswg.org/selectors-3/#specificity
-fn matches(elem: &ElementData, selector: &Selector) -> bool {
+///
+/// `ancestors` holds the elements enclosing `elem`, outermost first.
+fn matches(elem: &ElementData, ancestors: &[&ElementData], selector: &Selector) -> bool {
     match *selector {
         Selector::Simple(ref simple_selector) => matches_simple_selector(elem, simple_selector),
+        Selector::Complex(ref complex) => {
+            matches_compound_selector(elem, ancestors, &complex.subject)
+                && matches_ancestors(ancestors, &complex.ancestors)
+        }
     }
 }
 
+/// Returns true if the compound selectors left of a subject, rightmost last, match the
+/// subject's `ancestors`.
+fn matches_ancestors(
+    ancestors: &[&ElementData],
+    compounds: &[(CompoundSelector, Combinator)],
+) -> bool {
+    let Some(((compound, combinator), rest)) = compounds.split_last() else {
+        return true;
+    };
+    let matches_at = |i: usize| {
+        matches_compound_selector(ancestors[i], &ancestors[..i], compound)
+            && matches_ancestors(&ancestors[..i], rest)
+    };
+    match combinator {
+        Combinator::Child => ancestors.len().checked_sub(1).is_some_and(matches_at),
+        Combinator::Descendant => (0..ancestors.len()).rev().any(matches_at),
+    }
+}
+
+/// Selector matching for a compound selector, which may contain `&`.
+fn matches_compound_selector(
+    elem: &ElementData,
+    ancestors: &[&ElementData],
+    selector: &CompoundSelector,
+) -> bool {
+    matches_simple_selector(elem, &selector.simple)
+        && selector.nesting.as_ref().is_none_or(|parents| {
+            parents
+                .iter()
+                .any(|parent| matches(elem, ancestors, parent))
+        })
+}
+
 /// Selector matching for a single simple selector.
-fn matches_simple_selector(elem: &ElementData, selector: &SimpleSelector) -> bool {
+pub(crate) fn matches_simple_selector(elem: &ElementData, selector: &SimpleSelector) -> bool {
     // Check type selector
     if selector.tag_name.iter().any(|name| elem.tag_name != *name) {
         return false;
@@ -139,3 +902,610 @@ fn matches_simple_selector(elem: &ElementData, selector: &SimpleSelector) -> boo
     // We didn't find any non-matching selector components.
     true
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        computed::LengthPercentage,
+        css, dom, html,
+        invalidation::{Change, InvalidationMap},
+    };
+
+    #[test]
+    fn test_style_tree_evaluates_media_rules() {
+        let root = html::parse(String::from("<div><p>hello</p></div>")).unwrap();
+        let stylesheet = css::parse(String::from(
+            "p { color: red; } @media (max-width: 600px) { p { color: blue; } }",
+        ))
+        .unwrap();
+
+        let wide = Environment::default();
+        let styled = style_tree(&root, &stylesheet, &wide);
+        assert_eq!(
+            styled.children[0].value("color"),
+            Some(Value::Keyword(String::from("red")))
+        );
+
+        let narrow = Environment {
+            width: 400.0,
+            ..Environment::default()
+        };
+        let styled = style_tree(&root, &stylesheet, &narrow);
+        assert_eq!(
+            styled.children[0].value("color"),
+            Some(Value::Keyword(String::from("blue")))
+        );
+    }
+
+    #[test]
+    fn test_property_value() {
+        let root = html::parse(String::from("<div><p>hello</p></div>")).unwrap();
+        let stylesheet = css::parse(String::from(
+            "div { color: red; --gap: 4px; } p { margin: 1px; margin-left: 2px; }",
+        ))
+        .unwrap();
+        let styled = style_tree(&root, &stylesheet, &Environment::default());
+        let p = styled.find(&root.children[0]).unwrap();
+        assert!(std::ptr::eq(p.node, &root.children[0]));
+        assert_eq!(p.property_value("color").as_deref(), Some("rgb(255, 0, 0)"));
+        assert_eq!(
+            p.property_value("margin").as_deref(),
+            Some("1px 1px 1px 2px")
+        );
+        assert_eq!(styled.property_value("--gap").as_deref(), Some("4px"));
+        assert_eq!(p.property_value("--gap").as_deref(), Some("4px"));
+        assert_eq!(p.property_value("--unset"), None);
+    }
+
+    #[test]
+    fn test_style_tree_cascades_shorthands_with_longhands() {
+        let margin_left = |css: &str| {
+            let root = html::parse(String::from("<p class=\"x\"></p>")).unwrap();
+            let stylesheet = css::parse(String::from(css)).unwrap();
+            let styled = style_tree(&root, &stylesheet, &Environment::default());
+            styled.property_value("margin-left")
+        };
+        // A more specific shorthand beats a less specific longhand.
+        assert_eq!(
+            margin_left("p.x { margin: 0; } p { margin-left: 5px; }").as_deref(),
+            Some("0px")
+        );
+        // A later shorthand beats an earlier longhand of the same specificity, in the same rule
+        // or another one.
+        assert_eq!(
+            margin_left("p { margin-left: 5px; } p { margin: 0; }").as_deref(),
+            Some("0px")
+        );
+        assert_eq!(
+            margin_left("p { margin-left: 5px; margin: 1px; }").as_deref(),
+            Some("1px")
+        );
+        // A later longhand still beats an earlier shorthand, and an invalid shorthand loses.
+        assert_eq!(
+            margin_left("p { margin: 0; margin-left: 5px; }").as_deref(),
+            Some("5px")
+        );
+        assert_eq!(
+            margin_left("p { margin-left: 5px; } p { margin: red; }").as_deref(),
+            Some("5px")
+        );
+    }
+
+    fn color_of(styled: &StyledNode) -> Option<Value> {
+        styled.value("color")
+    }
+
+    fn keyword(keyword: &str) -> Option<Value> {
+        Some(Value::Keyword(String::from(keyword)))
+    }
+
+    #[test]
+    fn test_style_tree_orders_layers() {
+        let root = html::parse(String::from("<p class=\"a\"></p>")).unwrap();
+        let stylesheet = css::parse(String::from(
+            "@layer base, theme; \
+             p { color: unlayered; } \
+             @layer theme { p.a { color: theme; } } \
+             @layer base { p.a { color: base; } }",
+        ))
+        .unwrap();
+        let styled = style_tree(&root, &stylesheet, &Environment::default());
+        // Unlayered rules beat layered ones regardless of specificity.
+        assert_eq!(color_of(&styled), keyword("unlayered"));
+
+        let stylesheet = css::parse(String::from(
+            "@layer base, theme; \
+             @layer theme { p { color: theme; } } \
+             @layer base { p.a { color: base; } } \
+             @layer theme { @layer nested { p.a { color: nested; } } }",
+        ))
+        .unwrap();
+        let styled = style_tree(&root, &stylesheet, &Environment::default());
+        // Later layers win, and a layer's own rules beat its sub-layers.
+        assert_eq!(color_of(&styled), keyword("theme"));
+    }
+
+    #[test]
+    fn test_style_tree_cascades_important_declarations() {
+        let root = html::parse(String::from("<p class=\"a\"></p>")).unwrap();
+        let stylesheet = css::parse(String::from(
+            "p { color: important !important; } p.a { color: specific; }",
+        ))
+        .unwrap();
+        let styled = style_tree(&root, &stylesheet, &Environment::default());
+        assert_eq!(color_of(&styled), keyword("important"));
+
+        let stylesheet = css::parse(String::from(
+            "@layer base, theme; \
+             p { color: unlayered !important; } \
+             @layer theme { p { color: theme !important; } } \
+             @layer base { p { color: base !important; } }",
+        ))
+        .unwrap();
+        let styled = style_tree(&root, &stylesheet, &Environment::default());
+        // Earlier layers win among important declarations.
+        assert_eq!(color_of(&styled), keyword("base"));
+    }
+
+    #[test]
+    fn test_style_tree_applies_scope_proximity() {
+        let root = html::parse(String::from(
+            "<div class=\"light\"><div class=\"dark\"><p></p></div><div class=\"content\"><p></p></div></div>",
+        ))
+        .unwrap();
+        let stylesheet = css::parse(String::from(
+            "@scope (.dark) { p { color: white; } } \
+             @scope (.light) to (.content) { p { color: black; } } \
+             p { color: unscoped; }",
+        ))
+        .unwrap();
+        let styled = style_tree(&root, &stylesheet, &Environment::default());
+        // The nearer `.dark` root wins even though `.light` comes later.
+        assert_eq!(color_of(&styled.children[0].children[0]), keyword("white"));
+        // `.content` is a scoping limit for `.light`, so only the unscoped rule applies.
+        assert_eq!(
+            color_of(&styled.children[1].children[0]),
+            keyword("unscoped")
+        );
+    }
+
+    #[test]
+    fn test_style_tree_evaluates_supports_and_container_rules() {
+        let root = html::parse(String::from(
+            "<div class=\"card\"><p></p></div><div class=\"sidebar\"><p></p></div>",
+        ))
+        .unwrap();
+        let stylesheet = css::parse(String::from(
+            ".card { container-type: inline-size; container-name: card; width: 500px; } \
+             .sidebar { container-type: inline-size; width: 200px; } \
+             @supports (display: masonry) { p { color: masonry; } } \
+             @container (width > 300px) { p { color: wide; } } \
+             @container card (width > 300px) { p { color: card; } }",
+        ))
+        .unwrap();
+        let styled = style_tree(&root, &stylesheet, &Environment::default());
+        assert_eq!(color_of(&styled.children[0].children[0]), keyword("card"));
+        assert_eq!(color_of(&styled.children[1].children[0]), None);
+    }
+
+    #[test]
+    fn test_style_tree_matches_nested_rules() {
+        let root = html::parse(String::from(
+            "<div class=\"card\"><p class=\"title\"></p><section><p></p></section></div>",
+        ))
+        .unwrap();
+        let stylesheet = css::parse(String::from(
+            ".card { \
+               > p { color: child; } \
+               section & { color: never; } \
+               & section p { color: descendant; } \
+               .title { color: title; } \
+             } \
+             p.title { color: flat; }",
+        ))
+        .unwrap();
+        let styled = style_tree(&root, &stylesheet, &Environment::default());
+        assert_eq!(color_of(&styled), None);
+        // `.card .title` is more specific than `p.title`.
+        assert_eq!(color_of(&styled.children[0]), keyword("title"));
+        assert_eq!(
+            color_of(&styled.children[1].children[0]),
+            keyword("descendant")
+        );
+    }
+
+    #[test]
+    fn test_rule_index_matches_linear_scan() {
+        let root = html::parse(String::from(
+            "<div id=\"main\" class=\"a b\"><p class=\"b\"><span class=\"a\"></span></p>\
+             <section><p id=\"x\"></p></section></div>",
+        ))
+        .unwrap();
+        let stylesheet = css::parse(String::from(
+            "* { color: a; } div, .a { color: b; } #main .b, p { color: c; } \
+             .a.b { color: d; } div > p span { color: e; } #x, section p { color: f; } \
+             @scope (section) { p { color: g; } } @media print { p { color: h; } } \
+             .b { & span, &.a { color: i; } }",
+        ))
+        .unwrap();
+        let index = RuleIndex::new(&stylesheet);
+        let rules = ActiveRules {
+            index: &index,
+            active: index
+                .rules()
+                .iter()
+                .map(|&(_, rule)| conditions_hold(rule, &Environment::default()))
+                .collect(),
+        };
+
+        fn check<'a>(node: &'a Node, rules: &ActiveRules, ancestors: &mut Vec<&'a ElementData>) {
+            let NodeType::Element(ref elem) = node.node_type else {
+                return;
+            };
+            let mut context = Ancestors {
+                elements: ancestors.clone(),
+                containers: Vec::new(),
+                bloom: CountingBloomFilter::default(),
+                styles: Vec::new(),
+                container_sizes: Arc::default(),
+            };
+            for ancestor in ancestors.iter() {
+                context.bloom.insert_element(ancestor);
+            }
+            let linear = rules
+                .index
+                .rules()
+                .iter()
+                .enumerate()
+                .filter(|&(i, _)| rules.active[i])
+                .filter_map(|(_, &(layer, rule))| {
+                    let proximity = rule_proximity(elem, rule, &context)?;
+                    rule.selectors
+                        .iter()
+                        .find(|selector| matches(elem, ancestors, selector))
+                        .map(|selector| (layer, selector.specificity(), Reverse(proximity)))
+                })
+                .collect::<Vec<_>>();
+            let indexed = matching_rules(elem, rules, &context)
+                .into_iter()
+                .map(|(precedence, _)| precedence)
+                .collect::<Vec<_>>();
+            assert_eq!(indexed, linear, "{}", elem.tag_name);
+
+            ancestors.push(elem);
+            for child in &node.children {
+                check(child, rules, ancestors);
+            }
+            ancestors.pop();
+        }
+        check(&root, &rules, &mut Vec::new());
+    }
+
+    #[test]
+    fn test_ancestor_bloom_filter_rejects_rules() {
+        let depth = 200;
+        let source = format!(
+            "{}<p class=\"leaf\"></p>{}",
+            "<div class=\"level\">".repeat(depth),
+            "</div>".repeat(depth)
+        );
+        let root = html::parse(source).unwrap();
+        let rules = (0..1000)
+            .map(|i| format!(".missing{} .level p {{ color: c{}; }}", i, i))
+            .collect::<String>();
+        let stylesheet = css::parse(rules + ".level .leaf { color: found; }").unwrap();
+        let index = RuleIndex::new(&stylesheet);
+        let rules = ActiveRules {
+            index: &index,
+            active: vec![true; index.rules().len()],
+        };
+
+        let mut ancestors = Ancestors::default();
+        let mut cache = StyleSharingCache::default();
+        let styled = style_subtree(&root, &rules, &mut ancestors, &mut cache);
+        let mut leaf = &styled;
+        let mut bloom = CountingBloomFilter::default();
+        while let Some(child) = leaf.children.first() {
+            if let NodeType::Element(ref elem) = leaf.node.node_type {
+                bloom.insert_element(elem);
+            }
+            leaf = child;
+        }
+        assert_eq!(color_of(leaf), keyword("found"));
+
+        // Only the rule that matches gets past the filter; the rest are rejected without walking
+        // the 200 ancestors.
+        let NodeType::Element(ref elem) = leaf.node.node_type else {
+            panic!("expected the leaf to be an element");
+        };
+        let passing = index
+            .candidates(elem)
+            .filter(|candidate| candidate.may_match(&bloom))
+            .count();
+        assert_eq!(passing, 1);
+        assert_eq!(index.candidates(elem).count(), 1001);
+    }
+
+    #[test]
+    fn test_style_tree_shares_sibling_styles() {
+        let root = html::parse(String::from(
+            "<ul><li class=\"item\"></li><li class=\"item\"></li><li class=\"item last\"></li>\
+             <li class=\"item\"></li></ul>",
+        ))
+        .unwrap();
+        let stylesheet = css::parse(String::from(
+            ".item { color: item; } .last { color: last; } ul > .item { display: block; }",
+        ))
+        .unwrap();
+        let styled = style_tree(&root, &stylesheet, &Environment::default());
+        let items = &styled.children;
+        assert!(Arc::ptr_eq(
+            &items[0].specified_values,
+            &items[1].specified_values
+        ));
+        assert!(Arc::ptr_eq(
+            &items[0].specified_values,
+            &items[3].specified_values
+        ));
+        assert!(!Arc::ptr_eq(
+            &items[0].specified_values,
+            &items[2].specified_values
+        ));
+        assert_eq!(color_of(&items[1]), keyword("item"));
+        assert_eq!(color_of(&items[2]), keyword("last"));
+        assert_eq!(items[3].value("display"), keyword("block"));
+    }
+
+    #[test]
+    fn test_style_tree_skips_unstyled_nodes() {
+        let root = html::parse(String::from(
+            "<div class=\"note\"><!-- hidden --><?php echo ?>Hello<span>world</span></div>",
+        ))
+        .unwrap();
+        let stylesheet = css::parse(String::from(".note { color: note; }")).unwrap();
+        let styled = style_tree(&root, &stylesheet, &Environment::default());
+        assert_eq!(styled.children.len(), 2);
+        let text = &styled.children[0];
+        assert!(matches!(text.node.node_type, NodeType::Text(_)));
+        assert!(Arc::ptr_eq(
+            &text.specified_values,
+            &styled.specified_values
+        ));
+        assert_eq!(color_of(text), keyword("note"));
+
+        let retained = styled.retain();
+        assert!(retained.children[0].is_none());
+        assert!(retained.children[2].is_some());
+    }
+
+    #[test]
+    fn test_style_tree_collapses_whitespace() {
+        let space = || dom::text(String::from(" \n "));
+        let element =
+            |tag_name: &str| dom::elem(String::from(tag_name), HashMap::new(), Vec::new());
+        let children = vec![
+            space(),
+            element("span"),
+            space(),
+            element("span"),
+            space(),
+            space(),
+            element("p"),
+            space(),
+        ];
+        let root = dom::elem(String::from("div"), HashMap::new(), children);
+        let tags = |stylesheet: &str| {
+            let stylesheet = css::parse(String::from(stylesheet)).unwrap();
+            let styled = style_tree(&root, &stylesheet, &Environment::default());
+            styled
+                .children
+                .iter()
+                .map(|child| match child.node.node_type {
+                    NodeType::Element(ref elem) => elem.tag_name.clone(),
+                    _ => String::from("#text"),
+                })
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(
+            tags("p { display: block; }"),
+            vec!["span", "#text", "span", "p"]
+        );
+        assert_eq!(
+            tags("div { white-space: pre; } p { display: block; }").len(),
+            8
+        );
+        // A run of whitespace between inline content collapses to its first node.
+        assert_eq!(
+            tags("div { white-space: nowrap; }"),
+            vec!["span", "#text", "span", "#text", "p"]
+        );
+        // Newlines are preserved under `pre-line`.
+        assert_eq!(tags("div { white-space: pre-line; }").len(), 8);
+    }
+
+    #[test]
+    fn test_style_tree_computes_inherited_values() {
+        let mut root = html::parse(String::from(
+            "<div class=\"page\"><p class=\"note\">Hello</p><p></p></div>",
+        ))
+        .unwrap();
+        let stylesheet = css::parse(String::from(
+            ".page { color: #336699; font-size: 20px; } .dark { color: white; } \
+             .note { display: block; padding: 4px; }",
+        ))
+        .unwrap();
+        let index = RuleIndex::new(&stylesheet);
+        let environment = Environment::default();
+        let styled = style_tree_with_index(&root, &index, &environment);
+        let note = &styled.children[0];
+        assert_eq!(note.display(), Display::BLOCK);
+        assert_eq!(note.computed.color(), styled.computed.color());
+        assert_eq!(note.computed.font_size(), 20.0);
+        assert_eq!(note.computed.padding_top(), LengthPercentage::Px(4.0));
+        assert!(Arc::ptr_eq(&note.children[0].computed, &note.computed));
+
+        // Inherited values change even where the matched rules don't.
+        let map = InvalidationMap::new(&stylesheet);
+        let retained = styled.retain();
+        let NodeType::Element(ref mut elem) = root.node_type else {
+            unreachable!()
+        };
+        let old_value = elem
+            .attributes
+            .insert(String::from("class"), String::from("page dark"));
+        let change = Change::Attribute {
+            path: vec![],
+            name: String::from("class"),
+            old_value,
+        };
+        let mut dirty = retained.clone();
+        dirty.invalidate(&root, &change, &map).unwrap();
+        let styled = restyle(&root, dirty, &index, &environment);
+        assert_same_styles(&styled, &style_tree(&root, &stylesheet, &environment));
+        assert_eq!(
+            styled.children[1].computed.color(),
+            css::Color::new(255, 255, 255, 255)
+        );
+    }
+
+    fn assert_same_styles(a: &StyledNode, b: &StyledNode) {
+        assert!(std::ptr::eq(a.node, b.node));
+        assert_eq!(a.specified_values, b.specified_values);
+        assert_eq!(a.computed, b.computed);
+        assert_eq!(a.children.len(), b.children.len());
+        for (a, b) in a.children.iter().zip(&b.children) {
+            assert_same_styles(a, b);
+        }
+    }
+
+    #[test]
+    fn test_par_style_tree_matches_style_tree() {
+        let rows = (0..200)
+            .map(|i| {
+                format!(
+                    "<tr class=\"row{}\"><td class=\"cell\"><span>{}</span></td><td></td></tr>",
+                    i % 3,
+                    i
+                )
+            })
+            .collect::<String>();
+        let root = html::parse(format!(
+            "<div class=\"page\"><table>{}</table><p id=\"end\"></p></div>",
+            rows
+        ))
+        .unwrap();
+        let stylesheet = css::parse(String::from(
+            "td { color: cell; } .row1 td { color: odd; } .page .row2 > .cell span { color: span; } \
+             #end { display: block; } @scope (.row0) { span { color: scoped; } }",
+        ))
+        .unwrap();
+
+        let sequential = style_tree(&root, &stylesheet, &Environment::default());
+        let pool = rayon::ThreadPoolBuilder::new()
+            .num_threads(4)
+            .build()
+            .unwrap();
+        let parallel = pool.install(|| par_style_tree(&root, &stylesheet, &Environment::default()));
+        assert_same_styles(&sequential, &parallel);
+    }
+
+    #[test]
+    fn test_styling_types_are_send_and_sync() {
+        fn assert_send_sync<T: Send + Sync>() {}
+        assert_send_sync::<Node>();
+        assert_send_sync::<Stylesheet>();
+        assert_send_sync::<RuleIndex>();
+        assert_send_sync::<StyledNode>();
+    }
+
+    #[test]
+    fn test_restyle_recomputes_only_dirty_nodes() {
+        let mut root = html::parse(String::from(
+            "<div class=\"list\"><p class=\"item\"></p><p class=\"item\"></p>\
+             <section><p></p></section></div>",
+        ))
+        .unwrap();
+        let stylesheet = css::parse(String::from(
+            ".item { color: item; } .selected { color: selected; } \
+             .dark p { color: white; } @container (min-width: 100px) { p { display: block; } }",
+        ))
+        .unwrap();
+        let index = RuleIndex::new(&stylesheet);
+        let map = InvalidationMap::new(&stylesheet);
+        let environment = Environment::default();
+        let retained = style_tree_with_index(&root, &index, &environment).retain();
+
+        fn element<'n>(root: &'n mut Node, path: &[usize]) -> &'n mut ElementData {
+            let node = path.iter().fold(root, |node, &i| &mut node.children[i]);
+            match node.node_type {
+                NodeType::Element(ref mut elem) => elem,
+                _ => panic!("expected an element"),
+            }
+        }
+
+        // A class only the element itself depends on.
+        let mut dirty = retained.clone();
+        let old_value = element(&mut root, &[1])
+            .attributes
+            .insert(String::from("class"), String::from("item selected"));
+        let change = Change::Attribute {
+            path: vec![1],
+            name: String::from("class"),
+            old_value,
+        };
+        dirty.invalidate(&root, &change, &map).unwrap();
+        let styled = restyle(&root, dirty, &index, &environment);
+        assert_same_styles(&styled, &style_tree(&root, &stylesheet, &environment));
+        assert_eq!(color_of(&styled.children[1]), keyword("selected"));
+        assert!(Arc::ptr_eq(
+            &styled.children[0].specified_values,
+            &retained.children[0].as_ref().unwrap().specified_values
+        ));
+        assert!(Arc::ptr_eq(
+            &styled.children[2].children[0].specified_values,
+            &retained.children[2].as_ref().unwrap().children[0]
+                .as_ref()
+                .unwrap()
+                .specified_values
+        ));
+
+        // A class descendants depend on, and a style change that makes a query container.
+        let retained = styled.retain();
+        let old_value = element(&mut root, &[])
+            .attributes
+            .insert(String::from("class"), String::from("list dark"));
+        let change = Change::Attribute {
+            path: vec![],
+            name: String::from("class"),
+            old_value,
+        };
+        let mut dirty = retained.clone();
+        dirty.invalidate(&root, &change, &map).unwrap();
+        let styled = restyle(&root, dirty, &index, &environment);
+        assert_same_styles(&styled, &style_tree(&root, &stylesheet, &environment));
+        assert_eq!(color_of(&styled.children[2].children[0]), keyword("white"));
+
+        let new_stylesheet = css::parse(String::from(
+            ".item { color: item; } .selected { color: selected; } \
+             .dark p { color: white; } section { container-type: inline-size; width: 200px; } \
+             @container (min-width: 100px) { p { display: block; } }",
+        ))
+        .unwrap();
+        let new_index = RuleIndex::new(&new_stylesheet);
+        let retained = styled.retain();
+        let mut dirty = retained.clone();
+        dirty.invalidate_stylesheet(&root, &stylesheet, &new_stylesheet);
+        let styled = restyle(&root, dirty, &new_index, &environment);
+        assert_same_styles(&styled, &style_tree(&root, &new_stylesheet, &environment));
+        assert_eq!(
+            styled.children[2].children[0].value("display"),
+            keyword("block")
+        );
+        assert!(Arc::ptr_eq(
+            &styled.children[0].specified_values,
+            &retained.children[0].as_ref().unwrap().specified_values
+        ));
+    }
+}
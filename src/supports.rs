@@ -0,0 +1,87 @@
+use crate::{
+    computed::is_valid_declaration,
+    css::{Selector, Value},
+};
+
+/// A feature query, as found in the prelude of an `@supports` rule.
+#[derive(Clone, PartialEq, Debug)]
+pub enum SupportsCondition {
+    /// `(name: value)`: a declaration that must be supported.
+    Declaration(String, Value),
+    /// `selector(...)`: a selector that must be supported.
+    Selector(Selector),
+    /// A negated condition.
+    Not(Box<SupportsCondition>),
+    /// Conditions joined with `and`.
+    And(Vec<SupportsCondition>),
+    /// Conditions joined with `or`.
+    Or(Vec<SupportsCondition>),
+    /// Anything that couldn't be parsed. Always evaluates to false.
+    Unknown,
+}
+
+impl SupportsCondition {
+    /// Returns true if webwraith supports the features the condition asks for.
+    pub fn evaluate(&self) -> bool {
+        match self {
+            SupportsCondition::Declaration(name, value) => supports_declaration(name, value),
+            SupportsCondition::Selector(_) => true,
+            SupportsCondition::Not(condition) => !condition.evaluate(),
+            SupportsCondition::And(conditions) => conditions.iter().all(|c| c.evaluate()),
+            SupportsCondition::Or(conditions) => conditions.iter().any(|c| c.evaluate()),
+            SupportsCondition::Unknown => false,
+        }
+    }
+}
+
+/// Returns true if `name` is a property webwraith understands and `value` is valid for it.
+pub fn supports_declaration(name: &str, value: &Value) -> bool {
+    name.starts_with("--") || is_valid_declaration(name, value)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::css::Unit;
+
+    #[test]
+    fn test_supports_declaration() {
+        let keyword = |k: &str| Value::Keyword(String::from(k));
+        assert!(supports_declaration("display", &keyword("block")));
+        assert!(supports_declaration("display", &keyword("inline-grid")));
+        assert!(!supports_declaration("display", &keyword("masonry")));
+        assert!(supports_declaration(
+            "width",
+            &Value::Length(10.0, Unit::Px)
+        ));
+        assert!(supports_declaration("width", &keyword("auto")));
+        assert!(supports_declaration("width", &Value::Percentage(50.0)));
+        assert!(!supports_declaration(
+            "border-width",
+            &Value::Percentage(50.0)
+        ));
+        assert!(!supports_declaration("padding", &keyword("auto")));
+        assert!(supports_declaration("--anything", &keyword("goes")));
+        assert!(!supports_declaration("text-wrap", &keyword("balance")));
+    }
+
+    #[test]
+    fn test_evaluate() {
+        let block = SupportsCondition::Declaration(
+            String::from("display"),
+            Value::Keyword(String::from("block")),
+        );
+        let grid = SupportsCondition::Declaration(
+            String::from("display"),
+            Value::Keyword(String::from("masonry")),
+        );
+
+        assert!(block.evaluate());
+        assert!(!grid.evaluate());
+        assert!(SupportsCondition::Not(Box::new(grid.clone())).evaluate());
+        assert!(!SupportsCondition::And(vec![block.clone(), grid.clone()]).evaluate());
+        assert!(SupportsCondition::Or(vec![block, grid]).evaluate());
+        assert!(!SupportsCondition::Unknown.evaluate());
+        assert!(SupportsCondition::Not(Box::new(SupportsCondition::Unknown)).evaluate());
+    }
+}
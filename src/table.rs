@@ -0,0 +1,1724 @@
+use std::{iter, mem, ops::Range, sync::Arc};
+
+use crate::{
+    computed::{
+        BackgroundProperties, BorderCollapse, BorderStyle, CaptionSide, ComputedStyle, Display,
+        DisplayInside, DisplayInternal, LengthPercentage, LengthPercentageAuto,
+        LengthPercentageNone, PositionProperties, TableLayout, VerticalAlign,
+    },
+    dom::NodeType,
+    font::FontDatabase,
+    layout::{
+        build_contents, content_size, BoxType, ContainingBlock, Dimensions, EdgeSizes, LayoutBox,
+        Rect,
+    },
+    style::PropertyMap,
+};
+
+/// The role of a box in the table model of CSS 2.1 § 17.2.
+#[derive(Clone, Copy, PartialEq, Debug)]
+enum Role {
+    Table,
+    RowGroup,
+    Row,
+    Cell,
+    ColumnGroup,
+    Column,
+    Caption,
+    Other,
+}
+
+/// Where a row is in a table box: one of its children, or a child of one of its row groups.
+#[derive(Clone, Copy, PartialEq, Debug)]
+struct RowPath {
+    group: Option<usize>,
+    index: usize,
+}
+
+/// Where a column is in a table box: a column that is one of its children or a child of one of
+/// its column groups, or a column group without columns, which stands for its columns.
+#[derive(Clone, Copy, PartialEq, Debug)]
+struct ColumnPath {
+    group: Option<usize>,
+    index: Option<usize>,
+}
+
+/// A cell placed in the slots of the table grid.
+struct Cell {
+    row: usize,
+    column: usize,
+    rows: usize,
+    columns: usize,
+    /// The row box the cell is in, and its index there.
+    path: (RowPath, usize),
+}
+
+/// The grid of a table, as formed by the HTML table processing model: its rows in visual order,
+/// with the header group first and the footer group last, and its cells placed in the slots
+/// left free by the cells spanning rows above.
+struct TableGrid {
+    rows: Vec<RowPath>,
+    /// The row group of each section of rows, and the rows in it. Rows that are children of the
+    /// table box form sections without a row group.
+    sections: Vec<(Option<usize>, Range<usize>)>,
+    cells: Vec<Cell>,
+    /// The column box of each column, if it has one.
+    columns: Vec<Option<ColumnPath>>,
+    /// The index of the cell covering each slot of each row.
+    slots: Vec<Vec<Option<usize>>>,
+}
+
+/// How a column wants to be sized, from the widths of its column box and its cells.
+#[derive(Clone, Copy, PartialEq, Debug)]
+enum Constraint {
+    Auto,
+    Fixed(f32),
+    Percent(f32),
+}
+
+/// A column being sized by the automatic table layout algorithm, with the widths of the border
+/// boxes of its cells.
+#[derive(Clone, Copy, Debug)]
+struct Column {
+    min: f32,
+    max: f32,
+    constraint: Constraint,
+}
+
+/// A table box and the sizes of the edges around its cells, which layout and measuring share.
+struct Table {
+    grid: TableGrid,
+    /// The horizontal and vertical space around and between cells, which is 0 when borders
+    /// collapse.
+    spacing: (f32, f32),
+    /// The borders and padding of the table box. With collapsing borders, the borders are half
+    /// those of the outer edges of its first row, and it has no padding.
+    border: EdgeSizes,
+    padding: EdgeSizes,
+    /// The borders and padding of each cell, with the borders halved if they collapse.
+    cell_borders: Vec<EdgeSizes>,
+    cell_padding: Vec<EdgeSizes>,
+}
+
+/// Returns the style of the wrapper box generated by a table element with `style`, which keeps
+/// the properties of the element that position the table and the table's margins.
+pub(crate) fn wrapper_style(style: &ComputedStyle) -> ComputedStyle {
+    let mut wrapper = style.clone();
+    let box_ = &mut wrapper.box_;
+    box_.width = LengthPercentageAuto::Auto;
+    box_.height = LengthPercentageAuto::Auto;
+    box_.min_width = LengthPercentageAuto::Auto;
+    box_.min_height = LengthPercentageAuto::Auto;
+    box_.max_width = LengthPercentageNone::None;
+    box_.max_height = LengthPercentageNone::None;
+    for padding in [
+        &mut box_.padding_top,
+        &mut box_.padding_right,
+        &mut box_.padding_bottom,
+        &mut box_.padding_left,
+    ] {
+        *padding = LengthPercentage::Px(0.0);
+    }
+    let border = &mut wrapper.border;
+    border.border_top_width = 0.0;
+    border.border_right_width = 0.0;
+    border.border_bottom_width = 0.0;
+    border.border_left_width = 0.0;
+    wrapper.background = BackgroundProperties::default();
+    wrapper
+}
+
+/// Returns the style of the table box inside the wrapper box of a table element with `style`,
+/// which has the properties of the element that the wrapper box doesn't.
+fn table_style(style: &ComputedStyle) -> ComputedStyle {
+    let mut table = style.clone();
+    table.box_.margin_top = LengthPercentageAuto::Px(0.0);
+    table.box_.margin_right = LengthPercentageAuto::Px(0.0);
+    table.box_.margin_bottom = LengthPercentageAuto::Px(0.0);
+    table.box_.margin_left = LengthPercentageAuto::Px(0.0);
+    table.box_.opacity = 1.0;
+    table.position = PositionProperties::default();
+    table
+}
+
+/// Returns the role of `layout_box` in the table model. Text boxes share the style of their
+/// parent, and so take no part.
+fn role(layout_box: &LayoutBox) -> Role {
+    if let BoxType::InlineNode(styled) = layout_box.box_type {
+        if let NodeType::Text(_) = styled.node.node_type {
+            return Role::Other;
+        }
+    }
+    if layout_box.is_table_wrapper() {
+        return Role::Table;
+    }
+    match layout_box.style().display() {
+        Display::Internal(internal) => match internal {
+            DisplayInternal::TableRowGroup
+            | DisplayInternal::TableHeaderGroup
+            | DisplayInternal::TableFooterGroup => Role::RowGroup,
+            DisplayInternal::TableRow => Role::Row,
+            DisplayInternal::TableCell => Role::Cell,
+            DisplayInternal::TableColumnGroup => Role::ColumnGroup,
+            DisplayInternal::TableColumn => Role::Column,
+            DisplayInternal::TableCaption => Role::Caption,
+            _ => Role::Other,
+        },
+        _ => Role::Other,
+    }
+}
+
+/// Returns true if `parent` is a table or a part of one whose children are table structure
+/// rather than content, i.e. not a cell or a caption.
+pub(crate) fn is_table_part(parent: &LayoutBox) -> bool {
+    matches!(
+        role(parent),
+        Role::Table | Role::RowGroup | Role::Row | Role::ColumnGroup | Role::Column
+    )
+}
+
+/// Returns true if `layout_box` is a text box containing only white space, which is dropped
+/// between the parts of a table.
+fn is_white_space(layout_box: &LayoutBox) -> bool {
+    match layout_box.box_type {
+        BoxType::InlineNode(styled) => match styled.node.node_type {
+            NodeType::Text(ref text) => text
+                .chars()
+                .all(|c| matches!(c, ' ' | '\t' | '\n' | '\r' | '\x0c')),
+            _ => false,
+        },
+        _ => false,
+    }
+}
+
+/// Returns an anonymous table object with the display type `display`, inheriting from `parent`.
+fn anonymous<'a>(parent: &LayoutBox<'a>, display: Display) -> LayoutBox<'a> {
+    let mut style = ComputedStyle::compute(&PropertyMap::new(), Some(parent.style()));
+    style.box_.display = display;
+    LayoutBox::new(BoxType::AnonymousBlock(Arc::new(style)))
+}
+
+/// Returns `children`, with each run of consecutive boxes for which `wrap` is true wrapped in a
+/// box made by `wrapper`.
+fn wrap_runs<'a>(
+    children: Vec<LayoutBox<'a>>,
+    wrap: impl Fn(&LayoutBox<'a>) -> bool,
+    wrapper: impl Fn(Vec<LayoutBox<'a>>) -> LayoutBox<'a>,
+) -> Vec<LayoutBox<'a>> {
+    let mut wrapped = Vec::new();
+    let mut run = Vec::new();
+    for child in children {
+        match wrap(&child) {
+            true => run.push(child),
+            false => {
+                if !run.is_empty() {
+                    wrapped.push(wrapper(mem::take(&mut run)));
+                }
+                wrapped.push(child);
+            }
+        }
+    }
+    if !run.is_empty() {
+        wrapped.push(wrapper(run));
+    }
+    wrapped
+}
+
+/// Returns the children of `parent`, a table or a part of one, with the anonymous table objects
+/// of CSS 2.1 § 17.2.1 generated: columns have no children and column groups only columns, a
+/// run of children of a row that aren't cells is wrapped in an anonymous cell, and one of a row
+/// group or a table that aren't rows, row groups or columns in an anonymous row. White space
+/// between the parts is dropped.
+///
+/// A table wrapper box gets its captions, followed by the table box, which holds the rest.
+pub(crate) fn build_table_part<'a>(
+    parent: &LayoutBox<'a>,
+    children: Vec<LayoutBox<'a>>,
+) -> Vec<LayoutBox<'a>> {
+    let parent_role = role(parent);
+    let children = children.into_iter().filter(|child| !is_white_space(child));
+    match parent_role {
+        Role::Column => Vec::new(),
+        Role::ColumnGroup => children
+            .filter(|child| role(child) == Role::Column)
+            .collect(),
+        Role::Row => wrap_runs(
+            children.collect(),
+            |child| role(child) != Role::Cell,
+            |run| {
+                let mut cell = anonymous(parent, Display::Internal(DisplayInternal::TableCell));
+                cell.children = build_contents(&cell, run);
+                cell
+            },
+        ),
+        Role::RowGroup => wrap_runs(
+            children.collect(),
+            |child| role(child) != Role::Row,
+            |run| anonymous_row(parent, run),
+        ),
+        _ => {
+            let (captions, rest): (Vec<_>, Vec<_>) =
+                children.partition(|child| role(child) == Role::Caption);
+            let rest = wrap_runs(
+                rest,
+                |child| {
+                    !matches!(
+                        role(child),
+                        Role::RowGroup | Role::Row | Role::ColumnGroup | Role::Column
+                    )
+                },
+                |run| anonymous_row(parent, run),
+            );
+            let style = table_style(parent.box_type.style());
+            let mut table = LayoutBox::new(BoxType::TableBox(Arc::new(style)));
+            table.children = rest;
+            captions.into_iter().chain([table]).collect()
+        }
+    }
+}
+
+/// Returns an anonymous row in `parent` wrapping `run`.
+fn anonymous_row<'a>(parent: &LayoutBox<'a>, run: Vec<LayoutBox<'a>>) -> LayoutBox<'a> {
+    let mut row = anonymous(parent, Display::Internal(DisplayInternal::TableRow));
+    row.children = build_table_part(&row, run);
+    row
+}
+
+/// Returns the children of `parent`, which isn't a part of a table, with each run of table parts
+/// among them wrapped in an anonymous table, as in CSS 2.1 § 17.2.1. White space between the
+/// parts joins their run.
+pub(crate) fn wrap_misparented<'a>(
+    parent: &LayoutBox<'a>,
+    children: Vec<LayoutBox<'a>>,
+) -> Vec<LayoutBox<'a>> {
+    if children
+        .iter()
+        .all(|child| matches!(role(child), Role::Table | Role::Other))
+    {
+        return children;
+    }
+    let mut wrapped: Vec<LayoutBox> = Vec::new();
+    let mut run = Vec::new();
+    for child in children {
+        let misparented = !matches!(role(&child), Role::Table | Role::Other);
+        if misparented || (!run.is_empty() && is_white_space(&child)) {
+            run.push(child);
+            continue;
+        }
+        if !run.is_empty() {
+            wrapped.push(anonymous_table(parent, mem::take(&mut run)));
+        }
+        wrapped.push(child);
+    }
+    if !run.is_empty() {
+        wrapped.push(anonymous_table(parent, run));
+    }
+    wrapped
+}
+
+/// Returns an anonymous block-level table in `parent` wrapping `run`.
+fn anonymous_table<'a>(parent: &LayoutBox<'a>, run: Vec<LayoutBox<'a>>) -> LayoutBox<'a> {
+    let mut table = anonymous(parent, Display::TABLE);
+    table.children = build_table_part(&table, run);
+    table
+}
+
+/// Returns the value of the integer attribute `name` of the element that generated
+/// `layout_box`, if it has a valid one.
+fn attribute(layout_box: &LayoutBox, name: &str) -> Option<u32> {
+    match layout_box.styled_node()?.node.node_type {
+        NodeType::Element(ref data) => data.attributes.get(name)?.trim().parse().ok(),
+        _ => None,
+    }
+}
+
+/// Returns the number of columns a column or column group box stands for.
+fn span(layout_box: &LayoutBox) -> usize {
+    attribute(layout_box, "span").map_or(1, |span| span.clamp(1, 1000) as usize)
+}
+
+/// Returns the row at `path` in `table`.
+fn row<'b, 'a>(table: &'b LayoutBox<'a>, path: RowPath) -> &'b LayoutBox<'a> {
+    match path.group {
+        Some(group) => &table.children[group].children[path.index],
+        None => &table.children[path.index],
+    }
+}
+
+/// Returns the row at `path` in `table`, mutably.
+fn row_mut<'b, 'a>(table: &'b mut LayoutBox<'a>, path: RowPath) -> &'b mut LayoutBox<'a> {
+    match path.group {
+        Some(group) => &mut table.children[group].children[path.index],
+        None => &mut table.children[path.index],
+    }
+}
+
+/// Returns the column box at `path` in `table`, and its column group.
+fn column<'b, 'a>(
+    table: &'b LayoutBox<'a>,
+    path: ColumnPath,
+) -> (Option<&'b LayoutBox<'a>>, Option<&'b LayoutBox<'a>>) {
+    match (path.group, path.index) {
+        (Some(group), Some(index)) => (
+            Some(&table.children[group].children[index]),
+            Some(&table.children[group]),
+        ),
+        (Some(group), None) => (None, Some(&table.children[group])),
+        (None, Some(index)) => (Some(&table.children[index]), None),
+        (None, None) => (None, None),
+    }
+}
+
+impl TableGrid {
+    /// Forms the grid of the table box `table`, placing its cells as in the HTML table processing
+    /// model, with `colspan` and `rowspan` attributes. A cell spans the rows to the end of its
+    /// section if its `rowspan` is 0, and no further than that otherwise.
+    fn new(table: &LayoutBox) -> TableGrid {
+        let mut header = None;
+        let mut footer = None;
+        let mut bodies = Vec::new();
+        let mut loose = Vec::new();
+        let mut columns = Vec::new();
+        for (index, child) in table.children.iter().enumerate() {
+            let child_role = role(child);
+            if child_role != Role::Row && !loose.is_empty() {
+                bodies.push((None, mem::take(&mut loose)));
+            }
+            match child_role {
+                Role::Row => loose.push(RowPath { group: None, index }),
+                Role::RowGroup => {
+                    let rows = (0..child.children.len())
+                        .map(|row| RowPath {
+                            group: Some(index),
+                            index: row,
+                        })
+                        .collect::<Vec<_>>();
+                    let section = (Some(index), rows);
+                    match child.style().display() {
+                        Display::Internal(DisplayInternal::TableHeaderGroup)
+                            if header.is_none() =>
+                        {
+                            header = Some(section)
+                        }
+                        Display::Internal(DisplayInternal::TableFooterGroup)
+                            if footer.is_none() =>
+                        {
+                            footer = Some(section)
+                        }
+                        _ => bodies.push(section),
+                    }
+                }
+                Role::Column => {
+                    let path = ColumnPath {
+                        group: None,
+                        index: Some(index),
+                    };
+                    columns.extend(iter::repeat_n(Some(path), span(child)));
+                }
+                Role::ColumnGroup if child.children.is_empty() => {
+                    let path = ColumnPath {
+                        group: Some(index),
+                        index: None,
+                    };
+                    columns.extend(iter::repeat_n(Some(path), span(child)));
+                }
+                Role::ColumnGroup => {
+                    for (column, column_box) in child.children.iter().enumerate() {
+                        let path = ColumnPath {
+                            group: Some(index),
+                            index: Some(column),
+                        };
+                        columns.extend(iter::repeat_n(Some(path), span(column_box)));
+                    }
+                }
+                _ => {}
+            }
+        }
+        if !loose.is_empty() {
+            bodies.push((None, loose));
+        }
+
+        let mut grid = TableGrid {
+            rows: Vec::new(),
+            sections: Vec::new(),
+            cells: Vec::new(),
+            columns,
+            slots: Vec::new(),
+        };
+        for (group, rows) in header.into_iter().chain(bodies).chain(footer) {
+            let start = grid.rows.len();
+            let end = start + rows.len();
+            grid.rows.extend(rows);
+            grid.sections.push((group, start..end));
+            grid.slots.resize(end, Vec::new());
+            for y in start..end {
+                let path = grid.rows[y];
+                let mut x = 0;
+                for (index, cell) in row(table, path).children.iter().enumerate() {
+                    while matches!(grid.slots[y].get(x), Some(Some(_))) {
+                        x += 1;
+                    }
+                    let columns = attribute(cell, "colspan").map_or(1, |span| span.clamp(1, 1000));
+                    let rows = match attribute(cell, "rowspan").map_or(1, |span| span.min(65534)) {
+                        0 => end - y,
+                        span => (span as usize).min(end - y),
+                    };
+                    let columns = columns as usize;
+                    for slots in &mut grid.slots[y..y + rows] {
+                        if slots.len() < x + columns {
+                            slots.resize(x + columns, None);
+                        }
+                        slots[x..x + columns].fill(Some(grid.cells.len()));
+                    }
+                    grid.cells.push(Cell {
+                        row: y,
+                        column: x,
+                        rows,
+                        columns,
+                        path: (path, index),
+                    });
+                    x += columns;
+                }
+            }
+        }
+        let count = grid.slots.iter().map(Vec::len).max().unwrap_or(0);
+        if grid.columns.len() < count {
+            grid.columns.resize(count, None);
+        }
+        for slots in &mut grid.slots {
+            slots.resize(grid.columns.len(), None);
+        }
+        grid
+    }
+
+    /// Returns the cell box of `cell` in `table`.
+    fn cell<'b, 'a>(table: &'b LayoutBox<'a>, cell: &Cell) -> &'b LayoutBox<'a> {
+        &row(table, cell.path.0).children[cell.path.1]
+    }
+
+    /// Returns the cell box of `cell` in `table`, mutably.
+    fn cell_mut<'b, 'a>(table: &'b mut LayoutBox<'a>, cell: &Cell) -> &'b mut LayoutBox<'a> {
+        &mut row_mut(table, cell.path.0).children[cell.path.1]
+    }
+
+    /// Returns the row group of the row `y`, if it is in one.
+    fn group<'b, 'a>(&self, table: &'b LayoutBox<'a>, y: usize) -> Option<&'b LayoutBox<'a>> {
+        self.rows[y].group.map(|group| &table.children[group])
+    }
+}
+
+/// The border sides, in the order of `borders`.
+const TOP: usize = 0;
+const RIGHT: usize = 1;
+const BOTTOM: usize = 2;
+const LEFT: usize = 3;
+
+/// Returns the styles and widths of the borders of a box with `style`.
+fn borders(style: &ComputedStyle) -> [(BorderStyle, f32); 4] {
+    [
+        (style.border_top_style(), style.border_top_width()),
+        (style.border_right_style(), style.border_right_width()),
+        (style.border_bottom_style(), style.border_bottom_width()),
+        (style.border_left_style(), style.border_left_width()),
+    ]
+}
+
+/// Returns the width of a collapsed border, which is the widest of the borders meeting there, or
+/// 0 if any of them is `hidden`, as in CSS 2.1 § 17.6.2.1.
+fn resolve_border(candidates: &[(BorderStyle, f32)]) -> f32 {
+    let mut width = 0.0;
+    for &(style, candidate) in candidates {
+        if style == BorderStyle::Hidden {
+            return 0.0;
+        }
+        width = f32::max(width, candidate);
+    }
+    width
+}
+
+/// Returns the widths of the collapsed borders of the table box `table` with `grid`: those of the
+/// table box, and those on each side of each cell.
+fn collapsed_borders(table: &LayoutBox, grid: &TableGrid) -> (EdgeSizes, Vec<EdgeSizes>) {
+    let table_borders = borders(table.style());
+    let row_borders = |y: usize| borders(row(table, grid.rows[y]).style());
+    let group_borders = |y: usize| grid.group(table, y).map(|group| borders(group.style()));
+    let column_borders = |x: usize| match grid.columns[x] {
+        Some(path) => {
+            let (column_box, group) = column(table, path);
+            (
+                column_box.map(|column_box| borders(column_box.style())),
+                group.map(|group| (path.group, borders(group.style()))),
+            )
+        }
+        None => (None, None),
+    };
+    let rows = grid.rows.len();
+    let columns = grid.columns.len();
+
+    let mut cell_borders = Vec::with_capacity(grid.cells.len());
+    for cell in &grid.cells {
+        let own = borders(TableGrid::cell(table, cell).style());
+        let (top, bottom) = (cell.row, cell.row + cell.rows);
+        let (left, right) = (cell.column, cell.column + cell.columns);
+        let mut sides: [Vec<(BorderStyle, f32)>; 4] = own.map(|border| vec![border]);
+        let neighbour = |y: usize, x: usize| {
+            grid.slots[y][x]
+                .map(|index| borders(TableGrid::cell(table, &grid.cells[index]).style()))
+        };
+
+        // The horizontal lines above and below the cell.
+        for (line, side, opposite) in [(top, TOP, BOTTOM), (bottom, BOTTOM, TOP)] {
+            let candidates = &mut sides[side];
+            let (inner, outer) = match side {
+                TOP => (line, line.checked_sub(1)),
+                _ => (line - 1, (line < rows).then_some(line)),
+            };
+            candidates.push(row_borders(inner)[side]);
+            match outer {
+                Some(outer) => {
+                    candidates.push(row_borders(outer)[opposite]);
+                    candidates.extend(
+                        (left..right)
+                            .filter_map(|x| neighbour(outer, x))
+                            .map(|b| b[opposite]),
+                    );
+                    if grid.rows[outer].group != grid.rows[inner].group {
+                        candidates.extend(group_borders(inner).map(|b| b[side]));
+                        candidates.extend(group_borders(outer).map(|b| b[opposite]));
+                    }
+                }
+                None => {
+                    candidates.push(table_borders[side]);
+                    candidates.extend(group_borders(inner).map(|b| b[side]));
+                    for x in left..right {
+                        let (column_box, group) = column_borders(x);
+                        candidates.extend(column_box.map(|b| b[side]));
+                        candidates.extend(group.map(|(_, b)| b[side]));
+                    }
+                }
+            }
+        }
+
+        // The vertical lines left and right of the cell.
+        for (line, side, opposite) in [(left, LEFT, RIGHT), (right, RIGHT, LEFT)] {
+            let candidates = &mut sides[side];
+            let (inner, outer) = match side {
+                LEFT => (line, line.checked_sub(1)),
+                _ => (line - 1, (line < columns).then_some(line)),
+            };
+            let (inner_column, inner_group) = column_borders(inner);
+            candidates.extend(inner_column.map(|b| b[side]));
+            match outer {
+                Some(outer) => {
+                    candidates.extend(
+                        (top..bottom)
+                            .filter_map(|y| neighbour(y, outer))
+                            .map(|b| b[opposite]),
+                    );
+                    let (outer_column, outer_group) = column_borders(outer);
+                    candidates.extend(outer_column.map(|b| b[opposite]));
+                    let inner_group_index = inner_group.map(|(group, _)| group);
+                    let outer_group_index = outer_group.map(|(group, _)| group);
+                    if inner_group_index != outer_group_index {
+                        candidates.extend(inner_group.map(|(_, b)| b[side]));
+                        candidates.extend(outer_group.map(|(_, b)| b[opposite]));
+                    }
+                }
+                None => {
+                    candidates.push(table_borders[side]);
+                    candidates.extend(inner_group.map(|(_, b)| b[side]));
+                    for y in top..bottom {
+                        candidates.push(row_borders(y)[side]);
+                        candidates.extend(group_borders(y).map(|b| b[side]));
+                    }
+                }
+            }
+        }
+
+        let [top, right, bottom, left] = sides.map(|candidates| resolve_border(&candidates));
+        cell_borders.push(EdgeSizes {
+            left,
+            right,
+            top,
+            bottom,
+        });
+    }
+
+    // The table's borders are half those of the outer edges of its first row.
+    let first_row = grid
+        .cells
+        .iter()
+        .zip(&cell_borders)
+        .filter(|(cell, _)| cell.row == 0);
+    let outer = |side: usize, edge: fn(&EdgeSizes) -> f32| {
+        let widths = first_row.clone().filter(|(cell, _)| match side {
+            LEFT => cell.column == 0,
+            RIGHT => cell.column + cell.columns == columns,
+            _ => true,
+        });
+        widths
+            .map(|(_, border)| edge(border))
+            .reduce(f32::max)
+            .unwrap_or_else(|| resolve_border(&[table_borders[side]]))
+            / 2.0
+    };
+    let table_border = EdgeSizes {
+        left: outer(LEFT, |border| border.left),
+        right: outer(RIGHT, |border| border.right),
+        top: outer(TOP, |border| border.top),
+        bottom: {
+            let last = grid
+                .cells
+                .iter()
+                .zip(&cell_borders)
+                .filter(|(cell, _)| cell.row + cell.rows == rows);
+            last.map(|(_, border)| border.bottom)
+                .reduce(f32::max)
+                .unwrap_or_else(|| resolve_border(&[table_borders[BOTTOM]]))
+                / 2.0
+        },
+    };
+    let halved = cell_borders
+        .into_iter()
+        .map(|border| EdgeSizes {
+            left: border.left / 2.0,
+            right: border.right / 2.0,
+            top: border.top / 2.0,
+            bottom: border.bottom / 2.0,
+        })
+        .collect();
+    (table_border, halved)
+}
+
+/// Returns the padding of a box with `style`, resolving percentages against `reference`.
+fn padding(style: &ComputedStyle, reference: f32) -> EdgeSizes {
+    EdgeSizes {
+        left: style.padding_left().resolve(reference),
+        right: style.padding_right().resolve(reference),
+        top: style.padding_top().resolve(reference),
+        bottom: style.padding_bottom().resolve(reference),
+    }
+}
+
+/// Returns the widths of the borders of a box with `style`.
+fn border(style: &ComputedStyle) -> EdgeSizes {
+    EdgeSizes {
+        left: style.border_left_width(),
+        right: style.border_right_width(),
+        top: style.border_top_width(),
+        bottom: style.border_bottom_width(),
+    }
+}
+
+impl Table {
+    /// Forms the grid of the table box `table` and resolves the edges around it and its cells,
+    /// with percentages of padding resolved against `reference`.
+    fn new(table: &LayoutBox, reference: f32) -> Table {
+        let style = table.style();
+        let grid = TableGrid::new(table);
+        let cell_padding = grid
+            .cells
+            .iter()
+            .map(|cell| padding(TableGrid::cell(table, cell).style(), reference))
+            .collect();
+        match style.border_collapse() {
+            BorderCollapse::Collapse => {
+                let (border, cell_borders) = collapsed_borders(table, &grid);
+                Table {
+                    grid,
+                    spacing: (0.0, 0.0),
+                    border,
+                    padding: EdgeSizes::default(),
+                    cell_borders,
+                    cell_padding,
+                }
+            }
+            BorderCollapse::Separate => {
+                let cell_borders = grid
+                    .cells
+                    .iter()
+                    .map(|cell| border(TableGrid::cell(table, cell).style()))
+                    .collect();
+                Table {
+                    grid,
+                    spacing: style.border_spacing(),
+                    border: border(style),
+                    padding: padding(style, reference),
+                    cell_borders,
+                    cell_padding,
+                }
+            }
+        }
+    }
+
+    /// Returns the horizontal space in the table box that isn't in its columns: its borders and
+    /// padding and the spacing around the columns.
+    fn horizontal_extra(&self) -> f32 {
+        let columns = self.grid.columns.len();
+        let spacing = match columns {
+            0 => 0.0,
+            columns => self.spacing.0 * (columns + 1) as f32,
+        };
+        self.border.left + self.border.right + self.padding.left + self.padding.right + spacing
+    }
+
+    /// Returns the vertical space in the table box that isn't in its rows.
+    fn vertical_extra(&self) -> f32 {
+        let rows = self.grid.rows.len();
+        let spacing = match rows {
+            0 => 0.0,
+            rows => self.spacing.1 * (rows + 1) as f32,
+        };
+        self.border.top + self.border.bottom + self.padding.top + self.padding.bottom + spacing
+    }
+
+    /// Returns the horizontal padding and borders of the cell `index`.
+    fn cell_edges(&self, index: usize) -> f32 {
+        let (border, padding) = (self.cell_borders[index], self.cell_padding[index]);
+        border.left + border.right + padding.left + padding.right
+    }
+
+    /// Returns the width of the columns `columns` with the spacing between them.
+    fn span_width(&self, widths: &[f32], columns: Range<usize>) -> f32 {
+        let count = columns.len();
+        widths[columns].iter().sum::<f32>() + self.spacing.0 * count.saturating_sub(1) as f32
+    }
+
+    /// Returns the minimum and maximum widths and the constraint of each column of the table box
+    /// `table` for the automatic table layout algorithm of CSS 2.1 § 17.5.2.2.
+    ///
+    /// A cell spanning several columns makes them wider if it doesn't fit in them, sharing the
+    /// difference in proportion to their maximum widths.
+    fn auto_columns(&self, table: &LayoutBox, fonts: &FontDatabase) -> Vec<Column> {
+        let mut columns = self
+            .grid
+            .columns
+            .iter()
+            .map(|&path| {
+                let constraint = path
+                    .and_then(|path| {
+                        let (column_box, group) = column(table, path);
+                        column_box.or(group)
+                    })
+                    .map_or(Constraint::Auto, |column_box| {
+                        match column_box.style().width() {
+                            LengthPercentageAuto::Px(width) => Constraint::Fixed(width),
+                            LengthPercentageAuto::Percent(percent) => Constraint::Percent(percent),
+                            LengthPercentageAuto::Auto => Constraint::Auto,
+                        }
+                    });
+                let width = match constraint {
+                    Constraint::Fixed(width) => width,
+                    _ => 0.0,
+                };
+                Column {
+                    min: 0.0,
+                    max: width,
+                    constraint,
+                }
+            })
+            .collect::<Vec<_>>();
+
+        let mut order = (0..self.grid.cells.len()).collect::<Vec<_>>();
+        order.sort_by_key(|&index| self.grid.cells[index].columns);
+        for index in order {
+            let cell = &self.grid.cells[index];
+            let cell_box = TableGrid::cell(table, cell);
+            let style = cell_box.style();
+            let edges = self.cell_edges(index);
+            let (min_content, max_content) = cell_box.contents_intrinsic_widths(fonts);
+            let min = min_content + edges;
+            let mut max = max_content + edges;
+            let constraint = match style.width() {
+                LengthPercentageAuto::Px(width) => {
+                    let width = content_size(style.box_sizing(), edges)(width) + edges;
+                    max = width.max(min);
+                    Constraint::Fixed(width)
+                }
+                LengthPercentageAuto::Percent(percent) => Constraint::Percent(percent),
+                LengthPercentageAuto::Auto => Constraint::Auto,
+            };
+
+            let span = cell.column..cell.column + cell.columns;
+            if cell.columns == 1 {
+                let column = &mut columns[cell.column];
+                column.min = column.min.max(min);
+                column.max = column.max.max(max);
+                column.constraint = match (column.constraint, constraint) {
+                    (Constraint::Percent(a), Constraint::Percent(b)) => {
+                        Constraint::Percent(a.max(b))
+                    }
+                    (Constraint::Percent(percent), _) | (_, Constraint::Percent(percent)) => {
+                        Constraint::Percent(percent)
+                    }
+                    (Constraint::Fixed(a), Constraint::Fixed(b)) => Constraint::Fixed(a.max(b)),
+                    (Constraint::Fixed(width), _) | (_, Constraint::Fixed(width)) => {
+                        Constraint::Fixed(width)
+                    }
+                    _ => Constraint::Auto,
+                };
+                continue;
+            }
+            let inner = self.spacing.0 * (cell.columns - 1) as f32;
+            let weights = columns[span.clone()]
+                .iter()
+                .map(|column| column.max)
+                .collect::<Vec<_>>();
+            let min_sum = columns[span.clone()].iter().map(|c| c.min).sum::<f32>() + inner;
+            grow(&mut columns[span.clone()], &weights, min - min_sum, |c| {
+                &mut c.min
+            });
+            let max_sum = columns[span.clone()].iter().map(|c| c.max).sum::<f32>() + inner;
+            grow(&mut columns[span], &weights, max - max_sum, |c| &mut c.max);
+        }
+        for column in &mut columns {
+            column.max = column.max.max(column.min);
+            if let Constraint::Fixed(width) = column.constraint {
+                column.max = width.max(column.min);
+            }
+        }
+        columns
+    }
+
+    /// Returns the widths of the columns of the table box `table` under the fixed table layout
+    /// algorithm of CSS 2.1 § 17.5.2.1, in which `space` is the width of the columns together,
+    /// or `None` for the columns whose width comes from neither their column box nor the cells
+    /// of the first row.
+    fn fixed_columns(&self, table: &LayoutBox, space: f32) -> Vec<Option<f32>> {
+        let mut widths = self
+            .grid
+            .columns
+            .iter()
+            .map(|&path| {
+                let (column_box, group) = column(table, path?);
+                column_box.or(group)?.style().width().resolve(space)
+            })
+            .collect::<Vec<_>>();
+        for (index, cell) in self.grid.cells.iter().enumerate() {
+            if cell.row != 0 {
+                continue;
+            }
+            let style = TableGrid::cell(table, cell).style();
+            let edges = self.cell_edges(index);
+            let Some(width) = style.width().resolve(space) else {
+                continue;
+            };
+            let width = content_size(style.box_sizing(), edges)(width) + edges;
+            let span = cell.column..cell.column + cell.columns;
+            let inner = self.spacing.0 * (cell.columns - 1) as f32;
+            let share = ((width - inner) / cell.columns as f32).max(0.0);
+            for column in &mut widths[span] {
+                column.get_or_insert(share);
+            }
+        }
+        widths
+    }
+
+    /// Returns the minimum width of the table box `table` and the width it would be if nothing
+    /// limited it, under the algorithm for its `table-layout`, where `width` is its specified
+    /// width.
+    fn width_range(
+        &self,
+        table: &LayoutBox,
+        width: Option<f32>,
+        fonts: &FontDatabase,
+    ) -> (f32, f32) {
+        let extra = self.horizontal_extra();
+        match width {
+            Some(width) if table.style().table_layout() == TableLayout::Fixed => {
+                let set = self
+                    .fixed_columns(table, (width - extra).max(0.0))
+                    .into_iter()
+                    .flatten()
+                    .sum::<f32>();
+                (set + extra, width)
+            }
+            _ => {
+                let columns = self.auto_columns(table, fonts);
+                let min = columns.iter().map(|column| column.min).sum::<f32>() + extra;
+                let max = columns.iter().map(|column| column.max).sum::<f32>() + extra;
+                (min, width.unwrap_or(max))
+            }
+        }
+    }
+
+    /// Returns the widths of the columns of the table box `table` when it is `width` wide.
+    fn column_widths(&self, table: &LayoutBox, width: f32, fonts: &FontDatabase) -> Vec<f32> {
+        let style = table.style();
+        let space = (width - self.horizontal_extra()).max(0.0);
+        if style.table_layout() == TableLayout::Fixed && style.width() != LengthPercentageAuto::Auto
+        {
+            let widths = self.fixed_columns(table, space);
+            let set = widths.iter().flatten().sum::<f32>();
+            let auto = widths.iter().filter(|width| width.is_none()).count();
+            let remaining = (space - set).max(0.0);
+            return widths
+                .into_iter()
+                .map(|width| match width {
+                    Some(width) if auto == 0 && set > 0.0 && remaining > 0.0 => width * space / set,
+                    Some(width) => width,
+                    None => remaining / auto as f32,
+                })
+                .collect();
+        }
+        distribute_widths(&self.auto_columns(table, fonts), space)
+    }
+}
+
+/// Adds `extra` to the sizes of `columns` picked by `size`, in proportion to `weights`, or
+/// equally if they are all 0. Nothing is added if `extra` isn't positive.
+fn grow(columns: &mut [Column], weights: &[f32], extra: f32, size: fn(&mut Column) -> &mut f32) {
+    if extra <= 0.0 || columns.is_empty() {
+        return;
+    }
+    let total = weights.iter().sum::<f32>();
+    for (column, weight) in columns.iter_mut().zip(weights) {
+        *size(column) += match total > 0.0 {
+            true => extra * weight / total,
+            false => extra / weights.len() as f32,
+        };
+    }
+}
+
+/// Returns the widths of `columns` sharing `space`, in the way browsers do: each column gets its
+/// minimum width, then columns with percentage widths grow towards them, then those with fixed
+/// widths, and then the others towards their maximum widths. Any space left over goes to the
+/// columns without a specified width in proportion to their maximum widths, or failing that to
+/// those with fixed widths, then those with percentages.
+fn distribute_widths(columns: &[Column], space: f32) -> Vec<f32> {
+    let mut widths = columns.iter().map(|column| column.min).collect::<Vec<_>>();
+    let mut remaining = space - widths.iter().sum::<f32>();
+    if remaining <= 0.0 {
+        return widths;
+    }
+    let is_percent = |column: &Column| matches!(column.constraint, Constraint::Percent(_));
+    let is_fixed = |column: &Column| matches!(column.constraint, Constraint::Fixed(_));
+    let is_auto = |column: &Column| column.constraint == Constraint::Auto;
+    let stages: [&dyn Fn(&Column) -> bool; 3] = [&is_percent, &is_fixed, &is_auto];
+
+    let targets = columns
+        .iter()
+        .map(|column| match column.constraint {
+            Constraint::Percent(percent) => (space * percent / 100.0).max(column.min),
+            Constraint::Fixed(_) | Constraint::Auto => column.max,
+        })
+        .collect::<Vec<_>>();
+    for stage in stages {
+        let wanted = columns
+            .iter()
+            .zip(&targets)
+            .zip(&widths)
+            .filter(|((column, _), _)| stage(column))
+            .map(|((_, target), width)| target - width)
+            .sum::<f32>();
+        if wanted <= 0.0 {
+            continue;
+        }
+        let given = remaining.min(wanted);
+        for ((column, &target), width) in columns.iter().zip(&targets).zip(&mut widths) {
+            if stage(column) {
+                *width = match given < wanted {
+                    true => *width + (target - *width) * given / wanted,
+                    false => target,
+                };
+            }
+        }
+        remaining -= given;
+    }
+
+    if remaining > 0.0 {
+        for stage in stages.into_iter().rev() {
+            let picked = (0..columns.len())
+                .filter(|&index| stage(&columns[index]))
+                .collect::<Vec<_>>();
+            if picked.is_empty() {
+                continue;
+            }
+            let weights = picked
+                .iter()
+                .map(|&index| match columns[index].constraint {
+                    Constraint::Auto => columns[index].max,
+                    _ => widths[index],
+                })
+                .collect::<Vec<_>>();
+            let total = weights.iter().sum::<f32>();
+            for (&index, weight) in picked.iter().zip(weights) {
+                widths[index] += match total > 0.0 {
+                    true => remaining * weight / total,
+                    false => remaining / picked.len() as f32,
+                };
+            }
+            break;
+        }
+    }
+    widths
+}
+
+/// Returns true if a cell with `vertical_align` is aligned by its baseline with the other cells
+/// of its first row.
+fn is_baseline_aligned(vertical_align: VerticalAlign) -> bool {
+    !matches!(
+        vertical_align,
+        VerticalAlign::Top | VerticalAlign::Middle | VerticalAlign::Bottom
+    )
+}
+
+impl LayoutBox<'_> {
+    /// Returns true if the box is a table wrapper box, generated by an element with
+    /// `display: table` or `inline-table` or as an anonymous table. It contains the table's
+    /// captions and its table box.
+    pub(crate) fn is_table_wrapper(&self) -> bool {
+        self.style().display().inside() == Some(DisplayInside::Table)
+            && !matches!(self.box_type, BoxType::TableBox(_))
+    }
+
+    /// Returns the table box in a table wrapper box.
+    pub(crate) fn table_box(&self) -> Option<&LayoutBox<'_>> {
+        self.children
+            .iter()
+            .find(|child| matches!(child.box_type, BoxType::TableBox(_)))
+    }
+
+    /// Returns the min-content width of the captions of a table wrapper box.
+    fn captions_min_width(&self, fonts: &FontDatabase) -> f32 {
+        self.children
+            .iter()
+            .filter(|child| role(child) == Role::Caption)
+            .map(|caption| caption.intrinsic_outer_widths(fonts).0)
+            .fold(0.0, f32::max)
+    }
+
+    /// Returns the width of the border box of the table in a table wrapper box, as the table
+    /// layout algorithm for its `table-layout` determines it, when percentages resolve against
+    /// `reference` and `available` is the width of the containing block less the wrapper's
+    /// margins.
+    ///
+    /// A table with an `auto` width is as wide as its columns want to be, up to `available`. It
+    /// is never narrower than its columns' minimum widths or its captions. Its `width` is that of
+    /// its border box, as browsers treat it.
+    pub(crate) fn table_used_width(
+        &self,
+        reference: f32,
+        available: f32,
+        fonts: &FontDatabase,
+    ) -> f32 {
+        let Some(table) = self.table_box() else {
+            return 0.0;
+        };
+        let style = table.style();
+        let model = Table::new(table, reference);
+        let specified = style.width().resolve(reference);
+        let (min, max) = model.width_range(table, specified, fonts);
+        let mut width = match specified {
+            Some(_) => max,
+            None => available.min(max),
+        };
+        if let Some(max_width) = style.max_width().resolve(reference) {
+            width = width.min(max_width);
+        }
+        if let Some(min_width) = style.min_width().resolve(reference) {
+            width = width.max(min_width);
+        }
+        width.max(min).max(self.captions_min_width(fonts))
+    }
+
+    /// Returns the min-content and max-content widths of a table wrapper box: those of its
+    /// table, which are the sums of its columns' minimum and maximum widths, or its specified
+    /// width, and no narrower than its captions. Percentages are treated as `auto`.
+    pub(crate) fn table_intrinsic_widths(&self, fonts: &FontDatabase) -> (f32, f32) {
+        let Some(table) = self.table_box() else {
+            return (0.0, 0.0);
+        };
+        let style = table.style();
+        let model = Table::new(table, 0.0);
+        let specified = match style.width() {
+            LengthPercentageAuto::Px(width) => Some(width),
+            _ => None,
+        };
+        let (min, max) = model.width_range(table, specified, fonts);
+        let (mut min, mut max) = (specified.map_or(min, |width| width.max(min)), max.max(min));
+        if let LengthPercentageAuto::Px(min_width) = style.min_width() {
+            min = min.max(min_width);
+            max = max.max(min_width);
+        }
+        let captions = self.captions_min_width(fonts);
+        (min.max(captions), max.max(captions))
+    }
+
+    /// Lays out the contents of a table wrapper box in `containing_block`, its content area: its
+    /// captions above or below the table as their `caption-side` says, and its table box, which
+    /// is as wide as the wrapper. Returns the height of the contents.
+    pub(crate) fn layout_table_wrapper(
+        &mut self,
+        containing_block: ContainingBlock,
+        fonts: &FontDatabase,
+    ) -> f32 {
+        let rect = containing_block.rect;
+        let mut cursor = self.layout_captions(CaptionSide::Top, rect, rect.y, fonts);
+        if let Some(table) = self
+            .children
+            .iter_mut()
+            .find(|child| matches!(child.box_type, BoxType::TableBox(_)))
+        {
+            cursor += table.layout_table((rect.x, cursor), rect.width, fonts);
+        }
+        cursor = self.layout_captions(CaptionSide::Bottom, rect, cursor, fonts);
+        cursor - rect.y
+    }
+
+    /// Stacks the captions of a table wrapper box on `side` of the table from `y` down, as blocks
+    /// in its content area `rect`, and returns the y coordinate below them.
+    fn layout_captions(
+        &mut self,
+        side: CaptionSide,
+        rect: Rect,
+        y: f32,
+        fonts: &FontDatabase,
+    ) -> f32 {
+        let mut cursor = y;
+        for caption in self
+            .children
+            .iter_mut()
+            .filter(|child| role(child) == Role::Caption && child.style().caption_side() == side)
+        {
+            let containing_block = ContainingBlock {
+                rect: Rect { y: cursor, ..rect },
+                height: None,
+            };
+            caption.layout_block(containing_block, cursor, None, fonts);
+            caption.translate(0.0, caption.dimensions.margin.top);
+            cursor += caption.dimensions.margin_box().height;
+            caption.apply_relative_offset(containing_block);
+        }
+        cursor
+    }
+
+    /// Lays out a table box `width` wide with the top left corner of its border box at
+    /// `position`, as in CSS 2.1 § 17.5, and returns the height of its border box.
+    ///
+    /// The columns share the width by the table layout algorithm. Each row is as tall as its
+    /// tallest cell, with the cells aligned by their baselines taking the height of their
+    /// baselines into account, and as its `height`, and cells spanning several rows make them
+    /// taller equally if they don't fit. Any extra height from the table's `height` is shared in
+    /// proportion to the rows' heights. The contents of each cell are then aligned in it by its
+    /// `vertical-align`. Percentage heights of the table and its rows are treated as `auto`.
+    fn layout_table(&mut self, position: (f32, f32), width: f32, fonts: &FontDatabase) -> f32 {
+        let (x, y) = position;
+        let model = Table::new(self, width);
+        let widths = model.column_widths(self, width, fonts);
+        let (border, padding) = (model.border, model.padding);
+        let (h_spacing, v_spacing) = model.spacing;
+        self.dimensions = Dimensions {
+            content: Rect {
+                x: x + border.left + padding.left,
+                y: y + border.top + padding.top,
+                width: (width - border.left - border.right - padding.left - padding.right).max(0.0),
+                height: 0.0,
+            },
+            padding,
+            border,
+            margin: EdgeSizes::default(),
+        };
+        let content = self.dimensions.content;
+        let grid = &model.grid;
+
+        let mut column_x = Vec::with_capacity(widths.len());
+        let mut next_x = content.x + h_spacing;
+        for width in &widths {
+            column_x.push(next_x);
+            next_x += width + h_spacing;
+        }
+
+        // Lay out each cell at the width of its columns, to measure its height and baseline.
+        let mut measures = Vec::with_capacity(grid.cells.len());
+        for (index, cell) in grid.cells.iter().enumerate() {
+            let width = model.span_width(&widths, cell.column..cell.column + cell.columns);
+            let cell_box = TableGrid::cell_mut(self, cell);
+            cell_box.layout_cell(
+                (column_x[cell.column], content.y),
+                width,
+                (model.cell_borders[index], model.cell_padding[index]),
+                fonts,
+            );
+            let border_box = cell_box.dimensions.border_box();
+            let content_box = cell_box.dimensions.content;
+            let baseline = cell_box
+                .first_baseline()
+                .unwrap_or(content_box.y + content_box.height);
+            measures.push((border_box.height, baseline - border_box.y));
+        }
+
+        // Size the rows.
+        let rows = grid.rows.len();
+        let mut heights = grid
+            .rows
+            .iter()
+            .map(|&path| match row(self, path).style().height() {
+                LengthPercentageAuto::Px(height) => height,
+                _ => 0.0,
+            })
+            .collect::<Vec<_>>();
+        let mut ascents: Vec<Option<f32>> = vec![None; rows];
+        let mut descents = vec![0.0f32; rows];
+        for (cell, &(height, ascent)) in grid.cells.iter().zip(&measures) {
+            let baseline_aligned =
+                is_baseline_aligned(TableGrid::cell(self, cell).style().vertical_align());
+            if baseline_aligned {
+                let row_ascent = &mut ascents[cell.row];
+                *row_ascent = Some(row_ascent.map_or(ascent, |row_ascent| row_ascent.max(ascent)));
+            }
+            if cell.rows > 1 {
+                continue;
+            }
+            match baseline_aligned {
+                true => descents[cell.row] = descents[cell.row].max(height - ascent),
+                false => heights[cell.row] = heights[cell.row].max(height),
+            }
+        }
+        for (height, (ascent, descent)) in heights.iter_mut().zip(ascents.iter().zip(&descents)) {
+            if let Some(ascent) = ascent {
+                *height = height.max(ascent + descent);
+            }
+        }
+        let mut spanning = (0..grid.cells.len())
+            .filter(|&index| grid.cells[index].rows > 1)
+            .collect::<Vec<_>>();
+        spanning.sort_by_key(|&index| grid.cells[index].rows);
+        for index in spanning {
+            let cell = &grid.cells[index];
+            let span = cell.row..cell.row + cell.rows;
+            let inner = v_spacing * (cell.rows - 1) as f32;
+            let excess = measures[index].0 - heights[span.clone()].iter().sum::<f32>() - inner;
+            if excess > 0.0 {
+                for height in &mut heights[span] {
+                    *height += excess / cell.rows as f32;
+                }
+            }
+        }
+        if let LengthPercentageAuto::Px(height) = self.style().height() {
+            let total = heights.iter().sum::<f32>();
+            let extra = height - model.vertical_extra() - total;
+            if extra > 0.0 && rows > 0 {
+                for row_height in &mut heights {
+                    *row_height += match total > 0.0 {
+                        true => extra * *row_height / total,
+                        false => extra / rows as f32,
+                    };
+                }
+            }
+        }
+
+        let mut row_y = Vec::with_capacity(rows);
+        let mut next_y = content.y + v_spacing;
+        for height in &heights {
+            row_y.push(next_y);
+            next_y += height + v_spacing;
+        }
+        let content_height = match rows {
+            0 => 0.0,
+            _ => next_y - content.y,
+        };
+        let content_height = match self.style().height() {
+            LengthPercentageAuto::Px(height) => content_height
+                .max(height - border.top - border.bottom - padding.top - padding.bottom),
+            _ => content_height,
+        };
+        self.dimensions.content.height = content_height;
+
+        // Place the cells in their rows and align their contents.
+        let table_block = ContainingBlock {
+            rect: self.dimensions.content,
+            height: Some(content_height),
+        };
+        for (cell, &(height, ascent)) in grid.cells.iter().zip(&measures) {
+            let span = cell.row..cell.row + cell.rows;
+            let used = heights[span].iter().sum::<f32>() + v_spacing * (cell.rows - 1) as f32;
+            let cell_box = TableGrid::cell_mut(self, cell);
+            let free = used - height;
+            let shift = match cell_box.style().vertical_align() {
+                VerticalAlign::Top => 0.0,
+                VerticalAlign::Middle => free / 2.0,
+                VerticalAlign::Bottom => free,
+                _ => ascents[cell.row].map_or(0.0, |row_ascent| row_ascent - ascent),
+            };
+            cell_box.translate(0.0, row_y[cell.row] - content.y + shift);
+            let dimensions = &mut cell_box.dimensions;
+            dimensions.content.y -= shift;
+            dimensions.content.height += free;
+            cell_box.apply_relative_offset(table_block);
+        }
+
+        // Place the rows, row groups, columns and column groups, which span the table's grid.
+        let columns = widths.len();
+        let grid_x = content.x + h_spacing;
+        let grid_width = model.span_width(&widths, 0..columns);
+        let rows_rect = |span: Range<usize>| match span.is_empty() {
+            true => (content.y, 0.0),
+            false => {
+                let top = row_y[span.start];
+                (top, row_y[span.end - 1] + heights[span.end - 1] - top)
+            }
+        };
+        for (index, &path) in grid.rows.iter().enumerate() {
+            let (top, height) = rows_rect(index..index + 1);
+            row_mut(self, path).dimensions = span_dimensions(grid_x, top, grid_width, height);
+        }
+        for (group, span) in &grid.sections {
+            if let Some(group) = *group {
+                let (top, height) = rows_rect(span.clone());
+                self.children[group].dimensions = span_dimensions(grid_x, top, grid_width, height);
+            }
+        }
+        let (top, height) = rows_rect(0..rows);
+        let mut column_boxes: Vec<(ColumnPath, Range<usize>)> = Vec::new();
+        for (x, path) in grid.columns.iter().enumerate() {
+            let Some(path) = *path else {
+                continue;
+            };
+            match column_boxes.last_mut() {
+                Some((last, span)) if *last == path => span.end = x + 1,
+                _ => column_boxes.push((path, x..x + 1)),
+            }
+        }
+        let mut group_spans: Vec<(usize, Range<usize>)> = Vec::new();
+        for (path, span) in column_boxes {
+            let left = column_x[span.start];
+            let width = model.span_width(&widths, span.clone());
+            let dimensions = span_dimensions(left, top, width, height);
+            match (path.group, path.index) {
+                (Some(group), Some(index)) => {
+                    self.children[group].children[index].dimensions = dimensions
+                }
+                (None, Some(index)) => self.children[index].dimensions = dimensions,
+                _ => {}
+            }
+            if let Some(group) = path.group {
+                match group_spans.last_mut() {
+                    Some((last, group_span)) if *last == group => group_span.end = span.end,
+                    _ => group_spans.push((group, span)),
+                }
+            }
+        }
+        for (group, span) in group_spans {
+            let left = column_x[span.start];
+            let width = model.span_width(&widths, span);
+            self.children[group].dimensions = span_dimensions(left, top, width, height);
+        }
+
+        self.dimensions.border_box().height
+    }
+
+    /// Lays out the contents of a table cell `width` wide, with the top left corner of its border
+    /// box at `position` and the given borders and padding, at the height of its contents or its
+    /// `height` if that is taller.
+    fn layout_cell(
+        &mut self,
+        position: (f32, f32),
+        width: f32,
+        edges: (EdgeSizes, EdgeSizes),
+        fonts: &FontDatabase,
+    ) {
+        let ((x, y), (border, padding)) = (position, edges);
+        let dimensions = &mut self.dimensions;
+        dimensions.margin = EdgeSizes::default();
+        dimensions.border = border;
+        dimensions.padding = padding;
+        dimensions.content.x = x + border.left + padding.left;
+        dimensions.content.y = y + border.top + padding.top;
+        dimensions.content.width =
+            (width - border.left - border.right - padding.left - padding.right).max(0.0);
+        let containing_block = ContainingBlock {
+            rect: Rect {
+                x,
+                y,
+                width,
+                height: 0.0,
+            },
+            height: None,
+        };
+        let (height, min_height, max_height) = self.vertical_sizes(containing_block);
+        self.layout_contents((None, min_height, max_height), None, fonts);
+        if let Some(height) = height {
+            let content = &mut self.dimensions.content;
+            content.height = content.height.max(height);
+        }
+    }
+
+    /// Returns the baseline of a table wrapper box, which is that of the first cell of its
+    /// table's first row with one.
+    pub(crate) fn table_first_baseline(&self) -> Option<f32> {
+        let table = self.table_box()?;
+        let grid = TableGrid::new(table);
+        grid.cells
+            .iter()
+            .filter(|cell| cell.row == 0)
+            .find_map(|cell| TableGrid::cell(table, cell).first_baseline())
+    }
+}
+
+/// Returns the dimensions of a row, column or group spanning a rectangle of the table grid, which
+/// have no edges.
+fn span_dimensions(x: f32, y: f32, width: f32, height: f32) -> Dimensions {
+    Dimensions {
+        content: Rect {
+            x,
+            y,
+            width,
+            height,
+        },
+        ..Dimensions::default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::layout::tests::{with_layout_in, CH, MONOSPACE, VIEWPORT};
+
+    /// The display types of the table elements, which there is no user agent stylesheet for.
+    const TABLE: &str = "table { display: table; } caption { display: table-caption; } \
+                         colgroup { display: table-column-group; } col { display: table-column; } \
+                         tbody { display: table-row-group; } tr { display: table-row; } \
+                         td { display: table-cell; }";
+
+    /// Lays out `html` styled with `css` in the viewport, where text is set in the bundled
+    /// monospace font at 10px in 20px lines and the table elements have their display types, and
+    /// passes the root box to `f`.
+    fn with_layout(html: &str, css: &str, f: impl FnOnce(&LayoutBox)) {
+        let prefix = format!("{} * {{ line-height: 20px; }} {}", MONOSPACE, TABLE);
+        with_layout_in(VIEWPORT, &prefix, html, css, f);
+    }
+
+    /// Returns the x, y, width and height of the border box of `layout_box`.
+    fn border_box(layout_box: &LayoutBox) -> (f32, f32, f32, f32) {
+        let rect = layout_box.dimensions.border_box();
+        (rect.x, rect.y, rect.width, rect.height)
+    }
+
+    /// Returns the border boxes of the cells of the table in the table wrapper box `wrapper`, in
+    /// document order.
+    fn cells(wrapper: &LayoutBox) -> Vec<(f32, f32, f32, f32)> {
+        let table = wrapper.table_box().unwrap();
+        let mut cells = Vec::new();
+        for child in &table.children {
+            let rows = match role(child) {
+                Role::RowGroup => child.children.iter().collect(),
+                Role::Row => vec![child],
+                _ => Vec::new(),
+            };
+            for row in rows {
+                cells.extend(row.children.iter().map(border_box));
+            }
+        }
+        cells
+    }
+
+    #[test]
+    fn test_auto_layout() {
+        let css = "table { border-spacing: 2px 4px; } td { padding: 1px; }";
+        with_layout(
+            "<html><table><tr><td>a</td><td>bbb</td></tr><tr><td>cc</td><td>d</td></tr>\
+             </table></html>",
+            css,
+            |root| {
+                let wrapper = &root.children[0];
+                // The columns are as wide as their widest cells, and so the table.
+                assert_eq!(border_box(wrapper), (0.0, 0.0, 5.0 * CH + 10.0, 56.0));
+                assert_eq!(
+                    cells(wrapper),
+                    vec![
+                        (2.0, 4.0, 2.0 * CH + 2.0, 22.0),
+                        (2.0 * CH + 6.0, 4.0, 3.0 * CH + 2.0, 22.0),
+                        (2.0, 30.0, 2.0 * CH + 2.0, 22.0),
+                        (2.0 * CH + 6.0, 30.0, 3.0 * CH + 2.0, 22.0),
+                    ]
+                );
+                // The rows and row groups span the columns.
+                let tbody = &wrapper.table_box().unwrap().children[0];
+                assert_eq!(border_box(tbody), (2.0, 4.0, 5.0 * CH + 6.0, 48.0));
+                assert_eq!(
+                    border_box(&tbody.children[1]),
+                    (2.0, 30.0, 5.0 * CH + 6.0, 22.0)
+                );
+            },
+        );
+    }
+
+    #[test]
+    fn test_colspan_and_rowspan() {
+        with_layout(
+            "<html><table><tr><td colspan=\"2\">aaaaaaaaaa</td><td rowspan=\"2\">b</td></tr>\
+             <tr><td>c</td><td>d</td></tr></table></html>",
+            "",
+            |root| {
+                // The spanning cell shares its width equally between the columns it spans, as
+                // wide as their contents.
+                assert_eq!(
+                    cells(&root.children[0]),
+                    vec![
+                        (0.0, 0.0, 10.0 * CH, 20.0),
+                        (10.0 * CH, 0.0, CH, 40.0),
+                        (0.0, 20.0, 5.0 * CH, 20.0),
+                        (5.0 * CH, 20.0, 5.0 * CH, 20.0),
+                    ]
+                );
+            },
+        );
+    }
+
+    #[test]
+    fn test_column_widths() {
+        let css = "table { width: 400px; margin-left: auto; margin-right: auto; } \
+                   td { height: 20px; } .percent { width: 25%; } .fixed { width: 50px; }";
+        with_layout(
+            "<html><table><tr><td class=\"percent\"></td><td class=\"fixed\"></td><td></td>\
+             </tr></table></html>",
+            css,
+            |root| {
+                let wrapper = &root.children[0];
+                assert_eq!(border_box(wrapper), (200.0, 0.0, 400.0, 20.0));
+                // The columns get their percentages and fixed widths, and the rest of the width
+                // goes to the column without one.
+                assert_eq!(
+                    cells(wrapper),
+                    vec![
+                        (200.0, 0.0, 100.0, 20.0),
+                        (300.0, 0.0, 50.0, 20.0),
+                        (350.0, 0.0, 250.0, 20.0),
+                    ]
+                );
+            },
+        );
+    }
+
+    #[test]
+    fn test_fixed_layout() {
+        let css = "table { table-layout: fixed; width: 300px; border-spacing: 10px 0; } \
+                   col { width: 40px; } .fixed { width: 60px; }";
+        with_layout(
+            "<html><table><col><tr><td>aaaaaaaaaaaaaaaaaaaa</td><td class=\"fixed\">b</td>\
+             <td>c</td></tr><tr><td>d</td><td>e</td><td class=\"fixed\">f</td></tr>\
+             </table></html>",
+            css,
+            |root| {
+                let wrapper = &root.children[0];
+                // The widths come from the column and the first row, and the contents of the
+                // cells don't matter.
+                assert_eq!(
+                    &cells(wrapper)[..3],
+                    [
+                        (10.0, 0.0, 40.0, 20.0),
+                        (60.0, 0.0, 60.0, 20.0),
+                        (130.0, 0.0, 160.0, 20.0),
+                    ]
+                );
+                let colgroup = &wrapper.table_box().unwrap().children[0];
+                assert_eq!(border_box(colgroup), (10.0, 0.0, 40.0, 40.0));
+                assert_eq!(border_box(&colgroup.children[0]), (10.0, 0.0, 40.0, 40.0));
+            },
+        );
+    }
+
+    #[test]
+    fn test_collapsed_borders() {
+        let css = "table { border-collapse: collapse; border-spacing: 5px; border-width: 4px; \
+                   border-style: solid; padding: 3px; } \
+                   td { width: 20px; border-width: 2px; border-style: solid; } \
+                   .hidden { border-right-style: hidden; }";
+        with_layout(
+            "<html><table><tr><td>a</td><td class=\"hidden\">b</td><td>c</td></tr>\
+             </table></html>",
+            css,
+            |root| {
+                let wrapper = &root.children[0];
+                // The table has half the borders of its outer cells, and no padding or spacing.
+                let table = wrapper.table_box().unwrap();
+                assert_eq!(table.dimensions.border.left, 2.0);
+                assert_eq!(table.dimensions.padding, EdgeSizes::default());
+                assert_eq!(border_box(wrapper), (0.0, 0.0, 70.0, 28.0));
+                // The cells have half the widest border on each side, or none next to a hidden
+                // one.
+                assert_eq!(
+                    cells(wrapper),
+                    vec![
+                        (2.0, 2.0, 23.0, 24.0),
+                        (25.0, 2.0, 21.0, 24.0),
+                        (46.0, 2.0, 22.0, 24.0),
+                    ]
+                );
+                let cell = &table.children[0].children[0].children[1];
+                assert_eq!(
+                    cell.dimensions.border,
+                    EdgeSizes {
+                        left: 1.0,
+                        right: 0.0,
+                        top: 2.0,
+                        bottom: 2.0,
+                    }
+                );
+            },
+        );
+    }
+
+    #[test]
+    fn test_captions() {
+        let css =
+            ".bottom { caption-side: bottom; margin-top: 5px; } table { border-spacing: 1px; }";
+        with_layout(
+            "<html><table><caption class=\"bottom\">longer text</caption><caption>cap</caption>\
+             <tr><td>a</td></tr></table></html>",
+            css,
+            |root| {
+                let wrapper = &root.children[0];
+                // The table is as wide as the widest caption's min-content width, and the
+                // captions are stacked above and below it.
+                let width = 6.0 * CH;
+                assert_eq!(border_box(wrapper), (0.0, 0.0, width, 87.0));
+                assert_eq!(border_box(&wrapper.children[1]), (0.0, 0.0, width, 20.0));
+                assert_eq!(border_box(&wrapper.children[2]), (0.0, 20.0, width, 22.0));
+                assert_eq!(border_box(&wrapper.children[0]), (0.0, 47.0, width, 40.0));
+                assert_eq!(cells(wrapper), vec![(1.0, 21.0, width - 2.0, 20.0)]);
+            },
+        );
+    }
+
+    #[test]
+    fn test_vertical_alignment() {
+        let css = "table { height: 100px; } .big { font-size: 20px; } \
+                   .middle { vertical-align: middle; } .bottom { vertical-align: bottom; }";
+        with_layout(
+            "<html><table><tr><td class=\"big\">a</td><td>b</td><td class=\"middle\">c</td>\
+             <td class=\"bottom\">d</td></tr></table></html>",
+            css,
+            |root| {
+                let row = &root.children[0].table_box().unwrap().children[0].children[0];
+                let cells = &row.children;
+                // The extra height of the table goes to its row, and the cells fill it.
+                assert_eq!(border_box(row).3, 100.0);
+                assert!(cells.iter().all(|cell| border_box(cell).3 == 100.0));
+                // Cells aligned by their baselines share them.
+                assert_eq!(cells[0].lines[0].baseline, cells[1].lines[0].baseline);
+                assert_eq!(cells[2].lines[0].rect.y, 40.0);
+                assert_eq!(cells[3].lines[0].rect.y, 80.0);
+            },
+        );
+    }
+
+    #[test]
+    fn test_inline_table() {
+        let css = "p { display: block; } span { display: inline-table; border-spacing: 2px; } \
+                   b { display: table-cell; }";
+        with_layout(
+            "<html><p>a<span><b>bc</b><b>d</b></span></p></html>",
+            css,
+            |root| {
+                let p = &root.children[0];
+                let span = &p.children[1];
+                assert_eq!(border_box(span), (CH, 0.0, 3.0 * CH + 6.0, 24.0));
+                // The table's baseline is that of its first row, which is the paragraph's.
+                let cell = &span.table_box().unwrap().children[0].children[0];
+                assert_eq!(cell.lines[0].baseline, p.lines[0].baseline);
+            },
+        );
+    }
+}